@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// Append handle for one path; `None` until first write or after a failed write
+type SharedFile = Arc<tokio::sync::Mutex<Option<File>>>;
+
+/// Open append handles shared by all agents, one per path, so concurrent
+/// writes to the same file never interleave within a line
+static OPEN_FILES: LazyLock<Mutex<HashMap<PathBuf, SharedFile>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Append a single line to a file, creating the file (and its parent
+/// directories) if missing. The line is flushed immediately.
+pub async fn append_line(path: impl AsRef<Path>, line: &str) -> Result<()> {
+    let path = path.as_ref();
+    let handle = {
+        let mut files = OPEN_FILES
+            .lock()
+            .map_err(|_| anyhow::anyhow!("append_file handle cache poisoned"))?;
+        Arc::clone(files.entry(path.to_path_buf()).or_default())
+    };
+
+    let mut file = handle.lock().await;
+    let mut writer = match file.take() {
+        Some(writer) => writer,
+        None => open_for_append(path).await?,
+    };
+
+    let mut data = line.trim_end_matches(['\n', '\r']).to_string();
+    data.push('\n');
+
+    writer
+        .write_all(data.as_bytes())
+        .await
+        .with_context(|| format!("Failed to append to {}", path.display()))?;
+    writer
+        .flush()
+        .await
+        .with_context(|| format!("Failed to flush {}", path.display()))?;
+
+    // Keep the handle only after a successful write; failures reopen next time
+    *file = Some(writer);
+
+    Ok(())
+}
+
+async fn open_for_append(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {} for appending", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_append_line_creates_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("audit.log");
+
+        append_line(&path, "first").await.unwrap();
+        append_line(&path, "second\n").await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "first\nsecond\n");
+    }
+
+    #[tokio::test]
+    async fn test_append_line_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.log");
+
+        let writers: Vec<_> = (0..4)
+            .map(|agent| {
+                let path = path.clone();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        append_line(&path, &format!("agent-{} line {}", agent, i))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 100);
+        assert!(lines.iter().all(|line| line.starts_with("agent-")));
+    }
+}
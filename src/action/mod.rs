@@ -0,0 +1,87 @@
+pub mod append_file;
+pub mod notify;
+pub mod webhook;
+
+use crate::agent::Agent;
+use crate::config::helper::ActionType;
+use anyhow::Result;
+
+/// Execute an action that does not send keys to the terminal.
+/// Shared by rule and trigger execution, which handle `send_keys` themselves.
+pub async fn execute_non_key_action(
+    action: &ActionType,
+    agent: &Agent,
+    context: &str,
+) -> Result<()> {
+    match action {
+        ActionType::SendKeys(_) => {
+            tracing::debug!("{}: send_keys is executed by the caller", context);
+            Ok(())
+        }
+        ActionType::AppendFile { path, template } => {
+            if agent.is_dry_run() {
+                tracing::info!("{}: WOULD APPEND to {}: {:?}", context, path, template);
+                return Ok(());
+            }
+
+            tracing::info!("{}: Appending to {}", context, path);
+            append_file::append_line(path, template).await
+        }
+        ActionType::Notify { title, body } => {
+            if agent.is_dry_run() {
+                tracing::info!("{}: WOULD NOTIFY: {:?} {:?}", context, title, body);
+                return Ok(());
+            }
+
+            if notify::notify(title, body).await? {
+                tracing::info!("{}: Notified: {}", context, title);
+            }
+            Ok(())
+        }
+        ActionType::Webhook { target, message } => {
+            let Some(webhook) = agent.get_config().notifications.get(target) else {
+                anyhow::bail!("Unknown notification target: {}", target);
+            };
+
+            if agent.is_dry_run() {
+                tracing::info!("{}: WOULD POST to {}: {:?}", context, target, message);
+                return Ok(());
+            }
+
+            if agent.get_webhook_queue().enqueue(target, webhook, message) {
+                tracing::info!("{}: Queued webhook message for {}", context, target);
+            }
+            Ok(())
+        }
+        ActionType::Enqueue { queue, item } => {
+            if agent.is_dry_run() {
+                tracing::info!("{}: WOULD ENQUEUE to {}: {:?}", context, queue, item);
+                return Ok(());
+            }
+
+            tracing::info!("{}: Enqueued item to {}", context, queue);
+            agent.get_queues().enqueue(queue, item.clone());
+            Ok(())
+        }
+        ActionType::Signal(signal) => {
+            if agent.is_dry_run() {
+                tracing::info!("{}: WOULD SIGNAL {}", context, signal);
+                return Ok(());
+            }
+
+            tracing::info!("{}: Sending {}", context, signal);
+            agent.send_signal(*signal).await
+        }
+        // Group switches only change matching state, so they also apply in dry-run
+        ActionType::ActivateGroup(group) => {
+            tracing::info!("{}: Activating rule group {}", context, group);
+            agent.get_rule_groups().activate(group);
+            Ok(())
+        }
+        ActionType::DeactivateGroup(group) => {
+            tracing::info!("{}: Deactivating rule group {}", context, group);
+            agent.get_rule_groups().deactivate(group);
+            Ok(())
+        }
+    }
+}
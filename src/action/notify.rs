@@ -0,0 +1,90 @@
+use anyhow::Result;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use crate::rule::rate_limit::ActionRateLimiter;
+
+/// Maximum desktop notifications shown per minute across all agents
+const NOTIFICATIONS_PER_MINUTE: u32 = 5;
+
+/// Shared limiter so a repeating rule can't flood the desktop
+static NOTIFICATION_LIMITER: LazyLock<Mutex<ActionRateLimiter>> =
+    LazyLock::new(|| Mutex::new(ActionRateLimiter::new(NOTIFICATIONS_PER_MINUTE)));
+
+/// Show a desktop notification, subject to the shared rate limit.
+/// Returns false if the notification was suppressed.
+pub async fn notify(title: &str, body: &str) -> Result<bool> {
+    if !acquire(&NOTIFICATION_LIMITER, Instant::now()) {
+        tracing::debug!("Notification suppressed by rate limit: {}", title);
+        return Ok(false);
+    }
+
+    show(title, body).await?;
+    Ok(true)
+}
+
+/// Take a slot from the limiter, warning once per window when it is exhausted
+fn acquire(limiter: &Mutex<ActionRateLimiter>, now: Instant) -> bool {
+    let Ok(mut limiter) = limiter.lock() else {
+        return true;
+    };
+    if limiter.try_acquire(now) {
+        return true;
+    }
+    if limiter.should_warn() {
+        tracing::warn!(
+            "⚠️ More than {} notifications per minute, suppressing the rest of the minute",
+            NOTIFICATIONS_PER_MINUTE
+        );
+    }
+    false
+}
+
+#[cfg(all(
+    feature = "desktop-notify",
+    any(target_os = "linux", target_os = "macos")
+))]
+async fn show(title: &str, body: &str) -> Result<()> {
+    let title = title.to_string();
+    let body = body.to_string();
+
+    // notify-rust blocks on D-Bus / NSUserNotification, keep it off the runtime threads
+    tokio::task::spawn_blocking(move || {
+        notify_rust::Notification::new()
+            .summary(&title)
+            .body(&body)
+            .appname("ccauto")
+            .show()
+            .map(|_| ())
+    })
+    .await?
+    .map_err(|e| anyhow::anyhow!("Failed to show desktop notification: {}", e))
+}
+
+#[cfg(not(all(
+    feature = "desktop-notify",
+    any(target_os = "linux", target_os = "macos")
+)))]
+async fn show(title: &str, body: &str) -> Result<()> {
+    // Desktop notifications unavailable in this build, surface it in the log instead
+    tracing::warn!("🔔 {}: {}", title, body);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_acquire_rate_limits_notifications() {
+        let limiter = Mutex::new(ActionRateLimiter::new(NOTIFICATIONS_PER_MINUTE));
+        let now = Instant::now();
+
+        for _ in 0..NOTIFICATIONS_PER_MINUTE {
+            assert!(acquire(&limiter, now));
+        }
+        assert!(!acquire(&limiter, now + Duration::from_secs(10)));
+        assert!(acquire(&limiter, now + Duration::from_secs(61)));
+    }
+}
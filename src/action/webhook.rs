@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::notifications_config::{WebhookKind, WebhookTarget};
+
+/// Maximum number of messages waiting to be posted before new ones are dropped
+const QUEUE_CAPACITY: usize = 100;
+
+/// Timeout for a single webhook request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct WebhookJob {
+    target_name: String,
+    target: WebhookTarget,
+    message: String,
+}
+
+/// Send queue drained by a background worker that posts one message at a time
+pub struct WebhookQueue {
+    sender: mpsc::Sender<WebhookJob>,
+}
+
+impl WebhookQueue {
+    /// Spawn the worker on the current runtime; it stops once the queue is
+    /// dropped
+    pub fn start() -> Self {
+        let (sender, mut receiver) = mpsc::channel::<WebhookJob>(QUEUE_CAPACITY);
+
+        tokio::spawn(async move {
+            let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::error!("Failed to create webhook HTTP client: {}", e);
+                    return;
+                }
+            };
+
+            while let Some(job) = receiver.recv().await {
+                match post(&client, &job.target, &job.message).await {
+                    Ok(()) => tracing::debug!("Posted webhook message to '{}'", job.target_name),
+                    Err(e) => tracing::error!("Webhook '{}' failed: {:#}", job.target_name, e),
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    /// Queue a message for posting without waiting for the HTTP request, so
+    /// terminal monitoring is never blocked. Returns false if the message was dropped.
+    pub fn enqueue(&self, target_name: &str, target: &WebhookTarget, message: &str) -> bool {
+        let job = WebhookJob {
+            target_name: target_name.to_string(),
+            target: target.clone(),
+            message: message.to_string(),
+        };
+
+        match self.sender.try_send(job) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(job)) => {
+                tracing::warn!(
+                    "Webhook queue full, dropping message for '{}'",
+                    job.target_name
+                );
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(job)) => {
+                tracing::error!(
+                    "Webhook worker stopped, dropping message for '{}'",
+                    job.target_name
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Build the JSON body expected by the target's service
+pub fn build_payload(target: &WebhookTarget, message: &str) -> Value {
+    let mut payload = match target.kind {
+        WebhookKind::Slack => json!({ "text": message }),
+        WebhookKind::Discord => json!({ "content": message }),
+    };
+
+    if target.kind == WebhookKind::Slack
+        && let Some(channel) = &target.channel
+    {
+        payload["channel"] = json!(channel);
+    }
+    if let Some(username) = &target.username {
+        payload["username"] = json!(username);
+    }
+
+    payload
+}
+
+/// Post a message to a webhook target, failing on non-success status codes
+pub async fn post(client: &reqwest::Client, target: &WebhookTarget, message: &str) -> Result<()> {
+    let response = client
+        .post(&target.url)
+        .json(&build_payload(target, message))
+        .send()
+        .await
+        .with_context(|| format!("Failed to send webhook request to {}", target.url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Webhook returned {}: {}", status, body.trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, http::StatusCode, routing::post as post_route};
+    use std::sync::{Arc, Mutex};
+
+    fn target(kind: WebhookKind, url: String) -> WebhookTarget {
+        WebhookTarget {
+            url,
+            kind,
+            channel: Some("#ops".to_string()),
+            username: Some("ccauto".to_string()),
+        }
+    }
+
+    /// Start a local server recording JSON bodies posted to /hook; /fail returns 500
+    async fn start_test_server() -> (String, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&received);
+        let app = Router::new()
+            .route(
+                "/hook",
+                post_route(move |Json(body): Json<Value>| async move {
+                    recorder.lock().unwrap().push(body);
+                    StatusCode::OK
+                }),
+            )
+            .route(
+                "/fail",
+                post_route(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "boom") }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}", addr), received)
+    }
+
+    #[test]
+    fn test_build_payload_slack() {
+        let payload = build_payload(&target(WebhookKind::Slack, String::new()), "hi");
+        assert_eq!(
+            payload,
+            json!({ "text": "hi", "channel": "#ops", "username": "ccauto" })
+        );
+    }
+
+    #[test]
+    fn test_build_payload_discord() {
+        let payload = build_payload(&target(WebhookKind::Discord, String::new()), "hi");
+        assert_eq!(payload, json!({ "content": "hi", "username": "ccauto" }));
+    }
+
+    #[tokio::test]
+    async fn test_post_to_local_server() {
+        let (base_url, received) = start_test_server().await;
+        let client = reqwest::Client::new();
+
+        let slack = target(WebhookKind::Slack, format!("{}/hook", base_url));
+        post(&client, &slack, "Agent agent-0: error").await.unwrap();
+
+        let bodies = received.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["text"], "Agent agent-0: error");
+        assert_eq!(bodies[0]["channel"], "#ops");
+    }
+
+    #[tokio::test]
+    async fn test_queue_posts_messages() {
+        let (base_url, received) = start_test_server().await;
+        let queue = WebhookQueue::start();
+
+        let slack = target(WebhookKind::Slack, format!("{}/hook", base_url));
+        assert!(queue.enqueue("slack-ops", &slack, "first"));
+        assert!(queue.enqueue("slack-ops", &slack, "second"));
+
+        for _ in 0..100 {
+            if received.lock().unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let texts: Vec<_> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|body| body["text"].clone())
+            .collect();
+        assert_eq!(texts, vec![json!("first"), json!("second")]);
+    }
+
+    #[tokio::test]
+    async fn test_post_reports_http_failure() {
+        let (base_url, _) = start_test_server().await;
+        let client = reqwest::Client::new();
+
+        let failing = target(WebhookKind::Discord, format!("{}/fail", base_url));
+        let error = post(&client, &failing, "msg").await.unwrap_err();
+        assert!(error.to_string().contains("500"));
+        assert!(error.to_string().contains("boom"));
+    }
+}
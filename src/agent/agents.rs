@@ -1,34 +1,143 @@
 use anyhow::Result;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::task::JoinHandle;
-
-use crate::agent::Agent;
+use tokio::time::Duration;
+
+use crate::agent::ephemeral::{self, CommandResult};
+use crate::agent::quota::CommandUsage;
+use crate::agent::resources::ResourceUsage;
+use crate::agent::restarts::RestartCounters;
+use crate::agent::spawner::AgentSpawner;
+use crate::agent::state_times::StateDurations;
+use crate::agent::{Agent, AgentStatus};
 use crate::config::Config;
+use crate::config::helper::ActionType;
 use crate::config::rules_config::Rule;
+use crate::queue::QueueManager;
+use crate::rule::RuleHit;
+use crate::terminal::process_tree::ProcessInfo;
+use crate::trigger::execute_action_with_agent;
+use crate::trigger::history::{TriggerHistory, TriggerRun};
+use crate::trigger::pause::PausedTriggers;
+
+/// Policy picking the agent for one line of an entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgentSelector<'a> {
+    /// The agent `offset` places after the agent with index `start`
+    RoundRobin { start: usize, offset: usize },
+    /// The next idle agent, or the agent with the fewest child processes
+    LeastBusy,
+    /// Always the same agent for the same key (e.g. an issue number)
+    Sticky(&'a str),
+}
+
+/// State and resource usage of one agent, as returned by GET /api/agents
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentSummary {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub rules_paused: bool,
+    /// Command running in the shell's foreground
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_command: Option<String>,
+    /// Window title set in the shell, e.g. by claude
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    /// Whether a full-screen program shows the alternate screen
+    #[serde(default)]
+    pub alternate_screen: bool,
+    /// Processes started from the shell, in tree order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<ProcessInfo>,
+    #[serde(default)]
+    pub restarts: RestartCounters,
+    #[serde(default)]
+    pub times: StateDurations,
+    /// Seconds since the shell last printed anything
+    #[serde(default)]
+    pub last_output_secs: u64,
+    /// Automated commands sent this hour, with `agents.max_commands_per_hour`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands: Option<CommandUsage>,
+    /// TERM of the shell, from `terminal.term` or the agent's override
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub term: String,
+    /// Writes queued for the shell that it hasn't read yet
+    #[serde(default)]
+    pub pending_input: usize,
+    /// Port of the agent's own web UI, unset when it is served under
+    /// `/agents/<id>/` of the single server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(flatten)]
+    pub resources: ResourceUsage,
+}
+
+/// Whether every pool agent can do its work, as returned by GET /readyz
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Problems of each agent that isn't ready, keyed by agent ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failing: BTreeMap<String, Vec<String>>,
+}
+
+impl Readiness {
+    /// Readiness of `agents`, ready when none of them has a problem
+    pub fn of<'a>(agents: impl IntoIterator<Item = &'a Arc<Agent>>) -> Self {
+        let failing: BTreeMap<_, _> = agents
+            .into_iter()
+            .map(|agent| (agent.get_id(), agent.readiness_problems()))
+            .filter(|(_, problems)| !problems.is_empty())
+            .collect();
+        Self {
+            ready: failing.is_empty(),
+            failing,
+        }
+    }
+}
 
 /// Agents responsible for managing agent pool and monitoring agents
 pub struct Agents {
+    /// Configuration the pool was created from
+    config: Config,
     rules: Vec<Rule>,
     agents: Vec<Arc<Agent>>,
     next_agent_index: AtomicUsize,
+    trigger_history: TriggerHistory,
+    paused_triggers: PausedTriggers,
+    spawner: AgentSpawner,
+    /// Queues filled by enqueue actions and drained by queue entries
+    queues: Arc<QueueManager>,
 }
 
 impl Agents {
     /// Create a new agents system from configuration
     pub async fn new(rules: Vec<Rule>, config: &Config) -> Result<Self> {
         let pool_size = config.agents.pool;
+        let queues = Arc::new(QueueManager::new());
         let mut agents = Vec::with_capacity(pool_size);
 
         for i in 0..pool_size {
-            let agent = Agent::from_config(i, config).await?;
+            let agent = Agent::from_config(i, config, Arc::clone(&queues)).await?;
             agents.push(agent);
         }
 
         Ok(Self {
+            spawner: AgentSpawner::new(config, rules.clone(), pool_size, Arc::clone(&queues)),
+            config: config.clone(),
             rules,
             agents,
             next_agent_index: AtomicUsize::new(0),
+            trigger_history: TriggerHistory::new(config.agents.trigger_history),
+            paused_triggers: PausedTriggers::default(),
+            queues,
         })
     }
 
@@ -38,22 +147,81 @@ impl Agents {
         use crate::terminal::pty_process_trait::MockPtyProcess;
 
         let pool_size = config.agents.pool;
+        let queues = Arc::new(QueueManager::new());
         let mut agents = Vec::with_capacity(pool_size);
 
         for i in 0..pool_size {
             let mock_pty = Box::new(MockPtyProcess::new());
-            let agent = Agent::new_with_process(i, config, mock_pty).await?;
+            let agent = Agent::new_with_queues(i, config, mock_pty, Arc::clone(&queues)).await?;
             agents.push(agent);
         }
 
         Ok(Self {
+            spawner: AgentSpawner::with_mock(config, rules.clone(), pool_size, Arc::clone(&queues)),
+            config: config.clone(),
             rules,
             agents,
             next_agent_index: AtomicUsize::new(0),
+            trigger_history: TriggerHistory::new(config.agents.trigger_history),
+            paused_triggers: PausedTriggers::default(),
+            queues,
         })
     }
 
+    /// Create an agents system from already created agents (for testing with shared mocks).
+    /// The pool's queues are the first agent's, so its enqueue actions reach queue entries.
+    #[cfg(test)]
+    pub fn from_agents(rules: Vec<Rule>, agents: Vec<Arc<Agent>>) -> Self {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let queues = agents
+            .first()
+            .map(|agent| Arc::clone(&agent.queues))
+            .unwrap_or_default();
+        Self {
+            spawner: AgentSpawner::with_mock(
+                &config,
+                rules.clone(),
+                agents.len(),
+                Arc::clone(&queues),
+            ),
+            config,
+            rules,
+            agents,
+            next_agent_index: AtomicUsize::new(0),
+            trigger_history: TriggerHistory::new(crate::trigger::history::DEFAULT_HISTORY_SIZE),
+            paused_triggers: PausedTriggers::default(),
+            queues,
+        }
+    }
+
+    /// Configuration the pool was created from
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Rules every pool agent checks its output against
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Periodic and cron entries paused at runtime
+    pub fn paused_triggers(&self) -> &PausedTriggers {
+        &self.paused_triggers
+    }
+
+    /// Creates temporary agents for `spawn_agent` entries
+    pub fn spawner(&self) -> &AgentSpawner {
+        &self.spawner
+    }
+
+    /// Queues filled by enqueue actions and drained by queue entries
+    pub fn queues(&self) -> &Arc<QueueManager> {
+        &self.queues
+    }
+
     /// Get the number of agents in the pool
+    #[cfg(test)]
     pub fn size(&self) -> usize {
         self.agents.len()
     }
@@ -63,16 +231,51 @@ impl Agents {
         Arc::clone(&self.agents[index % self.agents.len()])
     }
 
-    /// Get the next agent using round-robin selection
-    #[allow(dead_code)]
-    pub fn get_next_agent(&self) -> Arc<Agent> {
+    /// Find an agent by its ID (e.g. "agent-1")
+    pub fn get_agent_by_id(&self, id: &str) -> Option<Arc<Agent>> {
+        self.agents
+            .iter()
+            .find(|agent| agent.get_id() == id)
+            .cloned()
+    }
+
+    /// Whether the web UI of any agent is read-only
+    pub fn any_read_only(&self) -> bool {
+        self.agents
+            .iter()
+            .any(|agent| agent.get_config().web_read_only(&agent.get_id()))
+    }
+
+    /// Agents with `tag`, or the whole pool without one. Never empty:
+    /// config parsing rejects tags no pool agent has, so the whole pool is
+    /// the fallback.
+    pub fn tagged(&self, tag: Option<&str>) -> Vec<Arc<Agent>> {
+        let tagged: Vec<_> = self
+            .agents
+            .iter()
+            .filter(|agent| agent.has_tag(tag))
+            .cloned()
+            .collect();
+        if tagged.is_empty() {
+            self.agents.clone()
+        } else {
+            tagged
+        }
+    }
+
+    /// Get the next agent with `tag` using round-robin selection
+    pub fn get_next_agent(&self, tag: Option<&str>) -> Arc<Agent> {
         let index = self.next_agent_index.fetch_add(1, Ordering::Relaxed);
-        self.get_agent_by_index(index)
+        let total_agents = self.agents.len();
+        (0..total_agents)
+            .map(|i| self.get_agent_by_index(index + i))
+            .find(|agent| agent.has_tag(tag))
+            .unwrap_or_else(|| self.get_agent_by_index(index))
     }
 
-    /// Get the next idle agent using round-robin selection
-    /// Returns None if all agents are active
-    pub async fn get_next_idle_agent(&self) -> Option<Arc<Agent>> {
+    /// Get the next idle agent with `tag` using round-robin selection
+    /// Returns None if all of those agents are active
+    pub async fn get_next_idle_agent(&self, tag: Option<&str>) -> Option<Arc<Agent>> {
         let total_agents = self.agents.len();
         let start_index = self.next_agent_index.load(Ordering::Relaxed);
 
@@ -81,7 +284,7 @@ impl Agents {
             let index = (start_index + i) % total_agents;
             let agent = self.get_agent_by_index(index);
 
-            if !agent.is_active().await {
+            if agent.has_tag(tag) && !agent.is_active() {
                 // Update the index for next call
                 self.next_agent_index
                     .store((index + 1) % total_agents, Ordering::Relaxed);
@@ -92,6 +295,223 @@ impl Agents {
         None // All agents are active
     }
 
+    /// Pick an agent with `tag` according to `selector`. Only statuses and
+    /// the shared round-robin index are read, so concurrent callers don't
+    /// interfere.
+    pub async fn select_agent(&self, selector: AgentSelector<'_>, tag: Option<&str>) -> Arc<Agent> {
+        let candidates = self.tagged(tag);
+        let pick = |index: usize| Arc::clone(&candidates[index % candidates.len()]);
+        match selector {
+            AgentSelector::RoundRobin { start, offset } => pick(start.wrapping_add(offset)),
+            AgentSelector::LeastBusy => match self.get_next_idle_agent(tag).await {
+                Some(agent) => agent,
+                None => get_least_busy_agent(&candidates).await,
+            },
+            AgentSelector::Sticky(key) => pick(sticky_hash(key) as usize),
+        }
+    }
+
+    /// Wait until any agent with `tag` is idle and return it
+    /// Returns None if the pool is empty
+    pub async fn wait_for_idle_agent(&self, tag: Option<&str>) -> Option<Arc<Agent>> {
+        if self.agents.is_empty() {
+            return None;
+        }
+        let candidates = self.tagged(tag);
+
+        // Watch before checking so a transition in between is not missed
+        let mut receivers: Vec<_> = candidates
+            .iter()
+            .map(|agent| agent.watch_status())
+            .collect();
+        if let Some(agent) = self.get_next_idle_agent(tag).await {
+            return Some(agent);
+        }
+
+        loop {
+            // The candidates own the senders, so the channels never close here
+            let (_, index, _) = futures_util::future::select_all(
+                receivers
+                    .iter_mut()
+                    .map(|receiver| Box::pin(receiver.changed())),
+            )
+            .await;
+
+            let status = receivers[index].borrow_and_update().clone();
+            if matches!(status, AgentStatus::Idle | AgentStatus::Sleeping) {
+                return Some(Arc::clone(&candidates[index]));
+            }
+        }
+    }
+
+    /// Skip rule actions of the agent `agent_id` until resume_rules or
+    /// `rules.pause_timeout`
+    pub fn pause_rules(&self, agent_id: &str) -> Result<()> {
+        self.get_agent_by_id(agent_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown agent: {}", agent_id))?
+            .pause_rules()
+    }
+
+    /// Run rule actions of the agent `agent_id` again; returns whether they
+    /// were paused
+    pub fn resume_rules(&self, agent_id: &str) -> Result<bool> {
+        let agent = self
+            .get_agent_by_id(agent_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown agent: {}", agent_id))?;
+        Ok(agent.resume_rules())
+    }
+
+    /// Clear the restart count and last exit reason of the agent `agent_id`
+    pub fn reset_restart_counters(&self, agent_id: &str) -> Result<()> {
+        self.get_agent_by_id(agent_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown agent: {}", agent_id))?
+            .reset_restart_counters();
+        Ok(())
+    }
+
+    /// Run `command` on a throwaway PTY outside the pool and collect its
+    /// output, killing it after `timeout`
+    pub async fn run_ephemeral(&self, command: &str, timeout: Duration) -> Result<CommandResult> {
+        ephemeral::run_ephemeral(self.spawner.config(), command, timeout).await
+    }
+
+    /// Send the key sequences `keys` to every pool agent, with the usual
+    /// delay between sequences. Agents are sent to concurrently, and one
+    /// failing agent doesn't stop the others: the error lists every agent
+    /// that failed. Returns the number of agents reached.
+    pub async fn broadcast_keys(&self, keys: &[String]) -> Result<usize> {
+        let action = ActionType::SendKeys(keys.to_vec());
+        let results = futures_util::future::join_all(self.agents.iter().map(|agent| {
+            let action = &action;
+            async move {
+                let result = execute_action_with_agent(action, agent, "Broadcast").await;
+                (agent.get_id(), result)
+            }
+        }))
+        .await;
+
+        let failures: Vec<String> = results
+            .iter()
+            .filter_map(|(id, result)| result.as_ref().err().map(|e| format!("{}: {:#}", id, e)))
+            .collect();
+        if !failures.is_empty() {
+            anyhow::bail!(
+                "Failed to send keys to {} of {} agents: {}",
+                failures.len(),
+                results.len(),
+                failures.join("; ")
+            );
+        }
+        Ok(results.len())
+    }
+
+    /// Get rule hit statistics for every agent, keyed by agent ID
+    pub fn rule_stats(&self) -> BTreeMap<String, Vec<RuleHit>> {
+        self.agents
+            .iter()
+            .map(|agent| (agent.get_id(), agent.get_rule_stats().snapshot()))
+            .collect()
+    }
+
+    /// Get the status and resource usage of every agent, keyed by agent ID
+    pub fn summaries(&self) -> BTreeMap<String, AgentSummary> {
+        self.agents
+            .iter()
+            .map(|agent| {
+                let summary = AgentSummary {
+                    status: format!("{:?}", agent.get_status()),
+                    tags: agent.get_tags().to_vec(),
+                    rules_paused: agent.are_rules_paused(),
+                    current_command: agent.get_current_command(),
+                    title: agent.get_title(),
+                    alternate_screen: agent.is_alternate_screen(),
+                    processes: agent.get_process_tree(),
+                    restarts: agent.get_restart_counters(),
+                    times: agent.get_state_durations(),
+                    last_output_secs: agent.get_last_output().elapsed().as_secs(),
+                    commands: agent.get_command_usage(),
+                    term: agent.get_config().terminal_for(&agent.get_id()).term,
+                    pending_input: agent.get_pending_input(),
+                    port: agent.get_web_port(),
+                    resources: agent.get_resource_usage(),
+                };
+                (agent.get_id(), summary)
+            })
+            .collect()
+    }
+
+    /// Readiness of the pool agents. Spawned agents are left out, as they
+    /// are torn down (monitors first) when their work is done.
+    pub fn readiness(&self) -> Readiness {
+        Readiness::of(&self.agents)
+    }
+
+    /// Stop the processes of every pool and spawned agent, giving them
+    /// `grace` to exit on SIGTERM before they are killed
+    pub async fn terminate_all(&self, grace: Duration) {
+        let agents = self
+            .agents
+            .iter()
+            .cloned()
+            .chain(self.spawner.live_agents());
+        futures_util::future::join_all(agents.map(|agent| async move {
+            agent.terminate_processes(grace).await;
+            agent.finish_recording().await;
+        }))
+        .await;
+    }
+
+    /// Start the pool agents' own web servers, in `web_ui.mode: per_agent`,
+    /// each serving the `control` API too. A port in use fails the pool,
+    /// unless `web_ui.port_fallback` finds another one.
+    pub async fn start_web_servers(self: &Arc<Self>, control: &Router) -> Result<()> {
+        for agent in &self.agents {
+            agent
+                .setup_web_server_if_enabled(Arc::downgrade(self), control.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Shut down the agents' own web servers gracefully, all at once
+    pub async fn stop_web_servers(&self) {
+        let agents = self
+            .agents
+            .iter()
+            .cloned()
+            .chain(self.spawner.live_agents());
+        futures_util::future::join_all(agents.map(|agent| async move {
+            agent.stop_web_server().await;
+        }))
+        .await;
+    }
+
+    /// Recent runs of trigger entries executed on this pool
+    pub fn trigger_history(&self) -> &TriggerHistory {
+        &self.trigger_history
+    }
+
+    /// Get the recorded trigger runs, keyed by trigger name
+    pub fn trigger_runs(&self) -> BTreeMap<String, Vec<TriggerRun>> {
+        self.trigger_history.snapshot()
+    }
+
+    /// Get the number of rate-limited rule actions for every agent, keyed by agent ID
+    pub fn dropped_actions(&self) -> BTreeMap<String, u64> {
+        self.agents
+            .iter()
+            .map(|agent| (agent.get_id(), agent.get_rule_stats().dropped_actions()))
+            .collect()
+    }
+
+    /// Get the number of output chunks rule monitors missed for every agent, keyed by agent ID
+    pub fn dropped_output(&self) -> BTreeMap<String, u64> {
+        self.agents
+            .iter()
+            .map(|agent| (agent.get_id(), agent.get_rule_stats().dropped_output()))
+            .collect()
+    }
+
     /// Start all monitoring systems: agent monitors with timeout monitoring per agent
     pub async fn start_all(&self) -> Result<Vec<JoinHandle<()>>> {
         let mut monitoring_handles = Vec::new();
@@ -108,6 +528,29 @@ impl Agents {
     }
 }
 
+/// The agent among `candidates` with the fewest child processes; the first
+/// one on ties
+async fn get_least_busy_agent(candidates: &[Arc<Agent>]) -> Arc<Agent> {
+    let mut best = (usize::MAX, 0);
+    for (index, agent) in candidates.iter().enumerate() {
+        let children = match agent.get_process().get_child_processes().await {
+            Ok(children) => children.len(),
+            Err(_) => continue,
+        };
+        if children < best.0 {
+            best = (children, index);
+        }
+    }
+    Arc::clone(&candidates[best.1])
+}
+
+/// FNV-1a, so a key maps to the same agent across runs and Rust versions
+fn sticky_hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,8 +632,8 @@ mod tests {
         assert!(result.is_ok(), "start_all should succeed with empty rules");
 
         let handles = result.unwrap();
-        // Should have 3 handles per agent (status monitoring, when monitoring, diff_timeout monitoring)
-        let expected_handles = config.agents.pool * 3;
+        // Should have 4 handles per agent (status, when, diff_timeout and respawn monitoring)
+        let expected_handles = config.agents.pool * 4;
         assert_eq!(handles.len(), expected_handles);
 
         // Clean up by aborting all handles
@@ -208,14 +651,14 @@ mod tests {
         let mut config = Config::default();
         config.web_ui.enabled = false; // Disable WebUI to avoid port conflicts
         let rules = vec![
-            Rule {
-                rule_type: RuleType::When(Regex::new("test").unwrap()),
-                action: ActionType::SendKeys(vec!["echo".to_string()]),
-            },
-            Rule {
-                rule_type: RuleType::DiffTimeout(std::time::Duration::from_secs(1)),
-                action: ActionType::SendKeys(vec!["timeout".to_string()]),
-            },
+            Rule::new(
+                RuleType::When(Regex::new("test").unwrap()),
+                ActionType::SendKeys(vec!["echo".to_string()]),
+            ),
+            Rule::new(
+                RuleType::DiffTimeout(std::time::Duration::from_secs(1)),
+                ActionType::SendKeys(vec!["timeout".to_string()]),
+            ),
         ];
 
         let agents = Agents::new_with_mock(rules, &config).await.unwrap();
@@ -224,8 +667,8 @@ mod tests {
         assert!(result.is_ok(), "start_all should succeed with rules");
 
         let handles = result.unwrap();
-        // Should have 3 handles per agent (status monitoring, when monitoring, diff_timeout monitoring)
-        let expected_handles = config.agents.pool * 3;
+        // Should have 4 handles per agent (status, when, diff_timeout and respawn monitoring)
+        let expected_handles = config.agents.pool * 4;
         assert_eq!(handles.len(), expected_handles);
 
         // Clean up by aborting all handles
@@ -253,4 +696,140 @@ mod tests {
         assert_eq!(agent1.get_id(), "agent-0"); // Should wrap to 0
         assert_eq!(agent10.get_id(), "agent-0"); // Should wrap to 0
     }
+
+    #[tokio::test]
+    async fn test_select_agent_policies() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.pool = 3;
+        let agents = Arc::new(Agents::new_with_mock(vec![], &config).await.unwrap());
+        agents.get_agent_by_index(0).set_status(AgentStatus::Active);
+        agents.get_agent_by_index(2).set_status(AgentStatus::Active);
+
+        let round_robin = AgentSelector::RoundRobin {
+            start: 2,
+            offset: 2,
+        };
+        assert_eq!(
+            agents.select_agent(round_robin, None).await.get_id(),
+            "agent-1"
+        );
+
+        // The only idle agent, also when selecting concurrently
+        let picks = futures_util::future::join_all(
+            (0..8).map(|_| agents.select_agent(AgentSelector::LeastBusy, None)),
+        )
+        .await;
+        assert!(picks.iter().all(|agent| agent.get_id() == "agent-1"));
+
+        // All busy: fewest child processes (all equal with mocks) wins
+        agents.get_agent_by_index(1).set_status(AgentStatus::Active);
+        let agent = agents.select_agent(AgentSelector::LeastBusy, None).await;
+        assert_eq!(agent.get_id(), "agent-0");
+
+        // Sticky ignores status and maps a key to the same agent every time
+        let first = agents
+            .select_agent(AgentSelector::Sticky("#42"), None)
+            .await;
+        for _ in 0..5 {
+            let again = agents
+                .select_agent(AgentSelector::Sticky("#42"), None)
+                .await;
+            assert_eq!(again.get_id(), first.get_id());
+        }
+        let mut used = std::collections::BTreeSet::new();
+        for issue in 0..30 {
+            let key = format!("#{}", issue);
+            used.insert(
+                agents
+                    .select_agent(AgentSelector::Sticky(&key), None)
+                    .await
+                    .get_id(),
+            );
+        }
+        assert_eq!(used.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_select_agent_by_tag() {
+        use crate::config::agents_config::AgentOverride;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.pool = 4;
+        for id in ["agent-1", "agent-3"] {
+            let tags = vec!["claude".to_string()];
+            let agent = AgentOverride {
+                tags,
+                ..Default::default()
+            };
+            config.agents.agent_overrides.insert(id.to_string(), agent);
+        }
+        let agents = Agents::new_with_mock(vec![], &config).await.unwrap();
+        let claude = Some("claude");
+        assert_eq!(agents.get_agent_by_index(1).get_tags(), ["claude"]);
+        assert_eq!(agents.tagged(claude).len(), 2);
+        assert_eq!(agents.tagged(None).len(), 4);
+
+        for _ in 0..4 {
+            assert!(agents.get_next_agent(claude).has_tag(claude));
+        }
+        let round_robin = AgentSelector::RoundRobin {
+            start: 0,
+            offset: 1,
+        };
+        let agent = agents.select_agent(round_robin, claude).await;
+        assert_eq!(agent.get_id(), "agent-3");
+        let agent = agents
+            .select_agent(AgentSelector::Sticky("#7"), claude)
+            .await;
+        assert!(agent.has_tag(claude));
+
+        // Untagged idle agents don't count
+        agents.get_agent_by_index(1).set_status(AgentStatus::Active);
+        let agent = agents.get_next_idle_agent(claude).await.unwrap();
+        assert_eq!(agent.get_id(), "agent-3");
+        agents.get_agent_by_index(3).set_status(AgentStatus::Active);
+        assert!(agents.get_next_idle_agent(claude).await.is_none());
+        let agent = agents.select_agent(AgentSelector::LeastBusy, claude).await;
+        assert_eq!(agent.get_id(), "agent-1");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_keys_reports_all_failures() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mocks = [
+            Arc::new(MockPtyProcess::new()),
+            Arc::new(MockPtyProcess::with_failure()),
+            Arc::new(MockPtyProcess::new()),
+        ];
+        let mut pool = Vec::new();
+        for (i, mock) in mocks.iter().enumerate() {
+            let process = Box::new(Arc::clone(mock));
+            pool.push(Agent::new_with_process(i, &config, process).await.unwrap());
+        }
+        let agents = Agents::from_agents(vec![], pool);
+        let keys = ["export FOO=1".to_string(), "\r".to_string()];
+
+        let error = agents.broadcast_keys(&keys).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to send keys to 1 of 3 agents: agent-1: Failed to send keys: PTY process communication error: Mock failure"
+        );
+        // The failing agent didn't stop the one after it
+        assert_eq!(mocks[0].get_sent_inputs(), keys);
+        assert_eq!(mocks[2].get_sent_inputs(), keys);
+
+        let agents = Agents::from_agents(vec![], vec![agents.get_agent_by_index(0)]);
+        assert_eq!(agents.broadcast_keys(&keys).await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_sticky_hash_is_stable() {
+        assert_eq!(sticky_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(sticky_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
 }
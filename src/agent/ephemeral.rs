@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::terminal::ansi::strip_ansi;
+use crate::terminal::process_tree;
+use crate::terminal::pty_session::{PtyEventData, PtySession};
+
+/// Outcome of a command run on a throwaway PTY
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandResult {
+    /// Everything the command printed, without colors and with `\n` line ends
+    pub output: String,
+    /// None if the command was killed, by a signal or for taking too long
+    pub exit_code: Option<u32>,
+    pub timed_out: bool,
+}
+
+/// Run `command` with `sh -c` on a PTY of its own, outside the agent pool,
+/// and collect its output once it exits. After `timeout` the command and
+/// everything it started are killed, and the output so far is returned.
+pub async fn run_ephemeral(
+    config: &Config,
+    command: &str,
+    timeout: Duration,
+) -> Result<CommandResult> {
+    let argv = ["sh".to_string(), "-c".to_string(), command.to_string()];
+    let session = PtySession::new(
+        &argv,
+        config.web_ui.cols as usize,
+        config.web_ui.rows as usize,
+        &config.terminal,
+    )
+    .await
+    .with_context(|| format!("Failed to start '{}'", command))?;
+    let mut events = session.subscribe().await;
+
+    let deadline = Instant::now() + timeout;
+    let mut output = String::new();
+    let ended = loop {
+        match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Ok(event)) => match event.data {
+                PtyEventData::Output { data } => output.push_str(&data),
+                data if data.exit_reason().is_some() => break Some(data),
+                _ => {}
+            },
+            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                tracing::warn!(
+                    "Ephemeral run of '{}' lost {} output chunks",
+                    command,
+                    skipped
+                );
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => {
+                anyhow::bail!("PTY of '{}' closed without an exit status", command)
+            }
+            Err(_) => break None,
+        }
+    };
+
+    let timed_out = ended.is_none();
+    if timed_out && let Ok(Some(shell_pid)) = session.get_shell_pid().await {
+        let mut pids = process_tree::shell_processes(shell_pid);
+        pids.push(shell_pid);
+        process_tree::signal_all(&pids, Signal::SIGKILL);
+    }
+    let exit_code = match ended {
+        Some(PtyEventData::ChildExited {
+            exit_code,
+            signal: None,
+        }) => Some(exit_code),
+        _ => None,
+    };
+
+    Ok(CommandResult {
+        output: strip_ansi(&output).replace("\r\n", "\n"),
+        exit_code,
+        timed_out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_collects_output_and_exit_code() {
+        let config = Config::default();
+        let result = run_ephemeral(&config, "echo hello; exit 3", Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result,
+            CommandResult {
+                output: "hello\n".to_string(),
+                exit_code: Some(3),
+                timed_out: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out() {
+        let config = Config::default();
+        let started = std::time::Instant::now();
+        let result = run_ephemeral(
+            &config,
+            "echo started; sleep 30",
+            Duration::from_millis(500),
+        )
+        .await
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(result.timed_out);
+        assert_eq!(result.exit_code, None);
+        assert_eq!(result.output, "started\n");
+    }
+}
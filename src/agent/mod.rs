@@ -1,74 +1,370 @@
 pub mod agents;
-
+pub mod ephemeral;
+pub mod pty_log;
+pub mod quota;
+pub mod recording;
+pub mod resources;
+pub mod respawn;
+pub mod restarts;
+pub mod spawner;
+pub mod state_times;
+
+use crate::action::webhook::WebhookQueue;
 use crate::config::Config;
-use crate::config::rules_config::Rule;
+use crate::config::helper::ActionType;
+use crate::config::rules_config::{Rule, RuleType};
+use crate::config::triggers_config::{WaitCondition, WaitFor};
+use crate::config::web_ui_config::WebUIMode;
+use crate::metrics;
+use crate::queue::QueueManager;
 use crate::rule::RuleProcessor;
-use crate::rule::{DiffTimeout, When};
+use crate::rule::groups::RuleGroups;
+use crate::rule::{DiffTimeout, RuleStats, Title, When};
+use crate::template::{TemplateContext, resolve_action};
+use crate::terminal::paste;
+use crate::terminal::process_tree::{self, ProcessInfo};
 use crate::terminal::pty_process::PtyProcess;
 use crate::terminal::pty_process_trait::PtyProcessTrait;
+use crate::terminal::pty_session::{PtyEvent, PtyEventData};
+use crate::terminal::secret;
 use crate::web_server::WebServer;
-use anyhow::Result;
-use std::sync::Arc;
-use std::sync::RwLock;
-use tokio::task::JoinHandle;
+use crate::web_server::server::{ServerHandle, WebTarget};
+use anyhow::{Context, Result};
+use axum::Router;
+use nix::sys::signal::Signal;
+use pty_log::PtyLog;
+use quota::{CommandQuota, CommandUsage};
+use recording::{Recorder, Recording};
+use resources::{ResourceSampler, ResourceUsage};
+use respawn::{RespawnPolicy, respawn_on_exit};
+use restarts::RestartCounters;
+use state_times::{StateDurations, StateTimes};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Instant;
+use tokio::sync::{broadcast, watch};
+use tokio::task::{AbortHandle, JoinHandle};
 use tokio::time::Duration;
 
 /// Agent status for state management
 #[derive(Debug, Clone, PartialEq)]
-enum AgentStatus {
-    Idle,   // Waiting and monitoring triggers
-    Active, // Executing tasks and monitoring rules
+pub enum AgentStatus {
+    Idle,     // Waiting and monitoring triggers
+    Active,   // Executing tasks and monitoring rules
+    Stuck,    // Executing tasks without output for `agents.stuck_after`
+    Error,    // Shell exited, waiting for a respawn
+    Sleeping, // Shell shut down after `agents.idle_timeout`, woken on demand
+}
+
+impl AgentStatus {
+    /// Status of a shell that is (not) `exited`, has `running` child
+    /// processes and printed nothing for `silent_for`
+    pub fn derive(
+        exited: bool,
+        running: bool,
+        silent_for: Duration,
+        stuck_after: Duration,
+    ) -> Self {
+        match (exited, running) {
+            (true, _) => AgentStatus::Error,
+            (false, false) => AgentStatus::Idle,
+            (false, true) if silent_for >= stuck_after => AgentStatus::Stuck,
+            (false, true) => AgentStatus::Active,
+        }
+    }
+
+    /// Whether the agent is executing a task (Active or Stuck)
+    pub fn is_busy(&self) -> bool {
+        matches!(self, AgentStatus::Active | AgentStatus::Stuck)
+    }
 }
 
+/// Buffered status transitions per subscriber
+const STATUS_CHANNEL_CAPACITY: usize = 16;
+
+/// Largest number of columns or rows a terminal can be resized to
+const MAX_TERMINAL_SIZE: u16 = 1000;
+
 pub struct Agent {
     index: usize,
     process: Box<dyn PtyProcessTrait>,
     config: Config,
-    status: RwLock<AgentStatus>,
-    web_server_handle: RwLock<Option<JoinHandle<()>>>,
+    /// Terminal size (cols, rows), from the config until resized
+    terminal_size: Mutex<(u16, u16)>,
+    /// Current status; a watch sender so updates are atomic and never block
+    status: watch::Sender<AgentStatus>,
+    /// Every status transition, for consumers that must see each edge, like
+    /// on_idle entries and recordings. A watch keeps only the latest status,
+    /// so an Active period shorter than a consumer's wakeup would vanish.
+    status_sender: broadcast::Sender<AgentStatus>,
+    rule_stats: Arc<RuleStats>,
+    rule_groups: RuleGroups,
+    web_server_handle: RwLock<Option<ServerHandle>>,
+    /// Port of the agent's own web server, once it listens
+    web_port: Mutex<Option<u16>>,
+    /// Time of the last output, the clock of stuck detection and diff_timeout rules
+    last_output: Mutex<Instant>,
+    /// Status transition timestamps, updated with the status
+    state_times: Mutex<StateTimes>,
+    shell_exited: AtomicBool,
+    resources: Mutex<ResourceSampler>,
+    /// Whether the current Stuck period was alerted
+    stuck_alerted: AtomicBool,
+    on_stuck: Option<ActionType>,
+    /// Processes started from the shell as of the last status monitor sample
+    processes: Mutex<Vec<ProcessInfo>>,
+    /// Foreground command as of the last status monitor sample
+    current_command: Mutex<Option<String>>,
+    /// Whether the shell runs a command according to its OSC 133 marks;
+    /// `None` until the current shell sent one
+    command_running: Mutex<Option<bool>>,
+    /// Window title set by programs in the shell (OSC 0/2)
+    title: watch::Receiver<String>,
+    /// Whether a full-screen program in the shell shows the alternate screen
+    alternate_screen: watch::Receiver<bool>,
+    restarts: Mutex<RestartCounters>,
+    command_quota: Mutex<CommandQuota>,
+    shutting_down: AtomicBool,
+    tags: Vec<String>,
+    /// Rule actions are skipped while set, e.g. during manual intervention
+    rules_paused: AtomicBool,
+    /// When paused rules resume on their own
+    rules_resume_at: Mutex<Option<Instant>>,
+    sleeping: AtomicBool,
+    /// Serializes sleep and wake so keys never hit a stopped shell
+    wake_lock: tokio::sync::Mutex<()>,
+    /// Session recording, when enabled
+    recorder: Mutex<Option<Recorder>>,
+    /// Tasks started by setup_monitoring, by name, checked for readiness
+    monitors: Mutex<Vec<(&'static str, AbortHandle)>>,
+    /// Messages of webhook actions waiting to be posted
+    webhook_queue: WebhookQueue,
+    /// Queues of the pool, filled by enqueue actions
+    queues: Arc<QueueManager>,
 }
 
 impl Agent {
     /// Create a new agent from configuration, handling web server setup
-    pub async fn from_config(index: usize, config: &Config) -> Result<Arc<Self>> {
-        let process = PtyProcess::from_config(config);
+    pub async fn from_config(
+        index: usize,
+        config: &Config,
+        queues: Arc<QueueManager>,
+    ) -> Result<Arc<Self>> {
+        let process = PtyProcess::from_config(config, &format!("agent-{}", index));
 
         // Start the PTY process
         process.start().await?;
 
-        Self::new_with_process(index, config, Box::new(process)).await
+        let agent = Self::new_with_queues(index, config, Box::new(process), queues).await?;
+        agent
+            .run_init()
+            .await
+            .with_context(|| format!("Init of agent {} failed", agent.get_id()))?;
+        Ok(agent)
     }
 
-    /// Create a new agent with a specific PTY process (for testing with mocks)
+    /// Send the configured `init_keys` to the shell, waiting for
+    /// `init_wait_for` before the first sequence and after each one so
+    /// entries can't interleave with the init. Agents created with
+    /// `new_with_process` only run this when called explicitly.
+    pub async fn run_init(&self) -> Result<()> {
+        let keys = self.config.agents.init_keys(&self.get_id());
+        if keys.is_empty() {
+            return Ok(());
+        }
+        if self.is_dry_run() {
+            tracing::info!("Agent {} init: WOULD SEND: {:?}", self.get_id(), keys);
+            return Ok(());
+        }
+
+        let wait = self.config.agents.init_wait_for()?;
+        tracing::info!(
+            "Agent {}: sending {} init key sequences",
+            self.get_id(),
+            keys.len()
+        );
+        if let Some(wait) = &wait {
+            crate::trigger::wait::wait_for(self, wait).await?;
+        }
+        for (i, key) in keys.iter().enumerate() {
+            match &wait {
+                Some(WaitFor {
+                    condition: WaitCondition::Pattern(pattern),
+                    timeout,
+                }) => crate::trigger::wait::send_and_wait(self, key, pattern, *timeout).await?,
+                _ => {
+                    if i > 0 {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                    self.send_keys(key).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a new agent with a specific PTY process and its own queues
+    /// (for testing with mocks)
+    #[cfg(test)]
     pub async fn new_with_process(
         index: usize,
         config: &Config,
         process: Box<dyn PtyProcessTrait>,
     ) -> Result<Arc<Self>> {
+        Self::new_with_queues(index, config, process, Arc::default()).await
+    }
+
+    /// Create a new agent with a specific PTY process, enqueueing to `queues`
+    pub async fn new_with_queues(
+        index: usize,
+        config: &Config,
+        process: Box<dyn PtyProcessTrait>,
+        queues: Arc<QueueManager>,
+    ) -> Result<Arc<Self>> {
+        let title = process.get_title_receiver().await?;
+        let alternate_screen = process.get_alternate_screen_receiver().await?;
         let agent = Arc::new(Agent {
             index,
             process,
             config: config.clone(),
-            status: RwLock::new(AgentStatus::Idle),
+            terminal_size: Mutex::new((config.web_ui.cols, config.web_ui.rows)),
+            status: watch::Sender::new(AgentStatus::Idle),
+            status_sender: broadcast::channel(STATUS_CHANNEL_CAPACITY).0,
+            rule_stats: Arc::new(RuleStats::for_agent(&format!("agent-{}", index))),
+            rule_groups: RuleGroups::new(config.default_active_groups()),
             web_server_handle: RwLock::new(None),
+            web_port: Mutex::new(None),
+            last_output: Mutex::new(Instant::now()),
+            state_times: Mutex::new(StateTimes::new(AgentStatus::Idle, Instant::now())),
+            shell_exited: AtomicBool::new(false),
+            resources: Mutex::new(ResourceSampler::new()),
+            processes: Mutex::new(Vec::new()),
+            current_command: Mutex::new(None),
+            command_running: Mutex::new(None),
+            title,
+            alternate_screen,
+            restarts: Mutex::new(RestartCounters::default()),
+            command_quota: Mutex::new(CommandQuota::new(config.agents.max_commands_per_hour)),
+            stuck_alerted: AtomicBool::new(false),
+            on_stuck: config.agents.on_stuck()?,
+            shutting_down: AtomicBool::new(false),
+            tags: config.agents.tags(&format!("agent-{}", index)).to_vec(),
+            rules_paused: AtomicBool::new(false),
+            rules_resume_at: Mutex::new(None),
+            sleeping: AtomicBool::new(false),
+            wake_lock: tokio::sync::Mutex::new(()),
+            recorder: Mutex::new(None),
+            monitors: Mutex::new(Vec::new()),
+            webhook_queue: WebhookQueue::start(),
+            queues,
         });
 
-        // Start web server if enabled
-        agent.setup_web_server_if_enabled().await?;
+        metrics::metrics().set_agent_status(&agent.get_id(), &AgentStatus::Idle);
 
         Ok(agent)
     }
 
+    /// Send keys to the shell, waking a sleeping agent first. Keys written
+    /// as `<paste>...</paste>` are pasted. Keys written as
+    /// `<secret>...</secret>` are sent without the markers and redacted
+    /// from logs and recordings from then on.
     pub async fn send_keys(&self, keys: &str) -> Result<()> {
+        let keys = match secret::secret_token(keys) {
+            Some(keys) => {
+                secret::registry().remember(paste::paste_token(keys).unwrap_or(keys));
+                keys
+            }
+            None => keys,
+        };
+        if let Some(text) = paste::paste_token(keys) {
+            return self.paste(text).await;
+        }
+        self.wake_if_sleeping().await?;
         self.get_process()
             .send_input(keys.to_string())
             .await
             .map_err(|e| anyhow::anyhow!("Failed to send keys: {}", e))
     }
 
+    /// Paste `text` into the shell like a terminal would, as one bracketed
+    /// paste if the program in the foreground asked for it
+    pub async fn paste(&self, text: &str) -> Result<()> {
+        // The woken shell's program decides about bracketed paste
+        self.wake_if_sleeping().await?;
+        let bracketed = self
+            .get_process()
+            .get_screen_text()
+            .await
+            .is_ok_and(|screen| screen.bracketed_paste);
+        self.get_process()
+            .send_input(paste::paste_input(text, bracketed))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to paste: {}", e))
+    }
+
+    /// Send `signal` to the program in the foreground of the shell. A
+    /// sleeping agent runs nothing, so it is left asleep.
+    pub async fn send_signal(&self, signal: Signal) -> Result<()> {
+        if self.is_sleeping() {
+            tracing::info!("Agent {} is sleeping, {} not sent", self.get_id(), signal);
+            return Ok(());
+        }
+        self.get_process()
+            .send_signal(signal)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send {}: {}", signal, e))
+    }
+
+    async fn wake_if_sleeping(&self) -> Result<()> {
+        if self.is_sleeping() {
+            // Boxed: waking sends the init keys through send_keys again
+            Box::pin(self.wake()).await?;
+        }
+        Ok(())
+    }
+
     /// Get terminal dimensions for asciinema integration
     pub fn get_terminal_dimensions(&self) -> (u16, u16) {
-        (self.config.web_ui.cols, self.config.web_ui.rows)
+        *self.terminal_size.lock().unwrap()
+    }
+
+    /// Resize the agent's terminal; web UI clients follow the new size
+    pub async fn resize(&self, cols: u16, rows: u16) -> Result<()> {
+        if !(1..=MAX_TERMINAL_SIZE).contains(&cols) || !(1..=MAX_TERMINAL_SIZE).contains(&rows) {
+            anyhow::bail!(
+                "Invalid terminal size {}x{}, expected 1 to {} columns and rows",
+                cols,
+                rows,
+                MAX_TERMINAL_SIZE
+            );
+        }
+        self.get_process()
+            .resize(cols, rows)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to resize terminal: {}", e))?;
+        *self.terminal_size.lock().unwrap() = (cols, rows);
+        tracing::info!(
+            "📐 Agent {} terminal resized to {}x{}",
+            self.get_id(),
+            cols,
+            rows
+        );
+        Ok(())
+    }
+
+    /// Get the configuration this agent was created with
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Check if actions should only be logged instead of sent (dry-run mode)
+    pub fn is_dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
+    /// Get the agent's position in the pool
+    pub fn get_index(&self) -> usize {
+        self.index
     }
 
     /// Get agent ID
@@ -76,59 +372,342 @@ impl Agent {
         format!("agent-{}", self.index)
     }
 
+    /// Get the tags configured in `agent_overrides`
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Whether entries and rules limited to `tag` apply to this agent; any
+    /// agent matches no tag
+    pub fn has_tag(&self, tag: Option<&str>) -> bool {
+        tag.is_none_or(|tag| self.tags.iter().any(|t| t == tag))
+    }
+
+    /// Skip rule actions until resume_rules or, unless it is `0s`,
+    /// `rules.pause_timeout` from now. Matching and statistics go on.
+    pub fn pause_rules(&self) -> Result<()> {
+        let timeout = self.config.rules.pause_timeout()?;
+        *self.rules_resume_at.lock().unwrap() = timeout.map(|timeout| Instant::now() + timeout);
+        self.rules_paused.store(true, Ordering::SeqCst);
+        tracing::info!("⏸️ Agent {} rules paused for {:?}", self.get_id(), timeout);
+        Ok(())
+    }
+
+    /// Run rule actions again; returns whether rules were paused
+    pub fn resume_rules(&self) -> bool {
+        let was_paused = self.rules_paused.swap(false, Ordering::SeqCst);
+        if was_paused {
+            tracing::info!("▶️ Agent {} rules resumed", self.get_id());
+        }
+        was_paused
+    }
+
+    /// Whether rule actions are paused, resuming them once the pause timed out
+    pub fn are_rules_paused(&self) -> bool {
+        if !self.rules_paused.load(Ordering::SeqCst) {
+            return false;
+        }
+        let timed_out = self
+            .rules_resume_at
+            .lock()
+            .unwrap()
+            .is_some_and(|resume_at| Instant::now() >= resume_at);
+        if timed_out {
+            self.resume_rules();
+        }
+        !timed_out
+    }
+
+    /// Get the rule hit statistics for this agent
+    pub fn get_rule_stats(&self) -> Arc<RuleStats> {
+        Arc::clone(&self.rule_stats)
+    }
+
+    /// Get the set of active rule groups for this agent
+    pub fn get_rule_groups(&self) -> &RuleGroups {
+        &self.rule_groups
+    }
+
+    /// Get the queue webhook actions of this agent post through
+    pub fn get_webhook_queue(&self) -> &WebhookQueue {
+        &self.webhook_queue
+    }
+
+    /// Get the queues enqueue actions of this agent fill
+    pub fn get_queues(&self) -> &QueueManager {
+        &self.queues
+    }
+
     /// Get access to the PTY process
     pub fn get_process(&self) -> &dyn PtyProcessTrait {
         self.process.as_ref()
     }
 
-    /// Check if the agent is currently active (true = Active, false = Idle)
-    pub async fn is_active(&self) -> bool {
-        matches!(*self.status.read().unwrap(), AgentStatus::Active)
+    /// Check if the agent is currently busy (true = Active or Stuck)
+    pub fn is_active(&self) -> bool {
+        self.get_status().is_busy()
     }
 
-    /// Set the status of the agent
-    async fn set_status(&self, new_status: AgentStatus) {
-        // First check if status actually needs to change (read lock only)
-        let needs_update =
-            match tokio::time::timeout(tokio::time::Duration::from_millis(50), async {
-                self.status.read().map_err(|_| ())
-            })
-            .await
-            {
-                Ok(Ok(current_status)) => *current_status != new_status,
-                _ => {
-                    tracing::warn!(
-                        "Status read timeout for agent {}, assuming update needed",
-                        self.get_id()
-                    );
-                    true
-                }
-            };
+    /// Get the current status of the agent
+    pub fn get_status(&self) -> AgentStatus {
+        self.status.borrow().clone()
+    }
 
-        // Only acquire write lock if status needs to change
-        if needs_update {
-            match tokio::time::timeout(tokio::time::Duration::from_millis(100), async {
-                self.status.write().map_err(|_| ())
-            })
+    /// Whether terminate_processes was called; the shell stays dead
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Whether the shell was shut down after `agents.idle_timeout`
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping.load(Ordering::SeqCst)
+    }
+
+    /// Shut the shell down until the agent is needed again
+    pub async fn sleep(&self) -> Result<()> {
+        let _guard = self.wake_lock.lock().await;
+        if self.is_sleeping() {
+            return Ok(());
+        }
+        // Set first so the respawn monitor leaves the exiting shell alone
+        self.sleeping.store(true, Ordering::SeqCst);
+        if let Err(e) = self.get_process().stop().await {
+            self.sleeping.store(false, Ordering::SeqCst);
+            anyhow::bail!("Failed to stop shell of agent {}: {}", self.get_id(), e);
+        }
+        self.set_status(AgentStatus::Sleeping);
+        Ok(())
+    }
+
+    /// Start a new shell for a sleeping agent and send its init keys again
+    pub async fn wake(&self) -> Result<()> {
+        let _guard = self.wake_lock.lock().await;
+        if !self.is_sleeping() {
+            return Ok(());
+        }
+        tracing::info!("⏰ Waking agent {}", self.get_id());
+        self.get_process()
+            .restart()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to wake agent {}: {}", self.get_id(), e))?;
+        self.record_restart();
+        self.shell_exited.store(false, Ordering::SeqCst);
+        self.sleeping.store(false, Ordering::SeqCst);
+        self.set_status(AgentStatus::Idle);
+        self.run_init()
             .await
+            .with_context(|| format!("Init of woken agent {} failed", self.get_id()))
+    }
+
+    /// Put the agent to sleep once it has been Idle for `idle_timeout`
+    async fn sleep_if_idle(&self, idle_timeout: Duration) {
+        if self.get_status() != AgentStatus::Idle
+            || self.state_times.lock().unwrap().since().elapsed() < idle_timeout
+        {
+            return;
+        }
+        tracing::info!(
+            "💤 Agent {} idle for {:?}, shutting its shell down",
+            self.get_id(),
+            idle_timeout
+        );
+        if let Err(e) = self.sleep().await {
+            tracing::error!("❌ {}", e);
+        }
+    }
+
+    /// Stop everything started from the shell: SIGTERM first, SIGKILL for
+    /// whatever is left after `grace`, then the shell itself
+    pub async fn terminate_processes(&self, grace: Duration) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let Ok(Some(shell_pid)) = self.get_process().get_shell_pid().await else {
+            return;
+        };
+
+        let pids = process_tree::shell_processes(shell_pid);
+        if !pids.is_empty() {
+            tracing::info!(
+                "Stopping {} processes of agent {}",
+                pids.len(),
+                self.get_id()
+            );
+            // Leaf-first, so parents can't restart what was just stopped
+            if let Err(e) = self
+                .get_process()
+                .kill_children(Signal::SIGTERM, true)
+                .await
             {
-                Ok(Ok(mut status)) => {
-                    *status = new_status.clone();
-                    tracing::debug!("🔄 Agent {} → {:?}", self.get_id(), new_status);
-                }
-                _ => {
-                    tracing::error!("Status write timeout for agent {}", self.get_id());
-                }
+                tracing::warn!("Failed to stop processes of agent {}: {}", self.get_id(), e);
+            }
+
+            let deadline = Instant::now() + grace;
+            while pids.iter().any(|pid| process_tree::is_alive(*pid)) && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(50)).await;
             }
         }
+
+        // Also catches processes forked while the others were stopping
+        let mut left = process_tree::shell_processes(shell_pid);
+        left.retain(|pid| process_tree::is_alive(*pid));
+        if !left.is_empty() {
+            tracing::warn!(
+                "Killing {} processes of agent {} that ignored SIGTERM",
+                left.len(),
+                self.get_id()
+            );
+        }
+        left.push(shell_pid);
+        process_tree::signal_all(&left, Signal::SIGKILL);
+    }
+
+    /// Get the CPU, memory and child count of the shell and its child
+    /// processes, as of the last status monitor sample
+    pub fn get_resource_usage(&self) -> ResourceUsage {
+        self.resources.lock().unwrap().usage().clone()
+    }
+
+    /// Get the processes started from the shell with their depth in its
+    /// process tree, as of the last status monitor sample
+    pub fn get_process_tree(&self) -> Vec<ProcessInfo> {
+        self.processes.lock().unwrap().clone()
+    }
+
+    /// Get the command line of the command running in the shell's
+    /// foreground, as of the last status monitor sample
+    pub fn get_current_command(&self) -> Option<String> {
+        self.current_command.lock().unwrap().clone()
+    }
+
+    /// Get the window title last set in the shell, empty if none was
+    pub fn get_title(&self) -> String {
+        self.title.borrow().clone()
+    }
+
+    /// Subscribe to changes of the window title
+    pub fn subscribe_title(&self) -> watch::Receiver<String> {
+        self.title.clone()
     }
 
-    /// Setup web server if enabled in configuration
-    async fn setup_web_server_if_enabled(self: &Arc<Self>) -> Result<()> {
-        if self.config.web_ui.enabled {
+    /// Whether the shell currently shows the alternate screen, i.e. a
+    /// full-screen program like vim, less or a TUI is in control
+    pub fn is_alternate_screen(&self) -> bool {
+        *self.alternate_screen.borrow()
+    }
+
+    /// Writes queued for the shell that it hasn't read yet, up to
+    /// `terminal.input_channel_bound`
+    pub fn get_pending_input(&self) -> usize {
+        self.process.pending_input()
+    }
+
+    /// Get the restart count and last exit reason of the shell
+    pub fn get_restart_counters(&self) -> RestartCounters {
+        self.restarts.lock().unwrap().clone()
+    }
+
+    /// Start counting restarts from zero again
+    pub fn reset_restart_counters(&self) {
+        *self.restarts.lock().unwrap() = RestartCounters::default();
+    }
+
+    /// Automated commands sent this hour, or None without
+    /// `agents.max_commands_per_hour`
+    pub fn get_command_usage(&self) -> Option<CommandUsage> {
+        self.command_quota
+            .lock()
+            .unwrap()
+            .usage(crate::rule::stats::unix_now())
+    }
+
+    /// Count one automated command against `agents.max_commands_per_hour`.
+    /// Fails once the quota of the current hour is used up; the first
+    /// refusal of each hour is logged as a warning.
+    pub fn take_command_quota(&self) -> Result<()> {
+        let mut quota = self.command_quota.lock().unwrap();
+        if quota.try_acquire(crate::rule::stats::unix_now()) {
+            return Ok(());
+        }
+        if quota.should_alert() {
+            tracing::warn!(
+                "🛑 Agent {} used up max_commands_per_hour, refusing automated keys until the next hour",
+                self.get_id()
+            );
+        }
+        anyhow::bail!(
+            "Agent {} reached max_commands_per_hour, automated keys refused",
+            self.get_id()
+        )
+    }
+
+    /// Count a new shell started after the previous one exited
+    fn record_restart(&self) {
+        let now = crate::rule::stats::unix_now();
+        self.restarts.lock().unwrap().record_restart(now);
+        metrics::metrics().record_restart(&self.get_id(), now);
+    }
+
+    /// Subscribe to every one of this agent's status transitions
+    pub fn subscribe_status(&self) -> broadcast::Receiver<AgentStatus> {
+        self.status_sender.subscribe()
+    }
+
+    /// Watch this agent's current status
+    pub fn watch_status(&self) -> watch::Receiver<AgentStatus> {
+        self.status.subscribe()
+    }
+
+    /// Wait until the status satisfies `condition` and return it
+    pub async fn wait_for_status(&self, condition: impl Fn(&AgentStatus) -> bool) -> AgentStatus {
+        match self.status.subscribe().wait_for(condition).await {
+            Ok(status) => status.clone(),
+            // The agent owns the sender, so the channel never closes here
+            Err(_) => self.get_status(),
+        }
+    }
+
+    /// Set the status of the agent, publishing it if it changed
+    pub(crate) fn set_status(&self, new_status: AgentStatus) {
+        // Compare, store and publish under the watch's lock, so concurrent
+        // updates are published in the order they are stored
+        self.status.send_if_modified(|status| {
+            if *status == new_status {
+                return false;
+            }
+            *status = new_status.clone();
+            tracing::debug!("🔄 Agent {} → {:?}", self.get_id(), new_status);
+            metrics::metrics().set_agent_status(&self.get_id(), &new_status);
+            let busy_period = self
+                .state_times
+                .lock()
+                .unwrap()
+                .transition(new_status.clone(), Instant::now());
+            if let Some(busy_period) = busy_period {
+                metrics::metrics().record_active_time(&self.get_id(), busy_period);
+            }
+
+            // Armed rule groups only last for one Active period
+            if new_status == AgentStatus::Idle {
+                self.rule_groups.reset();
+            }
+
+            // No subscribers is fine (e.g. no idle entries configured)
+            let _ = self.status_sender.send(new_status.clone());
+            true
+        });
+    }
+
+    /// Setup web server if enabled in configuration, listing `pool` on its
+    /// dashboard and serving the `control` API
+    pub(crate) async fn setup_web_server_if_enabled(
+        self: &Arc<Self>,
+        pool: Weak<Agents>,
+        control: Router,
+    ) -> Result<()> {
+        // In single mode one server for all agents is started with the pool
+        if self.config.web_ui.enabled && self.config.web_ui.mode == WebUIMode::PerAgent {
             let port = self.config.web_ui.base_port + self.index as u16;
             let host = self.config.web_ui.host.clone();
-            Arc::clone(self).start_web_server(port, host).await?;
+            self.start_web_server(port, host, pool, control).await?;
         }
         Ok(())
     }
@@ -136,23 +715,82 @@ impl Agent {
     /// Setup all monitoring systems for this agent
     pub async fn setup_monitoring(
         self: Arc<Self>,
-        rules: Vec<Rule>,
+        mut rules: Vec<Rule>,
     ) -> Result<Vec<JoinHandle<()>>> {
+        rules.retain(|rule| self.has_tag(rule.tag.as_deref()));
         let when_receiver = self.get_pty_receiver().await?;
         let diff_timeout_receiver = self.get_pty_receiver().await?;
+        let status_receiver = self.get_pty_receiver().await?;
+        let event_receiver = self.get_process().get_pty_event_receiver().await?;
+        let stuck_after = self.config.agents.stuck_after()?;
+        let idle_timeout = self.config.agents.idle_timeout()?;
 
         tracing::info!(
             "✅ Agent {} persistent string receivers created",
             self.get_id()
         );
 
-        let handles = vec![
-            self.setup_status_monitoring(),
-            self.setup_when_monitoring(rules.clone(), when_receiver),
-            self.setup_diff_timeout_monitoring(rules, diff_timeout_receiver),
+        let mut monitors = vec![
+            (
+                "status",
+                self.setup_status_monitoring(
+                    status_receiver,
+                    event_receiver,
+                    stuck_after,
+                    idle_timeout,
+                ),
+            ),
+            (
+                "when",
+                self.setup_when_monitoring(rules.clone(), when_receiver),
+            ),
+            (
+                "diff_timeout",
+                self.setup_diff_timeout_monitoring(rules.clone(), diff_timeout_receiver),
+            ),
         ];
+        if rules
+            .iter()
+            .any(|rule| matches!(rule.rule_type, RuleType::Title(_)))
+        {
+            monitors.push(("title", self.setup_title_monitoring(rules)));
+        }
+        if self.config.agents.respawn {
+            monitors.push(("respawn", self.setup_respawn_monitoring()));
+        }
+        if let Some(dir) = &self.config.logging.pty_dir {
+            monitors.push(("pty_log", self.setup_pty_log(dir).await?));
+        }
+        if self.config.recording.enabled {
+            self.setup_recording().await?;
+        }
 
-        Ok(handles)
+        *self.monitors.lock().unwrap() = monitors
+            .iter()
+            .map(|(name, handle)| (*name, handle.abort_handle()))
+            .collect();
+        Ok(monitors.into_iter().map(|(_, handle)| handle).collect())
+    }
+
+    /// Why the agent can't do its work, empty when it can: its monitors
+    /// haven't started or stopped, or the status monitor saw its shell
+    /// exit. A sleeping agent is ready, as it gets a new shell on demand.
+    pub fn readiness_problems(&self) -> Vec<String> {
+        let monitors = self.monitors.lock().unwrap();
+        let mut problems = Vec::new();
+        if monitors.is_empty() {
+            problems.push("monitors not started".to_string());
+        }
+        if self.shell_exited.load(Ordering::SeqCst) && !self.is_sleeping() {
+            problems.push("shell not running".to_string());
+        }
+        problems.extend(
+            monitors
+                .iter()
+                .filter(|(_, handle)| handle.is_finished())
+                .map(|(name, _)| format!("{} monitor stopped", name)),
+        );
+        problems
     }
 
     /// Get PTY receiver for this agent
@@ -171,16 +809,93 @@ impl Agent {
     }
 
     /// Setup status monitoring for this agent
-    fn setup_status_monitoring(self: &Arc<Self>) -> JoinHandle<()> {
+    fn setup_status_monitoring(
+        self: &Arc<Self>,
+        output: broadcast::Receiver<String>,
+        events: broadcast::Receiver<PtyEvent>,
+        stuck_after: Duration,
+        idle_timeout: Option<Duration>,
+    ) -> JoinHandle<()> {
         let agent = Arc::clone(self);
 
         tokio::spawn(async move {
-            if let Err(e) = agent.clone().start_monitoring().await {
+            if let Err(e) = agent
+                .clone()
+                .start_monitoring(output, events, stuck_after, idle_timeout)
+                .await
+            {
                 tracing::error!("❌ Agent {} status monitor failed: {}", agent.get_id(), e);
             }
         })
     }
 
+    /// Setup writing this agent's PTY output to its log file in `dir`
+    async fn setup_pty_log(self: &Arc<Self>, dir: &std::path::Path) -> Result<JoinHandle<()>> {
+        let logging = &self.config.logging;
+        let log = PtyLog::open(
+            dir,
+            &self.get_id(),
+            logging.pty_max_file_bytes(),
+            logging.pty_max_files,
+        )
+        .await?;
+        let receiver = self.get_pty_receiver().await?;
+        let agent_id = self.get_id();
+
+        Ok(tokio::spawn(async move {
+            pty_log::record(log, receiver, &agent_id).await;
+        }))
+    }
+
+    /// Setup recording this agent's session to `.cast` files. The recorder
+    /// is kept by the agent instead of returned, so it outlives the
+    /// monitors on shutdown and writes everything up to the end.
+    async fn setup_recording(self: &Arc<Self>) -> Result<()> {
+        let recording_config = &self.config.recording;
+        let recording = Recording::create(
+            &recording_config.dir,
+            &self.get_id(),
+            &self.config.terminal_for(&self.get_id()).term,
+            recording_config.rotate,
+            self.get_terminal_dimensions(),
+        )
+        .await?;
+        tracing::info!(
+            "Recording agent {} to {}",
+            self.get_id(),
+            recording.path().display()
+        );
+        let process = self.get_process();
+        let recorder = Recorder::spawn(
+            recording,
+            process.get_pty_bytes_receiver().await?,
+            process.get_pty_event_receiver().await?,
+            self.subscribe_status(),
+            self.get_id(),
+        );
+        *self.recorder.lock().unwrap() = Some(recorder);
+        Ok(())
+    }
+
+    /// Flush and stop the session recording, if any
+    pub async fn finish_recording(&self) {
+        let recorder = self.recorder.lock().unwrap().take();
+        if let Some(recorder) = recorder {
+            recorder.finish().await;
+        }
+    }
+
+    /// Setup respawning of this agent's shell when it exits
+    fn setup_respawn_monitoring(self: &Arc<Self>) -> JoinHandle<()> {
+        let agent = Arc::clone(self);
+
+        tokio::spawn(async move {
+            if let Err(e) = respawn_on_exit(&agent, &RespawnPolicy::default()).await {
+                tracing::error!("❌ Agent {} respawn monitor failed: {}", agent.get_id(), e);
+            }
+        })
+    }
+
     /// Setup when rule monitoring for this agent
     fn setup_when_monitoring(
         self: &Arc<Self>,
@@ -196,6 +911,18 @@ impl Agent {
         })
     }
 
+    /// Setup title rule monitoring for this agent
+    fn setup_title_monitoring(self: &Arc<Self>, rules: Vec<Rule>) -> JoinHandle<()> {
+        let title_monitor = Title::new(rules, Arc::clone(self));
+        let titles = self.subscribe_title();
+
+        tokio::spawn(async move {
+            if title_monitor.start_monitoring(titles).await.is_err() {
+                tracing::error!("❌ Agent title monitor failed");
+            }
+        })
+    }
+
     /// Setup timeout monitoring for this agent
     fn setup_diff_timeout_monitoring(
         self: &Arc<Self>,
@@ -211,42 +938,219 @@ impl Agent {
         })
     }
 
-    /// Start the WebServer for this agent if configured
-    async fn start_web_server(self: std::sync::Arc<Self>, port: u16, host: String) -> Result<()> {
-        let web_server = WebServer::new(port, host, std::sync::Arc::clone(&self));
-        let handle = tokio::spawn(async move {
-            if let Err(e) = web_server.start().await {
-                tracing::error!("❌ Web server failed on port {}: {}", port, e);
-            }
-        });
-
+    /// Start the WebServer for this agent if configured. A port in use
+    /// fails the agent, unless `web_ui.port_fallback` finds another one.
+    async fn start_web_server(
+        self: &Arc<Self>,
+        port: u16,
+        host: String,
+        pool: Weak<Agents>,
+        control: Router,
+    ) -> Result<()> {
+        let web_server = WebServer::new(port, host, WebTarget::Agent(Arc::clone(self), pool))
+            .with_control(control)
+            .with_tls(self.config.web_ui.tls.clone())
+            .with_base_path(self.config.web_ui.path_prefix())
+            .with_cors_origins(self.config.web_ui.cors_origins.clone())
+            .with_shutdown_timeout(self.config.web_ui.shutdown_timeout()?);
+        let (port, handle) = web_server.spawn(self.config.web_ui.port_fallback).await?;
+
+        *self.web_port.lock().unwrap() = Some(port);
         *self.web_server_handle.write().unwrap() = Some(handle);
         Ok(())
     }
 
-    /// Monitor agent status by checking child processes
-    async fn monitor(&self) {
+    /// Shut down the agent's own web server gracefully, see
+    /// [`ServerHandle::shutdown`]
+    pub async fn stop_web_server(&self) {
+        let handle = self.web_server_handle.write().unwrap().take();
+        if let Some(handle) = handle {
+            handle.shutdown().await;
+        }
+    }
+
+    /// Port of the agent's own web UI; None in single mode or without one
+    pub fn get_web_port(&self) -> Option<u16> {
+        *self.web_port.lock().unwrap()
+    }
+
+    /// Monitor agent status by checking the shell, its child processes and
+    /// the time since its last output, sampling resource usage and the
+    /// current command on the way
+    async fn monitor(&self, stuck_after: Duration) {
+        if self.is_sleeping() {
+            return;
+        }
+        if self.shell_exited.load(Ordering::SeqCst) {
+            self.set_status(AgentStatus::Error);
+            return;
+        }
         if let Ok(child_pids) = self.get_process().get_child_processes().await {
-            let new_status = if child_pids.is_empty() {
-                AgentStatus::Idle
-            } else {
-                AgentStatus::Active
-            };
+            let silent_for = self.last_output.lock().unwrap().elapsed();
+            // Shell integration marks are exact; child processes are a guess
+            // that also counts background jobs
+            let running = self
+                .command_running
+                .lock()
+                .unwrap()
+                .unwrap_or(!child_pids.is_empty());
+            let new_status = AgentStatus::derive(false, running, silent_for, stuck_after);
+
+            self.set_status(new_status);
+            self.sample_resources(&child_pids).await;
+        }
+    }
+
+    async fn sample_resources(&self, child_pids: &[u32]) {
+        if child_pids.is_empty() {
+            *self.current_command.lock().unwrap() = None;
+            self.processes.lock().unwrap().clear();
+        }
+        let now = Instant::now();
+        if !self.resources.lock().unwrap().is_due(now) {
+            return;
+        }
+        if !child_pids.is_empty() {
+            let command = self.get_process().get_current_command().await;
+            *self.current_command.lock().unwrap() = command.ok().flatten();
+        }
+        let shell_pid = self.get_process().get_shell_pid().await.ok().flatten();
+        *self.processes.lock().unwrap() = shell_pid
+            .filter(|_| !child_pids.is_empty())
+            .map(process_tree::process_tree)
+            .unwrap_or_default();
+        self.resources
+            .lock()
+            .unwrap()
+            .sample(shell_pid, child_pids, now);
+    }
 
-            self.set_status(new_status).await;
+    /// Alert once when the agent becomes Stuck: log it and run the
+    /// `on_stuck` action. Once it leaves Stuck, log how long it was stuck.
+    fn track_stuck(self: &Arc<Self>) {
+        let status = self.get_status();
+        let stuck = status == AgentStatus::Stuck;
+        match (self.stuck_alerted.swap(stuck, Ordering::SeqCst), stuck) {
+            (false, true) => {
+                tracing::warn!(
+                    "⚠️ Agent {} appears stuck: no output for {:?}",
+                    self.get_id(),
+                    self.last_output.lock().unwrap().elapsed()
+                );
+                if let Some(action) = &self.on_stuck {
+                    let action = resolve_action(action, &TemplateContext::for_agent(self));
+                    let agent = Arc::clone(self);
+                    tokio::spawn(async move {
+                        let context = format!("Agent {} on_stuck", agent.get_id());
+                        if let Err(e) =
+                            crate::trigger::execute_action_with_agent(&action, &agent, &context)
+                                .await
+                        {
+                            tracing::error!("❌ {} failed: {:#}", context, e);
+                        }
+                    });
+                }
+            }
+            (true, false) => {
+                let state_times = self.state_times.lock().unwrap();
+                match state_times.previous() {
+                    Some((AgentStatus::Stuck, stuck_for)) => tracing::warn!(
+                        "✅ Agent {} no longer stuck ({:?}) after {:?}",
+                        self.get_id(),
+                        status,
+                        stuck_for
+                    ),
+                    _ => {
+                        tracing::warn!("✅ Agent {} no longer stuck ({:?})", self.get_id(), status)
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
+    /// Record output and shell exits/respawns seen since the last check
+    fn track_shell(
+        &self,
+        output: &mut broadcast::Receiver<String>,
+        events: &mut broadcast::Receiver<PtyEvent>,
+    ) {
+        let mut got_output = false;
+        loop {
+            match output.try_recv() {
+                Ok(chunk) => metrics::metrics().record_pty_output(&self.get_id(), chunk.len()),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+            got_output = true;
+        }
+        while let Ok(event) = events.try_recv() {
+            if let PtyEventData::Init { .. } = event.data {
+                self.shell_exited.store(false, Ordering::SeqCst);
+                // A new shell may not have shell integration
+                *self.command_running.lock().unwrap() = None;
+                got_output = true;
+            } else if let PtyEventData::CommandStarted {} = event.data {
+                *self.command_running.lock().unwrap() = Some(true);
+            } else if let PtyEventData::CommandFinished { exit_code } = event.data {
+                tracing::debug!(
+                    "Agent {} command finished with exit code {:?}",
+                    self.get_id(),
+                    exit_code
+                );
+                *self.command_running.lock().unwrap() = Some(false);
+            } else if let Some(reason) = event.data.exit_reason() {
+                // The shell exited, its PTY closed or no shell could be started
+                self.shell_exited.store(true, Ordering::SeqCst);
+                // Sleeping and shutting down shells exit on purpose
+                if !self.is_sleeping() && !self.is_shutting_down() {
+                    self.restarts.lock().unwrap().record_exit(reason);
+                }
+            }
+        }
+        if got_output {
+            self.record_output(Instant::now());
+        }
+    }
+
+    /// Time of the last output of the shell
+    pub fn get_last_output(&self) -> Instant {
+        *self.last_output.lock().unwrap()
+    }
+
+    /// Note output of the shell at `at`
+    pub(crate) fn record_output(&self, at: Instant) {
+        *self.last_output.lock().unwrap() = at;
+    }
+
+    /// Time spent in the current status, busy and idle
+    pub fn get_state_durations(&self) -> StateDurations {
+        self.state_times.lock().unwrap().durations(Instant::now())
+    }
+
     /// Start monitoring this agent's status
-    pub async fn start_monitoring(self: std::sync::Arc<Self>) -> Result<()> {
+    pub async fn start_monitoring(
+        self: std::sync::Arc<Self>,
+        mut output: broadcast::Receiver<String>,
+        mut events: broadcast::Receiver<PtyEvent>,
+        stuck_after: Duration,
+        idle_timeout: Option<Duration>,
+    ) -> Result<()> {
         loop {
+            self.track_shell(&mut output, &mut events);
+
             // Add timeout to monitor operation to prevent hanging
-            match tokio::time::timeout(Duration::from_millis(200), self.monitor()).await {
+            match tokio::time::timeout(Duration::from_millis(200), self.monitor(stuck_after)).await
+            {
                 Ok(_) => {}
                 Err(_) => {
                     tracing::warn!("Agent {} monitor operation timed out", self.get_id());
                 }
             }
+            self.track_stuck();
+            if let Some(idle_timeout) = idle_timeout {
+                self.sleep_if_idle(idle_timeout).await;
+            }
 
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
@@ -312,20 +1216,76 @@ mod tests {
         let agent = Agent::new_with_process(0, &config, mock_pty).await.unwrap();
 
         // Agent should start as Idle
-        assert!(!agent.is_active().await, "Agent should start as Idle");
+        assert!(!agent.is_active(), "Agent should start as Idle");
 
         // Test status transitions
-        agent.set_status(AgentStatus::Active).await;
-        assert!(
-            agent.is_active().await,
-            "Agent should be Active after setting"
-        );
+        agent.set_status(AgentStatus::Active);
+        assert!(agent.is_active(), "Agent should be Active after setting");
 
-        agent.set_status(AgentStatus::Idle).await;
-        assert!(
-            !agent.is_active().await,
-            "Agent should be Idle after setting"
-        );
+        agent.set_status(AgentStatus::Idle);
+        assert!(!agent.is_active(), "Agent should be Idle after setting");
+    }
+
+    #[tokio::test]
+    async fn test_rule_groups_reset_on_idle() {
+        let agent = create_test_agent().await;
+
+        agent.set_status(AgentStatus::Active);
+        agent.get_rule_groups().activate("commit");
+        assert!(agent.get_rule_groups().is_enabled(Some("commit")));
+
+        agent.set_status(AgentStatus::Idle);
+        assert!(!agent.get_rule_groups().is_enabled(Some("commit")));
+    }
+
+    #[tokio::test]
+    async fn test_status_transitions_are_broadcast() {
+        let agent = create_test_agent().await;
+        let mut receiver = agent.subscribe_status();
+
+        agent.set_status(AgentStatus::Active);
+        agent.set_status(AgentStatus::Active);
+        agent.set_status(AgentStatus::Idle);
+
+        // Only actual changes are sent
+        assert_eq!(receiver.recv().await.unwrap(), AgentStatus::Active);
+        assert_eq!(receiver.recv().await.unwrap(), AgentStatus::Idle);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_transitions_keep_final_status() {
+        let agent = create_test_agent().await;
+        let mut receiver = agent.subscribe_status();
+        let waiter = tokio::spawn({
+            let agent = Arc::clone(&agent);
+            async move { agent.wait_for_status(|s| *s == AgentStatus::Error).await }
+        });
+        tokio::task::yield_now().await;
+
+        // Far more transitions than the channel buffers
+        for _ in 0..STATUS_CHANNEL_CAPACITY * 4 {
+            agent.set_status(AgentStatus::Active);
+            agent.set_status(AgentStatus::Idle);
+        }
+        agent.set_status(AgentStatus::Error);
+
+        let status = tokio::time::timeout(Duration::from_secs(2), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status, AgentStatus::Error);
+
+        // A lagging subscriber skips transitions, but still gets the last one
+        let mut last = None;
+        loop {
+            match receiver.try_recv() {
+                Ok(status) => last = Some(status),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        assert_eq!(last, Some(AgentStatus::Error));
     }
 
     #[tokio::test]
@@ -339,15 +1299,15 @@ mod tests {
         let agent = Agent::new_with_process(0, &config, mock_pty).await.unwrap();
 
         // Test initial state
-        assert_eq!(agent.is_active().await, false);
+        assert!(!agent.is_active());
 
         // Test Active state
-        agent.set_status(AgentStatus::Active).await;
-        assert_eq!(agent.is_active().await, true);
+        agent.set_status(AgentStatus::Active);
+        assert!(agent.is_active());
 
         // Test Idle state
-        agent.set_status(AgentStatus::Idle).await;
-        assert_eq!(agent.is_active().await, false);
+        agent.set_status(AgentStatus::Idle);
+        assert!(!agent.is_active());
     }
 
     #[tokio::test]
@@ -364,6 +1324,65 @@ mod tests {
         assert!(result.is_ok(), "send_keys should succeed with valid input");
     }
 
+    #[tokio::test]
+    async fn test_send_keys_pastes_paste_tokens() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+
+        agent
+            .send_keys("<paste>fix it\nthen test</paste>")
+            .await
+            .unwrap();
+        mock.set_bracketed_paste(true);
+        agent
+            .send_keys("<paste>fix it\nthen test</paste>")
+            .await
+            .unwrap();
+        agent.send_keys("\r").await.unwrap();
+
+        assert_eq!(
+            mock.get_sent_inputs(),
+            [
+                "fix it\rthen test",
+                "\x1b[200~fix it\rthen test\x1b[201~",
+                "\r"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_keys_remembers_secret_tokens() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+
+        agent
+            .send_keys("<secret>agent-secret-0d2e</secret>")
+            .await
+            .unwrap();
+        agent
+            .send_keys("<secret><paste>agent-paste-secret-6b7c</paste></secret>")
+            .await
+            .unwrap();
+        agent.send_keys("<secret>\r</secret>").await.unwrap();
+
+        assert_eq!(
+            mock.get_sent_inputs(),
+            ["agent-secret-0d2e", "agent-paste-secret-6b7c", "\r"]
+        );
+        assert_eq!(
+            secret::registry().redact("agent-secret-0d2e agent-paste-secret-6b7c\r\n"),
+            "*** ***\r\n"
+        );
+    }
+
     #[tokio::test]
     async fn test_send_keys_empty() {
         let mut config = Config::default();
@@ -385,17 +1404,17 @@ mod tests {
         let agent = create_test_agent().await;
 
         // Create some test rules
-        let rules = vec![Rule {
-            rule_type: RuleType::When(regex::Regex::new("test").unwrap()),
-            action: ActionType::SendKeys(vec!["echo".to_string()]),
-        }];
+        let rules = vec![Rule::new(
+            RuleType::When(regex::Regex::new("test").unwrap()),
+            ActionType::SendKeys(vec!["echo".to_string()]),
+        )];
 
         // Test setup_monitoring returns correct number of handles
         let result = agent.setup_monitoring(rules).await;
         assert!(result.is_ok(), "setup_monitoring should succeed");
 
         let handles = result.unwrap();
-        assert_eq!(handles.len(), 3, "Should return 3 monitoring handles");
+        assert_eq!(handles.len(), 4, "Should return 4 monitoring handles");
 
         // Clean up by aborting the handles
         for handle in handles {
@@ -427,23 +1446,274 @@ mod tests {
         let agent = create_test_agent().await;
 
         // Initially should be idle
-        assert!(!agent.is_active().await, "Agent should start as idle");
+        assert!(!agent.is_active(), "Agent should start as idle");
 
         // Call monitor method - this should check child processes and update status
-        agent.monitor().await;
+        agent.monitor(Duration::from_secs(60)).await;
 
         // Status might remain the same if no child processes are running
         // This test mainly ensures the monitor method doesn't panic
-        let status_after_monitor = agent.is_active().await;
+        let status_after_monitor = agent.is_active();
 
         // The status could be either idle or active depending on system state
         // The important thing is that the method completes without error
         assert!(
-            status_after_monitor == true || status_after_monitor == false,
+            matches!(status_after_monitor, true | false),
             "Monitor method should complete successfully"
         );
     }
 
+    #[tokio::test]
+    async fn test_monitor_tracks_current_command() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        assert_eq!(agent.get_current_command(), None);
+
+        mock_pty.set_children(&[(41, "sleep 100"), (42, "claude --resume")]);
+        agent.monitor(Duration::from_secs(60)).await;
+        assert_eq!(agent.get_status(), AgentStatus::Active);
+        assert_eq!(
+            agent.get_current_command().as_deref(),
+            Some("claude --resume")
+        );
+
+        // Cleared as soon as the command ends, without waiting for a sample
+        mock_pty.set_children(&[]);
+        agent.monitor(Duration::from_secs(60)).await;
+        assert_eq!(agent.get_current_command(), None);
+    }
+
+    #[tokio::test]
+    async fn test_shell_integration_marks_override_child_processes() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let mut output = mock_pty.get_pty_string_receiver().await.unwrap();
+        let mut events = mock_pty.get_pty_event_receiver().await.unwrap();
+        let mut status_after = async |data: Option<PtyEventData>| {
+            if let Some(data) = data {
+                mock_pty.mark_command(data);
+            }
+            agent.track_shell(&mut output, &mut events);
+            agent.monitor(Duration::from_secs(60)).await;
+            agent.get_status()
+        };
+
+        // Without marks, a background job looks like a running command
+        mock_pty.set_children(&[(41, "sleep 100")]);
+        assert_eq!(status_after(None).await, AgentStatus::Active);
+
+        // The shell reports it's back at the prompt
+        let finished = PtyEventData::CommandFinished { exit_code: Some(0) };
+        assert_eq!(status_after(Some(finished)).await, AgentStatus::Idle);
+
+        // A shell builtin runs without any child process
+        mock_pty.set_children(&[]);
+        let started = PtyEventData::CommandStarted {};
+        assert_eq!(status_after(Some(started)).await, AgentStatus::Active);
+
+        // A new shell may not send marks
+        mock_pty.mark_command(PtyEventData::Init {
+            cols: 80,
+            rows: 24,
+            initial_data: String::new(),
+            initial_seq: String::new(),
+        });
+        assert_eq!(status_after(None).await, AgentStatus::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_agent_alerts_once() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.on_stuck =
+            Some(serde_yml::from_str("{action: send_keys, keys: ['stuck ${agent_id}']}").unwrap());
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let stuck_after = Duration::from_secs(60);
+        mock_pty.set_children(&[(42, "claude")]);
+
+        *agent.last_output.lock().unwrap() = Instant::now() - Duration::from_secs(90);
+        for _ in 0..3 {
+            agent.monitor(stuck_after).await;
+            agent.track_stuck();
+        }
+        assert_eq!(agent.get_status(), AgentStatus::Stuck);
+        assert!(agent.stuck_alerted.load(Ordering::SeqCst));
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while mock_pty.get_sent_inputs().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), ["stuck agent-0"]);
+
+        // Output resumes
+        *agent.last_output.lock().unwrap() = Instant::now();
+        agent.monitor(stuck_after).await;
+        agent.track_stuck();
+        assert_eq!(agent.get_status(), AgentStatus::Active);
+        assert!(!agent.stuck_alerted.load(Ordering::SeqCst));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(mock_pty.get_sent_inputs().len(), 1);
+    }
+
+    #[test]
+    fn test_derive_status() {
+        let stuck_after = Duration::from_secs(60);
+        let quiet = Duration::from_secs(5);
+        let silent = Duration::from_secs(90);
+
+        assert_eq!(
+            AgentStatus::derive(false, false, silent, stuck_after),
+            AgentStatus::Idle
+        );
+        assert_eq!(
+            AgentStatus::derive(false, true, quiet, stuck_after),
+            AgentStatus::Active
+        );
+        assert_eq!(
+            AgentStatus::derive(false, true, silent, stuck_after),
+            AgentStatus::Stuck
+        );
+        assert_eq!(
+            AgentStatus::derive(true, true, quiet, stuck_after),
+            AgentStatus::Error
+        );
+        assert!(AgentStatus::Stuck.is_busy());
+        assert!(!AgentStatus::Error.is_busy());
+    }
+
+    #[tokio::test]
+    async fn test_status_follows_shell_exit_and_respawn() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.respawn = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+        let handles = Arc::clone(&agent).setup_monitoring(vec![]).await.unwrap();
+
+        let wait_for_status = |expected: AgentStatus| {
+            let agent = Arc::clone(&agent);
+            async move {
+                tokio::time::timeout(Duration::from_secs(2), async {
+                    while agent.get_status() != expected {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .unwrap();
+            }
+        };
+
+        agent.set_status(AgentStatus::Active);
+        wait_for_status(AgentStatus::Idle).await;
+
+        mock.exit(1);
+        wait_for_status(AgentStatus::Error).await;
+        assert!(!agent.is_active());
+        assert_eq!(
+            agent.get_restart_counters().last_exit_reason.as_deref(),
+            Some("exit code 1")
+        );
+
+        mock.restart().await.unwrap();
+        wait_for_status(AgentStatus::Idle).await;
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness_follows_shell_and_monitors() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.respawn = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+        assert_eq!(agent.readiness_problems(), ["monitors not started"]);
+
+        let handles = Arc::clone(&agent).setup_monitoring(vec![]).await.unwrap();
+        let wait_for_problem = |problem: &'static str| {
+            let agent = Arc::clone(&agent);
+            async move {
+                tokio::time::timeout(Duration::from_secs(2), async {
+                    while !agent.readiness_problems().iter().any(|p| p == problem) {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .unwrap();
+            }
+        };
+
+        // The mock's output ends at once, which stops the rule monitors
+        wait_for_problem("when monitor stopped").await;
+        wait_for_problem("diff_timeout monitor stopped").await;
+        assert!(
+            !agent
+                .readiness_problems()
+                .contains(&"shell not running".to_string())
+        );
+
+        mock.exit(1);
+        wait_for_problem("shell not running").await;
+
+        handles[0].abort();
+        wait_for_problem("status monitor stopped").await;
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_agent_sleeps_until_keys_arrive() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.init_keys = vec!["cd /tmp\r".to_string()];
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+
+        agent.sleep_if_idle(Duration::from_secs(60)).await;
+        assert!(!agent.is_sleeping());
+
+        agent.sleep_if_idle(Duration::ZERO).await;
+        assert!(agent.is_sleeping());
+        assert_eq!(agent.get_status(), AgentStatus::Sleeping);
+        assert_eq!(mock.stop_count(), 1);
+        // The exit of the hung up shell is no error
+        agent.monitor(Duration::from_secs(60)).await;
+        assert_eq!(agent.get_status(), AgentStatus::Sleeping);
+
+        agent.send_keys("ls\r").await.unwrap();
+        assert!(!agent.is_sleeping());
+        assert_eq!(agent.get_status(), AgentStatus::Idle);
+        assert_eq!(mock.restart_count(), 1);
+        assert_eq!(mock.get_sent_inputs(), ["cd /tmp\r", "ls\r"]);
+
+        // Waking restarted the idle time
+        agent.sleep_if_idle(Duration::from_secs(60)).await;
+        assert!(!agent.is_sleeping());
+    }
+
     #[tokio::test]
     async fn test_get_pty_receiver() {
         let _config = Config::default();
@@ -469,7 +1739,7 @@ mod tests {
 
         let agent = agent.unwrap();
         assert_eq!(agent.get_id(), "agent-0");
-        assert!(!agent.is_active().await, "New agent should start as idle");
+        assert!(!agent.is_active(), "New agent should start as idle");
     }
 
     #[tokio::test]
@@ -477,20 +1747,20 @@ mod tests {
         let agent = create_test_agent().await;
 
         // Test initial state
-        assert_eq!(agent.is_active().await, false, "Agent should start idle");
+        assert!(!agent.is_active(), "Agent should start idle");
 
         // Test multiple transitions
-        agent.set_status(AgentStatus::Active).await;
-        assert!(agent.is_active().await, "Agent should be active");
+        agent.set_status(AgentStatus::Active);
+        assert!(agent.is_active(), "Agent should be active");
 
-        agent.set_status(AgentStatus::Idle).await;
-        assert!(!agent.is_active().await, "Agent should be idle");
+        agent.set_status(AgentStatus::Idle);
+        assert!(!agent.is_active(), "Agent should be idle");
 
-        agent.set_status(AgentStatus::Active).await;
-        assert!(agent.is_active().await, "Agent should be active again");
+        agent.set_status(AgentStatus::Active);
+        assert!(agent.is_active(), "Agent should be active again");
 
-        agent.set_status(AgentStatus::Idle).await;
-        assert!(!agent.is_active().await, "Agent should be idle again");
+        agent.set_status(AgentStatus::Idle);
+        assert!(!agent.is_active(), "Agent should be idle again");
     }
 
     #[tokio::test]
@@ -529,6 +1799,31 @@ mod tests {
         assert_eq!(rows, 40, "Rows should match config");
     }
 
+    #[tokio::test]
+    async fn test_agent_resize() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+        let mut events = mock.get_pty_event_receiver().await.unwrap();
+
+        agent.resize(200, 50).await.unwrap();
+        assert_eq!(agent.get_terminal_dimensions(), (200, 50));
+        assert!(matches!(
+            events.try_recv().unwrap().data,
+            PtyEventData::Resize {
+                cols: 200,
+                rows: 50
+            }
+        ));
+
+        assert!(agent.resize(0, 50).await.is_err());
+        assert!(agent.resize(200, 5000).await.is_err());
+        assert_eq!(agent.get_terminal_dimensions(), (200, 50));
+    }
+
     #[tokio::test]
     async fn test_agent_get_process() {
         let agent = create_test_agent().await;
@@ -577,8 +1872,8 @@ mod tests {
         let handles = result.unwrap();
         assert_eq!(
             handles.len(),
-            3,
-            "Should return 3 monitoring handles even with empty rules"
+            4,
+            "Should return 4 monitoring handles even with empty rules"
         );
 
         // Clean up handles
@@ -587,6 +1882,22 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_setup_monitoring_without_respawn() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.respawn = false;
+        let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+
+        let handles = agent.setup_monitoring(vec![]).await.unwrap();
+        assert_eq!(handles.len(), 3, "No respawn monitor when disabled");
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
     #[tokio::test]
     async fn test_setup_monitoring_with_multiple_rules() {
         use crate::config::helper::ActionType;
@@ -595,21 +1906,21 @@ mod tests {
 
         let agent = create_test_agent().await;
         let rules = vec![
-            Rule {
-                rule_type: RuleType::When(Regex::new("test").unwrap()),
-                action: ActionType::SendKeys(vec!["echo matched".to_string()]),
-            },
-            Rule {
-                rule_type: RuleType::DiffTimeout(Duration::from_secs(1)),
-                action: ActionType::SendKeys(vec!["echo timeout".to_string()]),
-            },
+            Rule::new(
+                RuleType::When(Regex::new("test").unwrap()),
+                ActionType::SendKeys(vec!["echo matched".to_string()]),
+            ),
+            Rule::new(
+                RuleType::DiffTimeout(Duration::from_secs(1)),
+                ActionType::SendKeys(vec!["echo timeout".to_string()]),
+            ),
         ];
 
         let result = agent.setup_monitoring(rules).await;
         assert!(result.is_ok(), "Setup monitoring with rules should succeed");
 
         let handles = result.unwrap();
-        assert_eq!(handles.len(), 3, "Should return 3 monitoring handles");
+        assert_eq!(handles.len(), 4, "Should return 4 monitoring handles");
 
         // Clean up handles
         for handle in handles {
@@ -637,50 +1948,138 @@ mod tests {
         let agent = create_test_agent().await;
 
         // Test monitor method directly - should not panic
-        agent.monitor().await;
+        agent.monitor(Duration::from_secs(60)).await;
 
         // Agent status might change after monitoring, but shouldn't crash
-        let status = agent.is_active().await;
-        assert!(
-            status == true || status == false,
-            "Status should be boolean"
-        );
+        let status = agent.is_active();
+        assert!(matches!(status, true | false), "Status should be boolean");
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_concurrent_status_changes() {
         use tokio::task::JoinSet;
 
         let agent = create_test_agent().await;
+        let mut receiver = agent.subscribe_status();
         let mut set = JoinSet::new();
 
-        // Test concurrent status changes
-        for i in 0..10 {
+        // Fewer updates than the channel buffers, so none may be skipped
+        for i in 0..STATUS_CHANNEL_CAPACITY - 1 {
             let agent_clone = Arc::clone(&agent);
             set.spawn(async move {
-                if i % 2 == 0 {
-                    agent_clone.set_status(AgentStatus::Active).await;
-                } else {
-                    agent_clone.set_status(AgentStatus::Idle).await;
-                }
-                agent_clone.is_active().await
+                let status = match i % 3 {
+                    0 => AgentStatus::Active,
+                    1 => AgentStatus::Stuck,
+                    _ => AgentStatus::Idle,
+                };
+                agent_clone.set_status(status);
             });
         }
-
-        // Wait for all tasks to complete
-        let mut results = Vec::new();
         while let Some(result) = set.join_next().await {
-            results.push(result.unwrap());
+            result.unwrap();
         }
 
-        // All tasks should complete successfully
-        assert_eq!(results.len(), 10, "All concurrent tasks should complete");
-
-        // Final status should be boolean
-        let final_status = agent.is_active().await;
+        // Every stored change was published, in the order it was stored
+        let mut published = vec![AgentStatus::Idle];
+        loop {
+            match receiver.try_recv() {
+                Ok(status) => published.push(status),
+                Err(broadcast::error::TryRecvError::Empty) => break,
+                Err(e) => panic!("Status update dropped: {:?}", e),
+            }
+        }
         assert!(
-            final_status == true || final_status == false,
-            "Final status should be boolean"
+            published.windows(2).all(|pair| pair[0] != pair[1]),
+            "{:?}",
+            published
+        );
+        assert_eq!(published.last(), Some(&agent.get_status()));
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_rules() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+
+        assert!(!agent.are_rules_paused());
+        agent.pause_rules().unwrap();
+        assert!(agent.are_rules_paused());
+        assert!(agent.resume_rules());
+        assert!(!agent.are_rules_paused());
+        assert!(!agent.resume_rules());
+
+        // Paused rules resume on their own once pause_timeout passed
+        agent.pause_rules().unwrap();
+        *agent.rules_resume_at.lock().unwrap() = Some(Instant::now() - Duration::from_secs(1));
+        assert!(!agent.are_rules_paused());
+        assert!(!agent.resume_rules());
+    }
+
+    #[tokio::test]
+    async fn test_run_init_sends_init_keys() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.init_keys = vec!["cd ~/work\r".to_string(), "source .env\r".to_string()];
+        config.agents.agent_overrides.insert(
+            "agent-1".to_string(),
+            crate::config::agents_config::AgentOverride {
+                init_keys: Some(vec![]),
+                ..Default::default()
+            },
         );
+
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        // Not run on creation
+        assert!(mock_pty.get_sent_inputs().is_empty());
+        agent.run_init().await.unwrap();
+        assert_eq!(
+            mock_pty.get_sent_inputs(),
+            vec!["cd ~/work\r", "source .env\r"]
+        );
+
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(1, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        agent.run_init().await.unwrap();
+        assert!(mock_pty.get_sent_inputs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_init_waits_for_prompt() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.init_keys = vec!["cd ~/work\r".to_string()];
+        config.agents.init_wait_for =
+            Some(crate::config::triggers_config::WaitForSetting::Options {
+                pattern: Some("^mock output$".to_string()),
+                idle: false,
+                timeout: Some("1s".to_string()),
+            });
+
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        agent.run_init().await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["cd ~/work\r"]);
+
+        // The prompt never shows up
+        config.agents.init_wait_for =
+            Some(crate::config::triggers_config::WaitForSetting::Options {
+                pattern: Some(r"\$ $".to_string()),
+                idle: false,
+                timeout: Some("1s".to_string()),
+            });
+        let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+        assert!(agent.run_init().await.is_err());
     }
 }
@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::broadcast;
+
+use crate::terminal::secret::Redactor;
+
+/// Appends an agent's PTY output to `<dir>/<agent_id>.log`, each line
+/// prefixed with the time it arrived. Once the file reaches `max_bytes` it
+/// moves to `.log.1` (older ones to `.log.2`, ...) and a new one is started.
+/// Secret keys sent to the agent are redacted.
+pub struct PtyLog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: BufWriter<File>,
+    size: u64,
+    at_line_start: bool,
+    redactor: Redactor,
+}
+
+impl PtyLog {
+    pub async fn open(
+        dir: &Path,
+        agent_id: &str,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> Result<Self> {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create PTY log directory {}", dir.display()))?;
+        let path = dir.join(format!("{}.log", agent_id));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open PTY log {}", path.display()))?;
+        let size = file.metadata().await?.len();
+
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files: max_files.max(1),
+            file: BufWriter::new(file),
+            size,
+            at_line_start: true,
+            redactor: Redactor::default(),
+        })
+    }
+
+    /// Append a chunk of output, which may start or end mid-line
+    pub async fn write(&mut self, output: &str) -> Result<()> {
+        let output = self.redactor.redact(output);
+        self.write_redacted(&output).await
+    }
+
+    /// Write what the redactor held back and flush, once the output ended
+    pub async fn finish(&mut self) -> Result<()> {
+        let held = self.redactor.take_held();
+        self.write_redacted(&held).await?;
+        self.flush().await
+    }
+
+    async fn write_redacted(&mut self, output: &str) -> Result<()> {
+        if self.size >= self.max_bytes {
+            self.rotate().await?;
+        }
+        for line in output.split_inclusive('\n') {
+            if self.at_line_start {
+                let stamp = chrono::Local::now().format("[%Y-%m-%d %H:%M:%S%.3f] ");
+                self.write_raw(stamp.to_string().as_bytes()).await?;
+            }
+            self.write_raw(line.as_bytes()).await?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        Ok(())
+    }
+
+    /// Write a line of ccauto's own, e.g. about dropped output
+    pub async fn write_note(&mut self, note: &str) -> Result<()> {
+        if !self.at_line_start {
+            self.write("\n").await?;
+        }
+        self.write(&format!("[ccauto: {}]\n", note)).await
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush().await?)
+    }
+
+    async fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes).await?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `.log.N` files up by one, dropping the oldest, and start over
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        for n in (1..self.max_files).rev() {
+            let from = match n {
+                1 => self.path.clone(),
+                _ => self.rotated(n - 1),
+            };
+            if tokio::fs::try_exists(&from).await? {
+                tokio::fs::rename(&from, self.rotated(n)).await?;
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open PTY log {}", self.path.display()))?;
+        self.file = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), n))
+    }
+}
+
+/// Write the output of `receiver` to `log` until the PTY output closes.
+/// Writes are buffered and flushed whenever no more output is waiting, so a
+/// slow disk only makes this receiver lag instead of blocking the PTY reader.
+pub async fn record(mut log: PtyLog, mut receiver: broadcast::Receiver<String>, agent_id: &str) {
+    loop {
+        let result = match receiver.recv().await {
+            Ok(output) => log.write(&output).await,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // The broadcast only counts messages, not their size
+                tracing::warn!(
+                    "Agent {} PTY log fell behind, {} output chunks dropped",
+                    agent_id,
+                    skipped
+                );
+                log.write_note(&format!("{} output chunks dropped", skipped))
+                    .await
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let result = match result {
+            Ok(()) if receiver.is_empty() => log.flush().await,
+            result => result,
+        };
+        if let Err(e) = result {
+            tracing::error!("❌ Agent {} PTY log write failed: {:#}", agent_id, e);
+        }
+    }
+    if let Err(e) = log.finish().await {
+        tracing::error!("❌ Agent {} PTY log write failed: {:#}", agent_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::secret;
+
+    /// Lines of a log file with their timestamps removed
+    fn read_lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                assert!(line.starts_with("[20"), "no timestamp: {:?}", line);
+                line.split_once("] ").unwrap().1.to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lines_get_timestamps_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = PtyLog::open(dir.path(), "agent-0", 1024, 3).await.unwrap();
+        log.write("$ echo hi").await.unwrap();
+        log.write("\r\nhi\r\n$ ").await.unwrap();
+        log.write("exit\n").await.unwrap();
+        log.flush().await.unwrap();
+
+        let lines = read_lines(&dir.path().join("agent-0.log"));
+        assert_eq!(lines, ["$ echo hi", "hi", "$ exit"]);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = PtyLog::open(dir.path(), "agent-0", 10, 3).await.unwrap();
+        for n in 0..5 {
+            log.write(&format!("line {}\n", n)).await.unwrap();
+        }
+        log.flush().await.unwrap();
+
+        let path = |name: &str| dir.path().join(name);
+        assert_eq!(read_lines(&path("agent-0.log")), ["line 4"]);
+        assert_eq!(read_lines(&path("agent-0.log.1")), ["line 3"]);
+        assert_eq!(read_lines(&path("agent-0.log.2")), ["line 2"]);
+        assert!(!path("agent-0.log.3").exists());
+    }
+
+    #[tokio::test]
+    async fn test_record_reports_dropped_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = PtyLog::open(dir.path(), "agent-0", 1024, 1).await.unwrap();
+        let (sender, receiver) = broadcast::channel(2);
+        for n in 0..4 {
+            sender.send(format!("chunk {}\n", n)).unwrap();
+        }
+        drop(sender);
+        record(log, receiver, "agent-0").await;
+
+        let lines = read_lines(&dir.path().join("agent-0.log"));
+        assert_eq!(
+            lines,
+            ["[ccauto: 2 output chunks dropped]", "chunk 2", "chunk 3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_redacts_echoed_secrets() {
+        secret::registry().remember("pty-log-secret-91be");
+        let dir = tempfile::tempdir().unwrap();
+        let log = PtyLog::open(dir.path(), "agent-0", 1024, 1).await.unwrap();
+        let (sender, receiver) = broadcast::channel(8);
+        // The echo of the typed secret, split across reads
+        for chunk in ["Token: pty-log-se", "cret-91be\r\n", "$ pty-log"] {
+            sender.send(chunk.to_string()).unwrap();
+        }
+        drop(sender);
+        record(log, receiver, "agent-0").await;
+
+        let log = std::fs::read_to_string(dir.path().join("agent-0.log")).unwrap();
+        assert!(!log.contains("pty-log-secret-91be"));
+        let lines = read_lines(&dir.path().join("agent-0.log"));
+        assert_eq!(lines, ["Token: ***", "$ pty-log"]);
+    }
+}
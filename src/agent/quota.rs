@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+const HOUR_SECS: u64 = 3600;
+
+/// Automated commands an agent sent in the current clock hour
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CommandUsage {
+    pub used: u32,
+    pub limit: u32,
+}
+
+/// Caps the automated commands (send_keys actions of rules and entries) of
+/// one agent per clock hour; the count starts from zero again on the hour
+#[derive(Debug)]
+pub struct CommandQuota {
+    /// None disables the quota
+    limit: Option<u32>,
+    /// Hours since the Unix epoch of the current window
+    hour: u64,
+    used: u32,
+    alerted: bool,
+}
+
+impl CommandQuota {
+    pub fn new(limit: Option<u32>) -> Self {
+        Self {
+            limit,
+            hour: 0,
+            used: 0,
+            alerted: false,
+        }
+    }
+
+    /// Try to take one command at Unix time `now`. Returns false once the
+    /// quota of the current hour is used up.
+    pub fn try_acquire(&mut self, now: u64) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        if now / HOUR_SECS != self.hour {
+            self.hour = now / HOUR_SECS;
+            self.used = 0;
+            self.alerted = false;
+        }
+
+        if self.used < limit {
+            self.used += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true the first time it's called in an hour whose quota is
+    /// used up, so the caller alerts once per hour
+    pub fn should_alert(&mut self) -> bool {
+        !std::mem::replace(&mut self.alerted, true)
+    }
+
+    /// Usage at Unix time `now`, or None without a quota
+    pub fn usage(&self, now: u64) -> Option<CommandUsage> {
+        let used = if now / HOUR_SECS == self.hour {
+            self.used
+        } else {
+            0
+        };
+        self.limit.map(|limit| CommandUsage { used, limit })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_resets_on_the_hour() {
+        let mut quota = CommandQuota::new(Some(2));
+        // 10:59:00 and 11:00:00 of the same day
+        let before = 10 * HOUR_SECS + 59 * 60;
+        let after = 11 * HOUR_SECS;
+
+        assert!(quota.try_acquire(before));
+        assert!(quota.try_acquire(before));
+        assert!(!quota.try_acquire(before + 30));
+        assert!(quota.should_alert());
+        assert!(!quota.should_alert());
+        assert_eq!(
+            quota.usage(before),
+            Some(CommandUsage { used: 2, limit: 2 })
+        );
+
+        assert_eq!(quota.usage(after), Some(CommandUsage { used: 0, limit: 2 }));
+        assert!(quota.try_acquire(after));
+        assert!(quota.try_acquire(after));
+        assert!(!quota.try_acquire(after));
+        assert!(quota.should_alert());
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let mut quota = CommandQuota::new(None);
+        for _ in 0..1000 {
+            assert!(quota.try_acquire(0));
+        }
+        assert_eq!(quota.usage(0), None);
+    }
+}
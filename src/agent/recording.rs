@@ -0,0 +1,529 @@
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{Notify, broadcast};
+use tokio::task::JoinHandle;
+
+use super::AgentStatus;
+use crate::config::recording_config::Rotation;
+use crate::terminal::pty_session::{PtyEvent, PtyEventData};
+use crate::terminal::secret::Redactor;
+use crate::terminal::utf8::take_utf8;
+
+/// Writes an agent's session to asciicast v2 files in `dir`, named
+/// `<agent_id>-<start time>.cast`: a JSON header followed by one JSON line
+/// per event, `[seconds since the start, "o", output]` for output and
+/// `[seconds, "r", "COLSxROWS"]` for resizes. Secret keys sent to the
+/// agent are redacted from the output.
+pub struct Recording {
+    dir: PathBuf,
+    agent_id: String,
+    term: String,
+    rotation: Rotation,
+    size: (u16, u16),
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// Monotonic start of the current file, the clock of its events
+    started: Instant,
+    /// Local day the current file was started on
+    day: NaiveDate,
+    /// Bytes of a character split across output chunks
+    pending: Vec<u8>,
+    redactor: Redactor,
+}
+
+impl Recording {
+    pub async fn create(
+        dir: &Path,
+        agent_id: &str,
+        term: &str,
+        rotation: Rotation,
+        size: (u16, u16),
+    ) -> Result<Self> {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        let (path, file) = create_file(dir, agent_id).await?;
+        let mut recording = Self {
+            dir: dir.to_path_buf(),
+            agent_id: agent_id.to_string(),
+            term: term.to_string(),
+            rotation,
+            size,
+            path,
+            file,
+            started: Instant::now(),
+            day: Local::now().date_naive(),
+            pending: Vec::new(),
+            redactor: Redactor::default(),
+        };
+        recording.write_header().await?;
+        Ok(recording)
+    }
+
+    /// File currently written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a chunk of raw PTY output received at `now`
+    pub async fn output(&mut self, bytes: &[u8], now: Instant) -> Result<()> {
+        if self.rotation == Rotation::Daily && Local::now().date_naive() != self.day {
+            self.rotate().await?;
+        }
+        self.pending.extend_from_slice(bytes);
+        let text = self.redactor.redact(&take_utf8(&mut self.pending));
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event(now, "o", &text).await
+    }
+
+    /// Record the output the redactor held back, once the output ended
+    pub async fn finish_output(&mut self, now: Instant) -> Result<()> {
+        let held = self.redactor.take_held();
+        if held.is_empty() {
+            return Ok(());
+        }
+        self.write_event(now, "o", &held).await
+    }
+
+    /// Record a resize of the terminal at `now`; later files start with
+    /// the new size
+    pub async fn resize(&mut self, cols: u16, rows: u16, now: Instant) -> Result<()> {
+        self.size = (cols, rows);
+        self.write_event(now, "r", &format!("{}x{}", cols, rows))
+            .await
+    }
+
+    /// Record a status change; with session rotation a new file starts
+    /// when the agent becomes busy
+    pub async fn status(&mut self, was_busy: bool, status: &AgentStatus) -> Result<()> {
+        if self.rotation == Rotation::Session && !was_busy && status.is_busy() {
+            self.rotate().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush().await?)
+    }
+
+    /// Finish the current file and continue in a new one
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        let (path, file) = create_file(&self.dir, &self.agent_id).await?;
+        self.path = path;
+        self.file = file;
+        self.started = Instant::now();
+        self.day = Local::now().date_naive();
+        self.write_header().await
+    }
+
+    async fn write_header(&mut self) -> Result<()> {
+        let header = serde_json::json!({
+            "version": 2,
+            "width": self.size.0,
+            "height": self.size.1,
+            "timestamp": Local::now().timestamp(),
+            "env": {"TERM": self.term},
+        });
+        self.write_line(&header.to_string()).await
+    }
+
+    async fn write_event(&mut self, now: Instant, kind: &str, data: &str) -> Result<()> {
+        let time = now.saturating_duration_since(self.started).as_secs_f64();
+        // Microseconds, as asciinema writes them
+        let time = (time * 1e6).round() / 1e6;
+        let line = serde_json::to_string(&(time, kind, data))?;
+        self.write_line(&line).await
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        self.file.write_all(line.as_bytes()).await?;
+        self.file.write_all(b"\n").await?;
+        Ok(())
+    }
+}
+
+/// Create a new `.cast` file, numbering it when one of the same second
+/// already exists
+async fn create_file(dir: &Path, agent_id: &str) -> Result<(PathBuf, BufWriter<File>)> {
+    let stem = format!("{}-{}", agent_id, Local::now().format("%Y%m%d-%H%M%S"));
+    for n in 0.. {
+        let path = match n {
+            0 => dir.join(format!("{}.cast", stem)),
+            n => dir.join(format!("{}-{}.cast", stem, n)),
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, BufWriter::new(file))),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to create recording {}", path.display()));
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// A `.cast` file of an agent, finished or still being written
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordingFile {
+    pub file: String,
+    pub size: u64,
+    /// Unix time of the last write
+    pub modified: u64,
+}
+
+/// Recordings of `agent_id` in `dir`, newest first; none while `dir`
+/// doesn't exist
+pub async fn list_recordings(dir: &Path, agent_id: &str) -> Result<Vec<RecordingFile>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read recording directory {}", dir.display()));
+        }
+    };
+    let mut recordings = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(file) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !is_recording_of(&file, agent_id) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+        recordings.push(RecordingFile {
+            file,
+            size: metadata.len(),
+            modified,
+        });
+    }
+    // Names differ in the start time, and numbered ones came later
+    let stem = |recording: &RecordingFile| recording.file.trim_end_matches(".cast").to_string();
+    recordings.sort_by_key(|recording| std::cmp::Reverse(stem(recording)));
+    Ok(recordings)
+}
+
+/// Path of the recording `file` of `agent_id` in `dir`. None for any other
+/// name, so requests can't reach other agents' recordings or leave `dir`.
+pub fn recording_path(dir: &Path, agent_id: &str, file: &str) -> Option<PathBuf> {
+    is_recording_of(file, agent_id).then(|| dir.join(file))
+}
+
+/// Whether `file` is named like the recordings of `agent_id`, i.e.
+/// `<agent_id>-<digits and dashes>.cast`
+fn is_recording_of(file: &str, agent_id: &str) -> bool {
+    file.strip_prefix(agent_id)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".cast"))
+        .is_some_and(|stamp| {
+            !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// A running recording task, stopped with [`Recorder::finish`]
+pub struct Recorder {
+    stop: Arc<Notify>,
+    handle: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// Record `output`, the resizes among `events` and, for session
+    /// rotation, the changes of `status` until the PTY output closes or
+    /// the recorder is finished
+    pub fn spawn(
+        mut recording: Recording,
+        mut output: broadcast::Receiver<bytes::Bytes>,
+        mut events: broadcast::Receiver<PtyEvent>,
+        mut status: broadcast::Receiver<AgentStatus>,
+        agent_id: String,
+    ) -> Self {
+        let stop = Arc::new(Notify::new());
+        let stopped = Arc::clone(&stop);
+        let handle = tokio::spawn(async move {
+            let mut busy = false;
+            loop {
+                let result = tokio::select! {
+                    biased;
+                    _ = stopped.notified() => break,
+                    received = output.recv() => match received {
+                        Ok(bytes) => recording.output(&bytes, Instant::now()).await,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            tracing::warn!(
+                                "Agent {} recording fell behind, {} output chunks dropped",
+                                agent_id,
+                                skipped
+                            );
+                            Ok(())
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    Ok(event) = events.recv() => match event.data {
+                        PtyEventData::Resize { cols, rows } => {
+                            recording.resize(cols as u16, rows as u16, Instant::now()).await
+                        }
+                        _ => Ok(()),
+                    },
+                    Ok(new_status) = status.recv() => {
+                        let result = recording.status(busy, &new_status).await;
+                        busy = new_status.is_busy();
+                        result
+                    }
+                };
+                // Flushed whenever no more output is waiting, so the file
+                // only ever ends in a whole event
+                let result = match result {
+                    Ok(()) if output.is_empty() => recording.flush().await,
+                    result => result,
+                };
+                if let Err(e) = result {
+                    tracing::error!("❌ Agent {} recording write failed: {:#}", agent_id, e);
+                }
+            }
+
+            // Output that arrived before the stop still belongs to the session
+            while let Ok(bytes) = output.try_recv() {
+                if let Err(e) = recording.output(&bytes, Instant::now()).await {
+                    tracing::error!("❌ Agent {} recording write failed: {:#}", agent_id, e);
+                    break;
+                }
+            }
+            let result = match recording.finish_output(Instant::now()).await {
+                Ok(()) => recording.flush().await,
+                result => result,
+            };
+            if let Err(e) = result {
+                tracing::error!("❌ Agent {} recording write failed: {:#}", agent_id, e);
+            }
+        });
+        Self { stop, handle }
+    }
+
+    /// Write what was received so far, flush the file and stop recording
+    pub async fn finish(self) {
+        self.stop.notify_one();
+        let _ = self.handle.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Header and events of a `.cast` file, checking every line is JSON
+    fn read_cast(path: &Path) -> (serde_json::Value, Vec<(f64, String, String)>) {
+        let content = std::fs::read_to_string(path).unwrap();
+        let mut lines = content.lines();
+        let header = serde_json::from_str(lines.next().unwrap()).unwrap();
+        let events = lines
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (header, events)
+    }
+
+    #[tokio::test]
+    async fn test_recording_writes_asciicast_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recording = Recording::create(
+            dir.path(),
+            "agent-0",
+            "xterm-256color",
+            Rotation::Daily,
+            (80, 24),
+        )
+        .await
+        .unwrap();
+        let start = recording.started;
+
+        recording
+            .output(b"$ echo \xc3", start + Duration::from_millis(250))
+            .await
+            .unwrap();
+        recording
+            .output(b"\xa9\r\n", start + Duration::from_millis(500))
+            .await
+            .unwrap();
+        recording
+            .resize(120, 40, start + Duration::from_secs(1))
+            .await
+            .unwrap();
+        recording.flush().await.unwrap();
+
+        let (header, events) = read_cast(recording.path());
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 80);
+        assert_eq!(header["height"], 24);
+        assert_eq!(header["env"]["TERM"], "xterm-256color");
+        assert!(header["timestamp"].as_i64().unwrap() > 0);
+        assert_eq!(
+            events,
+            [
+                (0.25, "o".to_string(), "$ echo ".to_string()),
+                (0.5, "o".to_string(), "é\r\n".to_string()),
+                (1.0, "r".to_string(), "120x40".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recording_redacts_echoed_secrets() {
+        crate::terminal::secret::registry().remember("recording-secret-2a9f");
+        let dir = tempfile::tempdir().unwrap();
+        let recording = Recording::create(
+            dir.path(),
+            "agent-2",
+            "xterm-256color",
+            Rotation::Daily,
+            (80, 24),
+        )
+        .await
+        .unwrap();
+        let path = recording.path().to_path_buf();
+        let (output, receiver) = broadcast::channel(8);
+        let (_events, events) = broadcast::channel(1);
+        let (_status, status) = broadcast::channel(1);
+        let recorder = Recorder::spawn(recording, receiver, events, status, "agent-2".into());
+
+        // The echo of the typed secret, split across reads
+        for chunk in ["Token: recording-se", "cret-2a9f\r\n", "$ recording"] {
+            output.send(bytes::Bytes::from(chunk)).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        recorder.finish().await;
+
+        assert!(
+            !std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("recording-secret-2a9f")
+        );
+        let (_, events) = read_cast(&path);
+        let output: String = events.iter().map(|event| event.2.as_str()).collect();
+        assert_eq!(output, "Token: ***\r\n$ recording");
+    }
+
+    #[tokio::test]
+    async fn test_session_rotation_starts_a_file_per_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recording = Recording::create(
+            dir.path(),
+            "agent-1",
+            "xterm-256color",
+            Rotation::Session,
+            (80, 24),
+        )
+        .await
+        .unwrap();
+        recording.resize(100, 30, Instant::now()).await.unwrap();
+        let first = recording.path().to_path_buf();
+
+        recording.status(false, &AgentStatus::Active).await.unwrap();
+        // Stuck is the same task
+        recording.status(true, &AgentStatus::Stuck).await.unwrap();
+        recording.output(b"working", Instant::now()).await.unwrap();
+        recording.flush().await.unwrap();
+
+        let second = recording.path().to_path_buf();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        let (header, events) = read_cast(&second);
+        assert_eq!(
+            (header["width"].clone(), header["height"].clone()),
+            (100.into(), 30.into())
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].2, "working");
+    }
+
+    #[tokio::test]
+    async fn test_list_recordings_of_one_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "agent-1-20250101-090000.cast",
+            "agent-1-20250102-090000.cast",
+            "agent-1-20250102-090000-1.cast",
+            "agent-10-20250101-090000.cast",
+            "agent-1.log",
+        ] {
+            std::fs::write(dir.path().join(file), "{}\n").unwrap();
+        }
+
+        let files: Vec<String> = list_recordings(dir.path(), "agent-1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|recording| recording.file)
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "agent-1-20250102-090000-1.cast",
+                "agent-1-20250102-090000.cast",
+                "agent-1-20250101-090000.cast",
+            ]
+        );
+        assert!(
+            list_recordings(&dir.path().join("missing"), "agent-1")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(recording_path(dir.path(), "agent-1", "agent-1-20250101-090000.cast").is_some());
+        assert!(recording_path(dir.path(), "agent-1", "agent-10-20250101-090000.cast").is_none());
+        assert!(recording_path(dir.path(), "agent-1", "agent-1-../../etc/passwd.cast").is_none());
+        assert!(recording_path(dir.path(), "agent-1", "agent-1-.cast").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_finish_flushes_received_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let recording = Recording::create(
+            dir.path(),
+            "agent-2",
+            "xterm-256color",
+            Rotation::Daily,
+            (80, 24),
+        )
+        .await
+        .unwrap();
+        let path = recording.path().to_path_buf();
+        let (output, output_rx) = broadcast::channel(16);
+        let (_events, events_rx) = broadcast::channel(16);
+        let (_status, status_rx) = broadcast::channel(16);
+
+        let recorder = Recorder::spawn(
+            recording,
+            output_rx,
+            events_rx,
+            status_rx,
+            "agent-2".to_string(),
+        );
+        output.send(bytes::Bytes::from_static(b"one ")).unwrap();
+        output.send(bytes::Bytes::from_static(b"two")).unwrap();
+        recorder.finish().await;
+
+        let (_, events) = read_cast(&path);
+        let text: String = events.into_iter().map(|(_, _, data)| data).collect();
+        assert_eq!(text, "one two");
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two samples, so CPU usage is averaged over a
+/// meaningful window even though the status monitor ticks more often
+pub const RESOURCE_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Clock ticks per second of the times in /proc/<pid>/stat (USER_HZ, fixed
+/// at 100 by the Linux ABI)
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Resources used by an agent's shell and its child processes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU time used since the previous sample, in percent of one core
+    pub cpu_percent: f64,
+    /// Resident memory in bytes
+    pub rss_bytes: u64,
+    /// Child processes of the shell
+    pub child_count: usize,
+}
+
+/// Turns /proc readings into ResourceUsage, keeping the CPU times of the
+/// previous sample to compute CPU percentages
+#[derive(Debug, Default)]
+pub struct ResourceSampler {
+    last: Option<Instant>,
+    cpu_ticks: HashMap<u32, u64>,
+    usage: ResourceUsage,
+}
+
+impl ResourceSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latest sample
+    pub fn usage(&self) -> &ResourceUsage {
+        &self.usage
+    }
+
+    /// Whether a new sample is due at `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.last
+            .is_none_or(|last| now.duration_since(last) >= RESOURCE_SAMPLE_INTERVAL)
+    }
+
+    /// Sample the shell `shell_pid` and its `children`. Processes that
+    /// vanished meanwhile are skipped; the first sample of a process only
+    /// establishes its CPU baseline.
+    pub fn sample(&mut self, shell_pid: Option<u32>, children: &[u32], now: Instant) {
+        let elapsed = self.last.map(|last| now.duration_since(last).as_secs_f64());
+        let mut cpu_ticks = HashMap::new();
+        let mut used_ticks = 0;
+        let mut rss_bytes = 0;
+
+        for pid in shell_pid.iter().chain(children) {
+            let Some(stat) = read_stat(*pid) else {
+                continue;
+            };
+            if let Some(previous) = self.cpu_ticks.get(pid) {
+                used_ticks += stat.cpu_ticks.saturating_sub(*previous);
+            }
+            cpu_ticks.insert(*pid, stat.cpu_ticks);
+            rss_bytes += stat.rss_bytes;
+        }
+
+        let cpu_percent = match elapsed {
+            Some(elapsed) if elapsed > 0.0 => {
+                used_ticks as f64 / CLOCK_TICKS_PER_SEC / elapsed * 100.0
+            }
+            _ => 0.0,
+        };
+        self.usage = ResourceUsage {
+            cpu_percent,
+            rss_bytes,
+            child_count: children.len(),
+        };
+        self.cpu_ticks = cpu_ticks;
+        self.last = Some(now);
+    }
+}
+
+struct ProcStat {
+    /// User plus system time
+    cpu_ticks: u64,
+    rss_bytes: u64,
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let cpu_ticks = parse_cpu_ticks(&stat)?;
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    Some(ProcStat {
+        cpu_ticks,
+        rss_bytes: parse_rss_bytes(&status).unwrap_or(0),
+    })
+}
+
+/// utime + stime from /proc/<pid>/stat. The command name in parentheses may
+/// contain spaces, so fields are counted after its closing parenthesis.
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    // Fields 14 and 15 of the line; the first field after ')' is field 3
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// VmRSS from /proc/<pid>/status (absent for kernel threads and zombies)
+fn parse_rss_bytes(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Format bytes as e.g. "12.3 MiB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_fields() {
+        let stat = "4242 (my (odd) cmd) S 1 4242 4242 0 -1 4194304 100 0 0 0 250 50 0 0 20 0 1 0 1000 1000000 300";
+        assert_eq!(parse_cpu_ticks(stat), Some(300));
+        assert_eq!(parse_cpu_ticks("garbage"), None);
+
+        let status = "Name:\tbash\nVmPeak:\t  9000 kB\nVmRSS:\t    5120 kB\n";
+        assert_eq!(parse_rss_bytes(status), Some(5 * 1024 * 1024));
+        assert_eq!(parse_rss_bytes("Name:\tkthreadd\n"), None);
+    }
+
+    #[test]
+    fn test_sample_own_process() {
+        let mut sampler = ResourceSampler::new();
+        let start = Instant::now();
+        assert!(sampler.is_due(start));
+
+        sampler.sample(Some(std::process::id()), &[], start);
+        assert!(sampler.usage().rss_bytes > 0);
+        assert_eq!(sampler.usage().cpu_percent, 0.0);
+        assert!(!sampler.is_due(start));
+
+        // Vanished processes are skipped but still counted as children
+        sampler.sample(
+            Some(std::process::id()),
+            &[u32::MAX],
+            start + RESOURCE_SAMPLE_INTERVAL,
+        );
+        assert_eq!(sampler.usage().child_count, 1);
+        assert!(sampler.usage().cpu_percent >= 0.0);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024 / 2), "1.5 GiB");
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::Result;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+
+use crate::agent::{Agent, AgentStatus};
+use crate::terminal::pty_session::{PtyEvent, PtyEventData};
+
+/// How an agent brings its shell back after it exited
+#[derive(Debug, Clone)]
+pub struct RespawnPolicy {
+    /// Wait before the first respawn, doubled for each further one
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Respawns in a row after which the agent is given up
+    pub max_respawns: u32,
+    /// A shell running this long resets the count of respawns in a row
+    pub reset_after: Duration,
+}
+
+impl Default for RespawnPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_respawns: 5,
+            reset_after: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RespawnPolicy {
+    fn backoff(&self, respawns: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(respawns))
+            .min(self.max_backoff)
+    }
+}
+
+/// Restart the agent's shell whenever it exits, then send its init keys
+/// again. Returns once `max_respawns` respawns in a row didn't keep the
+/// shell running.
+pub async fn respawn_on_exit(agent: &Agent, policy: &RespawnPolicy) -> Result<()> {
+    let mut events = agent.get_process().get_pty_event_receiver().await?;
+    let mut respawns = 0;
+    let mut started = Instant::now();
+
+    loop {
+        // Failed spawns are retried below, or by the next wake
+        let reason = match events.recv().await {
+            Ok(PtyEvent {
+                data: data @ (PtyEventData::ChildExited { .. } | PtyEventData::ReaderClosed { .. }),
+                ..
+            }) => data.exit_reason().unwrap_or_default(),
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        if agent.is_shutting_down() {
+            return Ok(());
+        }
+        // Hung up on purpose; waking the agent starts a new shell
+        if agent.is_sleeping() {
+            continue;
+        }
+        tracing::warn!("💀 Agent {} shell exited ({})", agent.get_id(), reason);
+        if started.elapsed() >= policy.reset_after {
+            respawns = 0;
+        }
+
+        loop {
+            if respawns >= policy.max_respawns {
+                tracing::error!(
+                    "❌ Agent {} shell died {} times in a row, giving up respawning it",
+                    agent.get_id(),
+                    respawns
+                );
+                return Ok(());
+            }
+            let backoff = policy.backoff(respawns);
+            respawns += 1;
+            tracing::info!(
+                "Respawning agent {} shell in {:?} ({}/{})",
+                agent.get_id(),
+                backoff,
+                respawns,
+                policy.max_respawns
+            );
+            tokio::time::sleep(backoff).await;
+
+            match agent.get_process().restart().await {
+                Ok(()) => {
+                    agent.record_restart();
+                    break;
+                }
+                Err(e) => tracing::error!("❌ Agent {} respawn failed: {}", agent.get_id(), e),
+            }
+        }
+        started = Instant::now();
+        tracing::info!("🔄 Agent {} shell respawned", agent.get_id());
+
+        agent.set_status(AgentStatus::Idle);
+        if let Err(e) = agent.run_init().await {
+            tracing::error!(
+                "❌ Init of respawned agent {} failed: {}",
+                agent.get_id(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use std::sync::Arc;
+
+    fn policy() -> RespawnPolicy {
+        RespawnPolicy {
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+            max_respawns: 3,
+            reset_after: Duration::from_secs(60),
+        }
+    }
+
+    async fn agent_with(mock: &Arc<MockPtyProcess>) -> Arc<Agent> {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.init_keys = vec!["cd /tmp\r".to_string()];
+        Agent::new_with_process(0, &config, Box::new(Arc::clone(mock)))
+            .await
+            .unwrap()
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = policy();
+        let backoffs: Vec<_> = (0..4).map(|n| policy.backoff(n).as_millis()).collect();
+        assert_eq!(backoffs, [10, 20, 40, 40]);
+    }
+
+    #[tokio::test]
+    async fn test_respawn_restarts_shell_and_sends_init_keys() {
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = agent_with(&mock).await;
+        let task = tokio::spawn({
+            let agent = Arc::clone(&agent);
+            async move { respawn_on_exit(&agent, &policy()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        mock.exit(1);
+        wait_until(|| !mock.get_sent_inputs().is_empty()).await;
+        assert_eq!(mock.restart_count(), 1);
+        assert_eq!(mock.get_sent_inputs(), ["cd /tmp\r"]);
+
+        mock.exit(0);
+        wait_until(|| mock.get_sent_inputs().len() == 2).await;
+        assert_eq!(mock.restart_count(), 2);
+        assert!(!task.is_finished());
+        task.abort();
+
+        let counters = agent.get_restart_counters();
+        assert_eq!(counters.restarts, 2);
+        assert!(counters.last_restart_at.is_some());
+        agent.reset_restart_counters();
+        assert_eq!(agent.get_restart_counters(), Default::default());
+    }
+
+    #[tokio::test]
+    async fn test_sleeping_agent_is_not_respawned() {
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = agent_with(&mock).await;
+        let task = tokio::spawn({
+            let agent = Arc::clone(&agent);
+            async move { respawn_on_exit(&agent, &policy()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        agent.sleep().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(mock.restart_count(), 0);
+
+        agent.wake().await.unwrap();
+        assert_eq!(mock.restart_count(), 1);
+        assert_eq!(mock.get_sent_inputs(), ["cd /tmp\r"]);
+        // Going to sleep is no exit worth reporting
+        assert_eq!(agent.get_restart_counters().restarts, 1);
+        assert_eq!(agent.get_restart_counters().last_exit_reason, None);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_respawn_gives_up_after_max_respawns() {
+        let mock = Arc::new(MockPtyProcess::with_failure());
+        let agent = agent_with(&mock).await;
+        let task = tokio::spawn({
+            let agent = Arc::clone(&agent);
+            async move { respawn_on_exit(&agent, &policy()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        mock.exit(127);
+        tokio::time::timeout(Duration::from_secs(2), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(mock.restart_count(), 3);
+    }
+}
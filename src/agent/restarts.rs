@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// How often an agent's shell was restarted and why it last exited. Kept
+/// for the lifetime of the ccauto process; only a reset clears them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestartCounters {
+    /// Shells started after the first one, by respawn or wake
+    pub restarts: u32,
+    /// Unix time of the last restart
+    pub last_restart_at: Option<u64>,
+    /// Why the shell last exited without being put to sleep or shut down,
+    /// e.g. "exit code 1"
+    pub last_exit_reason: Option<String>,
+}
+
+impl RestartCounters {
+    pub fn record_restart(&mut self, at: u64) {
+        self.restarts = self.restarts.saturating_add(1);
+        self.last_restart_at = Some(at);
+    }
+
+    /// Record why the shell ended, see `PtyEventData::exit_reason`
+    pub fn record_exit(&mut self, reason: String) {
+        self.last_exit_reason = Some(reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_restarts_and_exits() {
+        let mut counters = RestartCounters::default();
+        counters.record_exit("exit code 1".to_string());
+        counters.record_restart(100);
+        counters.record_exit("killed (Hangup)".to_string());
+        counters.record_restart(200);
+
+        assert_eq!(
+            counters,
+            RestartCounters {
+                restarts: 2,
+                last_restart_at: Some(200),
+                last_exit_reason: Some("killed (Hangup)".to_string()),
+            }
+        );
+    }
+}
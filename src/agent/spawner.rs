@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, timeout};
+
+use crate::agent::{Agent, AgentStatus};
+use crate::config::Config;
+use crate::config::rules_config::Rule;
+use crate::queue::QueueManager;
+#[cfg(test)]
+use crate::terminal::pty_process_trait::MockPtyProcess;
+
+/// How long a spawned agent may stay idle after receiving its action before
+/// it is considered done without ever becoming active
+pub const SPAWN_IDLE_GRACE: Duration = Duration::from_secs(10);
+
+/// Creates temporary agents for `spawn_agent: true` entries, one per source line
+pub struct AgentSpawner {
+    config: Config,
+    rules: Vec<Rule>,
+    next_index: AtomicUsize,
+    /// Concurrency limit per trigger name
+    limits: Mutex<HashMap<String, Arc<Semaphore>>>,
+    idle_grace: Duration,
+    /// Spawned agents, to stop their processes on shutdown
+    live: Mutex<Vec<Weak<Agent>>>,
+    /// Queues of the pool, shared with spawned agents
+    queues: Arc<QueueManager>,
+    /// Processes of spawned agents when testing with mocks
+    #[cfg(test)]
+    mocks: Option<Mutex<Vec<Arc<MockPtyProcess>>>>,
+}
+
+impl AgentSpawner {
+    /// Spawned agents get IDs after the pool's (`first_index` = pool size)
+    /// and no web server
+    pub fn new(
+        config: &Config,
+        rules: Vec<Rule>,
+        first_index: usize,
+        queues: Arc<QueueManager>,
+    ) -> Self {
+        let mut config = config.clone();
+        config.web_ui.enabled = false;
+        Self {
+            config,
+            rules,
+            next_index: AtomicUsize::new(first_index),
+            limits: Mutex::new(HashMap::new()),
+            idle_grace: SPAWN_IDLE_GRACE,
+            live: Mutex::new(Vec::new()),
+            queues,
+            #[cfg(test)]
+            mocks: None,
+        }
+    }
+
+    /// Spawner creating agents on mock PTYs for testing
+    #[cfg(test)]
+    pub fn with_mock(
+        config: &Config,
+        rules: Vec<Rule>,
+        first_index: usize,
+        queues: Arc<QueueManager>,
+    ) -> Self {
+        Self {
+            mocks: Some(Mutex::new(Vec::new())),
+            ..Self::new(config, rules, first_index, queues)
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_idle_grace(self, idle_grace: Duration) -> Self {
+        Self { idle_grace, ..self }
+    }
+
+    /// Mock PTYs of the agents spawned so far
+    #[cfg(test)]
+    pub fn spawned_mocks(&self) -> Vec<Arc<MockPtyProcess>> {
+        self.mocks
+            .as_ref()
+            .map(|mocks| mocks.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// Config the spawned agents are created from
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Start a fresh agent with rule monitoring, waiting while `limit` agents
+    /// spawned for `trigger` are still alive
+    pub async fn spawn(&self, trigger: &str, limit: usize) -> Result<SpawnedAgent> {
+        let semaphore = Arc::clone(
+            self.limits
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(trigger.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit))),
+        );
+        if semaphore.available_permits() == 0 {
+            tracing::info!(
+                "Entry '{}' has {} spawned agents running, waiting for one to finish",
+                trigger,
+                limit
+            );
+        }
+        let permit = semaphore.acquire_owned().await?;
+
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        let agent = self
+            .create_agent(index)
+            .await
+            .with_context(|| format!("Failed to spawn an agent for entry '{}'", trigger))?;
+        let handles = Arc::clone(&agent)
+            .setup_monitoring(self.rules.clone())
+            .await?;
+        {
+            let mut live = self.live.lock().unwrap_or_else(|e| e.into_inner());
+            live.retain(|agent| agent.strong_count() > 0);
+            live.push(Arc::downgrade(&agent));
+        }
+        tracing::info!(
+            "🐣 Spawned agent {} for entry '{}'",
+            agent.get_id(),
+            trigger
+        );
+
+        Ok(SpawnedAgent {
+            agent,
+            handles,
+            idle_grace: self.idle_grace,
+            _permit: permit,
+        })
+    }
+
+    /// Spawned agents that have not been torn down yet
+    pub fn live_agents(&self) -> Vec<Arc<Agent>> {
+        self.live
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    async fn create_agent(&self, index: usize) -> Result<Arc<Agent>> {
+        #[cfg(test)]
+        if let Some(mocks) = &self.mocks {
+            let mock_pty = Arc::new(MockPtyProcess::new());
+            mocks.lock().unwrap().push(Arc::clone(&mock_pty));
+            let queues = Arc::clone(&self.queues);
+            return Agent::new_with_queues(index, &self.config, Box::new(mock_pty), queues).await;
+        }
+        Agent::from_config(index, &self.config, Arc::clone(&self.queues)).await
+    }
+}
+
+/// A temporary agent; its process and monitoring stop when this is dropped
+pub struct SpawnedAgent {
+    agent: Arc<Agent>,
+    handles: Vec<JoinHandle<()>>,
+    idle_grace: Duration,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl SpawnedAgent {
+    pub fn agent(&self) -> &Arc<Agent> {
+        &self.agent
+    }
+
+    /// Keep the agent until it finishes its work (becomes active, then idle
+    /// again) or `max_runtime` passes, then tear it down
+    pub async fn retire_when_idle(self, max_runtime: Option<Duration>) {
+        let finished = async {
+            let started = timeout(
+                self.idle_grace,
+                self.agent.wait_for_status(AgentStatus::is_busy),
+            )
+            .await;
+            if started.is_ok() {
+                self.agent.wait_for_status(|status| !status.is_busy()).await;
+            }
+        };
+
+        match max_runtime {
+            Some(max_runtime) => {
+                if timeout(max_runtime, finished).await.is_err() {
+                    tracing::warn!(
+                        "Spawned agent {} exceeded max_runtime ({:?})",
+                        self.agent.get_id(),
+                        max_runtime
+                    );
+                }
+            }
+            None => finished.await,
+        }
+        tracing::info!("Retiring spawned agent {}", self.agent.get_id());
+    }
+}
+
+impl Drop for SpawnedAgent {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawner() -> AgentSpawner {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        AgentSpawner::with_mock(&config, vec![], 1, Arc::default())
+            .with_idle_grace(Duration::from_millis(100))
+    }
+
+    #[tokio::test]
+    async fn test_spawned_agent_is_torn_down() {
+        let spawner = spawner();
+        let spawned = spawner.spawn("tasks", 2).await.unwrap();
+        assert_eq!(spawned.agent().get_id(), "agent-1");
+
+        let agent = Arc::downgrade(spawned.agent());
+        spawned.retire_when_idle(None).await;
+        // Aborted monitoring tasks release their references asynchronously
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while agent.upgrade().is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(spawner.live_agents().is_empty());
+
+        let next = spawner.spawn("tasks", 2).await.unwrap();
+        assert_eq!(next.agent().get_id(), "agent-2");
+        assert_eq!(spawner.live_agents().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_respects_concurrency_limit() {
+        let spawner = spawner();
+        let first = spawner.spawn("tasks", 1).await.unwrap();
+        assert!(
+            timeout(Duration::from_millis(100), spawner.spawn("tasks", 1))
+                .await
+                .is_err()
+        );
+        // Other entries have their own limit
+        assert!(spawner.spawn("other", 1).await.is_ok());
+
+        drop(first);
+        assert!(spawner.spawn("tasks", 1).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retire_after_max_runtime() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        // The agent never becomes active, so only max_runtime ends the wait
+        let spawner = AgentSpawner::with_mock(&config, vec![], 1, Arc::default())
+            .with_idle_grace(Duration::from_secs(60));
+        let spawned = spawner.spawn("tasks", 1).await.unwrap();
+
+        let started = std::time::Instant::now();
+        spawned
+            .retire_when_idle(Some(Duration::from_millis(200)))
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use super::AgentStatus;
+
+/// Time an agent spent in its states, in whole seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateDurations {
+    /// Time in the current status
+    pub state_secs: u64,
+    /// Time spent busy (Active or Stuck), the current task included
+    pub active_secs: u64,
+    /// Time spent Idle between tasks, the current wait included
+    pub idle_secs: u64,
+    /// Transitions from not busy to busy, i.e. tasks started
+    pub activations: u32,
+}
+
+/// Status transition timestamps of one agent, kept up to date by its
+/// status updates
+#[derive(Debug)]
+pub struct StateTimes {
+    status: AgentStatus,
+    since: Instant,
+    /// Start of the current busy period
+    busy_since: Option<Instant>,
+    /// Status and length of the period before the current one
+    previous: Option<(AgentStatus, Duration)>,
+    activations: u32,
+    total_active: Duration,
+    total_idle: Duration,
+}
+
+impl StateTimes {
+    pub fn new(status: AgentStatus, now: Instant) -> Self {
+        Self {
+            busy_since: status.is_busy().then_some(now),
+            status,
+            since: now,
+            previous: None,
+            activations: 0,
+            total_active: Duration::ZERO,
+            total_idle: Duration::ZERO,
+        }
+    }
+
+    /// Record the change to `status` at `now`, returning the length of the
+    /// busy period it ended, if any
+    pub fn transition(&mut self, status: AgentStatus, now: Instant) -> Option<Duration> {
+        let period = now.saturating_duration_since(self.since);
+        if self.status == AgentStatus::Idle {
+            self.total_idle += period;
+        }
+        let mut busy_period = None;
+        match (self.busy_since, status.is_busy()) {
+            (None, true) => {
+                self.activations += 1;
+                self.busy_since = Some(now);
+            }
+            (Some(busy_since), false) => {
+                let busy = now.saturating_duration_since(busy_since);
+                self.total_active += busy;
+                self.busy_since = None;
+                busy_period = Some(busy);
+            }
+            _ => {}
+        }
+        self.previous = Some((std::mem::replace(&mut self.status, status), period));
+        self.since = now;
+        busy_period
+    }
+
+    /// When the current status was entered
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+
+    /// Status and length of the period before the current one
+    pub fn previous(&self) -> Option<&(AgentStatus, Duration)> {
+        self.previous.as_ref()
+    }
+
+    pub fn durations(&self, now: Instant) -> StateDurations {
+        let current = now.saturating_duration_since(self.since);
+        let active = self.total_active
+            + self
+                .busy_since
+                .map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+        let idle = match self.status {
+            AgentStatus::Idle => self.total_idle + current,
+            _ => self.total_idle,
+        };
+        StateDurations {
+            state_secs: current.as_secs(),
+            active_secs: active.as_secs(),
+            idle_secs: idle.as_secs(),
+            activations: self.activations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_times_aggregate_tasks() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut times = StateTimes::new(AgentStatus::Idle, start);
+
+        // Two tasks, the first one stuck for a while
+        times.transition(AgentStatus::Active, at(10));
+        times.transition(AgentStatus::Stuck, at(40));
+        times.transition(AgentStatus::Active, at(100));
+        times.transition(AgentStatus::Idle, at(110));
+        assert_eq!(
+            times.previous(),
+            Some(&(AgentStatus::Active, Duration::from_secs(10)))
+        );
+        times.transition(AgentStatus::Active, at(130));
+
+        assert_eq!(times.since(), at(130));
+        assert_eq!(
+            times.durations(at(135)),
+            StateDurations {
+                state_secs: 5,
+                active_secs: 105,
+                idle_secs: 30,
+                activations: 2,
+            }
+        );
+
+        times.transition(AgentStatus::Idle, at(140));
+        assert_eq!(times.durations(at(150)).idle_secs, 40);
+        assert_eq!(times.durations(at(150)).active_secs, 110);
+    }
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -11,4 +11,141 @@ pub struct Cli {
     /// Enable debug logging for internal details
     #[arg(short, long)]
     pub debug: bool,
+
+    /// Log matched actions instead of sending keys to agents
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Path to the runtime state file shared between ccauto invocations
+    #[arg(long, global = true)]
+    pub state_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Show runtime information from a running ccauto process
+    Show {
+        /// Show per-agent rule hit statistics
+        #[arg(long)]
+        stats: bool,
+
+        /// Show the next three runs of each cron entry in the config
+        #[arg(long)]
+        schedule: bool,
+
+        /// Show the recent runs of each trigger entry
+        #[arg(long)]
+        history: bool,
+
+        /// Show the status, CPU, memory and child processes of each agent
+        #[arg(long)]
+        agents: bool,
+
+        /// Show the parsed rules, entries and settings of the config file
+        #[arg(long)]
+        config: bool,
+
+        /// With --config, show those of the ccauto process whose control
+        /// API is at this URL instead, e.g. http://localhost:9990
+        #[arg(long, value_name = "URL", requires = "config")]
+        remote: Option<String>,
+    },
+    /// Replay sample output against the configured rules without starting agents
+    TestRule {
+        /// File with sample terminal output (reads stdin when omitted or "-")
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// Feed the sample in chunks of this many bytes to reproduce chunk-boundary issues
+        #[arg(long)]
+        chunk_size: Option<usize>,
+    },
+    /// Run a trigger entry now in the running ccauto process, or pause/resume it
+    #[command(args_conflicts_with_subcommands = true)]
+    Trigger {
+        #[command(subcommand)]
+        command: Option<TriggerCommand>,
+
+        /// Name of the trigger entry to run
+        #[arg(required = true)]
+        name: Option<String>,
+
+        /// Agent to run on (e.g. agent-1); defaults to the next idle agent
+        #[arg(long)]
+        agent: Option<String>,
+    },
+    /// Send keys to the agents of the running ccauto process
+    Send {
+        /// Send to every agent in the pool
+        #[arg(long, required = true)]
+        all: bool,
+
+        /// Key sequences sent one after another, like the keys of a
+        /// send_keys action (e.g. "export FOO=1" Enter, or C-c)
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Connect this terminal to an agent of the running ccauto process
+    Attach {
+        /// Agent to attach to (e.g. agent-1)
+        agent: String,
+
+        /// Key that detaches, in send_keys notation
+        #[arg(long, default_value = "C-\\")]
+        detach_key: String,
+    },
+    /// Resize the terminal of an agent of the running ccauto process
+    Resize {
+        /// Agent to resize (e.g. agent-1)
+        agent: String,
+        cols: u16,
+        rows: u16,
+    },
+    /// Run a shell command on a throwaway terminal of the running ccauto
+    /// process, outside the agent pool, and print its output
+    Exec {
+        /// Command, run with `sh -c`
+        command: String,
+
+        /// Kill the command after this long (e.g. 30s); defaults to 60s
+        #[arg(long)]
+        timeout: Option<String>,
+    },
+    /// Reset the restart count and last exit reason of an agent of the
+    /// running ccauto process
+    ResetRestarts {
+        /// Agent to reset (e.g. agent-1)
+        agent: String,
+    },
+    /// Maintain the persistent store of `dedupe: true` entries
+    Dedupe {
+        #[command(subcommand)]
+        command: DedupeCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TriggerCommand {
+    /// Skip the runs of a timer or cron entry until it is resumed
+    Pause {
+        /// Name of the trigger entry
+        name: String,
+    },
+    /// Resume a paused timer or cron entry
+    Resume {
+        /// Name of the trigger entry
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DedupeCommand {
+    /// Forget the lines seen by a trigger so they are processed again
+    Clear {
+        /// Name of the trigger entry
+        trigger: String,
+    },
 }
@@ -1,6 +1,16 @@
-use crate::config::rules_config::RuleConfig;
-use crate::config::triggers_config::TriggerConfig;
+use crate::config::helper::{ActionType, parse_action};
+use crate::config::rules_config::{RuleConfig, RuleGroupConfig};
+use crate::config::terminal_config::TerminalOverride;
+use crate::config::triggers_config::{
+    ActionConfig, TriggerConfig, WaitCondition, WaitFor, WaitForSetting,
+};
+use crate::trigger::history::DEFAULT_HISTORY_SIZE;
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::config::helper::parse_duration;
 
 // Agents config matching config.yaml structure
 #[derive(Debug, Deserialize, Clone)]
@@ -11,6 +21,68 @@ pub struct AgentsConfig {
     pub triggers: Vec<TriggerConfig>,
     #[serde(default)]
     pub rules: Vec<RuleConfig>,
+    /// Named rule groups, evaluated after ungrouped rules
+    #[serde(default)]
+    pub rule_groups: Vec<RuleGroupConfig>,
+    /// Runs kept per trigger for `ccauto show --history` and GET /api/triggers
+    #[serde(default = "default_trigger_history")]
+    pub trigger_history: usize,
+    /// Time all `shutdown` entries together may take before ccauto exits anyway
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: String,
+    /// Time processes started in the agents' shells get to exit on SIGTERM
+    /// at shutdown before they are killed
+    #[serde(default = "default_shutdown_grace")]
+    pub shutdown_grace: String,
+    /// Keep entries paused with `ccauto trigger pause` paused across restarts
+    #[serde(default)]
+    pub persist_paused: bool,
+    /// Key sequences sent to each agent's shell right after it starts,
+    /// before any entry runs, e.g. `["cd ~/work\r", "source .env\r"]`
+    #[serde(default)]
+    pub init_keys: Vec<String>,
+    /// Prompt to wait for before the first init key sequence and after each
+    /// one, e.g. `{pattern: "\\$ $", timeout: 10s}`
+    #[serde(default)]
+    pub init_wait_for: Option<WaitForSetting>,
+    /// Settings of single agents, keyed by agent ID (e.g. `agent-1`)
+    #[serde(default)]
+    pub agent_overrides: BTreeMap<String, AgentOverride>,
+    /// Restart an agent's shell when it exits, with exponential backoff
+    #[serde(default = "default_respawn")]
+    pub respawn: bool,
+    /// Time without output after which a busy agent counts as stuck
+    #[serde(default = "default_stuck_after")]
+    pub stuck_after: String,
+    /// Action run once each time an agent becomes stuck, e.g.
+    /// `{action: notify, body: "${agent_id} looks stuck"}`
+    #[serde(default)]
+    pub on_stuck: Option<ActionConfig>,
+    /// Time an agent may stay Idle before its shell is shut down until an
+    /// entry or the web UI needs it again, e.g. `2h`. Off when unset.
+    #[serde(default)]
+    pub idle_timeout: Option<String>,
+    /// Send_keys actions rules and entries may send to one agent per clock
+    /// hour; further ones are refused until the next hour. Off when unset.
+    #[serde(default)]
+    pub max_commands_per_hour: Option<u32>,
+}
+
+/// Per-agent settings replacing the pool-wide ones
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AgentOverride {
+    /// Replaces `init_keys` for this agent
+    #[serde(default)]
+    pub init_keys: Option<Vec<String>>,
+    /// Labels entries and rules can target with `tag:`, e.g. `[claude, gpu]`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Replaces `terminal.term` and `terminal.lang` for this agent
+    #[serde(default)]
+    pub terminal: Option<TerminalOverride>,
+    /// Replaces `web_ui.read_only` for this agent
+    #[serde(default)]
+    pub read_only: Option<bool>,
 }
 
 impl Default for AgentsConfig {
@@ -19,14 +91,116 @@ impl Default for AgentsConfig {
             pool: default_pool_size(),
             triggers: Vec::new(),
             rules: Vec::new(),
+            rule_groups: Vec::new(),
+            trigger_history: default_trigger_history(),
+            shutdown_timeout: default_shutdown_timeout(),
+            shutdown_grace: default_shutdown_grace(),
+            persist_paused: false,
+            init_keys: Vec::new(),
+            init_wait_for: None,
+            agent_overrides: BTreeMap::new(),
+            respawn: default_respawn(),
+            stuck_after: default_stuck_after(),
+            on_stuck: None,
+            idle_timeout: None,
+            max_commands_per_hour: None,
         }
     }
 }
 
+impl AgentsConfig {
+    pub fn shutdown_timeout(&self) -> Result<Duration> {
+        parse_duration(&self.shutdown_timeout)
+    }
+
+    pub fn shutdown_grace(&self) -> Result<Duration> {
+        parse_duration(&self.shutdown_grace).context("Invalid 'shutdown_grace'")
+    }
+
+    pub fn stuck_after(&self) -> Result<Duration> {
+        parse_duration(&self.stuck_after).context("Invalid 'stuck_after'")
+    }
+
+    pub fn on_stuck(&self) -> Result<Option<ActionType>> {
+        self.on_stuck
+            .as_ref()
+            .map(|on_stuck| {
+                parse_action(&on_stuck.action, &on_stuck.keys, &on_stuck.params)
+                    .context("Invalid 'on_stuck' action")
+            })
+            .transpose()
+    }
+
+    pub fn idle_timeout(&self) -> Result<Option<Duration>> {
+        self.idle_timeout
+            .as_deref()
+            .map(|timeout| parse_duration(timeout).context("Invalid 'idle_timeout'"))
+            .transpose()
+    }
+
+    /// Init key sequences of the agent `agent_id`, its override taking precedence
+    pub fn init_keys(&self, agent_id: &str) -> &[String] {
+        self.agent_overrides
+            .get(agent_id)
+            .and_then(|agent| agent.init_keys.as_deref())
+            .unwrap_or(&self.init_keys)
+    }
+
+    /// Tags of the agent `agent_id`
+    pub fn tags(&self, agent_id: &str) -> &[String] {
+        self.agent_overrides
+            .get(agent_id)
+            .map(|agent| agent.tags.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Whether any pool agent has `tag`
+    pub fn has_tag(&self, tag: &str) -> bool {
+        (0..self.pool).any(|index| {
+            self.tags(&format!("agent-{}", index))
+                .iter()
+                .any(|t| t == tag)
+        })
+    }
+
+    /// Parsed `init_wait_for`. Only a prompt pattern can tell that the
+    /// shell has handled a key sequence.
+    pub fn init_wait_for(&self) -> Result<Option<WaitFor>> {
+        let Some(setting) = &self.init_wait_for else {
+            return Ok(None);
+        };
+        let wait = WaitFor::parse(setting).context("Invalid 'init_wait_for'")?;
+        if !matches!(wait.condition, WaitCondition::Pattern(_)) {
+            anyhow::bail!("'init_wait_for' needs a 'pattern'");
+        }
+        Ok(Some(wait))
+    }
+}
+
 fn default_pool_size() -> usize {
     1
 }
 
+fn default_respawn() -> bool {
+    true
+}
+
+fn default_shutdown_grace() -> String {
+    "5s".to_string()
+}
+
+fn default_stuck_after() -> String {
+    "60s".to_string()
+}
+
+fn default_trigger_history() -> usize {
+    DEFAULT_HISTORY_SIZE
+}
+
+fn default_shutdown_timeout() -> String {
+    "10s".to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,6 +209,13 @@ mod tests {
     fn test_default_agents_config() {
         let config = AgentsConfig::default();
         assert_eq!(config.pool, 1);
+        assert_eq!(config.trigger_history, 20);
+        assert_eq!(config.shutdown_timeout().unwrap(), Duration::from_secs(10));
+        assert_eq!(config.shutdown_grace().unwrap(), Duration::from_secs(5));
+        assert!(!config.persist_paused);
+        assert!(config.respawn);
+        assert_eq!(config.stuck_after().unwrap(), Duration::from_secs(60));
+        assert_eq!(config.idle_timeout().unwrap(), None);
         assert!(config.triggers.is_empty());
         assert!(config.rules.is_empty());
     }
@@ -53,6 +234,24 @@ rules: []
 "#;
         let config: AgentsConfig = serde_yml::from_str(yaml).unwrap();
         assert_eq!(config.pool, 2);
+
+        let config: AgentsConfig = serde_yml::from_str("respawn: false").unwrap();
+        assert!(!config.respawn);
+
+        let config: AgentsConfig = serde_yml::from_str("idle_timeout: 2h").unwrap();
+        assert_eq!(
+            config.idle_timeout().unwrap(),
+            Some(Duration::from_secs(2 * 3600))
+        );
+
+        let yaml = r#"on_stuck: {action: notify, body: "${agent_id} looks stuck"}"#;
+        let config: AgentsConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(matches!(
+            config.on_stuck().unwrap(),
+            Some(ActionType::Notify { body, .. }) if body == "${agent_id} looks stuck"
+        ));
+        let config: AgentsConfig = serde_yml::from_str("on_stuck: {action: notify}").unwrap();
+        assert!(config.on_stuck().is_err());
     }
 
     #[test]
@@ -63,4 +262,26 @@ rules: []
         assert!(config.triggers.is_empty());
         assert!(config.rules.is_empty());
     }
+
+    #[test]
+    fn test_init_keys_with_override() {
+        let yaml = r#"
+pool: 2
+init_keys: ["cd ~/work\r"]
+init_wait_for: {pattern: "\\$ $", timeout: 5s}
+agent_overrides:
+  agent-1:
+    init_keys: ["cd ~/docs\r"]
+"#;
+        let config: AgentsConfig = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.init_keys("agent-0"), ["cd ~/work\r"]);
+        assert_eq!(config.init_keys("agent-1"), ["cd ~/docs\r"]);
+        assert!(config.tags("agent-0").is_empty());
+        let wait = config.init_wait_for().unwrap().unwrap();
+        assert_eq!(wait.timeout, Duration::from_secs(5));
+
+        // Idle says nothing about the shell having read the keys
+        let config: AgentsConfig = serde_yml::from_str("init_wait_for: idle").unwrap();
+        assert!(config.init_wait_for().is_err());
+    }
 }
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+/// Day-of-week numbers (0 or 7 = Sunday) in a standard cron field
+static DAY_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|[,-])([0-7])\b").unwrap());
+
+const DAY_NAMES: [&str; 8] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Parsed cron expression with an optional IANA timezone (local time when unset)
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    expression: String,
+    schedule: cron::Schedule,
+    timezone: Option<Tz>,
+}
+
+impl PartialEq for CronSchedule {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression && self.timezone == other.timezone
+    }
+}
+
+impl CronSchedule {
+    /// Parse a standard 5-field expression ("0 9 * * 1-5") or the cron crate's
+    /// 6/7-field form with seconds
+    pub fn parse(expression: &str, timezone: Option<&str>) -> Result<Self> {
+        let normalized = normalize_expression(expression)?;
+        let schedule = cron::Schedule::from_str(&normalized)
+            .with_context(|| format!("Invalid cron expression: {}", expression))?;
+        let timezone = timezone
+            .map(|name| {
+                name.parse::<Tz>()
+                    .map_err(|_| anyhow::anyhow!("Unknown timezone: {}", name))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            expression: expression.to_string(),
+            schedule,
+            timezone,
+        })
+    }
+
+    /// The expression as written in the config
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Timezone name, or "local"
+    pub fn timezone_name(&self) -> String {
+        self.timezone
+            .map(|tz| tz.name().to_string())
+            .unwrap_or_else(|| "local".to_string())
+    }
+
+    /// Next `count` fire times strictly after `after`
+    pub fn upcoming_after(&self, after: DateTime<Utc>, count: usize) -> Vec<DateTime<FixedOffset>> {
+        match self.timezone {
+            Some(tz) => self
+                .schedule
+                .after(&after.with_timezone(&tz))
+                .take(count)
+                .map(|time| time.fixed_offset())
+                .collect(),
+            None => self
+                .schedule
+                .after(&after.with_timezone(&Local))
+                .take(count)
+                .map(|time| time.fixed_offset())
+                .collect(),
+        }
+    }
+
+    /// Next fire time after now
+    pub fn next(&self) -> Option<DateTime<FixedOffset>> {
+        self.upcoming_after(Utc::now(), 1).into_iter().next()
+    }
+}
+
+/// Convert a standard 5-field expression to the cron crate's syntax: add a
+/// seconds field and spell out day-of-week numbers, which the crate counts
+/// from Sunday = 1.
+fn normalize_expression(expression: &str) -> Result<String> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    match fields.len() {
+        5 => {
+            let days = DAY_NUMBER.replace_all(fields[4], |caps: &regex::Captures| {
+                let day: usize = caps[2].parse().unwrap_or(0);
+                format!("{}{}", &caps[1], DAY_NAMES[day])
+            });
+            Ok(format!("0 {} {}", fields[..4].join(" "), days))
+        }
+        6 | 7 => Ok(fields.join(" ")),
+        _ => anyhow::bail!(
+            "Cron expression must have 5 fields (minute hour day month weekday): {}",
+            expression
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_normalize_expression() {
+        assert_eq!(
+            normalize_expression("0 9 * * 1-5").unwrap(),
+            "0 0 9 * * Mon-Fri"
+        );
+        assert_eq!(
+            normalize_expression("*/15 * * * 0,6").unwrap(),
+            "0 */15 * * * Sun,Sat"
+        );
+        // Steps are not day numbers
+        assert_eq!(
+            normalize_expression("0 9 * * 1-5/2").unwrap(),
+            "0 0 9 * * Mon-Fri/2"
+        );
+        assert_eq!(
+            normalize_expression("30 0 9 * * *").unwrap(),
+            "30 0 9 * * *"
+        );
+        assert!(normalize_expression("0 9 *").is_err());
+    }
+
+    #[test]
+    fn test_upcoming_weekdays() {
+        let schedule = CronSchedule::parse("0 9 * * 1-5", Some("Asia/Tokyo")).unwrap();
+        // Friday 2024-03-08 12:00 in Tokyo
+        let after = Utc.with_ymd_and_hms(2024, 3, 8, 3, 0, 0).unwrap();
+
+        let runs: Vec<String> = schedule
+            .upcoming_after(after, 3)
+            .iter()
+            .map(|time| time.to_rfc3339())
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                "2024-03-11T09:00:00+09:00",
+                "2024-03-12T09:00:00+09:00",
+                "2024-03-13T09:00:00+09:00",
+            ]
+        );
+        assert_eq!(schedule.timezone_name(), "Asia/Tokyo");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CronSchedule::parse("61 9 * * *", None).is_err());
+        assert!(CronSchedule::parse("0 9 * * *", Some("Mars/Olympus")).is_err());
+        assert!(
+            CronSchedule::parse("0 9 * * *", None)
+                .unwrap()
+                .next()
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::helper::parse_duration;
+
+/// Settings of the persistent store used by `dedupe: true` entries
+#[derive(Debug, Deserialize, Clone)]
+pub struct DedupeConfig {
+    /// File holding previously seen source lines, keyed by trigger name
+    #[serde(default = "default_file")]
+    pub file: PathBuf,
+    /// Maximum lines remembered per trigger; the oldest are forgotten first
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    /// How long a seen line suppresses repeats (e.g. "24h")
+    #[serde(default = "default_ttl")]
+    pub ttl: String,
+}
+
+impl Default for DedupeConfig {
+    fn default() -> Self {
+        Self {
+            file: default_file(),
+            max_entries: default_max_entries(),
+            ttl: default_ttl(),
+        }
+    }
+}
+
+impl DedupeConfig {
+    pub fn ttl(&self) -> Result<Duration> {
+        parse_duration(&self.ttl)
+    }
+}
+
+fn default_file() -> PathBuf {
+    PathBuf::from(".ccauto/dedupe.json")
+}
+
+fn default_max_entries() -> usize {
+    1000
+}
+
+fn default_ttl() -> String {
+    "168h".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_dedupe_config() {
+        let config: DedupeConfig = serde_yml::from_str("{}").unwrap();
+        assert_eq!(config.file, PathBuf::from(".ccauto/dedupe.json"));
+        assert_eq!(config.max_entries, 1000);
+        assert_eq!(config.ttl().unwrap(), Duration::from_secs(7 * 24 * 3600));
+    }
+
+    #[test]
+    fn test_dedupe_config_deserialization() {
+        let yaml = r#"
+file: /tmp/seen.json
+max_entries: 50
+ttl: 30m
+"#;
+        let config: DedupeConfig = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.file, PathBuf::from("/tmp/seen.json"));
+        assert_eq!(config.max_entries, 50);
+        assert_eq!(config.ttl().unwrap(), Duration::from_secs(1800));
+    }
+}
@@ -0,0 +1,248 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::Config;
+use crate::config::rules_config::{Rule, ScreenMode};
+use crate::config::triggers_config::Trigger;
+use crate::config::web_ui_config::WebUIConfig;
+use crate::terminal::secret;
+
+/// The rules, entries and settings a ccauto process runs with, as served
+/// by GET /api/config and printed by `ccauto show --config`. Secret keys,
+/// webhook secrets and the exec secret are left out.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub rules: Vec<RuleSummary>,
+    /// Sorted by name
+    pub triggers: Vec<TriggerSummary>,
+    pub web_ui: WebUIConfig,
+    pub agents: AgentSettings,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RuleSummary {
+    /// `when: <regex>`, `when_title: <regex>` or `diff_timeout: <secs>s`
+    pub pattern: String,
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Whether the rule applies when an agent starts: it is ungrouped, or
+    /// its group is active by default
+    pub enabled: bool,
+    /// Agent tag the rule is limited to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub screen: ScreenMode,
+    pub strip_ansi: bool,
+    /// Grace period before the action runs, e.g. `10s`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TriggerSummary {
+    pub name: String,
+    /// The event, e.g. `timer:300s` or `webhook:deploy`
+    pub event: String,
+    pub action: String,
+    /// Agent tag the entry is limited to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Paused with `ccauto trigger pause`
+    pub paused: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// `agents:` settings other than the rules and entries
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AgentSettings {
+    pub pool: usize,
+    pub init_keys: Vec<String>,
+    pub agent_overrides: BTreeMap<String, OverrideSummary>,
+    pub respawn: bool,
+    pub stuck_after: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_stuck: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_commands_per_hour: Option<u32>,
+    pub trigger_history: usize,
+    pub shutdown_timeout: String,
+    pub shutdown_grace: String,
+    pub persist_paused: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct OverrideSummary {
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_keys: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+}
+
+impl EffectiveConfig {
+    /// Summary of `config` with its parsed `rules` and `triggers`, entries
+    /// for which `is_paused` holds marked as paused
+    pub fn new<'a>(
+        config: &Config,
+        rules: &[Rule],
+        triggers: impl IntoIterator<Item = &'a Trigger>,
+        is_paused: impl Fn(&str) -> bool,
+    ) -> Self {
+        let active_groups: BTreeSet<String> = config.default_active_groups();
+        let rules = rules
+            .iter()
+            .map(|rule| RuleSummary {
+                pattern: rule.describe(),
+                action: rule.action.describe(),
+                group: rule.group.clone(),
+                enabled: rule
+                    .group
+                    .as_ref()
+                    .is_none_or(|group| active_groups.contains(group)),
+                tag: rule.tag.clone(),
+                screen: rule.screen,
+                strip_ansi: rule.strip_ansi,
+                delay: rule
+                    .delay
+                    .as_ref()
+                    .map(|delay| format!("{}s", delay.duration.as_secs())),
+            })
+            .collect();
+        let mut triggers: Vec<TriggerSummary> = triggers
+            .into_iter()
+            .map(|trigger| TriggerSummary {
+                name: trigger.name.clone(),
+                event: trigger.trigger.describe(),
+                action: trigger.action.describe(),
+                tag: trigger.tag.clone(),
+                paused: is_paused(&trigger.name),
+                source: trigger.source.clone(),
+                after: trigger.after.clone(),
+            })
+            .collect();
+        triggers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let agents = &config.agents;
+        Self {
+            rules,
+            triggers,
+            web_ui: WebUIConfig {
+                exec_secret: config
+                    .web_ui
+                    .exec_secret
+                    .as_ref()
+                    .map(|_| secret::REDACTED.to_string()),
+                ..config.web_ui.clone()
+            },
+            agents: AgentSettings {
+                pool: agents.pool,
+                init_keys: secret::redact_keys(&agents.init_keys),
+                agent_overrides: agents
+                    .agent_overrides
+                    .iter()
+                    .map(|(id, agent)| {
+                        let summary = OverrideSummary {
+                            tags: agent.tags.clone(),
+                            init_keys: agent.init_keys.as_deref().map(secret::redact_keys),
+                            read_only: agent.read_only,
+                        };
+                        (id.clone(), summary)
+                    })
+                    .collect(),
+                respawn: agents.respawn,
+                stuck_after: agents.stuck_after.clone(),
+                on_stuck: agents
+                    .on_stuck()
+                    .ok()
+                    .flatten()
+                    .map(|action| action.describe()),
+                idle_timeout: agents.idle_timeout.clone(),
+                max_commands_per_hour: agents.max_commands_per_hour,
+                trigger_history: agents.trigger_history,
+                shutdown_timeout: agents.shutdown_timeout.clone(),
+                shutdown_grace: agents.shutdown_grace.clone(),
+                persist_paused: agents.persist_paused,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_config_redacts_secrets() {
+        let config: Config = serde_yml::from_str(
+            r#"
+agents:
+  pool: 2
+  init_keys: ["<secret>hunter2</secret>", "cd ~/work\r"]
+  rules:
+    - when: "Password:"
+      action: send_keys
+      keys: ["${env.TOKEN}", "\r"]
+      secret: true
+  rule_groups:
+    - name: review
+      rules:
+        - when: "LGTM"
+          action: send_keys
+          keys: ["merge\r"]
+  triggers:
+    - name: deploy
+      event: "webhook:deploy"
+      secret: "s3cret"
+      action: send_keys
+      keys: ["deploy\r"]
+    - name: nightly
+      event: "cron"
+      cron: "0 3 * * *"
+      timezone: "UTC"
+      action: send_keys
+      keys: ["make\r"]
+web_ui:
+  exec_secret: "3x3c"
+"#,
+        )
+        .unwrap();
+        let rules = config.parse_rules().unwrap();
+        let triggers = config.parse_triggers().unwrap();
+        let effective = EffectiveConfig::new(&config, &rules, &triggers, |name| name == "nightly");
+
+        assert_eq!(effective.rules.len(), 2);
+        assert_eq!(effective.rules[0].pattern, "when: Password:");
+        assert!(!effective.rules[0].action.contains("TOKEN"));
+        assert!(effective.rules[0].enabled);
+        assert_eq!(effective.rules[1].group.as_deref(), Some("review"));
+        assert!(!effective.rules[1].enabled);
+
+        assert_eq!(
+            effective.triggers[0],
+            TriggerSummary {
+                name: "deploy".to_string(),
+                event: "webhook:deploy (with secret)".to_string(),
+                action: "send_keys [\"deploy\\r\"]".to_string(),
+                tag: None,
+                paused: false,
+                source: None,
+                after: None,
+            }
+        );
+        assert_eq!(effective.triggers[1].event, "cron 0 3 * * * (UTC)");
+        assert!(effective.triggers[1].paused);
+        assert_eq!(effective.agents.init_keys[1], "cd ~/work\r");
+
+        let json = serde_json::to_string(&effective).unwrap();
+        assert!(!json.contains("s3cret"));
+        assert!(!json.contains("hunter2"));
+        assert!(!json.contains("TOKEN"));
+        assert!(!json.contains("3x3c"));
+    }
+}
@@ -1,10 +1,122 @@
 use anyhow::{Result, anyhow};
+use nix::sys::signal::Signal;
+use serde::Deserialize;
 use std::time::Duration;
 
+use crate::terminal::secret;
+
+/// Default line written by append_file actions
+const DEFAULT_APPEND_TEMPLATE: &str = "${timestamp} ${agent_id} ${0}";
+
+/// Default title of notify actions
+const DEFAULT_NOTIFY_TITLE: &str = "ccauto: ${agent_id}";
+
+/// Default item of enqueue actions: the source line or first capture
+const DEFAULT_ENQUEUE_ITEM: &str = "${1}";
+
 // Shared action types for both entries and rules
 #[derive(Debug, Clone, PartialEq)]
 pub enum ActionType {
     SendKeys(Vec<String>),
+    /// Append a resolved template line to a file
+    AppendFile {
+        path: String,
+        template: String,
+    },
+    /// Show a desktop notification (logged when unsupported)
+    Notify {
+        title: String,
+        body: String,
+    },
+    /// Post a message to a named webhook target from `notifications:`
+    Webhook {
+        target: String,
+        message: String,
+    },
+    /// Enable a rule group on the agent
+    ActivateGroup(String),
+    /// Disable a rule group on the agent
+    DeactivateGroup(String),
+    /// Append a resolved item to a named queue drained by `queue:` entries
+    Enqueue {
+        queue: String,
+        item: String,
+    },
+    /// Send a signal to the program in the foreground of the agent's shell
+    Signal(Signal),
+}
+
+impl ActionType {
+    /// Short human readable description (e.g. for `test-rule` output)
+    pub fn describe(&self) -> String {
+        match self {
+            ActionType::SendKeys(keys) => format!("send_keys {:?}", secret::redact_keys(keys)),
+            ActionType::AppendFile { path, template } => {
+                format!("append_file {} {:?}", path, template)
+            }
+            ActionType::Notify { title, body } => format!("notify {:?} {:?}", title, body),
+            ActionType::Webhook { target, message } => format!("webhook {} {:?}", target, message),
+            ActionType::ActivateGroup(group) => format!("activate_group {}", group),
+            ActionType::DeactivateGroup(group) => format!("deactivate_group {}", group),
+            ActionType::Enqueue { queue, item } => format!("enqueue {} {:?}", queue, item),
+            ActionType::Signal(signal) => format!("signal {}", signal),
+        }
+    }
+
+    /// Keys and templates resolved when the action runs, defaults included
+    pub fn templates(&self) -> Vec<&str> {
+        match self {
+            ActionType::SendKeys(keys) => keys.iter().map(String::as_str).collect(),
+            ActionType::AppendFile { path, template } => vec![path, template],
+            ActionType::Notify { title, body } => vec![title, body],
+            ActionType::Webhook { message, .. } => vec![message],
+            ActionType::Enqueue { item, .. } => vec![item],
+            ActionType::ActivateGroup(_)
+            | ActionType::DeactivateGroup(_)
+            | ActionType::Signal(_) => Vec::new(),
+        }
+    }
+}
+
+// Action-specific YAML fields shared by triggers and rules
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ActionParams {
+    /// File path for append_file
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Line template for append_file
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Notification title template for notify
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Notification body template for notify
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Notification target name for webhook
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Message template for webhook
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Rule group name for activate_group / deactivate_group
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Queue name for enqueue
+    #[serde(default)]
+    pub queue: Option<String>,
+    /// Item template for enqueue (default "${1}")
+    #[serde(default)]
+    pub item: Option<String>,
+    /// Text template for paste
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Signal name for signal, e.g. `INT` or `SIGTERM`
+    #[serde(default)]
+    pub signal: Option<String>,
+    /// Keep the keys of send_keys and paste out of logs and recordings
+    #[serde(default)]
+    pub secret: bool,
 }
 
 /// Parse duration string (e.g., "30s", "5m", "2h") into Duration
@@ -37,8 +149,24 @@ pub fn parse_duration(s: &str) -> Result<Duration> {
     Ok(duration)
 }
 
+/// Parse a signal name, with or without the `SIG` prefix
+pub fn parse_signal(name: &str) -> Result<Signal> {
+    let upper = name.trim().to_uppercase();
+    let full = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{}", upper)
+    };
+    full.parse()
+        .map_err(|_| anyhow!("Unknown signal: {}", name))
+}
+
 /// Parse and validate action from YAML fields into ActionType
-pub fn parse_action(action: &Option<String>, keys: &[String]) -> Result<ActionType> {
+pub fn parse_action(
+    action: &Option<String>,
+    keys: &[String],
+    params: &ActionParams,
+) -> Result<ActionType> {
     let action = if let Some(action_type) = action {
         match action_type.as_str() {
             "send_keys" => {
@@ -47,12 +175,90 @@ pub fn parse_action(action: &Option<String>, keys: &[String]) -> Result<ActionTy
                 }
                 ActionType::SendKeys(keys.to_vec())
             }
+            // A paste is sent like keys, the `keys` following it (e.g. Enter)
+            "paste" => {
+                let Some(text) = &params.text else {
+                    anyhow::bail!("paste action requires 'text' field");
+                };
+                let paste = format!("<paste>{}</paste>", text);
+                ActionType::SendKeys(std::iter::once(paste).chain(keys.to_vec()).collect())
+            }
+            "append_file" => {
+                let Some(path) = &params.path else {
+                    anyhow::bail!("append_file action requires 'path' field");
+                };
+                ActionType::AppendFile {
+                    path: path.clone(),
+                    template: params
+                        .template
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_APPEND_TEMPLATE.to_string()),
+                }
+            }
+            "notify" => {
+                let Some(body) = &params.body else {
+                    anyhow::bail!("notify action requires 'body' field");
+                };
+                ActionType::Notify {
+                    title: params
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_NOTIFY_TITLE.to_string()),
+                    body: body.clone(),
+                }
+            }
+            "webhook" => {
+                let (Some(target), Some(message)) = (&params.target, &params.message) else {
+                    anyhow::bail!("webhook action requires 'target' and 'message' fields");
+                };
+                ActionType::Webhook {
+                    target: target.clone(),
+                    message: message.clone(),
+                }
+            }
+            "activate_group" | "deactivate_group" => {
+                let Some(group) = &params.group else {
+                    anyhow::bail!("{} action requires 'group' field", action_type);
+                };
+                if action_type == "activate_group" {
+                    ActionType::ActivateGroup(group.clone())
+                } else {
+                    ActionType::DeactivateGroup(group.clone())
+                }
+            }
+            "enqueue" => {
+                let Some(queue) = &params.queue else {
+                    anyhow::bail!("enqueue action requires 'queue' field");
+                };
+                ActionType::Enqueue {
+                    queue: queue.clone(),
+                    item: params
+                        .item
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_ENQUEUE_ITEM.to_string()),
+                }
+            }
+            "signal" => {
+                let Some(signal) = &params.signal else {
+                    anyhow::bail!("signal action requires 'signal' field");
+                };
+                ActionType::Signal(parse_signal(signal)?)
+            }
             _ => anyhow::bail!("Unknown action type: {}", action_type),
         }
     } else {
         anyhow::bail!("Must have 'action' field");
     };
 
+    if params.secret {
+        let ActionType::SendKeys(keys) = action else {
+            anyhow::bail!("'secret' is only supported for send_keys and paste actions");
+        };
+        return Ok(ActionType::SendKeys(
+            keys.iter().map(|key| secret::secret_keys(key)).collect(),
+        ));
+    }
+
     Ok(action)
 }
 
@@ -76,7 +282,7 @@ mod tests {
     fn test_parse_action_send_keys() {
         let action = Some("send_keys".to_string());
         let keys = vec!["hello".to_string(), "world".to_string()];
-        let result = parse_action(&action, &keys).unwrap();
+        let result = parse_action(&action, &keys, &ActionParams::default()).unwrap();
         assert_eq!(
             result,
             ActionType::SendKeys(vec!["hello".to_string(), "world".to_string()])
@@ -87,7 +293,190 @@ mod tests {
     fn test_parse_action_send_keys_empty_keys() {
         let action = Some("send_keys".to_string());
         let keys = vec![];
-        let result = parse_action(&action, &keys);
+        let result = parse_action(&action, &keys, &ActionParams::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_action_append_file() {
+        let action = Some("append_file".to_string());
+        let params = ActionParams {
+            path: Some("logs/${agent_id}.log".to_string()),
+            ..ActionParams::default()
+        };
+        let result = parse_action(&action, &[], &params).unwrap();
+        assert_eq!(
+            result,
+            ActionType::AppendFile {
+                path: "logs/${agent_id}.log".to_string(),
+                template: DEFAULT_APPEND_TEMPLATE.to_string(),
+            }
+        );
+
+        // path is required
+        let result = parse_action(&action, &[], &ActionParams::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_action_notify() {
+        let action = Some("notify".to_string());
+        let params = ActionParams {
+            body: Some("${agent_id} looks stuck".to_string()),
+            ..ActionParams::default()
+        };
+        let result = parse_action(&action, &[], &params).unwrap();
+        assert_eq!(
+            result,
+            ActionType::Notify {
+                title: DEFAULT_NOTIFY_TITLE.to_string(),
+                body: "${agent_id} looks stuck".to_string(),
+            }
+        );
+
+        // body is required
+        let result = parse_action(&action, &[], &ActionParams::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_action_signal() {
+        let action = Some("signal".to_string());
+        for (name, signal) in [
+            ("INT", Signal::SIGINT),
+            ("sigterm", Signal::SIGTERM),
+            ("SIGQUIT", Signal::SIGQUIT),
+        ] {
+            let params = ActionParams {
+                signal: Some(name.to_string()),
+                ..ActionParams::default()
+            };
+            let result = parse_action(&action, &[], &params).unwrap();
+            assert_eq!(result, ActionType::Signal(signal));
+        }
+        assert_eq!(
+            ActionType::Signal(Signal::SIGINT).describe(),
+            "signal SIGINT"
+        );
+
+        let params = ActionParams {
+            signal: Some("STOPIT".to_string()),
+            ..ActionParams::default()
+        };
+        let error = parse_action(&action, &[], &params).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown signal: STOPIT");
+        // signal is required
+        assert!(parse_action(&action, &[], &ActionParams::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_action_webhook() {
+        let action = Some("webhook".to_string());
+        let params = ActionParams {
+            target: Some("slack-ops".to_string()),
+            message: Some("Agent ${agent_id}: ${1}".to_string()),
+            ..ActionParams::default()
+        };
+        let result = parse_action(&action, &[], &params).unwrap();
+        assert_eq!(
+            result,
+            ActionType::Webhook {
+                target: "slack-ops".to_string(),
+                message: "Agent ${agent_id}: ${1}".to_string(),
+            }
+        );
+
+        let params = ActionParams {
+            target: Some("slack-ops".to_string()),
+            ..ActionParams::default()
+        };
+        assert!(parse_action(&action, &[], &params).is_err());
+    }
+
+    #[test]
+    fn test_parse_action_enqueue() {
+        let action = Some("enqueue".to_string());
+        let params = ActionParams {
+            queue: Some("reviews".to_string()),
+            ..ActionParams::default()
+        };
+        assert_eq!(
+            parse_action(&action, &[], &params).unwrap(),
+            ActionType::Enqueue {
+                queue: "reviews".to_string(),
+                item: "${1}".to_string(),
+            }
+        );
+
+        assert!(parse_action(&action, &[], &ActionParams::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_action_paste() {
+        let action = Some("paste".to_string());
+        let params = ActionParams {
+            text: Some("Fix ${1}\nThen run the tests".to_string()),
+            ..ActionParams::default()
+        };
+        assert_eq!(
+            parse_action(&action, &["\r".to_string()], &params).unwrap(),
+            ActionType::SendKeys(vec![
+                "<paste>Fix ${1}\nThen run the tests</paste>".to_string(),
+                "\r".to_string(),
+            ])
+        );
+
+        assert!(parse_action(&action, &[], &ActionParams::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_action_secret() {
+        let params = ActionParams {
+            secret: true,
+            ..ActionParams::default()
+        };
+        let keys = ["${env.TOKEN}".to_string(), "\r".to_string()];
+        let action = parse_action(&Some("send_keys".to_string()), &keys, &params).unwrap();
+        assert_eq!(
+            action,
+            ActionType::SendKeys(vec![
+                "<secret>${env.TOKEN}</secret>".to_string(),
+                "<secret>\r</secret>".to_string(),
+            ])
+        );
+        assert_eq!(action.describe(), r#"send_keys ["***", "***"]"#);
+
+        let params = ActionParams {
+            group: Some("commit".to_string()),
+            ..params
+        };
+        let error = parse_action(&Some("activate_group".to_string()), &[], &params).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'secret' is only supported for send_keys and paste actions"
+        );
+    }
+
+    #[test]
+    fn test_parse_action_group() {
+        let params = ActionParams {
+            group: Some("commit".to_string()),
+            ..ActionParams::default()
+        };
+        assert_eq!(
+            parse_action(&Some("activate_group".to_string()), &[], &params).unwrap(),
+            ActionType::ActivateGroup("commit".to_string())
+        );
+        assert_eq!(
+            parse_action(&Some("deactivate_group".to_string()), &[], &params).unwrap(),
+            ActionType::DeactivateGroup("commit".to_string())
+        );
+
+        let result = parse_action(
+            &Some("activate_group".to_string()),
+            &[],
+            &ActionParams::default(),
+        );
         assert!(result.is_err());
     }
 
@@ -95,7 +484,7 @@ mod tests {
     fn test_parse_action_no_action() {
         let action = None;
         let keys = vec!["hello".to_string()];
-        let result = parse_action(&action, &keys);
+        let result = parse_action(&action, &keys, &ActionParams::default());
         assert!(result.is_err());
     }
 
@@ -103,7 +492,7 @@ mod tests {
     fn test_parse_action_unknown_action() {
         let action = Some("unknown_action".to_string());
         let keys = vec!["hello".to_string()];
-        let result = parse_action(&action, &keys);
+        let result = parse_action(&action, &keys, &ActionParams::default());
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Settings of the log files kept next to the tracing output
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoggingConfig {
+    /// Directory receiving the raw PTY output of each agent as
+    /// `<agent_id>.log`; no PTY logs are written when unset
+    #[serde(default)]
+    pub pty_dir: Option<PathBuf>,
+    /// Size in MB at which a PTY log is rotated
+    #[serde(default = "default_pty_max_file_mb")]
+    pub pty_max_file_mb: u64,
+    /// PTY log files kept per agent, the current one included
+    #[serde(default = "default_pty_max_files")]
+    pub pty_max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            pty_dir: None,
+            pty_max_file_mb: default_pty_max_file_mb(),
+            pty_max_files: default_pty_max_files(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// `pty_max_file_mb` in bytes
+    pub fn pty_max_file_bytes(&self) -> u64 {
+        self.pty_max_file_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+fn default_pty_max_file_mb() -> u64 {
+    10
+}
+
+fn default_pty_max_files() -> usize {
+    5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logging_config_deserialization() {
+        let config: LoggingConfig = serde_yml::from_str("{}").unwrap();
+        assert_eq!(config.pty_dir, None);
+        assert_eq!(config.pty_max_file_bytes(), 10 * 1024 * 1024);
+        assert_eq!(config.pty_max_files, 5);
+
+        let yaml = r#"
+pty_dir: logs
+pty_max_file_mb: 2
+pty_max_files: 3
+"#;
+        let config: LoggingConfig = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.pty_dir, Some(PathBuf::from("logs")));
+        assert_eq!(config.pty_max_file_bytes(), 2 * 1024 * 1024);
+        assert_eq!(config.pty_max_files, 3);
+    }
+}
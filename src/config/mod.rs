@@ -1,15 +1,31 @@
 pub mod agents_config;
+pub mod cron_schedule;
+pub mod dedupe_config;
+pub mod effective;
 pub mod helper;
+pub mod logging_config;
+pub mod notifications_config;
+pub mod recording_config;
 pub mod rules_config;
+pub mod rules_settings_config;
+pub mod terminal_config;
 pub mod triggers_config;
 pub mod web_ui_config;
 
 use crate::config::agents_config::AgentsConfig;
+use crate::config::dedupe_config::DedupeConfig;
+use crate::config::helper::ActionType;
+use crate::config::logging_config::LoggingConfig;
+use crate::config::notifications_config::WebhookTarget;
+use crate::config::recording_config::RecordingConfig;
 use crate::config::rules_config::Rule;
-use crate::config::triggers_config::Trigger;
+use crate::config::rules_settings_config::RulesSettingsConfig;
+use crate::config::terminal_config::TerminalConfig;
+use crate::config::triggers_config::{Trigger, TriggerType, startup_order};
 use crate::config::web_ui_config::WebUIConfig;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 
 /// Main configuration structure matching config.yaml format
@@ -19,6 +35,26 @@ pub struct Config {
     pub web_ui: WebUIConfig,
     #[serde(default)]
     pub agents: AgentsConfig,
+    #[serde(default)]
+    pub rules: RulesSettingsConfig,
+    /// Named webhook targets for `webhook` actions
+    #[serde(default)]
+    pub notifications: BTreeMap<String, WebhookTarget>,
+    /// Persistent store for `dedupe: true` entries
+    #[serde(default)]
+    pub dedupe: DedupeConfig,
+    /// Per-agent PTY output logs
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// asciinema recordings of the agents' sessions
+    #[serde(default)]
+    pub recording: RecordingConfig,
+    /// TERM and LANG of the agents' shells and their PTY buffers
+    #[serde(default)]
+    pub terminal: TerminalConfig,
+    /// Log matched actions instead of sending keys
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 impl Config {
@@ -29,29 +65,157 @@ impl Config {
 
         let config: Config = serde_yml::from_str(&content)
             .map_err(|e| anyhow::anyhow!("Failed to parse YAML config: {}", e))?;
+        config.terminal.validate()?;
+        config.web_ui.validate()?;
 
         Ok(config)
     }
 
+    /// Terminal settings of the agent `agent_id`, its override taking precedence
+    pub fn terminal_for(&self, agent_id: &str) -> TerminalConfig {
+        let agent = self.agents.agent_overrides.get(agent_id);
+        self.terminal
+            .with_override(agent.and_then(|agent| agent.terminal.as_ref()))
+    }
+
+    /// Whether the web UI of the agent `agent_id` refuses input, its
+    /// override taking precedence
+    pub fn web_read_only(&self, agent_id: &str) -> bool {
+        self.agents
+            .agent_overrides
+            .get(agent_id)
+            .and_then(|agent| agent.read_only)
+            .unwrap_or(self.web_ui.read_only)
+    }
+
     /// Parse triggers from config
     pub fn parse_triggers(&self) -> Result<Vec<Trigger>> {
         let mut triggers = Vec::new();
+        let mut webhook_paths = BTreeSet::new();
+        let mut stdin_entry: Option<&str> = None;
         for trigger_config in &self.agents.triggers {
             let trigger = Trigger::try_from(trigger_config.clone())?;
+            self.validate_action(&trigger.action)?;
+            self.validate_tag(&trigger.tag, &format!("Entry '{}'", trigger.name))?;
+            if let Some(on_timeout) = &trigger.on_timeout {
+                self.validate_action(on_timeout)?;
+            }
+            if let TriggerType::Webhook { path, .. } = &trigger.trigger
+                && !webhook_paths.insert(path.clone())
+            {
+                anyhow::bail!("Duplicate webhook trigger path: {}", path);
+            }
+            if trigger.trigger == TriggerType::Stdin {
+                if let Some(first) = stdin_entry {
+                    anyhow::bail!(
+                        "Only one stdin entry is allowed, found '{}' and '{}'",
+                        first,
+                        trigger_config.name
+                    );
+                }
+                stdin_entry = Some(&trigger_config.name);
+            }
             triggers.push(trigger);
         }
+        self.validate_startup_dependencies(&triggers)?;
         Ok(triggers)
     }
 
-    /// Parse rules from config
+    /// Check that `after:` names a startup entry and that there are no cycles
+    fn validate_startup_dependencies(&self, triggers: &[Trigger]) -> Result<()> {
+        for trigger in triggers {
+            let Some(after) = &trigger.after else {
+                continue;
+            };
+            match triggers.iter().find(|other| other.name == *after) {
+                None => anyhow::bail!(
+                    "Entry '{}' runs after unknown entry '{}'",
+                    trigger.name,
+                    after
+                ),
+                Some(other) if other.trigger != TriggerType::OnStart => anyhow::bail!(
+                    "Entry '{}' runs after '{}', which is not a startup entry",
+                    trigger.name,
+                    after
+                ),
+                Some(_) => {}
+            }
+        }
+
+        let startup: Vec<Trigger> = triggers
+            .iter()
+            .filter(|trigger| trigger.trigger == TriggerType::OnStart)
+            .cloned()
+            .collect();
+        startup_order(&startup)?;
+        Ok(())
+    }
+
+    /// Parse rules from config: ungrouped rules first, then each rule group in order
     pub fn parse_rules(&self) -> Result<Vec<Rule>> {
         let mut rules = Vec::new();
         for rule_config in &self.agents.rules {
             let rule = Rule::try_from(rule_config.clone())?;
+            self.validate_action(&rule.action)?;
+            self.validate_tag(&rule.tag, &format!("Rule '{}'", rule.describe()))?;
             rules.push(rule);
         }
+
+        let mut names = BTreeSet::new();
+        for group in &self.agents.rule_groups {
+            if !names.insert(group.name.as_str()) {
+                anyhow::bail!("Duplicate rule group: {}", group.name);
+            }
+            for rule_config in &group.rules {
+                let mut rule = Rule::try_from(rule_config.clone())?;
+                self.validate_action(&rule.action)?;
+                self.validate_tag(&rule.tag, &format!("Rule '{}'", rule.describe()))?;
+                rule.group = Some(group.name.clone());
+                rules.push(rule);
+            }
+        }
+
+        if let Some(on_stuck) = self.agents.on_stuck()? {
+            self.validate_action(&on_stuck)
+                .context("Invalid 'on_stuck' action")?;
+        }
         Ok(rules)
     }
+
+    /// Names of rule groups that are active by default
+    pub fn default_active_groups(&self) -> BTreeSet<String> {
+        self.agents
+            .rule_groups
+            .iter()
+            .filter(|group| group.active)
+            .map(|group| group.name.clone())
+            .collect()
+    }
+
+    /// Check that a `tag:` of `owner` is carried by at least one pool agent
+    fn validate_tag(&self, tag: &Option<String>, owner: &str) -> Result<()> {
+        match tag {
+            Some(tag) if !self.agents.has_tag(tag) => {
+                anyhow::bail!("{} targets tag '{}', which no agent has", owner, tag)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that an action only references defined notification targets and rule groups
+    fn validate_action(&self, action: &ActionType) -> Result<()> {
+        match action {
+            ActionType::Webhook { target, .. } if !self.notifications.contains_key(target) => {
+                anyhow::bail!("Unknown notification target: {}", target);
+            }
+            ActionType::ActivateGroup(group) | ActionType::DeactivateGroup(group)
+                if !self.agents.rule_groups.iter().any(|g| &g.name == group) =>
+            {
+                anyhow::bail!("Unknown rule group: {}", group);
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -186,5 +350,215 @@ agents:
         assert_eq!(config.agents.pool, 2); // specified
         assert!(config.agents.triggers.is_empty()); // default
         assert!(config.agents.rules.is_empty()); // default
+        assert!(!config.dry_run); // default
+        assert_eq!(config.rules.max_actions_per_minute, 60); // default
+    }
+
+    #[test]
+    fn test_config_dry_run() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "dry_run: true").unwrap();
+
+        let config = Config::from_file(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.dry_run);
+    }
+
+    #[test]
+    fn test_terminal_for_agent() {
+        let yaml = r#"
+terminal:
+  lang: C.UTF-8
+agents:
+  agent_overrides:
+    agent-1:
+      terminal: {term: screen-256color}
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let agent_0 = config.terminal_for("agent-0");
+        assert_eq!(agent_0.term, "xterm-256color");
+        assert_eq!(agent_0.lang.as_deref(), Some("C.UTF-8"));
+        let agent_1 = config.terminal_for("agent-1");
+        assert_eq!(agent_1.term, "screen-256color");
+        assert_eq!(agent_1.lang.as_deref(), Some("C.UTF-8"));
+    }
+
+    #[test]
+    fn test_web_read_only_for_agent() {
+        let yaml = r#"
+web_ui:
+  read_only: true
+agents:
+  agent_overrides:
+    agent-1:
+      read_only: false
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        assert!(config.web_read_only("agent-0"));
+        assert!(!config.web_read_only("agent-1"));
+        assert!(!Config::default().web_read_only("agent-0"));
+    }
+
+    #[test]
+    fn test_parse_rules_validates_webhook_target() {
+        let yaml = r#"
+notifications:
+  slack-ops:
+    url: "http://localhost/hook"
+agents:
+  rules:
+    - when: "error: (.+)"
+      action: webhook
+      target: slack-ops
+      message: "Agent ${agent_id}: ${1}"
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.parse_rules().unwrap().len(), 1);
+
+        let yaml = yaml.replace("target: slack-ops", "target: missing");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        let error = config.parse_rules().unwrap_err();
+        assert!(error.to_string().contains("Unknown notification target"));
+    }
+
+    #[test]
+    fn test_parse_rules_with_groups() {
+        let yaml = r#"
+agents:
+  rules:
+    - when: "All tests passed"
+      action: activate_group
+      group: commit
+  rule_groups:
+    - name: commit
+      rules:
+        - when: "Commit these changes?"
+          action: send_keys
+          keys: ["y"]
+    - name: base
+      active: true
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let rules = config.parse_rules().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].group, None);
+        assert_eq!(rules[1].group.as_deref(), Some("commit"));
+        assert_eq!(
+            config.default_active_groups(),
+            BTreeSet::from(["base".to_string()])
+        );
+
+        let yaml = yaml.replace("group: commit", "group: missing");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        let error = config.parse_rules().unwrap_err();
+        assert!(error.to_string().contains("Unknown rule group"));
+    }
+
+    #[test]
+    fn test_parse_triggers_validates_startup_dependencies() {
+        let yaml = r#"
+agents:
+  triggers:
+    - name: start-claude
+      event: startup
+      action: send_keys
+      keys: ["claude"]
+      after: git-pull
+    - name: git-pull
+      event: startup
+      action: send_keys
+      keys: ["git pull"]
+      after: start-claude
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let error = config.parse_triggers().unwrap_err().to_string();
+        assert!(
+            error.contains("cycle: start-claude -> git-pull -> start-claude"),
+            "{}",
+            error
+        );
+
+        let yaml = yaml.replace("after: start-claude", "after: missing");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        let error = config.parse_triggers().unwrap_err().to_string();
+        assert!(error.contains("unknown entry 'missing'"), "{}", error);
+
+        let yaml = yaml.replace("      after: missing\n", "");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(config.parse_triggers().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_validates_tags() {
+        let yaml = r#"
+agents:
+  pool: 2
+  agent_overrides:
+    agent-1:
+      tags: [claude, gpu]
+    agent-2:
+      tags: [spare]
+  triggers:
+    - name: review
+      event: startup
+      action: send_keys
+      keys: ["claude"]
+      tag: claude
+  rules:
+    - when: "Proceed\\?"
+      action: send_keys
+      keys: ["y"]
+      tag: gpu
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.parse_triggers().unwrap()[0].tag.as_deref(),
+            Some("claude")
+        );
+        assert_eq!(config.parse_rules().unwrap()[0].tag.as_deref(), Some("gpu"));
+
+        // agent-2 is outside the pool
+        let yaml = yaml.replace("tag: claude", "tag: spare");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        let error = config.parse_triggers().unwrap_err().to_string();
+        assert!(
+            error.contains("Entry 'review' targets tag 'spare'"),
+            "{}",
+            error
+        );
+
+        let yaml = yaml.replace("tag: gpu", "tag: tpu");
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        let error = config.parse_rules().unwrap_err().to_string();
+        assert!(error.contains("tag 'tpu', which no agent has"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_triggers_allows_one_stdin_entry() {
+        let yaml = r#"
+agents:
+  triggers:
+    - name: tasks
+      event: stdin
+      action: send_keys
+      keys: ["${1}"]
+    - name: more-tasks
+      event: stdin
+      action: send_keys
+      keys: ["${1}"]
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let error = config.parse_triggers().unwrap_err().to_string();
+        assert!(
+            error.contains("Only one stdin entry is allowed, found 'tasks' and 'more-tasks'"),
+            "{}",
+            error
+        );
+
+        let yaml = yaml.replacen("event: stdin", "event: startup", 1);
+        let config: Config = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(config.parse_triggers().unwrap().len(), 2);
     }
 }
@@ -0,0 +1,53 @@
+use serde::Deserialize;
+
+/// Payload format expected by a webhook target
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    #[default]
+    Slack,
+    Discord,
+}
+
+/// Named webhook target referenced by `webhook` actions
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct WebhookTarget {
+    pub url: String,
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Default channel (Slack only)
+    #[serde(default)]
+    pub channel: Option<String>,
+    /// Display name of the posting bot
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_notifications_deserialization() {
+        let yaml = r##"
+slack-ops:
+  url: "https://hooks.slack.com/services/T/B/X"
+  channel: "#ops"
+  username: ccauto
+team-discord:
+  kind: discord
+  url: "https://discord.com/api/webhooks/1/abc"
+"##;
+        let targets: BTreeMap<String, WebhookTarget> = serde_yml::from_str(yaml).unwrap();
+
+        let slack = &targets["slack-ops"];
+        assert_eq!(slack.kind, WebhookKind::Slack);
+        assert_eq!(slack.channel.as_deref(), Some("#ops"));
+        assert_eq!(slack.username.as_deref(), Some("ccauto"));
+
+        let discord = &targets["team-discord"];
+        assert_eq!(discord.kind, WebhookKind::Discord);
+        assert!(discord.channel.is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Recording of the agents' sessions as asciinema `.cast` files
+#[derive(Debug, Deserialize, Clone)]
+pub struct RecordingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory receiving `<agent_id>-<start time>.cast` files
+    #[serde(default = "default_dir")]
+    pub dir: PathBuf,
+    /// When a recording ends and the next one starts
+    #[serde(default)]
+    pub rotate: Rotation,
+}
+
+/// When an agent's recording moves on to a new file
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// On the first output of a new local day
+    #[default]
+    Daily,
+    /// Whenever the agent becomes Active, so each task gets its own file
+    Session,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_dir(),
+            rotate: Rotation::default(),
+        }
+    }
+}
+
+fn default_dir() -> PathBuf {
+    PathBuf::from("recordings")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_config_deserialization() {
+        let config: RecordingConfig = serde_yml::from_str("enabled: true").unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.dir, PathBuf::from("recordings"));
+        assert_eq!(config.rotate, Rotation::Daily);
+
+        let config: RecordingConfig =
+            serde_yml::from_str("{dir: /tmp/casts, rotate: session}").unwrap();
+        assert!(!config.enabled);
+        assert_eq!(config.dir, PathBuf::from("/tmp/casts"));
+        assert_eq!(config.rotate, Rotation::Session);
+        assert!(serde_yml::from_str::<RecordingConfig>("rotate: hourly").is_err());
+    }
+}
@@ -1,21 +1,97 @@
 use crate::config::helper::parse_duration;
-use crate::config::helper::{ActionType, parse_action};
+use crate::config::helper::{ActionParams, ActionType, parse_action};
 use anyhow::{Context, Result, anyhow};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
+use std::sync::LazyLock;
 use std::time::Duration;
 
+/// A numbered capture group placeholder such as `${1}` or `${1|sh}`
+static CAPTURE_PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{[1-9][0-9]*(\|[a-z]+)?\}").unwrap());
+
+/// How a rule's `when` pattern is interpreted
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Regular expression (default)
+    #[default]
+    Regex,
+    /// Plain substring check
+    Literal,
+    /// Simple `*` / `?` wildcard pattern
+    Glob,
+}
+
+/// Which terminal screen a rule applies on
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenMode {
+    /// The normal, scrolling screen of the shell
+    Primary,
+    /// The alternate screen full-screen programs (vim, less, TUIs) draw on
+    Alternate,
+    /// Either screen (default)
+    #[default]
+    Any,
+}
+
+impl ScreenMode {
+    /// Whether a rule with this mode applies while the terminal shows the
+    /// alternate screen (`true`) or the primary one (`false`)
+    pub fn matches(self, alternate_screen: bool) -> bool {
+        match self {
+            ScreenMode::Primary => !alternate_screen,
+            ScreenMode::Alternate => alternate_screen,
+            ScreenMode::Any => true,
+        }
+    }
+}
+
 // YAML structure for loading rules
 #[derive(Debug, Deserialize, Clone)]
 pub struct RuleConfig {
     pub when: Option<String>,
+    #[serde(default, rename = "match")]
+    pub match_mode: MatchMode,
+    #[serde(default = "default_strip_ansi")]
+    pub strip_ansi: bool,
     #[serde(default)]
     pub diff_timeout: Option<String>,
+    /// Match the window title (OSC 0/2) instead of the output; not `title`,
+    /// which names notify's notification title
+    #[serde(default)]
+    pub when_title: Option<String>,
+    /// Wait this long (e.g. "10s") after a match before running the action
+    #[serde(default)]
+    pub delay: Option<String>,
+    /// Cancel a pending delayed action if output matches this regex first
+    #[serde(default)]
+    pub cancel_on: Option<String>,
+    /// Only apply the rule to agents with this tag
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Only apply the rule on the primary or the alternate screen
+    #[serde(default)]
+    pub screen: ScreenMode,
     #[serde(default)]
     pub action: Option<String>,
     #[serde(default)]
     pub keys: Vec<String>,
+    #[serde(flatten)]
+    pub params: ActionParams,
+}
+
+// YAML structure for a named group of rules that can be switched on and off
+#[derive(Debug, Deserialize, Clone)]
+pub struct RuleGroupConfig {
+    pub name: String,
+    /// Whether the group is active when the agent starts or returns to Idle
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
 }
 
 // Parsed and validated structure for runtime use
@@ -23,46 +99,190 @@ pub struct RuleConfig {
 pub struct Rule {
     pub rule_type: RuleType,
     pub action: ActionType,
+    /// Match against ANSI-stripped output (true) or the raw chunk (false)
+    pub strip_ansi: bool,
+    /// Rule group this rule belongs to (ungrouped rules are always active)
+    pub group: Option<String>,
+    /// Agent tag the rule is limited to (all agents when unset)
+    pub tag: Option<String>,
+    /// Run the action after a grace period instead of immediately
+    pub delay: Option<RuleDelay>,
+    /// Screen the terminal must show for the rule to apply
+    pub screen: ScreenMode,
+}
+
+/// Grace period before a `when` rule's action runs
+#[derive(Debug, Clone)]
+pub struct RuleDelay {
+    pub duration: Duration,
+    /// Output that cancels the pending action
+    pub cancel_on: Option<Regex>,
 }
 
 #[derive(Debug, Clone)]
 pub enum RuleType {
     When(Regex),
     DiffTimeout(Duration),
+    /// Fires when the window title changes to one matching
+    Title(Regex),
 }
 
 impl TryFrom<RuleConfig> for Rule {
     type Error = anyhow::Error;
 
     fn try_from(config: RuleConfig) -> Result<Self> {
-        let rule_type = match (&config.when, &config.diff_timeout) {
-            (Some(pattern), None) => {
-                let regex = Regex::new(pattern)
-                    .with_context(|| format!("Invalid regex pattern: {}", pattern))?;
-                RuleType::When(regex)
-            }
-            (None, Some(timeout_str)) => {
+        let action = parse_action(&config.action, &config.keys, &config.params)?;
+        let compile = |pattern| compile_rule_pattern(pattern, config.match_mode, &action);
+        let rule_type = match (&config.when, &config.diff_timeout, &config.when_title) {
+            (Some(pattern), None, None) => RuleType::When(compile(pattern)?),
+            (None, Some(timeout_str), None) => {
                 let duration = parse_duration(timeout_str)?;
                 RuleType::DiffTimeout(duration)
             }
-            (Some(_), Some(_)) => {
+            (None, None, Some(pattern)) => RuleType::Title(compile(pattern)?),
+            (None, None, None) => {
                 return Err(anyhow!(
-                    "Rule cannot have both 'when' and 'diff_timeout' fields"
+                    "Rule must have a 'when', 'diff_timeout' or 'when_title' field"
                 ));
             }
-            (None, None) => {
+            _ => {
                 return Err(anyhow!(
-                    "Rule must have either 'when' or 'diff_timeout' field"
+                    "Rule can only have one of the 'when', 'diff_timeout' and 'when_title' fields"
                 ));
             }
         };
 
-        let action = parse_action(&config.action, &config.keys)?;
+        if config.screen != ScreenMode::Any && config.diff_timeout.is_some() {
+            return Err(anyhow!(
+                "'screen' can only be used with 'when' and 'when_title' rules"
+            ));
+        }
+
+        let delay = match (&config.delay, &config.cancel_on) {
+            (Some(_), _) if config.when.is_none() => {
+                return Err(anyhow!("'delay' can only be used with 'when' rules"));
+            }
+            (Some(delay), cancel_on) => Some(RuleDelay {
+                duration: parse_duration(delay)?,
+                cancel_on: cancel_on
+                    .as_deref()
+                    .map(|pattern| {
+                        Regex::new(pattern)
+                            .with_context(|| format!("Invalid cancel_on pattern: {}", pattern))
+                    })
+                    .transpose()?,
+            }),
+            (None, Some(_)) => {
+                return Err(anyhow!("'cancel_on' requires 'delay'"));
+            }
+            (None, None) => None,
+        };
 
-        Ok(Self { rule_type, action })
+        Ok(Self {
+            rule_type,
+            action,
+            strip_ansi: config.strip_ansi,
+            group: None,
+            tag: config.tag,
+            delay,
+            screen: config.screen,
+        })
     }
 }
 
+impl Rule {
+    /// Rule of `rule_type` running `action`, with the defaults of a rule
+    /// loaded from YAML for everything else
+    #[cfg(test)]
+    pub fn new(rule_type: RuleType, action: ActionType) -> Self {
+        Self {
+            rule_type,
+            action,
+            strip_ansi: default_strip_ansi(),
+            group: None,
+            tag: None,
+            delay: None,
+            screen: ScreenMode::default(),
+        }
+    }
+
+    /// Short human readable description used in statistics
+    pub fn describe(&self) -> String {
+        match &self.rule_type {
+            RuleType::When(regex) => format!("when: {}", regex.as_str()),
+            RuleType::DiffTimeout(duration) => format!("diff_timeout: {}s", duration.as_secs()),
+            RuleType::Title(regex) => format!("when_title: {}", regex.as_str()),
+        }
+    }
+}
+
+fn default_strip_ansi() -> bool {
+    true
+}
+
+impl Default for RuleConfig {
+    /// The same defaults as a rule in YAML that sets none of the fields
+    fn default() -> Self {
+        Self {
+            when: None,
+            match_mode: MatchMode::default(),
+            strip_ansi: default_strip_ansi(),
+            diff_timeout: None,
+            when_title: None,
+            delay: None,
+            cancel_on: None,
+            tag: None,
+            screen: ScreenMode::default(),
+            action: None,
+            keys: Vec::new(),
+            params: ActionParams::default(),
+        }
+    }
+}
+
+/// Compile the `when` or `when_title` pattern of a rule, making sure the
+/// keys and templates of its action only use capture groups the match mode
+/// provides
+fn compile_rule_pattern(pattern: &str, mode: MatchMode, action: &ActionType) -> Result<Regex> {
+    if mode != MatchMode::Regex
+        && let Some(template) = action
+            .templates()
+            .into_iter()
+            .find(|template| CAPTURE_PLACEHOLDER.is_match(template))
+    {
+        return Err(anyhow!(
+            "Capture group placeholders cannot be used with '{:?}' match mode: {} (in {:?})",
+            mode,
+            pattern,
+            template
+        ));
+    }
+    compile_pattern(pattern, mode)
+}
+
+/// Compile a `when` pattern into a regex according to its match mode
+pub fn compile_pattern(pattern: &str, mode: MatchMode) -> Result<Regex> {
+    let source = match mode {
+        MatchMode::Regex => pattern.to_string(),
+        MatchMode::Literal => regex::escape(pattern),
+        MatchMode::Glob => glob_to_regex(pattern),
+    };
+    Regex::new(&source).with_context(|| format!("Invalid regex pattern: {}", pattern))
+}
+
+/// Translate a glob pattern (`*` any run, `?` any single char) into regex source
+fn glob_to_regex(pattern: &str) -> String {
+    let mut source = String::with_capacity(pattern.len() * 2);
+    for ch in pattern.chars() {
+        match ch {
+            '*' => source.push_str(".*"),
+            '?' => source.push('.'),
+            _ => source.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    source
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,9 +292,9 @@ mod tests {
     fn test_rule_try_from_pattern() {
         let rule = RuleConfig {
             when: Some("test".to_string()),
-            diff_timeout: None,
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
+            ..Default::default()
         };
 
         let rule = Rule::try_from(rule).unwrap();
@@ -89,10 +309,10 @@ mod tests {
     #[test]
     fn test_rule_try_from_diff_timeout() {
         let rule = RuleConfig {
-            when: None,
             diff_timeout: Some("5m".to_string()),
             action: Some("send_keys".to_string()),
             keys: vec!["timeout".to_string()],
+            ..Default::default()
         };
 
         let rule = Rule::try_from(rule).unwrap();
@@ -111,6 +331,7 @@ mod tests {
             diff_timeout: Some("5m".to_string()),
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
+            ..Default::default()
         };
 
         assert!(Rule::try_from(rule).is_err());
@@ -119,12 +340,247 @@ mod tests {
     #[test]
     fn test_rule_try_from_no_fields_error() {
         let rule = RuleConfig {
-            when: None,
-            diff_timeout: None,
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
+            ..Default::default()
         };
 
         assert!(Rule::try_from(rule).is_err());
     }
+
+    fn when_config(pattern: &str, mode: MatchMode, keys: Vec<&str>) -> RuleConfig {
+        RuleConfig {
+            when: Some(pattern.to_string()),
+            match_mode: mode,
+            action: Some("send_keys".to_string()),
+            keys: keys.into_iter().map(String::from).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rule_try_from_literal_mode() {
+        let config = when_config("Do you want to proceed?", MatchMode::Literal, vec!["1"]);
+        let rule = Rule::try_from(config).unwrap();
+        let RuleType::When(regex) = rule.rule_type else {
+            panic!("Expected when rule type");
+        };
+        assert!(regex.is_match("> Do you want to proceed? (y/n)"));
+        assert!(!regex.is_match("Do you want to proceed"));
+    }
+
+    #[test]
+    fn test_rule_try_from_glob_mode() {
+        let config = when_config("Deploy * to prod?", MatchMode::Glob, vec!["${0}"]);
+        let rule = Rule::try_from(config).unwrap();
+        let RuleType::When(regex) = rule.rule_type else {
+            panic!("Expected when rule type");
+        };
+        assert!(regex.is_match("Deploy app (v2) to prod!"));
+        assert!(!regex.is_match("Deploy app to staging!"));
+        assert_eq!(
+            regex.find("Deploy app to prod!").unwrap().as_str(),
+            "Deploy app to prod!"
+        );
+    }
+
+    #[test]
+    fn test_rule_rejects_capture_placeholders_without_regex() {
+        let literal = when_config("proceed?", MatchMode::Literal, vec!["${1}"]);
+        assert!(Rule::try_from(literal).is_err());
+
+        let glob = when_config("issue *", MatchMode::Glob, vec!["open ${2}"]);
+        assert!(Rule::try_from(glob).is_err());
+
+        // ${0} is always available
+        let whole = when_config("proceed?", MatchMode::Literal, vec!["${0}"]);
+        assert!(Rule::try_from(whole).is_ok());
+    }
+
+    #[test]
+    fn test_rule_rejects_capture_placeholders_in_templates() {
+        let mut glob = when_config("issue *", MatchMode::Glob, vec![]);
+        glob.action = Some("append_file".to_string());
+        glob.params.path = Some("issues.log".to_string());
+        glob.params.template = Some("echo ${1|sh}".to_string());
+        assert!(Rule::try_from(glob.clone()).is_err());
+
+        glob.params.template = Some("echo ${0|sh}".to_string());
+        assert!(Rule::try_from(glob).is_ok());
+    }
+
+    #[test]
+    fn test_rule_rejects_default_enqueue_item_without_regex() {
+        // enqueue without `item` enqueues ${1}, which literal matches lack
+        let mut literal = when_config("Build failed", MatchMode::Literal, vec![]);
+        literal.action = Some("enqueue".to_string());
+        literal.params.queue = Some("failures".to_string());
+        let error = Rule::try_from(literal.clone()).unwrap_err();
+        assert!(error.to_string().contains("${1}"), "{}", error);
+
+        literal.params.item = Some("${0}".to_string());
+        assert!(Rule::try_from(literal).is_ok());
+    }
+
+    #[test]
+    fn test_match_mode_deserialization() {
+        let yaml = r#"
+when: "a.b"
+match: literal
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.match_mode, MatchMode::Literal);
+
+        let yaml = r#"
+when: "a.b"
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(config.match_mode, MatchMode::Regex);
+    }
+
+    #[test]
+    fn test_screen_mode_deserialization() {
+        let yaml = r#"
+when: "Save changes?"
+screen: alternate
+action: send_keys
+keys: ["y"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        let rule = Rule::try_from(config).unwrap();
+        assert_eq!(rule.screen, ScreenMode::Alternate);
+        assert!(rule.screen.matches(true));
+        assert!(!rule.screen.matches(false));
+
+        let config = when_config("a", MatchMode::Regex, vec!["x"]);
+        assert_eq!(config.screen, ScreenMode::Any);
+
+        let yaml = r#"
+diff_timeout: "1m"
+screen: primary
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(Rule::try_from(config).is_err());
+    }
+
+    #[test]
+    fn test_append_file_deserialization() {
+        let yaml = r#"
+when: "error:"
+action: append_file
+path: "logs/${agent_id}.log"
+template: "${timestamp} ${0}"
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        let rule = Rule::try_from(config).unwrap();
+        assert_eq!(
+            rule.action,
+            ActionType::AppendFile {
+                path: "logs/${agent_id}.log".to_string(),
+                template: "${timestamp} ${0}".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_delay_deserialization() {
+        let yaml = r#"
+when: "Compacting conversation"
+delay: 10s
+cancel_on: "Compacted"
+action: send_keys
+keys: ["\x1b"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        let delay = Rule::try_from(config).unwrap().delay.unwrap();
+        assert_eq!(delay.duration, Duration::from_secs(10));
+        assert!(delay.cancel_on.unwrap().is_match("Compacted conversation"));
+
+        let yaml = r#"
+when: "x"
+cancel_on: "y"
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(Rule::try_from(config).is_err());
+
+        let yaml = r#"
+diff_timeout: 5m
+delay: 10s
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(Rule::try_from(config).is_err());
+    }
+
+    #[test]
+    fn test_title_deserialization() {
+        let yaml = r#"
+when_title: "Claude needs your * to use *"
+match: glob
+action: notify
+title: "Permission"
+body: "${0}"
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        let rule = Rule::try_from(config).unwrap();
+        assert_eq!(
+            rule.describe(),
+            "when_title: Claude needs your .* to use .*"
+        );
+        let RuleType::Title(regex) = rule.rule_type else {
+            panic!("Expected title rule type");
+        };
+        assert!(regex.is_match("Claude needs your permission to use Bash"));
+        assert!(
+            matches!(rule.action, ActionType::Notify { ref title, .. } if title == "Permission")
+        );
+
+        let yaml = r#"
+when_title: "x"
+when: "x"
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(Rule::try_from(config).is_err());
+
+        let yaml = r#"
+when_title: "x"
+delay: 10s
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(Rule::try_from(config).is_err());
+    }
+
+    #[test]
+    fn test_strip_ansi_deserialization() {
+        let yaml = r#"
+when: '\x1b\[31m'
+strip_ansi: false
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(!config.strip_ansi);
+        assert!(!Rule::try_from(config).unwrap().strip_ansi);
+
+        let yaml = r#"
+when: "x"
+action: send_keys
+keys: ["x"]
+"#;
+        let config: RuleConfig = serde_yml::from_str(yaml).unwrap();
+        assert!(config.strip_ansi);
+    }
 }
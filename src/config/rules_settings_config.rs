@@ -0,0 +1,98 @@
+use crate::config::helper::parse_duration;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Global settings applied to all rules
+#[derive(Debug, Deserialize, Clone)]
+pub struct RulesSettingsConfig {
+    /// Maximum rule actions fired per agent per minute (0 disables the limit)
+    #[serde(default = "default_max_actions_per_minute")]
+    pub max_actions_per_minute: u32,
+    /// Time after which rules paused for manual intervention resume on
+    /// their own (`0s` keeps them paused until resumed)
+    #[serde(default = "default_pause_timeout")]
+    pub pause_timeout: String,
+    /// Quiet time in milliseconds that ends a burst of output before `when`
+    /// rules look at it (0 evaluates every chunk as it arrives)
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Output after which a burst is evaluated without waiting for quiet
+    #[serde(default = "default_debounce_max_bytes")]
+    pub debounce_max_bytes: usize,
+}
+
+impl Default for RulesSettingsConfig {
+    fn default() -> Self {
+        Self {
+            max_actions_per_minute: default_max_actions_per_minute(),
+            pause_timeout: default_pause_timeout(),
+            debounce_ms: default_debounce_ms(),
+            debounce_max_bytes: default_debounce_max_bytes(),
+        }
+    }
+}
+
+impl RulesSettingsConfig {
+    /// `pause_timeout`, None when paused rules never resume on their own
+    pub fn pause_timeout(&self) -> Result<Option<Duration>> {
+        let timeout = parse_duration(&self.pause_timeout).context("Invalid 'pause_timeout'")?;
+        Ok(Some(timeout).filter(|timeout| !timeout.is_zero()))
+    }
+
+    /// Quiet time ending a burst of output, see `debounce_ms`
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+}
+
+fn default_max_actions_per_minute() -> u32 {
+    60
+}
+
+fn default_pause_timeout() -> String {
+    "10m".to_string()
+}
+
+fn default_debounce_ms() -> u64 {
+    20
+}
+
+fn default_debounce_max_bytes() -> usize {
+    8 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules_settings() {
+        let config = RulesSettingsConfig::default();
+        assert_eq!(config.max_actions_per_minute, 60);
+        assert_eq!(
+            config.pause_timeout().unwrap(),
+            Some(Duration::from_secs(600))
+        );
+    }
+
+    #[test]
+    fn test_rules_settings_explicit_zero() {
+        let config: RulesSettingsConfig = serde_yml::from_str("max_actions_per_minute: 0").unwrap();
+        assert_eq!(config.max_actions_per_minute, 0);
+
+        let config: RulesSettingsConfig = serde_yml::from_str("pause_timeout: 0s").unwrap();
+        assert_eq!(config.pause_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn test_rules_settings_partial_deserialization() {
+        let config: RulesSettingsConfig = serde_yml::from_str("{}").unwrap();
+        assert_eq!(config.max_actions_per_minute, 60);
+        assert_eq!(config.debounce(), Duration::from_millis(20));
+        assert_eq!(config.debounce_max_bytes, 8192);
+
+        let config: RulesSettingsConfig = serde_yml::from_str("debounce_ms: 0").unwrap();
+        assert!(config.debounce().is_zero());
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+/// Environment of the agents' shells and buffering of their PTYs
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct TerminalConfig {
+    /// TERM of the shells
+    #[serde(default = "default_term")]
+    pub term: String,
+    /// LANG of the shells, e.g. `en_US.UTF-8`; inherited from ccauto when unset
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Bytes read from the PTY at once. Larger reads mean fewer, bigger output
+    /// chunks under heavy output; smaller ones hand output to rules sooner
+    /// but fill the output channels faster.
+    #[serde(default = "default_read_buf_size")]
+    pub read_buf_size: usize,
+    /// Output chunks buffered for each reader of an agent's output, such as
+    /// a rule monitor; a reader falling further behind misses the oldest.
+    /// Each slot may hold up to `read_buf_size` bytes per reader.
+    #[serde(default = "default_output_channel_capacity")]
+    pub output_channel_capacity: usize,
+    /// Writes queued for a shell that isn't reading its input, so a runaway
+    /// rule can't pile up keystrokes in memory
+    #[serde(default = "default_input_channel_bound")]
+    pub input_channel_bound: usize,
+    /// What sending input does once `input_channel_bound` writes are queued
+    #[serde(default)]
+    pub on_input_full: InputFull,
+}
+
+/// How a write to a shell with a full input queue is handled
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFull {
+    /// Wait until the shell reads enough of its input (default)
+    #[default]
+    Wait,
+    /// Refuse the write with an error right away
+    Fail,
+}
+
+/// Accepted values of `read_buf_size`
+const READ_BUF_SIZE_RANGE: RangeInclusive<usize> = 256..=1024 * 1024;
+/// Accepted values of `output_channel_capacity`
+const OUTPUT_CHANNEL_CAPACITY_RANGE: RangeInclusive<usize> = 16..=65536;
+/// Accepted values of `input_channel_bound`
+const INPUT_CHANNEL_BOUND_RANGE: RangeInclusive<usize> = 1..=65536;
+
+/// Terminal settings of a single agent, replacing those of `terminal`
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TerminalOverride {
+    #[serde(default)]
+    pub term: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            term: default_term(),
+            lang: None,
+            read_buf_size: default_read_buf_size(),
+            output_channel_capacity: default_output_channel_capacity(),
+            input_channel_bound: default_input_channel_bound(),
+            on_input_full: InputFull::default(),
+        }
+    }
+}
+
+impl TerminalConfig {
+    /// These settings with those of `agent` taking precedence
+    pub fn with_override(&self, agent: Option<&TerminalOverride>) -> Self {
+        let Some(agent) = agent else {
+            return self.clone();
+        };
+        Self {
+            term: agent.term.clone().unwrap_or_else(|| self.term.clone()),
+            lang: agent.lang.clone().or_else(|| self.lang.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Reject buffer sizes too small to work with or too large to be sane
+    pub fn validate(&self) -> Result<()> {
+        for (name, value, range) in [
+            ("read_buf_size", self.read_buf_size, READ_BUF_SIZE_RANGE),
+            (
+                "output_channel_capacity",
+                self.output_channel_capacity,
+                OUTPUT_CHANNEL_CAPACITY_RANGE,
+            ),
+            (
+                "input_channel_bound",
+                self.input_channel_bound,
+                INPUT_CHANNEL_BOUND_RANGE,
+            ),
+        ] {
+            if !range.contains(&value) {
+                anyhow::bail!(
+                    "terminal.{} must be between {} and {}, got {}",
+                    name,
+                    range.start(),
+                    range.end(),
+                    value
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Variables set in a shell's environment
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![("TERM".to_string(), self.term.clone())];
+        if let Some(lang) = &self.lang {
+            env.push(("LANG".to_string(), lang.clone()));
+        }
+        env
+    }
+}
+
+fn default_term() -> String {
+    "xterm-256color".to_string()
+}
+
+fn default_read_buf_size() -> usize {
+    4096
+}
+
+fn default_output_channel_capacity() -> usize {
+    1024
+}
+
+fn default_input_channel_bound() -> usize {
+    256
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_override() {
+        let config: TerminalConfig = serde_yml::from_str("lang: en_US.UTF-8").unwrap();
+        assert_eq!(config.term, "xterm-256color");
+
+        let agent: TerminalOverride = serde_yml::from_str("term: screen-256color").unwrap();
+        let merged = config.with_override(Some(&agent));
+        assert_eq!(
+            merged.env(),
+            [
+                ("TERM".to_string(), "screen-256color".to_string()),
+                ("LANG".to_string(), "en_US.UTF-8".to_string()),
+            ]
+        );
+        assert_eq!(config.with_override(None), config);
+        assert_eq!(TerminalConfig::default().env().len(), 1);
+    }
+
+    #[test]
+    fn test_buffer_settings() {
+        let config = TerminalConfig::default();
+        assert_eq!(config.read_buf_size, 4096);
+        assert_eq!(config.output_channel_capacity, 1024);
+        assert_eq!(config.input_channel_bound, 256);
+        assert_eq!(config.on_input_full, InputFull::Wait);
+        config.validate().unwrap();
+
+        let config: TerminalConfig =
+            serde_yml::from_str("read_buf_size: 65536\ninput_channel_bound: 1").unwrap();
+        config.validate().unwrap();
+        let agent: TerminalOverride = serde_yml::from_str("term: screen").unwrap();
+        assert_eq!(config.with_override(Some(&agent)).read_buf_size, 65536);
+        let config: TerminalConfig = serde_yml::from_str("on_input_full: fail").unwrap();
+        assert_eq!(config.on_input_full, InputFull::Fail);
+        assert!(serde_yml::from_str::<TerminalConfig>("on_input_full: drop").is_err());
+
+        for yaml in [
+            "read_buf_size: 16",
+            "output_channel_capacity: 0",
+            "input_channel_bound: 100000",
+        ] {
+            let config: TerminalConfig = serde_yml::from_str(yaml).unwrap();
+            let error = config.validate().unwrap_err().to_string();
+            assert!(error.starts_with("terminal."), "{}", error);
+        }
+    }
+}
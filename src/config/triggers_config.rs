@@ -1,12 +1,21 @@
+use crate::config::cron_schedule::CronSchedule;
 use crate::config::helper::parse_duration;
-use crate::config::helper::{ActionType, parse_action};
-use anyhow::Result;
+use crate::config::helper::{ActionParams, ActionType, parse_action};
+use anyhow::{Context, Result};
+use regex::Regex;
 use serde::Deserialize;
 use std::convert::TryFrom;
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// Time a `source` command may run (or stay silent while streaming) before it is killed
+pub const DEFAULT_SOURCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Agents alive at once for a `spawn_agent: true` entry unless configured
+pub const DEFAULT_MAX_CONCURRENT_SPAWNED: usize = 4;
+
 // YAML structure for loading trigger configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct TriggerConfig {
     pub name: String,
     pub event: String,
@@ -14,10 +23,108 @@ pub struct TriggerConfig {
     pub action: Option<String>,
     #[serde(default)]
     pub keys: Vec<String>,
+    #[serde(flatten)]
+    pub params: ActionParams,
     #[serde(default)]
     pub source: Option<String>,
+    /// Skip source lines seen before: `true`, or `{ttl: 24h}` to allow them again later
+    #[serde(default)]
+    pub dedupe: DedupeSetting,
+    /// Kill the `source` command when it is silent this long (default 60s)
+    #[serde(default)]
+    pub source_timeout: Option<String>,
+    /// Stop after processing this many source lines (the command is killed)
+    #[serde(default)]
+    pub max_lines: Option<usize>,
+    /// Split each line into ${1}, ${2}, ... fields: a separator such as "\t",
+    /// `whitespace`, or `{regex: "..."}`
+    #[serde(default)]
+    pub split: Option<SplitSetting>,
+    /// `json` parses the source output as a JSON array, one element per run of the action
+    #[serde(default)]
+    pub format: SourceFormat,
+    /// Retry a failing source command, e.g. `{attempts: 3, backoff: 5s}`
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+    /// How long the agent must stay idle before an `idle` entry fires
+    #[serde(default)]
+    pub min_idle: Option<String>,
+    /// Shared secret a `webhook:` request must send in the X-Ccauto-Secret
+    /// header, or `true` to keep the keys out of logs and recordings
+    #[serde(default)]
+    pub secret: Option<SecretSetting>,
+    /// JSON pointer (e.g. "/issue/title") selecting the `webhook:` body field used as ${1}
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Glob (relative to the watched directory) selecting files for `watch:` entries
+    #[serde(default)]
+    pub glob: Option<String>,
+    /// Pass the changed file's content as ${1} instead of its path
+    #[serde(default)]
+    pub read_content: bool,
+    /// Skip deleted-file events
+    #[serde(default)]
+    pub ignore_deleted: bool,
+    /// Cron expression for `cron` entries (e.g. "0 9 * * 1-5")
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// IANA timezone of the cron expression (local time when unset)
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Delay before the first run of a `timer:` entry (runs at startup when unset)
+    #[serde(default)]
+    pub initial_delay: Option<String>,
+    /// Random ± offset applied to each run of a `timer:` entry
+    #[serde(default)]
+    pub jitter: Option<String>,
+    /// Skip (and count) `timer:` runs while every agent is busy
+    #[serde(default)]
+    pub skip_if_active: bool,
+    /// Defer `timer:` runs until an agent goes idle
+    #[serde(default)]
+    pub queue_if_active: bool,
+    /// Stop a `timer:` entry after this many runs
+    #[serde(default)]
+    pub max_runs: Option<u32>,
+    /// How source command lines are spread over the agent pool
+    #[serde(default)]
+    pub distribution: Distribution,
+    /// Template picking the agent with `distribution: sticky` (default `${1}`)
+    #[serde(default)]
+    pub sticky_key: Option<String>,
+    /// Condition to wait for before sending the action: `idle`, or
+    /// `{pattern: "\\$ $", timeout: 10s}` matched against the agent's output
+    #[serde(default)]
+    pub wait_for: Option<WaitForSetting>,
+    /// Abort an execution that takes longer than this (unlimited when unset)
+    #[serde(default)]
+    pub max_runtime: Option<String>,
+    /// Action sent to the agent when `max_runtime` expires, e.g.
+    /// `{action: send_keys, keys: ["\u0003"]}` for Ctrl+C
     #[serde(default)]
-    pub dedupe: bool,
+    pub on_timeout: Option<ActionConfig>,
+    /// Items a `queue:` entry pops per wake-up (default 1)
+    #[serde(default)]
+    pub batch: Option<usize>,
+    /// What happens to `queue:` items whose action fails: `drop` (default),
+    /// `requeue`, or `failed_queue` (moved to `<name>.failed`)
+    #[serde(default)]
+    pub failed_items: Option<FailedItems>,
+    /// Name of a startup entry that must finish before this startup entry runs
+    #[serde(default)]
+    pub after: Option<String>,
+    /// Whether to run (`run`) or skip (`skip`, default) when the `after` entry failed
+    #[serde(default)]
+    pub after_failure: Option<AfterFailure>,
+    /// Run each source line on a fresh temporary agent instead of the pool
+    #[serde(default)]
+    pub spawn_agent: bool,
+    /// Spawned agents alive at once for this entry (default 4)
+    #[serde(default)]
+    pub max_concurrent_spawned: Option<usize>,
+    /// Only run on pool agents with this tag, e.g. `claude`
+    #[serde(default)]
+    pub tag: Option<String>,
 }
 
 // Parsed and validated structure for runtime use
@@ -27,14 +134,354 @@ pub struct Trigger {
     pub trigger: TriggerType,
     pub action: ActionType,
     pub source: Option<String>,
-    #[allow(dead_code)] // Will be used for future deduplication functionality
-    pub dedupe: bool,
+    pub dedupe: Option<Dedupe>,
+    pub source_timeout: Duration,
+    pub max_lines: Option<usize>,
+    pub split: Option<FieldSplit>,
+    pub format: SourceFormat,
+    pub retry: Option<RetryPolicy>,
+    pub distribution: Distribution,
+    /// Template hashed to pick the agent with `distribution: sticky`
+    pub sticky_key: Option<String>,
+    pub after: Option<String>,
+    pub after_failure: AfterFailure,
+    pub wait_for: Option<WaitFor>,
+    pub max_runtime: Option<Duration>,
+    pub on_timeout: Option<ActionType>,
+    pub spawn_agent: bool,
+    pub max_concurrent_spawned: usize,
+    /// Agent tag the entry is limited to (all agents when unset)
+    pub tag: Option<String>,
+}
+
+/// `secret:` as written in YAML. It is a flag of the action like on rules,
+/// except that on `webhook:` entries a string is the shared secret.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum SecretSetting {
+    Keys(bool),
+    Webhook(String),
+}
+
+/// `dedupe:` as written in YAML
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum DedupeSetting {
+    Enabled(bool),
+    Options {
+        #[serde(default)]
+        ttl: Option<String>,
+    },
+}
+
+impl Default for DedupeSetting {
+    fn default() -> Self {
+        Self::Enabled(false)
+    }
+}
+
+/// Deduplication of source lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Dedupe {
+    /// Lines seen longer ago than this are treated as new (store default when unset)
+    pub ttl: Option<Duration>,
+}
+
+/// `split:` as written in YAML
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum SplitSetting {
+    Separator(String),
+    Pattern { regex: String },
+}
+
+/// How a line is split into the ${1}, ${2}, ... fields
+#[derive(Debug, Clone)]
+pub enum FieldSplit {
+    Separator(String),
+    Whitespace,
+    Regex(Regex),
+}
+
+impl FieldSplit {
+    pub fn parse(setting: &SplitSetting) -> Result<Self> {
+        match setting {
+            SplitSetting::Separator(separator) if separator == "whitespace" => Ok(Self::Whitespace),
+            SplitSetting::Separator(separator) if separator.is_empty() => {
+                anyhow::bail!("'split' separator must not be empty")
+            }
+            SplitSetting::Separator(separator) => Ok(Self::Separator(separator.clone())),
+            SplitSetting::Pattern { regex } => {
+                Ok(Self::Regex(Regex::new(regex).with_context(|| {
+                    format!("Invalid split regex: {}", regex)
+                })?))
+            }
+        }
+    }
+
+    pub fn fields<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Self::Separator(separator) => line.split(separator.as_str()).collect(),
+            Self::Whitespace => line.split_whitespace().collect(),
+            Self::Regex(regex) => regex.split(line).collect(),
+        }
+    }
+}
+
+/// `retry:` as written in YAML
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct RetryConfig {
+    /// Total attempts, including the first run
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for each further retry
+    #[serde(default = "default_retry_backoff")]
+    pub backoff: String,
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_backoff() -> String {
+    "5s".to_string()
+}
+
+/// Retries of a failing source command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (1-based)
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+/// How the output of a source command is divided into items
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceFormat {
+    /// One item per non-empty line
+    #[default]
+    Lines,
+    /// One item per element of a JSON array (or the whole document otherwise)
+    Json,
+}
+
+/// How the lines of a source command are assigned to agents
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    /// Every line goes to the agent selected for the entry
+    #[default]
+    Single,
+    /// Lines rotate through the pool, starting at the selected agent
+    RoundRobin,
+    /// Each line goes to the next idle agent, or the agent with the fewest
+    /// child processes if all are busy
+    LeastBusy,
+    /// Lines with the same `sticky_key` always go to the same agent
+    Sticky,
+}
+
+/// Key of a `distribution: sticky` entry when `sticky_key` is unset
+pub const DEFAULT_STICKY_KEY: &str = "${1}";
+
+/// A nested action (such as `on_timeout:`) as written in YAML
+#[derive(Debug, Deserialize, Clone)]
+pub struct ActionConfig {
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(flatten)]
+    pub params: ActionParams,
+}
+
+/// `wait_for:` as written in YAML
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum WaitForSetting {
+    /// `idle`
+    Keyword(String),
+    Options {
+        #[serde(default)]
+        pattern: Option<String>,
+        #[serde(default)]
+        idle: bool,
+        #[serde(default)]
+        timeout: Option<String>,
+    },
+}
+
+/// Time a `wait_for` condition may take before the action fails
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Condition an agent must reach before an entry sends its action
+#[derive(Debug, Clone)]
+pub struct WaitFor {
+    pub condition: WaitCondition,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum WaitCondition {
+    /// The agent has no running child processes
+    Idle,
+    /// A line of the agent's output (or screen) matches
+    Pattern(Regex),
+}
+
+impl std::fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Idle => write!(f, "idle"),
+            Self::Pattern(pattern) => write!(f, "pattern '{}'", pattern),
+        }
+    }
+}
+
+impl WaitFor {
+    pub fn parse(setting: &WaitForSetting) -> Result<Self> {
+        let (pattern, idle, timeout) = match setting {
+            WaitForSetting::Keyword(keyword) if keyword == "idle" => (None, true, None),
+            WaitForSetting::Keyword(keyword) => {
+                anyhow::bail!("Unknown 'wait_for' condition: {}", keyword)
+            }
+            WaitForSetting::Options {
+                pattern,
+                idle,
+                timeout,
+            } => (pattern.as_deref(), *idle, timeout.as_deref()),
+        };
+
+        let condition = match (pattern, idle) {
+            (Some(pattern), false) => WaitCondition::Pattern(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid wait_for pattern: {}", pattern))?,
+            ),
+            (None, true) => WaitCondition::Idle,
+            _ => anyhow::bail!("'wait_for' needs either a 'pattern' or 'idle: true'"),
+        };
+        Ok(Self {
+            condition,
+            timeout: match timeout {
+                Some(timeout) => parse_duration(timeout)?,
+                None => DEFAULT_WAIT_TIMEOUT,
+            },
+        })
+    }
+}
+
+/// What a startup entry does when the entry it runs `after` failed
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AfterFailure {
+    /// Skip the entry (and its own dependents)
+    #[default]
+    Skip,
+    /// Run the entry anyway
+    Run,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TriggerType {
     OnStart,
-    Periodic { interval: Duration },
+    /// Runs on every agent when ccauto is stopped with Ctrl+C
+    OnShutdown,
+    Periodic {
+        interval: Duration,
+        options: PeriodicOptions,
+    },
+    /// Periodic entry fired at cron schedule times instead of a fixed interval
+    Cron {
+        schedule: Box<CronSchedule>,
+    },
+    /// Runs when an agent goes from Active back to Idle
+    OnIdle {
+        min_idle: Duration,
+    },
+    /// Runs when POST /api/triggers/{path} is received on the control API
+    Webhook {
+        path: String,
+        secret: Option<String>,
+        field: Option<String>,
+    },
+    /// Runs when files matching `glob` under `path` change
+    FileWatch {
+        path: PathBuf,
+        glob: String,
+        read_content: bool,
+        ignore_deleted: bool,
+    },
+    /// Drains the named queue filled by `enqueue` actions, ${1} being the item
+    Queue {
+        name: String,
+        batch: usize,
+        failed_items: FailedItems,
+    },
+    /// Runs once per line piped into the process's stdin, until EOF
+    Stdin,
+}
+
+impl TriggerType {
+    /// The event as written in the config, e.g. `timer:300s`; webhook
+    /// secrets are only mentioned
+    pub fn describe(&self) -> String {
+        match self {
+            TriggerType::OnStart => "startup".to_string(),
+            TriggerType::OnShutdown => "shutdown".to_string(),
+            TriggerType::Periodic { interval, .. } => format!("timer:{}s", interval.as_secs()),
+            TriggerType::Cron { schedule } => format!(
+                "cron {} ({})",
+                schedule.expression(),
+                schedule.timezone_name()
+            ),
+            TriggerType::OnIdle { min_idle } => format!("idle ({}s)", min_idle.as_secs()),
+            TriggerType::Webhook { path, secret, .. } => match secret {
+                Some(_) => format!("webhook:{} (with secret)", path),
+                None => format!("webhook:{}", path),
+            },
+            TriggerType::FileWatch { path, glob, .. } => {
+                format!("watch:{} ({})", path.display(), glob)
+            }
+            TriggerType::Queue { name, batch, .. } => format!("queue:{} (batch {})", name, batch),
+            TriggerType::Stdin => "stdin".to_string(),
+        }
+    }
+}
+
+/// Handling of `queue:` items whose action failed
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FailedItems {
+    /// Log the failure and forget the item
+    #[default]
+    Drop,
+    /// Put the item back at the end of the queue
+    Requeue,
+    /// Move the item to the `<name>.failed` queue
+    FailedQueue,
+}
+
+/// Scheduling options of `timer:` entries
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PeriodicOptions {
+    /// Wait this long before the first run instead of running at startup
+    pub initial_delay: Option<Duration>,
+    /// Shift each run by a random offset within ±jitter
+    pub jitter: Option<Duration>,
+    /// Log skipped runs when every agent is busy
+    pub skip_if_active: bool,
+    /// Defer runs until an agent goes idle instead of skipping them
+    pub queue_if_active: bool,
+    /// Retire the entry after this many executions
+    pub max_runs: Option<u32>,
 }
 
 impl TryFrom<TriggerConfig> for Trigger {
@@ -48,25 +495,373 @@ impl TryFrom<TriggerConfig> for Trigger {
                 .ok_or_else(|| anyhow::anyhow!("Invalid timer format"))?
                 .to_string();
             let interval = parse_duration(&duration_str)?;
-            TriggerType::Periodic { interval }
+            let options = PeriodicOptions {
+                initial_delay: config
+                    .initial_delay
+                    .as_deref()
+                    .map(parse_duration)
+                    .transpose()?,
+                jitter: config.jitter.as_deref().map(parse_duration).transpose()?,
+                skip_if_active: config.skip_if_active,
+                queue_if_active: config.queue_if_active,
+                max_runs: config.max_runs,
+            };
+            if options.max_runs == Some(0) {
+                anyhow::bail!("'max_runs' must be at least 1");
+            }
+            TriggerType::Periodic { interval, options }
+        } else if config.event == "cron" {
+            let Some(expression) = &config.cron else {
+                anyhow::bail!("'cron' event requires a 'cron' expression");
+            };
+            TriggerType::Cron {
+                schedule: Box::new(CronSchedule::parse(expression, config.timezone.as_deref())?),
+            }
         } else if config.event == "startup" {
             TriggerType::OnStart
+        } else if config.event == "shutdown" {
+            TriggerType::OnShutdown
+        } else if config.event == "stdin" {
+            if config.source.is_some() {
+                anyhow::bail!("'stdin' entries read their lines from stdin, not a 'source'");
+            }
+            TriggerType::Stdin
+        } else if let Some(path) = config.event.strip_prefix("webhook:") {
+            if path.is_empty() || path.contains('/') {
+                anyhow::bail!("Invalid webhook path: {}", path);
+            }
+            TriggerType::Webhook {
+                path: path.to_string(),
+                secret: match &config.secret {
+                    Some(SecretSetting::Webhook(secret)) => Some(secret.clone()),
+                    _ => None,
+                },
+                field: config.field.clone(),
+            }
+        } else if let Some(path) = config.event.strip_prefix("watch:") {
+            if path.is_empty() {
+                anyhow::bail!("'watch:' event requires a path");
+            }
+            let glob = config.glob.clone().unwrap_or_else(|| "*".to_string());
+            globset::Glob::new(&glob).with_context(|| format!("Invalid glob pattern: {}", glob))?;
+            TriggerType::FileWatch {
+                path: PathBuf::from(path),
+                glob,
+                read_content: config.read_content,
+                ignore_deleted: config.ignore_deleted,
+            }
+        } else if let Some(name) = config.event.strip_prefix("queue:") {
+            if name.is_empty() {
+                anyhow::bail!("'queue:' event requires a queue name");
+            }
+            let batch = config.batch.unwrap_or(1);
+            if batch == 0 {
+                anyhow::bail!("'batch' must be at least 1");
+            }
+            TriggerType::Queue {
+                name: name.to_string(),
+                batch,
+                failed_items: config.failed_items.unwrap_or_default(),
+            }
+        } else if config.event == "idle" {
+            let min_idle = match &config.min_idle {
+                Some(min_idle) => parse_duration(min_idle)?,
+                None => Duration::ZERO,
+            };
+            TriggerType::OnIdle { min_idle }
         } else {
             return Err(anyhow::anyhow!("Unknown event type: {}", config.event));
         };
 
-        let action = parse_action(&config.action, &config.keys)?;
+        if (config.initial_delay.is_some()
+            || config.jitter.is_some()
+            || config.skip_if_active
+            || config.queue_if_active
+            || config.max_runs.is_some())
+            && !matches!(trigger, TriggerType::Periodic { .. })
+        {
+            anyhow::bail!(
+                "'initial_delay', 'jitter', 'skip_if_active', 'queue_if_active' and 'max_runs' are only supported for timer entries"
+            );
+        }
+        let has_lines = config.source.is_some() || trigger == TriggerType::Stdin;
+        if config.distribution != Distribution::Single
+            && (!has_lines
+                || !matches!(
+                    trigger,
+                    TriggerType::OnStart
+                        | TriggerType::Periodic { .. }
+                        | TriggerType::Cron { .. }
+                        | TriggerType::Stdin
+                ))
+        {
+            anyhow::bail!(
+                "'distribution' is only supported for startup, timer and cron entries with a source, and stdin entries"
+            );
+        }
+        if config.sticky_key.is_some() && config.distribution != Distribution::Sticky {
+            anyhow::bail!("'sticky_key' requires 'distribution: sticky'");
+        }
+        if (config.cron.is_some() || config.timezone.is_some())
+            && !matches!(trigger, TriggerType::Cron { .. })
+        {
+            anyhow::bail!("'cron' and 'timezone' are only supported for cron entries");
+        }
+        if (config.batch.is_some() || config.failed_items.is_some())
+            && !matches!(trigger, TriggerType::Queue { .. })
+        {
+            anyhow::bail!("'batch' and 'failed_items' are only supported for queue entries");
+        }
+        if config.min_idle.is_some() && !matches!(trigger, TriggerType::OnIdle { .. }) {
+            anyhow::bail!("'min_idle' is only supported for idle entries");
+        }
+        if (matches!(config.secret, Some(SecretSetting::Webhook(_))) || config.field.is_some())
+            && !matches!(trigger, TriggerType::Webhook { .. })
+        {
+            anyhow::bail!("'secret' and 'field' are only supported for webhook entries");
+        }
+        if (config.glob.is_some() || config.read_content || config.ignore_deleted)
+            && !matches!(trigger, TriggerType::FileWatch { .. })
+        {
+            anyhow::bail!(
+                "'glob', 'read_content' and 'ignore_deleted' are only supported for watch entries"
+            );
+        }
+
+        if config.after.is_some() && trigger != TriggerType::OnStart {
+            anyhow::bail!("'after' is only supported for startup entries");
+        }
+        if config.after_failure.is_some() && config.after.is_none() {
+            anyhow::bail!("'after_failure' requires 'after'");
+        }
+
+        if config.spawn_agent {
+            if !has_lines {
+                anyhow::bail!("'spawn_agent' requires a 'source' command or a stdin entry");
+            }
+            if config.distribution != Distribution::Single {
+                anyhow::bail!("'spawn_agent' can't be combined with 'distribution'");
+            }
+            if config.on_timeout.is_some() {
+                anyhow::bail!(
+                    "'on_timeout' can't be combined with 'spawn_agent'; spawned agents are torn down on timeout"
+                );
+            }
+        } else if config.max_concurrent_spawned.is_some() {
+            anyhow::bail!("'max_concurrent_spawned' requires 'spawn_agent: true'");
+        }
+        let max_concurrent_spawned = config
+            .max_concurrent_spawned
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_SPAWNED);
+        if max_concurrent_spawned == 0 {
+            anyhow::bail!("'max_concurrent_spawned' must be at least 1");
+        }
+
+        // `secret` is read by the entry, so the flag is handed on to the action
+        let params = ActionParams {
+            secret: config.secret == Some(SecretSetting::Keys(true)),
+            ..config.params.clone()
+        };
+        let action = parse_action(&config.action, &config.keys, &params)?;
+
+        if config.on_timeout.is_some() && config.max_runtime.is_none() {
+            anyhow::bail!("'on_timeout' requires 'max_runtime'");
+        }
+        let on_timeout = config
+            .on_timeout
+            .as_ref()
+            .map(|on_timeout| {
+                parse_action(&on_timeout.action, &on_timeout.keys, &on_timeout.params)
+                    .context("Invalid 'on_timeout' action")
+            })
+            .transpose()?;
+
+        if config.source_timeout.is_some() && config.source.is_none() {
+            anyhow::bail!("'source_timeout' requires a 'source' command");
+        }
+        if config.max_lines.is_some() && !has_lines {
+            anyhow::bail!("'max_lines' requires a 'source' command or a stdin entry");
+        }
+        let source_timeout = match &config.source_timeout {
+            Some(timeout) => parse_duration(timeout)?,
+            None => DEFAULT_SOURCE_TIMEOUT,
+        };
+
+        if config.split.is_some()
+            && config.source.is_none()
+            && !matches!(
+                trigger,
+                TriggerType::Webhook { .. }
+                    | TriggerType::FileWatch { .. }
+                    | TriggerType::Queue { .. }
+                    | TriggerType::Stdin
+            )
+        {
+            anyhow::bail!(
+                "'split' requires a 'source' command or a webhook/watch/queue/stdin entry"
+            );
+        }
+        let split = config.split.as_ref().map(FieldSplit::parse).transpose()?;
+        if config.format == SourceFormat::Json {
+            if config.source.is_none() {
+                anyhow::bail!("'format: json' requires a 'source' command");
+            }
+            if split.is_some() {
+                anyhow::bail!("'split' can't be combined with 'format: json'");
+            }
+        }
+
+        let retry = match &config.retry {
+            Some(_) if config.source.is_none() => {
+                anyhow::bail!("'retry' requires a 'source' command")
+            }
+            Some(retry) if retry.attempts == 0 => {
+                anyhow::bail!("'retry.attempts' must be at least 1")
+            }
+            Some(retry) => Some(RetryPolicy {
+                attempts: retry.attempts,
+                backoff: parse_duration(&retry.backoff)?,
+            }),
+            None => None,
+        };
+
+        let dedupe = match config.dedupe {
+            DedupeSetting::Enabled(false) => None,
+            DedupeSetting::Enabled(true) => Some(Dedupe::default()),
+            DedupeSetting::Options { ttl } => Some(Dedupe {
+                ttl: ttl.as_deref().map(parse_duration).transpose()?,
+            }),
+        };
 
         Ok(Self {
             name: config.name,
             trigger,
             action,
             source: config.source,
-            dedupe: config.dedupe,
+            dedupe,
+            source_timeout,
+            max_lines: config.max_lines,
+            split,
+            format: config.format,
+            retry,
+            distribution: config.distribution,
+            sticky_key: (config.distribution == Distribution::Sticky).then(|| {
+                config
+                    .sticky_key
+                    .unwrap_or_else(|| DEFAULT_STICKY_KEY.to_string())
+            }),
+            after: config.after,
+            after_failure: config.after_failure.unwrap_or_default(),
+            wait_for: config.wait_for.as_ref().map(WaitFor::parse).transpose()?,
+            max_runtime: config
+                .max_runtime
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?,
+            on_timeout,
+            spawn_agent: config.spawn_agent,
+            max_concurrent_spawned,
+            tag: config.tag,
         })
     }
 }
 
+#[cfg(test)]
+impl Default for Trigger {
+    /// A startup entry sending nothing, with the same defaults as an entry
+    /// in YAML that sets none of the other fields
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(Vec::new()),
+            source: None,
+            dedupe: None,
+            source_timeout: DEFAULT_SOURCE_TIMEOUT,
+            max_lines: None,
+            split: None,
+            format: SourceFormat::default(),
+            retry: None,
+            distribution: Distribution::default(),
+            sticky_key: None,
+            after: None,
+            after_failure: AfterFailure::default(),
+            wait_for: None,
+            max_runtime: None,
+            on_timeout: None,
+            spawn_agent: false,
+            max_concurrent_spawned: DEFAULT_MAX_CONCURRENT_SPAWNED,
+            tag: None,
+        }
+    }
+}
+
+/// Indices of `entries` in run order: each entry comes after the entry named
+/// in its `after:`, otherwise the configured order is kept. Fails on cycles.
+pub fn startup_order(entries: &[Trigger]) -> Result<Vec<usize>> {
+    let mut order = Vec::with_capacity(entries.len());
+    let mut done = vec![false; entries.len()];
+    let is_done = |done: &[bool], name: &str| {
+        entries
+            .iter()
+            .zip(done)
+            .all(|(entry, done)| entry.name != name || *done)
+    };
+
+    while order.len() < entries.len() {
+        let ready: Vec<usize> = (0..entries.len())
+            .filter(|&i| {
+                !done[i]
+                    && entries[i]
+                        .after
+                        .as_deref()
+                        .is_none_or(|after| is_done(&done, after))
+            })
+            .collect();
+
+        if ready.is_empty() {
+            anyhow::bail!(
+                "Startup entries depend on each other in a cycle: {}",
+                describe_cycle(entries, &done)
+            );
+        }
+        for i in ready {
+            done[i] = true;
+            order.push(i);
+        }
+    }
+    Ok(order)
+}
+
+/// Follow the `after:` chain from the first pending entry until a name repeats,
+/// e.g. "build -> pull -> build"
+fn describe_cycle(entries: &[Trigger], done: &[bool]) -> String {
+    let pending = |name: &str| {
+        entries
+            .iter()
+            .zip(done)
+            .find(|(entry, done)| entry.name == name && !**done)
+            .map(|(entry, _)| entry)
+    };
+
+    let mut chain: Vec<&str> = Vec::new();
+    let mut current = entries
+        .iter()
+        .zip(done)
+        .find(|(_, done)| !**done)
+        .map(|(entry, _)| entry);
+    while let Some(entry) = current {
+        if let Some(start) = chain.iter().position(|name| *name == entry.name) {
+            chain.drain(..start);
+            chain.push(&entry.name);
+            break;
+        }
+        chain.push(&entry.name);
+        current = entry.after.as_deref().and_then(pending);
+    }
+    chain.join(" -> ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,16 +874,16 @@ mod tests {
             event: "startup".to_string(),
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
-            source: None,
-            dedupe: false,
+            ..Default::default()
         };
 
         let trigger = Trigger::try_from(config).unwrap();
         assert_eq!(trigger.name, "test_startup");
         assert_eq!(trigger.trigger, TriggerType::OnStart);
-        match trigger.action {
-            ActionType::SendKeys(keys) => assert_eq!(keys, vec!["hello"]),
-        }
+        assert_eq!(
+            trigger.action,
+            ActionType::SendKeys(vec!["hello".to_string()])
+        );
     }
 
     #[test]
@@ -99,7 +894,8 @@ mod tests {
             action: Some("send_keys".to_string()),
             keys: vec!["tick".to_string()],
             source: Some("source1".to_string()),
-            dedupe: true,
+            dedupe: DedupeSetting::Enabled(true),
+            ..Default::default()
         };
 
         let trigger = Trigger::try_from(config).unwrap();
@@ -107,11 +903,421 @@ mod tests {
         assert_eq!(
             trigger.trigger,
             TriggerType::Periodic {
-                interval: Duration::from_secs(30)
+                interval: Duration::from_secs(30),
+                options: PeriodicOptions::default(),
             }
         );
         assert_eq!(trigger.source, Some("source1".to_string()));
-        assert_eq!(trigger.dedupe, true);
+        assert_eq!(trigger.dedupe, Some(Dedupe::default()));
+    }
+
+    #[test]
+    fn test_trigger_from_idle_config() {
+        let config = TriggerConfig {
+            name: "next_task".to_string(),
+            event: "idle".to_string(),
+            action: Some("send_keys".to_string()),
+            keys: vec!["next".to_string()],
+            min_idle: Some("5s".to_string()),
+            ..Default::default()
+        };
+
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::OnIdle {
+                min_idle: Duration::from_secs(5)
+            }
+        );
+
+        // min_idle defaults to firing immediately
+        let trigger = Trigger::try_from(TriggerConfig {
+            min_idle: None,
+            ..config.clone()
+        })
+        .unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::OnIdle {
+                min_idle: Duration::ZERO
+            }
+        );
+
+        // min_idle is rejected on other events
+        assert!(
+            Trigger::try_from(TriggerConfig {
+                event: "startup".to_string(),
+                ..config
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_trigger_from_webhook_config() {
+        let config = TriggerConfig {
+            name: "issue".to_string(),
+            event: "webhook:issue-assigned".to_string(),
+            action: Some("send_keys".to_string()),
+            keys: vec!["fix ${1}".to_string(), "\r".to_string()],
+            secret: Some(SecretSetting::Webhook("s3cret".to_string())),
+            field: Some("/issue/title".to_string()),
+            ..Default::default()
+        };
+
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::Webhook {
+                path: "issue-assigned".to_string(),
+                secret: Some("s3cret".to_string()),
+                field: Some("/issue/title".to_string()),
+            }
+        );
+
+        let bad_path = TriggerConfig {
+            event: "webhook:".to_string(),
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(bad_path).is_err());
+
+        // secret is rejected on other events
+        let not_webhook = TriggerConfig {
+            event: "startup".to_string(),
+            ..config
+        };
+        assert!(Trigger::try_from(not_webhook).is_err());
+    }
+
+    #[test]
+    fn test_trigger_from_watch_config() {
+        let config = TriggerConfig {
+            name: "inbox".to_string(),
+            event: "watch:./inbox".to_string(),
+            action: Some("send_keys".to_string()),
+            keys: vec!["${1}".to_string(), "\r".to_string()],
+            glob: Some("*.md".to_string()),
+            read_content: true,
+            ignore_deleted: true,
+            ..Default::default()
+        };
+
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::FileWatch {
+                path: PathBuf::from("./inbox"),
+                glob: "*.md".to_string(),
+                read_content: true,
+                ignore_deleted: true,
+            }
+        );
+
+        let bad_glob = TriggerConfig {
+            glob: Some("[".to_string()),
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(bad_glob).is_err());
+
+        let not_watch = TriggerConfig {
+            event: "startup".to_string(),
+            ..config
+        };
+        assert!(Trigger::try_from(not_watch).is_err());
+    }
+
+    #[test]
+    fn test_trigger_from_cron_config() {
+        let config = TriggerConfig {
+            name: "standup".to_string(),
+            event: "cron".to_string(),
+            action: Some("send_keys".to_string()),
+            keys: vec!["standup".to_string()],
+            cron: Some("0 9 * * 1-5".to_string()),
+            timezone: Some("Asia/Tokyo".to_string()),
+            ..Default::default()
+        };
+
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::Cron {
+                schedule: Box::new(CronSchedule::parse("0 9 * * 1-5", Some("Asia/Tokyo")).unwrap())
+            }
+        );
+
+        let missing = TriggerConfig {
+            cron: None,
+            timezone: None,
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(missing).is_err());
+
+        let not_cron = TriggerConfig {
+            event: "timer:5m".to_string(),
+            ..config
+        };
+        assert!(Trigger::try_from(not_cron).is_err());
+    }
+
+    #[test]
+    fn test_trigger_from_timer_config_with_options() {
+        let config = TriggerConfig {
+            name: "poll".to_string(),
+            event: "timer:5m".to_string(),
+            action: Some("send_keys".to_string()),
+            keys: vec!["poll".to_string()],
+            initial_delay: Some("30s".to_string()),
+            jitter: Some("10s".to_string()),
+            ..Default::default()
+        };
+
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::Periodic {
+                interval: Duration::from_secs(300),
+                options: PeriodicOptions {
+                    initial_delay: Some(Duration::from_secs(30)),
+                    jitter: Some(Duration::from_secs(10)),
+                    skip_if_active: false,
+                    queue_if_active: false,
+                    max_runs: None,
+                },
+            }
+        );
+
+        let bad_jitter = TriggerConfig {
+            jitter: Some("soon".to_string()),
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(bad_jitter).is_err());
+
+        let one_shot = Trigger::try_from(TriggerConfig {
+            max_runs: Some(1),
+            ..config.clone()
+        })
+        .unwrap();
+        assert!(matches!(
+            one_shot.trigger,
+            TriggerType::Periodic { options, .. } if options.max_runs == Some(1)
+        ));
+        assert!(
+            Trigger::try_from(TriggerConfig {
+                max_runs: Some(0),
+                ..config.clone()
+            })
+            .is_err()
+        );
+
+        let not_timer = TriggerConfig {
+            event: "startup".to_string(),
+            ..config
+        };
+        assert!(Trigger::try_from(not_timer).is_err());
+    }
+
+    #[test]
+    fn test_distribution_deserialization() {
+        let yaml = r#"
+name: fan_out
+event: "timer:1m"
+source: "gh issue list"
+action: send_keys
+keys: ["${1}"]
+distribution: least_busy
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(trigger.distribution, Distribution::LeastBusy);
+
+        // Without a source there are no lines to distribute
+        let no_source = TriggerConfig {
+            source: None,
+            ..config
+        };
+        assert!(Trigger::try_from(no_source).is_err());
+    }
+
+    #[test]
+    fn test_secret_deserialization() {
+        let parse = |event: &str, secret: &str| {
+            let yaml = format!(
+                "name: login\nevent: \"{}\"\naction: send_keys\nkeys: [\"${{env.TOKEN}}\", \"\\r\"]\nsecret: {}\n",
+                event, secret
+            );
+            let config: TriggerConfig = serde_yml::from_str(&yaml).unwrap();
+            Trigger::try_from(config)
+        };
+
+        let trigger = parse("startup", "true").unwrap();
+        assert_eq!(
+            trigger.action,
+            ActionType::SendKeys(vec![
+                "<secret>${env.TOKEN}</secret>".to_string(),
+                "<secret>\r</secret>".to_string(),
+            ])
+        );
+        assert!(matches!(
+            parse("startup", "false").unwrap().action,
+            ActionType::SendKeys(keys) if keys[0] == "${env.TOKEN}"
+        ));
+
+        // A string is the shared secret of a webhook entry, not a flag
+        let trigger = parse("webhook:login", "s3cret").unwrap();
+        assert!(matches!(
+            trigger.trigger,
+            TriggerType::Webhook { secret: Some(secret), .. } if secret == "s3cret"
+        ));
+        assert!(matches!(
+            trigger.action,
+            ActionType::SendKeys(keys) if keys[0] == "${env.TOKEN}"
+        ));
+        assert!(parse("startup", "s3cret").is_err());
+    }
+
+    #[test]
+    fn test_dedupe_deserialization() {
+        let parse = |dedupe: &str| {
+            let yaml = format!(
+                "name: issues\nevent: startup\nsource: \"gh issue list\"\naction: send_keys\nkeys: [\"${{1}}\"]\ndedupe: {}\n",
+                dedupe
+            );
+            let config: TriggerConfig = serde_yml::from_str(&yaml).unwrap();
+            Trigger::try_from(config).map(|trigger| trigger.dedupe)
+        };
+
+        assert_eq!(parse("false").unwrap(), None);
+        assert_eq!(parse("true").unwrap(), Some(Dedupe { ttl: None }));
+        assert_eq!(
+            parse("{ttl: 24h}").unwrap(),
+            Some(Dedupe {
+                ttl: Some(Duration::from_secs(24 * 3600))
+            })
+        );
+        assert!(parse("{ttl: soon}").is_err());
+    }
+
+    #[test]
+    fn test_source_timeout() {
+        let yaml = r#"
+name: issues
+event: "timer:5m"
+source: "gh issue list"
+source_timeout: 30s
+action: send_keys
+keys: ["${1}"]
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(trigger.source_timeout, Duration::from_secs(30));
+
+        let default = TriggerConfig {
+            source_timeout: None,
+            max_lines: None,
+            split: None,
+            format: SourceFormat::Lines,
+            retry: None,
+            ..config.clone()
+        };
+        assert_eq!(
+            Trigger::try_from(default).unwrap().source_timeout,
+            DEFAULT_SOURCE_TIMEOUT
+        );
+
+        let no_source = TriggerConfig {
+            source: None,
+            ..config
+        };
+        assert!(Trigger::try_from(no_source).is_err());
+    }
+
+    #[test]
+    fn test_field_split() {
+        let split = |yaml: &str| {
+            let setting: SplitSetting = serde_yml::from_str(yaml).unwrap();
+            FieldSplit::parse(&setting)
+        };
+
+        let tab = split(r#""\t""#).unwrap();
+        assert_eq!(
+            tab.fields("123\tfix login bug"),
+            vec!["123", "fix login bug"]
+        );
+
+        let comma = split(r#"",""#).unwrap();
+        assert_eq!(comma.fields("a,,c"), vec!["a", "", "c"]);
+
+        let whitespace = split("whitespace").unwrap();
+        assert_eq!(whitespace.fields("  open   42 "), vec!["open", "42"]);
+
+        let regex = split(r#"{regex: "\\s*[;|]\\s*"}"#).unwrap();
+        assert_eq!(regex.fields("a ; b|c"), vec!["a", "b", "c"]);
+
+        assert!(split(r#""""#).is_err());
+        assert!(split(r#"{regex: "("}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_format_deserialization() {
+        let yaml = r#"
+name: issues
+event: startup
+source: "gh issue list --json number,title"
+format: json
+action: send_keys
+keys: ["fix #${.number}: ${.title}"]
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(trigger.format, SourceFormat::Json);
+
+        let no_source = TriggerConfig {
+            source: None,
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(no_source).is_err());
+
+        let with_split = TriggerConfig {
+            split: Some(SplitSetting::Separator(",".to_string())),
+            ..config
+        };
+        assert!(Trigger::try_from(with_split).is_err());
+    }
+
+    #[test]
+    fn test_retry_deserialization() {
+        let yaml = r#"
+name: issues
+event: startup
+source: "gh issue list"
+retry: {attempts: 4, backoff: 2s}
+action: send_keys
+keys: ["${1}"]
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let retry = Trigger::try_from(config.clone()).unwrap().retry.unwrap();
+        assert_eq!(retry.attempts, 4);
+        assert_eq!(retry.delay(1), Duration::from_secs(2));
+        assert_eq!(retry.delay(3), Duration::from_secs(8));
+
+        let defaults = TriggerConfig {
+            retry: serde_yml::from_str("{}").unwrap(),
+            ..config.clone()
+        };
+        assert_eq!(
+            Trigger::try_from(defaults).unwrap().retry,
+            Some(RetryPolicy {
+                attempts: 3,
+                backoff: Duration::from_secs(5)
+            })
+        );
+
+        let no_source = TriggerConfig {
+            source: None,
+            ..config
+        };
+        assert!(Trigger::try_from(no_source).is_err());
     }
 
     #[test]
@@ -121,8 +1327,7 @@ mod tests {
             event: "invalid_event".to_string(),
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
-            source: None,
-            dedupe: false,
+            ..Default::default()
         };
 
         assert!(Trigger::try_from(config).is_err());
@@ -135,8 +1340,7 @@ mod tests {
             event: "timer:invalid".to_string(),
             action: Some("send_keys".to_string()),
             keys: vec!["hello".to_string()],
-            source: None,
-            dedupe: false,
+            ..Default::default()
         };
 
         assert!(Trigger::try_from(config).is_err());
@@ -147,17 +1351,277 @@ mod tests {
         assert_eq!(TriggerType::OnStart, TriggerType::OnStart);
         assert_eq!(
             TriggerType::Periodic {
-                interval: Duration::from_secs(30)
+                interval: Duration::from_secs(30),
+                options: PeriodicOptions::default(),
             },
             TriggerType::Periodic {
-                interval: Duration::from_secs(30)
+                interval: Duration::from_secs(30),
+                options: PeriodicOptions::default(),
             }
         );
         assert_ne!(
             TriggerType::OnStart,
             TriggerType::Periodic {
-                interval: Duration::from_secs(30)
+                interval: Duration::from_secs(30),
+                options: PeriodicOptions::default(),
+            }
+        );
+    }
+
+    fn startup_entry(name: &str, after: Option<&str>) -> Trigger {
+        let mut config: TriggerConfig = serde_yml::from_str(&format!(
+            "{{name: {}, event: startup, action: send_keys, keys: [x]}}",
+            name
+        ))
+        .unwrap();
+        config.after = after.map(str::to_string);
+        Trigger::try_from(config).unwrap()
+    }
+
+    #[test]
+    fn test_startup_order() {
+        let entries = vec![
+            startup_entry("claude", Some("pull")),
+            startup_entry("pull", None),
+            startup_entry("greeting", None),
+            startup_entry("review", Some("claude")),
+        ];
+        assert_eq!(startup_order(&entries).unwrap(), vec![1, 2, 0, 3]);
+
+        let entries = vec![
+            startup_entry("greeting", None),
+            startup_entry("a", Some("b")),
+            startup_entry("b", Some("c")),
+            startup_entry("c", Some("a")),
+        ];
+        let error = startup_order(&entries).unwrap_err().to_string();
+        assert!(error.ends_with("cycle: a -> b -> c -> a"), "{}", error);
+
+        let entries = vec![startup_entry("self", Some("self"))];
+        assert!(startup_order(&entries).is_err());
+    }
+
+    #[test]
+    fn test_after_only_for_startup_entries() {
+        let parse =
+            |yaml: &str| Trigger::try_from(serde_yml::from_str::<TriggerConfig>(yaml).unwrap());
+
+        let trigger = parse(
+            "{name: b, event: startup, action: send_keys, keys: [x], after: a, after_failure: run}",
+        )
+        .unwrap();
+        assert_eq!(trigger.after.as_deref(), Some("a"));
+        assert_eq!(trigger.after_failure, AfterFailure::Run);
+
+        assert!(
+            parse("{name: b, event: 'timer:5m', action: send_keys, keys: [x], after: a}").is_err()
+        );
+        assert!(
+            parse("{name: b, event: startup, action: send_keys, keys: [x], after_failure: run}")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_wait_for_parse() {
+        let parse = |yaml: &str| WaitFor::parse(&serde_yml::from_str(yaml).unwrap());
+
+        let wait = parse("idle").unwrap();
+        assert!(matches!(wait.condition, WaitCondition::Idle));
+        assert_eq!(wait.timeout, DEFAULT_WAIT_TIMEOUT);
+
+        let wait = parse(r#"{pattern: "\\$ $", timeout: 10s}"#).unwrap();
+        assert!(
+            matches!(&wait.condition, WaitCondition::Pattern(p) if p.is_match("user@host:~$ "))
+        );
+        assert_eq!(wait.timeout, Duration::from_secs(10));
+
+        let wait = parse("{idle: true, timeout: 2m}").unwrap();
+        assert!(matches!(wait.condition, WaitCondition::Idle));
+        assert_eq!(wait.timeout, Duration::from_secs(120));
+
+        assert!(parse("busy").is_err());
+        assert!(parse("{timeout: 10s}").is_err());
+        assert!(parse(r#"{pattern: "$", idle: true}"#).is_err());
+        assert!(parse(r#"{pattern: "("}"#).is_err());
+    }
+
+    #[test]
+    fn test_max_runtime_and_on_timeout() {
+        let parse =
+            |yaml: &str| Trigger::try_from(serde_yml::from_str::<TriggerConfig>(yaml).unwrap());
+
+        let trigger = parse(
+            r#"{name: t, event: startup, action: send_keys, keys: [go], max_runtime: 10m,
+                on_timeout: {action: send_keys, keys: ["\u0003"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(trigger.max_runtime, Some(Duration::from_secs(600)));
+        assert_eq!(
+            trigger.on_timeout,
+            Some(ActionType::SendKeys(vec!["\u{3}".to_string()]))
+        );
+
+        // Unlimited by default
+        let trigger = parse("{name: t, event: startup, action: send_keys, keys: [go]}").unwrap();
+        assert_eq!(trigger.max_runtime, None);
+
+        assert!(
+            parse(r#"{name: t, event: startup, action: send_keys, keys: [go], on_timeout: {action: send_keys, keys: [x]}}"#)
+                .is_err()
+        );
+        assert!(
+            parse("{name: t, event: startup, action: send_keys, keys: [go], max_runtime: 1m, on_timeout: {action: send_keys}}")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_trigger_from_queue_config() {
+        let parse =
+            |yaml: &str| Trigger::try_from(serde_yml::from_str::<TriggerConfig>(yaml).unwrap());
+
+        let trigger = parse(
+            "{name: r, event: 'queue:reviews', action: send_keys, keys: ['${1}'], batch: 5, failed_items: failed_queue}",
+        )
+        .unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::Queue {
+                name: "reviews".to_string(),
+                batch: 5,
+                failed_items: FailedItems::FailedQueue,
+            }
+        );
+
+        let trigger =
+            parse("{name: r, event: 'queue:reviews', action: send_keys, keys: ['${1}']}").unwrap();
+        assert_eq!(
+            trigger.trigger,
+            TriggerType::Queue {
+                name: "reviews".to_string(),
+                batch: 1,
+                failed_items: FailedItems::Drop,
             }
         );
+
+        assert!(parse("{name: r, event: 'queue:', action: send_keys, keys: [x]}").is_err());
+        assert!(
+            parse("{name: r, event: 'queue:reviews', action: send_keys, keys: [x], batch: 0}")
+                .is_err()
+        );
+        assert!(
+            parse("{name: r, event: startup, action: send_keys, keys: [x], batch: 2}").is_err()
+        );
+    }
+
+    #[test]
+    fn test_trigger_from_shutdown_config() {
+        let config: TriggerConfig = serde_yml::from_str(
+            r#"{name: exit-claude, event: shutdown, action: send_keys, keys: ["/exit", "\r"]}"#,
+        )
+        .unwrap();
+        let trigger = Trigger::try_from(config).unwrap();
+        assert_eq!(trigger.trigger, TriggerType::OnShutdown);
+    }
+
+    #[test]
+    fn test_spawn_agent_deserialization() {
+        let yaml = r#"
+name: tasks
+event: startup
+source: "cat tasks.txt"
+action: send_keys
+keys: ["${1}"]
+spawn_agent: true
+max_concurrent_spawned: 2
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert!(trigger.spawn_agent);
+        assert_eq!(trigger.max_concurrent_spawned, 2);
+
+        let default_limit = TriggerConfig {
+            max_concurrent_spawned: None,
+            ..config.clone()
+        };
+        assert_eq!(
+            Trigger::try_from(default_limit)
+                .unwrap()
+                .max_concurrent_spawned,
+            DEFAULT_MAX_CONCURRENT_SPAWNED
+        );
+
+        let zero = TriggerConfig {
+            max_concurrent_spawned: Some(0),
+            tag: None,
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(zero).is_err());
+        let no_source = TriggerConfig {
+            source: None,
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(no_source).is_err());
+        let distributed = TriggerConfig {
+            distribution: Distribution::RoundRobin,
+            ..config.clone()
+        };
+        assert!(Trigger::try_from(distributed).is_err());
+        let limit_only = TriggerConfig {
+            spawn_agent: false,
+            ..config
+        };
+        assert!(Trigger::try_from(limit_only).is_err());
+    }
+
+    #[test]
+    fn test_trigger_from_stdin_config() {
+        let config: TriggerConfig = serde_yml::from_str(
+            r#"{name: piped, event: stdin, action: send_keys, keys: ["${1}", "\r"], max_lines: 10}"#,
+        )
+        .unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(trigger.trigger, TriggerType::Stdin);
+        assert_eq!(trigger.max_lines, Some(10));
+
+        // Lines come from stdin, so a source command makes no sense
+        let with_source = TriggerConfig {
+            source: Some("cat tasks.txt".to_string()),
+            ..config
+        };
+        assert!(Trigger::try_from(with_source).is_err());
+    }
+
+    #[test]
+    fn test_sticky_distribution() {
+        let yaml = r#"
+name: issues
+event: "timer:5m"
+source: "gh issue list"
+action: send_keys
+keys: ["${1}"]
+distribution: sticky
+"#;
+        let config: TriggerConfig = serde_yml::from_str(yaml).unwrap();
+        let trigger = Trigger::try_from(config.clone()).unwrap();
+        assert_eq!(trigger.distribution, Distribution::Sticky);
+        assert_eq!(trigger.sticky_key.as_deref(), Some(DEFAULT_STICKY_KEY));
+
+        let keyed = TriggerConfig {
+            sticky_key: Some("${.number}".to_string()),
+            ..config.clone()
+        };
+        assert_eq!(
+            Trigger::try_from(keyed).unwrap().sticky_key.as_deref(),
+            Some("${.number}")
+        );
+
+        let not_sticky = TriggerConfig {
+            distribution: Distribution::RoundRobin,
+            sticky_key: Some("${1}".to_string()),
+            ..config
+        };
+        assert!(Trigger::try_from(not_sticky).is_err());
     }
 }
@@ -1,6 +1,11 @@
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize, Clone)]
+use super::helper::parse_duration;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct WebUIConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
@@ -12,6 +17,88 @@ pub struct WebUIConfig {
     pub cols: u16,
     #[serde(default = "default_rows")]
     pub rows: u16,
+    /// Port of the control API and webhook entries while the web UI is
+    /// disabled; the web servers serve them otherwise
+    #[serde(default = "default_control_port")]
+    pub control_port: u16,
+    /// Shared secret allowing POST /api/exec, sent in the `X-Ccauto-Secret`
+    /// header; commands can't be run through the control API without one
+    #[serde(default)]
+    pub exec_secret: Option<String>,
+    /// One server per agent, or one for all of them
+    #[serde(default)]
+    pub mode: WebUIMode,
+    /// Web terminals show the agents but refuse input
+    #[serde(default)]
+    pub read_only: bool,
+    /// Serve the agents' web UI over HTTPS and WSS
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Page showing an agent's terminal
+    #[serde(default)]
+    pub frontend: WebFrontend,
+    /// A web UI port in use moves to the next free one instead of
+    /// stopping ccauto
+    #[serde(default)]
+    pub port_fallback: bool,
+    /// Path every web UI route is served under, e.g. `/agents` behind a
+    /// reverse proxy that keeps it
+    #[serde(default)]
+    pub base_path: String,
+    /// Origins whose pages may call the web UI: `*` for any, none if empty
+    #[serde(default = "default_cors_origins")]
+    pub cors_origins: Vec<String>,
+    /// Time between pings to each web terminal connection, e.g. `30s`;
+    /// `0s` sends none
+    #[serde(default = "default_ping_interval")]
+    pub ping_interval: String,
+    /// Time a web terminal has to answer a ping before it is dropped
+    #[serde(default = "default_pong_timeout")]
+    pub pong_timeout: String,
+    /// Largest message a web terminal may send, in bytes
+    #[serde(default = "default_max_input_bytes")]
+    pub max_input_bytes: usize,
+    /// Largest paste message a web terminal may send, in bytes
+    #[serde(default = "default_max_paste_bytes")]
+    pub max_paste_bytes: usize,
+    /// Messages a web terminal may send per second; 0 for no limit
+    #[serde(default = "default_max_input_rate")]
+    pub max_input_rate: u32,
+    /// Time a web server gets on shutdown to close its WebSockets and
+    /// finish the requests in flight before it is dropped
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: String,
+}
+
+/// PEM files of the web UI's certificate (chain) and private key
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// How agents are spread over web servers
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebUIMode {
+    /// Each agent on its own port, `base_port` plus its index
+    #[default]
+    PerAgent,
+    /// Every agent on `base_port` under `/agents/<id>/`, with a landing
+    /// page listing them at `/`
+    Single,
+}
+
+/// Terminal of the agent page
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebFrontend {
+    /// asciinema-player following the terminal, with an input box
+    #[default]
+    Asciinema,
+    /// xterm.js emulating the terminal, typed into directly, with
+    /// selection and scrollback
+    Xterm,
 }
 
 impl Default for WebUIConfig {
@@ -22,14 +109,118 @@ impl Default for WebUIConfig {
             base_port: default_base_port(),
             cols: default_cols(),
             rows: default_rows(),
+            control_port: default_control_port(),
+            exec_secret: None,
+            mode: WebUIMode::default(),
+            read_only: false,
+            tls: None,
+            frontend: WebFrontend::default(),
+            port_fallback: false,
+            base_path: String::new(),
+            cors_origins: default_cors_origins(),
+            ping_interval: default_ping_interval(),
+            pong_timeout: default_pong_timeout(),
+            max_input_bytes: default_max_input_bytes(),
+            max_paste_bytes: default_max_paste_bytes(),
+            max_input_rate: default_max_input_rate(),
+            shutdown_timeout: default_shutdown_timeout(),
         }
     }
 }
 
+impl WebUIConfig {
+    /// `https` when the web UI is served with TLS, else `http`
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.is_some() { "https" } else { "http" }
+    }
+
+    /// `base_path` as a route prefix: `/agents` for `agents/`, empty for `/`
+    pub fn path_prefix(&self) -> String {
+        match self.base_path.trim_matches('/') {
+            "" => String::new(),
+            path => format!("/{}", path),
+        }
+    }
+
+    pub fn ping_interval(&self) -> Result<Duration> {
+        parse_duration(&self.ping_interval).context("Invalid 'web_ui.ping_interval'")
+    }
+
+    pub fn pong_timeout(&self) -> Result<Duration> {
+        parse_duration(&self.pong_timeout).context("Invalid 'web_ui.pong_timeout'")
+    }
+
+    pub fn shutdown_timeout(&self) -> Result<Duration> {
+        parse_duration(&self.shutdown_timeout).context("Invalid 'web_ui.shutdown_timeout'")
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.ping_interval()?;
+        self.pong_timeout()?;
+        self.shutdown_timeout()?;
+        if let Some(c) = self
+            .base_path
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || "/-._~".contains(c)))
+        {
+            anyhow::bail!(
+                "web_ui.base_path may only contain letters, digits, '/', '-', '.', '_' and '~', got '{}' in {}",
+                c,
+                self.base_path
+            );
+        }
+        for origin in &self.cors_origins {
+            let valid = origin == "*"
+                || origin.split_once("://").is_some_and(|(scheme, host)| {
+                    matches!(scheme, "http" | "https") && !host.is_empty() && !host.contains('/')
+                });
+            if !valid {
+                anyhow::bail!(
+                    "web_ui.cors_origins takes '*' or origins like https://example.com, got {}",
+                    origin
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 fn default_base_port() -> u16 {
     9990
 }
 
+fn default_control_port() -> u16 {
+    9989
+}
+
+fn default_cors_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_ping_interval() -> String {
+    "30s".to_string()
+}
+
+fn default_pong_timeout() -> String {
+    "10s".to_string()
+}
+
+fn default_shutdown_timeout() -> String {
+    "5s".to_string()
+}
+
+fn default_max_input_bytes() -> usize {
+    8 * 1024
+}
+
+fn default_max_paste_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_input_rate() -> u32 {
+    100
+}
+
 fn default_enabled() -> bool {
     true
 }
@@ -53,17 +244,103 @@ mod tests {
     #[test]
     fn test_default_web_ui_config() {
         let config = WebUIConfig::default();
-        assert_eq!(config.enabled, true);
+        assert!(config.enabled);
         assert_eq!(config.host, "localhost");
         assert_eq!(config.base_port, 9990);
         assert_eq!(config.cols, 80);
         assert_eq!(config.rows, 24);
+        assert_eq!(config.control_port, 9989);
+        assert_eq!(config.mode, WebUIMode::PerAgent);
+    }
+
+    #[test]
+    fn test_mode_deserialization() {
+        let config: WebUIConfig = serde_yml::from_str("mode: single").unwrap();
+        assert_eq!(config.mode, WebUIMode::Single);
+        let config: WebUIConfig = serde_yml::from_str("mode: per_agent").unwrap();
+        assert_eq!(config.mode, WebUIMode::PerAgent);
+        assert!(serde_yml::from_str::<WebUIConfig>("mode: shared").is_err());
+    }
+
+    #[test]
+    fn test_frontend_deserialization() {
+        assert_eq!(WebUIConfig::default().frontend, WebFrontend::Asciinema);
+        let config: WebUIConfig = serde_yml::from_str("frontend: xterm").unwrap();
+        assert_eq!(config.frontend, WebFrontend::Xterm);
+        assert!(serde_yml::from_str::<WebUIConfig>("frontend: hterm").is_err());
+    }
+
+    #[test]
+    fn test_base_path_and_cors_origins() {
+        let config = WebUIConfig::default();
+        assert_eq!(config.path_prefix(), "");
+        assert_eq!(config.cors_origins, ["*"]);
+        config.validate().unwrap();
+
+        let config: WebUIConfig = serde_yml::from_str(
+            "base_path: /ccauto/agents/\ncors_origins: [https://dash.example.com, http://localhost:3000]",
+        )
+        .unwrap();
+        assert_eq!(config.path_prefix(), "/ccauto/agents");
+        config.validate().unwrap();
+
+        for yaml in [
+            "base_path: /a b",
+            "base_path: /{id}",
+            "cors_origins: [dash.example.com]",
+            "cors_origins: [https://dash.example.com/app]",
+        ] {
+            let config: WebUIConfig = serde_yml::from_str(yaml).unwrap();
+            assert!(config.validate().is_err(), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn test_keepalive_durations() {
+        let config = WebUIConfig::default();
+        assert_eq!(config.ping_interval().unwrap(), Duration::from_secs(30));
+        assert_eq!(config.pong_timeout().unwrap(), Duration::from_secs(10));
+
+        let config: WebUIConfig =
+            serde_yml::from_str("ping_interval: 0s\npong_timeout: 1m").unwrap();
+        assert_eq!(config.ping_interval().unwrap(), Duration::ZERO);
+        assert_eq!(config.pong_timeout().unwrap(), Duration::from_secs(60));
+
+        let config: WebUIConfig = serde_yml::from_str("ping_interval: soon").unwrap();
+        assert!(config.validate().is_err());
+
+        assert_eq!(config.shutdown_timeout().unwrap(), Duration::from_secs(5));
+        let config: WebUIConfig = serde_yml::from_str("shutdown_timeout: later").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_port_fallback_deserialization() {
+        assert!(!WebUIConfig::default().port_fallback);
+        let config: WebUIConfig = serde_yml::from_str("port_fallback: true").unwrap();
+        assert!(config.port_fallback);
+    }
+
+    #[test]
+    fn test_tls_deserialization() {
+        assert_eq!(WebUIConfig::default().scheme(), "http");
+        let config: WebUIConfig =
+            serde_yml::from_str("tls: {cert: certs/web.pem, key: certs/web.key}").unwrap();
+        assert_eq!(
+            config.tls,
+            Some(TlsConfig {
+                cert: PathBuf::from("certs/web.pem"),
+                key: PathBuf::from("certs/web.key"),
+            })
+        );
+        assert_eq!(config.scheme(), "https");
+        assert!(serde_yml::from_str::<WebUIConfig>("tls: {cert: web.pem}").is_err());
     }
 
     #[test]
     fn test_default_functions() {
         assert_eq!(default_base_port(), 9990);
-        assert_eq!(default_enabled(), true);
+        assert!(default_enabled());
         assert_eq!(default_host(), "localhost");
         assert_eq!(default_cols(), 80);
         assert_eq!(default_rows(), 24);
@@ -79,7 +356,7 @@ cols: 120
 rows: 30
 "#;
         let config: WebUIConfig = serde_yml::from_str(yaml).unwrap();
-        assert_eq!(config.enabled, false);
+        assert!(!config.enabled);
         assert_eq!(config.host, "0.0.0.0");
         assert_eq!(config.base_port, 8080);
         assert_eq!(config.cols, 120);
@@ -92,7 +369,7 @@ rows: 30
 base_port: 8000
 "#;
         let config: WebUIConfig = serde_yml::from_str(yaml).unwrap();
-        assert_eq!(config.enabled, true); // default
+        assert!(config.enabled); // default
         assert_eq!(config.host, "localhost"); // default
         assert_eq!(config.base_port, 8000); // specified
         assert_eq!(config.cols, 80); // default
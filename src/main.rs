@@ -1,7 +1,12 @@
+mod action;
 mod agent;
 mod cli;
 mod config;
+mod metrics;
+mod queue;
 mod rule;
+mod state;
+mod template;
 mod terminal;
 mod trigger;
 mod web_server;
@@ -10,75 +15,548 @@ mod web_ui;
 use agent::Agents;
 use anyhow::{Context, Result};
 use clap::Parser;
-use cli::Cli;
+use cli::{Cli, Commands, DedupeCommand, TriggerCommand};
 use config::Config;
-use std::path::PathBuf;
+use config::effective::EffectiveConfig;
+use config::web_ui_config::WebUIMode;
+use state::RuntimeState;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
 use trigger::Triggers;
+use web_server::WebServer;
+use web_server::control::ControlServer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments first to get debug flag
     let cli = Cli::parse();
 
-    // Initialize logging based on debug flag
-    let level = if cli.debug {
+    let state_path = cli
+        .state_file
+        .unwrap_or_else(|| PathBuf::from(state::DEFAULT_STATE_FILE));
+
+    match cli.command {
+        Some(Commands::Show {
+            stats,
+            schedule,
+            history,
+            agents,
+            config,
+            remote,
+        }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            if config {
+                run_show_config_command(&rules_path, remote.as_deref()).await?;
+            }
+            if !config || stats || schedule || history || agents {
+                run_show_command(state_path, rules_path, stats, schedule, history, agents)?
+            }
+        }
+        Some(Commands::TestRule { input, chunk_size }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_test_rule_command(rules_path, input, chunk_size)?
+        }
+        Some(Commands::Trigger {
+            command: Some(command),
+            ..
+        }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_pause_command(rules_path, command).await?
+        }
+        Some(Commands::Trigger {
+            command: None,
+            name,
+            agent,
+        }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            let name = name.context("Missing trigger name")?;
+            run_trigger_command(rules_path, &name, agent.as_deref()).await?
+        }
+        Some(Commands::Send { all: _, keys }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_send_command(rules_path, &keys).await?
+        }
+        Some(Commands::Attach { agent, detach_key }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_attach_command(rules_path, &agent, &detach_key).await?
+        }
+        Some(Commands::Resize { agent, cols, rows }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_resize_command(rules_path, &agent, cols, rows).await?
+        }
+        Some(Commands::Exec { command, timeout }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_exec_command(rules_path, &command, timeout.as_deref()).await?
+        }
+        Some(Commands::ResetRestarts { agent }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_reset_restarts_command(rules_path, &agent).await?
+        }
+        Some(Commands::Dedupe { command }) => {
+            init_logging(cli.debug, false);
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            run_dedupe_command(rules_path, command)?
+        }
+        None => {
+            // Run automation command (main mode)
+            let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
+            let mut config = Config::from_file(rules_path.to_str().unwrap())?;
+            config.dry_run |= cli.dry_run;
+
+            init_logging(cli.debug, config.dry_run);
+            run_automation_command(config, rules_path, state_path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Initialize logging based on debug and dry-run flags
+fn init_logging(debug: bool, dry_run: bool) {
+    let level = if debug {
         tracing::Level::DEBUG // DEBUG level or higher when --debug
+    } else if dry_run {
+        tracing::Level::INFO // INFO level so "WOULD SEND" lines are visible in dry-run
     } else {
         tracing::Level::WARN // WARN level or higher in normal operation (errors and warnings only)
     };
     tracing_subscriber::fmt().with_max_level(level).init();
+}
+
+/// Show runtime information written by a running ccauto process
+fn run_show_command(
+    state_path: PathBuf,
+    rules_path: PathBuf,
+    stats: bool,
+    schedule: bool,
+    history: bool,
+    agents: bool,
+) -> Result<()> {
+    if !stats && !schedule && !history && !agents {
+        anyhow::bail!("Nothing to show: pass --stats, --schedule, --history, --agents or --config");
+    }
+
+    if schedule {
+        let config = Config::from_file(rules_path.to_str().unwrap())?;
+        let triggers = config.parse_triggers()?;
+        println!("📅 Upcoming cron runs");
+        print!(
+            "{}",
+            trigger::periodic::format_cron_schedule(&triggers, chrono::Utc::now(), 3)
+        );
+    }
+    if !stats && !history && !agents {
+        return Ok(());
+    }
+
+    let state = RuntimeState::read_from_file(&state_path)?;
+    let now = rule::stats::unix_now();
+    if stats {
+        println!(
+            "📊 Rule statistics (pid {}, updated {}s ago)",
+            state.pid,
+            now.saturating_sub(state.updated_at)
+        );
+        print!("{}", state.format_rule_stats());
+    }
+    if history {
+        println!(
+            "🕘 Trigger runs (pid {}, updated {}s ago)",
+            state.pid,
+            now.saturating_sub(state.updated_at)
+        );
+        print!(
+            "{}",
+            trigger::history::format_history(&state.trigger_history, &state.paused_triggers, now)
+        );
+    }
+    if agents {
+        println!(
+            "🖥️  Agents (pid {}, updated {}s ago)",
+            state.pid,
+            now.saturating_sub(state.updated_at)
+        );
+        print!("{}", state.format_agents());
+    }
+
+    Ok(())
+}
+
+/// Print the parsed rules, entries and settings of the config file, or
+/// with `remote` those of the ccauto process whose control API is there,
+/// in the same form so the two can be compared
+async fn run_show_config_command(rules_path: &Path, remote: Option<&str>) -> Result<()> {
+    let effective = match remote {
+        Some(url) => web_server::control::request_config(url).await?,
+        None => {
+            let config = Config::from_file(rules_path.to_str().unwrap())?;
+            let rules = config.parse_rules()?;
+            let triggers = config.parse_triggers()?;
+            serde_json::to_value(EffectiveConfig::new(&config, &rules, &triggers, |_| false))?
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&effective)?);
+    Ok(())
+}
 
-    // Run automation command (main mode)
-    let rules_path = cli.config.unwrap_or_else(|| PathBuf::from("config.yaml"));
-    run_automation_command(rules_path).await?;
+/// Match sample output against the configured rules and print the results
+fn run_test_rule_command(
+    rules_path: PathBuf,
+    input: Option<PathBuf>,
+    chunk_size: Option<usize>,
+) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let rules = config.parse_rules()?;
+
+    let text = match input {
+        Some(path) if path.as_os_str() != "-" => std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read input file: {}", path.display()))?,
+        _ => std::io::read_to_string(std::io::stdin()).context("Failed to read stdin")?,
+    };
+
+    let groups = rule::groups::RuleGroups::new(config.default_active_groups());
+    let hits = rule::tester::test_rules(rules, &groups, &text, chunk_size);
+    print!("{}", rule::tester::format_hits(&hits));
+    if !config.agents.rule_groups.is_empty() {
+        println!("Active rule groups: {:?}", groups.active());
+    }
+
+    Ok(())
+}
+
+/// Run a trigger entry in the running process via its control API
+async fn run_trigger_command(rules_path: PathBuf, name: &str, agent: Option<&str>) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let response = web_server::control::request_manual_run(&config.web_ui, name, agent).await?;
+
+    if !response.ok {
+        anyhow::bail!("Trigger '{}' failed: {}", name, response.message);
+    }
+    println!(
+        "▶️ {} ({} lines processed)",
+        response.message, response.lines
+    );
+    Ok(())
+}
+
+/// Pause or resume a periodic entry in the running process via its control API
+async fn run_pause_command(rules_path: PathBuf, command: TriggerCommand) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let (name, paused) = match &command {
+        TriggerCommand::Pause { name } => (name, true),
+        TriggerCommand::Resume { name } => (name, false),
+    };
+    let response = web_server::control::request_pause(&config.web_ui, name, paused).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+    let icon = if response.paused { "⏸️" } else { "▶️" };
+    println!("{} {}", icon, response.message);
+    Ok(())
+}
+
+/// Send keys to every agent of the running process via its control API
+async fn run_send_command(rules_path: PathBuf, keys: &[String]) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let response = web_server::control::request_broadcast(&config.web_ui, keys).await?;
 
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+    println!("📣 {}", response.message);
     Ok(())
 }
 
+/// Connect the terminal to an agent through its web UI WebSocket
+async fn run_attach_command(rules_path: PathBuf, agent_id: &str, detach_key: &str) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    if !config.web_ui.enabled {
+        anyhow::bail!("ccauto attach connects through the web UI, but web_ui is disabled");
+    }
+    let detach_byte = match terminal::pty_session::parse_key(detach_key)[..] {
+        [byte] => byte,
+        _ => anyhow::bail!(
+            "Detach key must be a single key, e.g. C-\\ or C-], not {:?}",
+            detach_key
+        ),
+    };
+    let url = web_server::attach::running_websocket_url(&config.web_ui, agent_id).await?;
+
+    println!("🔌 Attaching to {} (detach with {})", agent_id, detach_key);
+    let detached =
+        web_server::attach::attach(&url, detach_byte, config.web_ui.tls.as_ref()).await?;
+    match detached {
+        web_server::attach::Detached::ByUser => println!("\r\n🔌 Detached from {}", agent_id),
+        web_server::attach::Detached::Closed => {
+            println!("\r\n🔌 Connection to {} closed", agent_id)
+        }
+    }
+    Ok(())
+}
+
+/// Resize the terminal of an agent in the running ccauto process
+async fn run_resize_command(
+    rules_path: PathBuf,
+    agent_id: &str,
+    cols: u16,
+    rows: u16,
+) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let response =
+        web_server::control::request_resize(&config.web_ui, agent_id, cols, rows).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+    println!("📐 {}", response.message);
+    Ok(())
+}
+
+/// Run a command in a running ccauto process, exiting with its exit code
+/// (124 when it timed out, like timeout(1))
+async fn run_exec_command(rules_path: PathBuf, command: &str, timeout: Option<&str>) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let response = web_server::control::request_exec(
+        &config.web_ui,
+        config.web_ui.exec_secret.as_deref(),
+        command,
+        timeout,
+    )
+    .await?;
+
+    let Some(result) = response.result else {
+        anyhow::bail!("{}", response.message);
+    };
+    print!("{}", result.output);
+    let code = match (result.timed_out, result.exit_code) {
+        (true, _) => 124,
+        (false, Some(code)) => code as i32,
+        (false, None) => 1,
+    };
+    if code != 0 {
+        eprintln!("{}", response.message);
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// Reset the restart counters of an agent in the running ccauto process
+async fn run_reset_restarts_command(rules_path: PathBuf, agent_id: &str) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let response = web_server::control::request_reset_restarts(&config.web_ui, agent_id).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+    println!("🧹 {}", response.message);
+    Ok(())
+}
+
+/// Maintain the persistent dedupe store configured in the config file
+fn run_dedupe_command(rules_path: PathBuf, command: DedupeCommand) -> Result<()> {
+    let config = Config::from_file(rules_path.to_str().unwrap())?;
+    let store = state::dedupe::DedupeStore::from_config(&config.dedupe)?;
+
+    match command {
+        DedupeCommand::Clear { trigger } => {
+            let removed = store.clear(&trigger)?;
+            println!(
+                "🧹 Cleared {} seen lines of '{}' from {}",
+                removed,
+                trigger,
+                config.dedupe.file.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep entries paused that were paused when the previous process wrote its state
+fn restore_paused_triggers(agents: &Agents, state_path: &std::path::Path) {
+    if !state_path.exists() {
+        return;
+    }
+    match RuntimeState::read_from_file(state_path) {
+        Ok(state) => {
+            if !state.paused_triggers.is_empty() {
+                println!(
+                    "⏸️ Keeping paused entries: {}",
+                    state
+                        .paused_triggers
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            agents.paused_triggers().restore(state.paused_triggers);
+        }
+        Err(e) => tracing::warn!("Could not restore paused entries: {:#}", e),
+    }
+}
+
 /// Run automation command (default mode when no subcommand is provided)
-async fn run_automation_command(rules_path: PathBuf) -> Result<()> {
+async fn run_automation_command(
+    config: Config,
+    rules_path: PathBuf,
+    state_path: PathBuf,
+) -> Result<()> {
     // Create core components
-    let config = Arc::new(Config::from_file(rules_path.to_str().unwrap())?);
+    let config = Arc::new(config);
 
-    let base_port = config.web_ui.base_port;
+    // A certificate that can't be loaded stops here, not in every server
+    if config.web_ui.enabled
+        && let Some(tls) = &config.web_ui.tls
+    {
+        web_server::tls::server_config(tls)?;
+    }
 
     println!("🎯 RuleAgents started");
     println!("📂 Config file: {}", rules_path.display());
-    println!("🌐 Terminal available at: http://localhost:{}", base_port);
+    if config.dry_run {
+        println!("🧪 Dry-run mode: matched actions are logged, no keys are sent");
+    }
     println!("🛑 Press Ctrl+C to stop");
 
     // Parse configuration
     let rules = config.parse_rules()?;
+    let shutdown_timeout = config.agents.shutdown_timeout()?;
+    let shutdown_grace = config.agents.shutdown_grace()?;
+
+    // Remember lines of dedupe entries across restarts
+    state::dedupe::install(state::dedupe::DedupeStore::from_config(&config.dedupe)?);
 
     // Create agents system (includes agent pool and web server management)
     let agents = Arc::new(Agents::new(rules, &config).await?);
+    if config.agents.persist_paused {
+        restore_paused_triggers(&agents, &state_path);
+    }
 
-    // 1. Start triggers (startup + periodic)
+    // The web servers serve the control API and webhook entries, which
+    // otherwise get a port of their own
     let trigger_list = config.parse_triggers()?;
+    let control = web_server::control::router(&trigger_list, Arc::clone(&agents));
+    agents.start_web_servers(&control).await?;
+    let mut single_server = None;
+    let mut control_server = None;
+    if config.web_ui.enabled && config.web_ui.mode == WebUIMode::Single {
+        let web_server = WebServer::new(
+            config.web_ui.base_port,
+            config.web_ui.host.clone(),
+            Arc::clone(&agents),
+        )
+        .with_tls(config.web_ui.tls.clone())
+        .with_base_path(config.web_ui.path_prefix())
+        .with_cors_origins(config.web_ui.cors_origins.clone())
+        .with_shutdown_timeout(config.web_ui.shutdown_timeout()?)
+        .with_control(control);
+        let (port, handle) = web_server.spawn(config.web_ui.port_fallback).await?;
+        single_server = Some(handle);
+        println!(
+            "🌐 Terminals available at: {}://{}:{}{}/",
+            config.web_ui.scheme(),
+            config.web_ui.host,
+            port,
+            config.web_ui.path_prefix()
+        );
+    } else if !config.web_ui.enabled && !trigger::get_webhook_triggers(&trigger_list).is_empty() {
+        let server = ControlServer::new(
+            &trigger_list,
+            Arc::clone(&agents),
+            config.web_ui.host.clone(),
+            config.web_ui.control_port,
+        );
+        control_server = Some(server.spawn().await?);
+    } else {
+        for (id, summary) in agents.summaries() {
+            if let Some(port) = summary.port {
+                println!(
+                    "🌐 Terminal of {} available at: {}://{}:{}{}/",
+                    id,
+                    config.web_ui.scheme(),
+                    config.web_ui.host,
+                    port,
+                    config.web_ui.path_prefix()
+                );
+            }
+        }
+    }
+
+    // 1. Start triggers (startup + periodic)
     let triggers = Triggers::new(trigger_list, Arc::clone(&agents));
     let trigger_handles = triggers.start_all().await?;
 
     // 2. Start agents (monitoring)
     let agent_handles = agents.start_all().await?;
 
+    // 3. Publish runtime state for `ccauto show`
+    let state_handle = state::start_state_writer(Arc::clone(&agents), state_path.clone());
+
     // Wait for Ctrl+C signal
     signal::ctrl_c()
         .await
         .context("Failed to listen for ctrl_c")?;
-    println!("\n🛑 Received Ctrl+C, shutting down...");
+    println!("\n🛑 Received Ctrl+C, shutting down... (press Ctrl+C again to force)");
+
+    // A second Ctrl+C skips the cleanup below
+    tokio::spawn(async {
+        if signal::ctrl_c().await.is_ok() {
+            println!("\n⚡ Received Ctrl+C again, exiting immediately");
+            std::process::exit(130);
+        }
+    });
 
-    // Shutdown all systems
-    for handle in trigger_handles {
+    // Let shutdown entries clean up while everything is still running,
+    // but never wait on a stuck action forever
+    if tokio::time::timeout(shutdown_timeout, triggers.execute_shutdown_entries())
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "Shutdown entries did not finish within {:?}, exiting anyway",
+            shutdown_timeout
+        );
+    }
+
+    // Stop triggers so nothing new reaches the agents, then the monitors so
+    // that dying shells are not respawned
+    for handle in trigger_handles.into_iter().chain(control_server) {
         handle.abort();
     }
     for handle in agent_handles {
         handle.abort();
     }
 
-    println!("🧹 Shutting down...");
+    // Close the web terminals while the processes stop, so browsers see
+    // the server going away instead of a dead port
+    println!("🧹 Stopping agent processes...");
+    tokio::join!(
+        agents.terminate_all(shutdown_grace),
+        agents.stop_web_servers(),
+        async {
+            if let Some(server) = single_server {
+                server.shutdown().await;
+            }
+        }
+    );
+
+    state_handle.abort();
+    if let Err(e) = RuntimeState::collect(&agents).write_to_file(&state_path) {
+        tracing::warn!("Failed to write runtime state: {}", e);
+    }
 
-    // Force exit to ensure all threads terminate
+    // Force exit to ensure all threads terminate (PTY readers block on reads)
     std::process::exit(0);
 }
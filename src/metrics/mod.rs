@@ -0,0 +1,270 @@
+use prometheus::{
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::agent::AgentStatus;
+
+/// Metrics of the running process
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Every status an agent can be in, so a status gauge drops back to 0
+const STATUSES: [AgentStatus; 5] = [
+    AgentStatus::Idle,
+    AgentStatus::Active,
+    AgentStatus::Stuck,
+    AgentStatus::Error,
+    AgentStatus::Sleeping,
+];
+
+/// Prometheus metrics of agents, rules, triggers and the web UI, served
+/// as text on `/metrics` of the web servers
+pub struct Metrics {
+    registry: Registry,
+    agent_status: IntGaugeVec,
+    rule_matches: IntCounterVec,
+    trigger_runs: HistogramVec,
+    pty_output_bytes: IntCounterVec,
+    dropped_output: IntCounterVec,
+    agent_restarts: IntCounterVec,
+    agent_last_restart: IntGaugeVec,
+    agent_active_seconds: CounterVec,
+    websocket_clients: IntGaugeVec,
+    queue_depth: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("ccauto".to_string()), None).expect("valid metrics prefix");
+        let gauge = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), labels).expect("valid gauge");
+            registry
+                .register(Box::new(gauge.clone()))
+                .expect("gauge registered once");
+            gauge
+        };
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("counter registered once");
+            counter
+        };
+
+        let agent_status = gauge(
+            "agent_status",
+            "1 for the current status of each agent, 0 for the others",
+            &["agent", "status"],
+        );
+        let rule_matches = counter(
+            "rule_matches_total",
+            "Rule matches per agent and rule",
+            &["agent", "rule"],
+        );
+        let pty_output_bytes = counter(
+            "pty_output_bytes_total",
+            "Bytes the agent's shell printed",
+            &["agent"],
+        );
+        let dropped_output = counter(
+            "dropped_output_total",
+            "Output chunks the agent's rule monitors missed by falling behind",
+            &["agent"],
+        );
+        let agent_restarts = counter(
+            "agent_restarts_total",
+            "Shells started again after the previous one exited",
+            &["agent"],
+        );
+        let agent_last_restart = gauge(
+            "agent_last_restart_timestamp_seconds",
+            "Unix time of the agent's last shell restart",
+            &["agent"],
+        );
+        let agent_active_seconds = CounterVec::new(
+            Opts::new(
+                "agent_active_seconds_total",
+                "Time the agent spent busy (Active or Stuck), counted as each busy period ends",
+            ),
+            &["agent"],
+        )
+        .expect("valid counter");
+        registry
+            .register(Box::new(agent_active_seconds.clone()))
+            .expect("counter registered once");
+        let websocket_clients = gauge(
+            "websocket_clients",
+            "Web terminals connected to each agent",
+            &["agent"],
+        );
+        let queue_depth = gauge("queue_depth", "Items waiting in each queue", &["queue"]);
+        let trigger_runs = HistogramVec::new(
+            HistogramOpts::new(
+                "trigger_run_duration_seconds",
+                "Duration of trigger entry runs",
+            ),
+            &["trigger", "result"],
+        )
+        .expect("valid histogram");
+        registry
+            .register(Box::new(trigger_runs.clone()))
+            .expect("histogram registered once");
+
+        Self {
+            registry,
+            agent_status,
+            rule_matches,
+            trigger_runs,
+            pty_output_bytes,
+            dropped_output,
+            agent_restarts,
+            agent_last_restart,
+            agent_active_seconds,
+            websocket_clients,
+            queue_depth,
+        }
+    }
+
+    /// Mark `status` as the current status of `agent`
+    pub fn set_agent_status(&self, agent: &str, status: &AgentStatus) {
+        for candidate in &STATUSES {
+            self.agent_status
+                .with_label_values(&[agent, &format!("{:?}", candidate)])
+                .set((candidate == status) as i64);
+        }
+    }
+
+    /// Count a match of `rule`, as described in rule statistics
+    pub fn record_rule_match(&self, agent: &str, rule: &str) {
+        self.rule_matches.with_label_values(&[agent, rule]).inc();
+    }
+
+    /// Record a run of the trigger entry `trigger` that took `duration`
+    pub fn record_trigger_run(&self, trigger: &str, duration: Duration, succeeded: bool) {
+        let result = if succeeded { "ok" } else { "error" };
+        self.trigger_runs
+            .with_label_values(&[trigger, result])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Count `bytes` of shell output of `agent`
+    pub fn record_pty_output(&self, agent: &str, bytes: usize) {
+        self.pty_output_bytes
+            .with_label_values(&[agent])
+            .inc_by(bytes as u64);
+    }
+
+    /// Count `chunks` of output the rule monitors of `agent` missed
+    pub fn record_dropped_output(&self, agent: &str, chunks: u64) {
+        self.dropped_output
+            .with_label_values(&[agent])
+            .inc_by(chunks);
+    }
+
+    /// Count a shell restart of `agent` at Unix time `at`
+    pub fn record_restart(&self, agent: &str, at: u64) {
+        self.agent_restarts.with_label_values(&[agent]).inc();
+        self.agent_last_restart
+            .with_label_values(&[agent])
+            .set(at as i64);
+    }
+
+    /// Add a busy period of `agent` that just ended
+    pub fn record_active_time(&self, agent: &str, period: Duration) {
+        self.agent_active_seconds
+            .with_label_values(&[agent])
+            .inc_by(period.as_secs_f64());
+    }
+
+    /// Count a web terminal of `agent` until the returned guard is dropped
+    pub fn websocket_client(&self, agent: &str) -> WebSocketClient {
+        let gauge = self.websocket_clients.with_label_values(&[agent]);
+        gauge.inc();
+        WebSocketClient { gauge }
+    }
+
+    /// Web terminals of `agent` connected now
+    pub fn websocket_clients(&self, agent: &str) -> i64 {
+        self.websocket_clients.with_label_values(&[agent]).get()
+    }
+
+    /// Set the number of items waiting in `queue`
+    pub fn set_queue_depth(&self, queue: &str, depth: usize) {
+        self.queue_depth
+            .with_label_values(&[queue])
+            .set(depth as i64);
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("❌ Failed to encode metrics: {}", e);
+        }
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connected web terminal, counted in `ccauto_websocket_clients`
+pub struct WebSocketClient {
+    gauge: prometheus::IntGauge,
+}
+
+impl Drop for WebSocketClient {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
+/// The process-wide metrics
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_text() {
+        let metrics = Metrics::new();
+        metrics.set_agent_status("agent-1", &AgentStatus::Active);
+        metrics.set_agent_status("agent-1", &AgentStatus::Stuck);
+        metrics.record_rule_match("agent-1", "when: Approve");
+        metrics.record_trigger_run("poller", Duration::from_millis(250), false);
+        metrics.set_queue_depth("issues", 3);
+        metrics.record_dropped_output("agent-1", 4);
+        metrics.record_restart("agent-1", 1_700_000_000);
+        metrics.record_restart("agent-1", 1_700_000_060);
+        metrics.record_active_time("agent-1", Duration::from_millis(1500));
+        metrics.record_active_time("agent-1", Duration::from_secs(2));
+        let client = metrics.websocket_client("agent-1");
+        let _other = metrics.websocket_client("agent-1");
+        drop(client);
+
+        let text = metrics.encode();
+        for series in [
+            r#"ccauto_agent_status{agent="agent-1",status="Active"} 0"#,
+            r#"ccauto_agent_status{agent="agent-1",status="Stuck"} 1"#,
+            r#"ccauto_rule_matches_total{agent="agent-1",rule="when: Approve"} 1"#,
+            r#"ccauto_trigger_run_duration_seconds_count{result="error",trigger="poller"} 1"#,
+            r#"ccauto_queue_depth{queue="issues"} 3"#,
+            r#"ccauto_websocket_clients{agent="agent-1"} 1"#,
+            r#"ccauto_dropped_output_total{agent="agent-1"} 4"#,
+            r#"ccauto_agent_restarts_total{agent="agent-1"} 2"#,
+            r#"ccauto_agent_last_restart_timestamp_seconds{agent="agent-1"} 1700000060"#,
+            r#"ccauto_agent_active_seconds_total{agent="agent-1"} 3.5"#,
+        ] {
+            assert!(text.contains(series), "{} missing from\n{}", series, text);
+        }
+    }
+}
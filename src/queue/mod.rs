@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+use crate::metrics;
+
+/// Named in-memory FIFO queues filled by `enqueue` actions and drained by
+/// `queue:` entries. Consumers are woken through a watch channel whose
+/// value counts the items enqueued so far.
+pub struct QueueManager {
+    queues: Mutex<BTreeMap<String, VecDeque<String>>>,
+    enqueued: watch::Sender<u64>,
+}
+
+impl QueueManager {
+    pub fn new() -> Self {
+        Self {
+            queues: Mutex::new(BTreeMap::new()),
+            enqueued: watch::Sender::new(0),
+        }
+    }
+
+    /// Append an item to `queue` and wake its consumers
+    pub fn enqueue(&self, queue: &str, item: String) {
+        {
+            let mut queues = self.lock();
+            let items = queues.entry(queue.to_string()).or_default();
+            items.push_back(item);
+            metrics::metrics().set_queue_depth(queue, items.len());
+        }
+        self.enqueued.send_modify(|count| *count += 1);
+    }
+
+    /// Remove up to `max` items from the front of `queue`
+    pub fn pop_batch(&self, queue: &str, max: usize) -> Vec<String> {
+        let mut queues = self.lock();
+        let Some(items) = queues.get_mut(queue) else {
+            return Vec::new();
+        };
+        let count = max.min(items.len());
+        let batch = items.drain(..count).collect();
+        metrics::metrics().set_queue_depth(queue, items.len());
+        batch
+    }
+
+    /// Number of items waiting in `queue`
+    pub fn len(&self, queue: &str) -> usize {
+        self.lock().get(queue).map_or(0, VecDeque::len)
+    }
+
+    /// Receiver that changes on every enqueue, to any queue
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.enqueued.subscribe()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, VecDeque<String>>> {
+        self.queues.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for QueueManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop_batch_is_fifo() {
+        let queues = QueueManager::new();
+        for item in ["a", "b", "c"] {
+            queues.enqueue("tasks", item.to_string());
+        }
+
+        assert_eq!(queues.pop_batch("tasks", 2), vec!["a", "b"]);
+        assert_eq!(queues.len("tasks"), 1);
+        assert_eq!(queues.pop_batch("tasks", 5), vec!["c"]);
+        assert!(queues.pop_batch("tasks", 5).is_empty());
+        assert!(queues.pop_batch("missing", 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_wakes_subscribers() {
+        let queues = QueueManager::new();
+        let mut receiver = queues.subscribe();
+        receiver.borrow_and_update();
+
+        queues.enqueue("tasks", "a".to_string());
+        tokio::time::timeout(std::time::Duration::from_secs(1), receiver.changed())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+/// Collects PTY output chunks into batches, so rules run once per burst
+/// instead of once per tiny write
+///
+/// A batch is handed over once no chunk arrived for `quiet`, or as soon as
+/// it holds `max_bytes`. A zero `quiet` passes every chunk through as is.
+#[derive(Debug)]
+pub struct OutputDebounce {
+    pending: String,
+    quiet: Duration,
+    max_bytes: usize,
+    last_data_at: Instant,
+}
+
+impl OutputDebounce {
+    pub fn new(quiet: Duration, max_bytes: usize) -> Self {
+        Self {
+            pending: String::new(),
+            quiet,
+            max_bytes,
+            last_data_at: Instant::now(),
+        }
+    }
+
+    /// Add a chunk; returns the batch if it reached `max_bytes`
+    pub fn push(&mut self, chunk: &str, now: Instant) -> Option<String> {
+        self.last_data_at = now;
+        self.pending.push_str(chunk);
+        if self.quiet.is_zero() || self.pending.len() >= self.max_bytes {
+            return self.take();
+        }
+        None
+    }
+
+    /// Return the batch once no chunk arrived for `quiet`
+    pub fn flush_if_quiet(&mut self, now: Instant) -> Option<String> {
+        if now.duration_since(self.last_data_at) < self.quiet {
+            return None;
+        }
+        self.take()
+    }
+
+    /// Return the batch regardless of timing
+    pub fn take(&mut self) -> Option<String> {
+        let batch = std::mem::take(&mut self.pending);
+        (!batch.is_empty()).then_some(batch)
+    }
+
+    /// Drop the batch
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_of_small_chunks_is_batched() {
+        let quiet = Duration::from_millis(20);
+        let mut debounce = OutputDebounce::new(quiet, 8 * 1024);
+        let start = Instant::now();
+
+        // A streamed answer: 500 writes of a few bytes, 1ms apart
+        let mut batches = Vec::new();
+        let mut now = start;
+        for i in 0..500 {
+            now = start + Duration::from_millis(i);
+            batches.extend(debounce.push(&format!("tok{} ", i % 10), now));
+            batches.extend(debounce.flush_if_quiet(now));
+        }
+        assert!(batches.is_empty());
+        assert!(debounce.flush_if_quiet(now + quiet / 2).is_none());
+        batches.extend(debounce.flush_if_quiet(now + quiet));
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 500 * 5);
+        assert!(debounce.take().is_none());
+    }
+
+    #[test]
+    fn test_batch_handed_over_at_max_bytes() {
+        let mut debounce = OutputDebounce::new(Duration::from_millis(20), 16);
+        let now = Instant::now();
+        assert!(debounce.push("0123456789", now).is_none());
+        assert_eq!(
+            debounce.push("abcdef", now).as_deref(),
+            Some("0123456789abcdef")
+        );
+        assert!(
+            debounce
+                .flush_if_quiet(now + Duration::from_secs(1))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_zero_quiet_passes_chunks_through() {
+        let mut debounce = OutputDebounce::new(Duration::ZERO, 8 * 1024);
+        let now = Instant::now();
+        assert_eq!(debounce.push("a", now).as_deref(), Some("a"));
+        assert_eq!(debounce.push("b", now).as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,130 @@
+use regex::Regex;
+use std::time::Instant;
+
+use crate::config::helper::ActionType;
+
+/// A rule action waiting for its grace period to pass
+#[derive(Debug, Clone)]
+pub struct PendingAction {
+    /// Position of the rule in the config's rule list
+    pub rule_index: usize,
+    pub action: ActionType,
+    pub due: Instant,
+    pub cancel_on: Option<Regex>,
+}
+
+/// Per-agent queue of delayed rule actions
+#[derive(Debug, Default)]
+pub struct DelayedActions {
+    pending: Vec<PendingAction>,
+}
+
+impl DelayedActions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule an action unless the same rule already has one pending.
+    /// Returns false if it was already scheduled.
+    pub fn schedule(&mut self, action: PendingAction) -> bool {
+        if self.is_pending(action.rule_index) {
+            return false;
+        }
+        self.pending.push(action);
+        true
+    }
+
+    pub fn is_pending(&self, rule_index: usize) -> bool {
+        self.pending.iter().any(|p| p.rule_index == rule_index)
+    }
+
+    /// Drop pending actions whose `cancel_on` pattern matches the line.
+    /// Returns the rule indices that were cancelled.
+    pub fn cancel_matching(&mut self, line: &str) -> Vec<usize> {
+        let mut cancelled = Vec::new();
+        self.pending.retain(|p| {
+            let cancel = p.cancel_on.as_ref().is_some_and(|re| re.is_match(line));
+            if cancel {
+                cancelled.push(p.rule_index);
+            }
+            !cancel
+        });
+        cancelled
+    }
+
+    /// Remove and return actions whose grace period has passed
+    pub fn take_due(&mut self, now: Instant) -> Vec<PendingAction> {
+        let (due, waiting) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| p.due <= now);
+        self.pending = waiting;
+        due
+    }
+
+    /// Drop all pending actions, returning how many were dropped
+    pub fn clear(&mut self) -> usize {
+        std::mem::take(&mut self.pending).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn pending(rule_index: usize, due: Instant, cancel_on: Option<&str>) -> PendingAction {
+        PendingAction {
+            rule_index,
+            action: ActionType::SendKeys(vec!["\x1b".to_string()]),
+            due,
+            cancel_on: cancel_on.map(|p| Regex::new(p).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_take_due_fires_after_delay() {
+        let mut delayed = DelayedActions::new();
+        let now = Instant::now();
+        delayed.schedule(pending(0, now + Duration::from_secs(10), None));
+
+        assert!(delayed.take_due(now + Duration::from_secs(5)).is_empty());
+        let due = delayed.take_due(now + Duration::from_secs(10));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].rule_index, 0);
+        assert!(!delayed.is_pending(0));
+    }
+
+    #[test]
+    fn test_cancel_matching() {
+        let mut delayed = DelayedActions::new();
+        let due = Instant::now() + Duration::from_secs(10);
+        delayed.schedule(pending(0, due, Some("Compacted")));
+        delayed.schedule(pending(1, due, None));
+
+        assert!(delayed.cancel_matching("still compacting").is_empty());
+        assert_eq!(delayed.cancel_matching("✓ Compacted conversation"), vec![0]);
+        assert!(!delayed.is_pending(0));
+        assert!(delayed.is_pending(1));
+    }
+
+    #[test]
+    fn test_schedule_dedupes_same_rule() {
+        let mut delayed = DelayedActions::new();
+        let now = Instant::now();
+        assert!(delayed.schedule(pending(3, now + Duration::from_secs(1), None)));
+        assert!(!delayed.schedule(pending(3, now + Duration::from_secs(2), None)));
+
+        // The original deadline is kept
+        assert_eq!(delayed.take_due(now + Duration::from_secs(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut delayed = DelayedActions::new();
+        let due = Instant::now();
+        delayed.schedule(pending(0, due, None));
+        delayed.schedule(pending(1, due, None));
+        assert_eq!(delayed.clear(), 2);
+        assert!(delayed.take_due(due).is_empty());
+    }
+}
@@ -8,14 +8,18 @@ use crate::config::rules_config::{Rule, RuleType};
 use tokio::sync::broadcast;
 use tokio::time::interval;
 
-use super::{RuleProcessor, execute_rule_action};
+use super::{RuleProcessor, RuleStats, execute_rule_action};
 
 /// Diff timeout processor responsible for checking diff_timeout rules for a single agent
 pub struct DiffTimeout {
     durations: Vec<Duration>,
     pub(crate) actions: Vec<ActionType>,
+    rule_indices: Vec<usize>,
+    groups: Vec<Option<String>>,
+    stats: Arc<RuleStats>,
     agent: Arc<Agent>,
-    last_activity: std::sync::Mutex<Instant>,
+    /// The agent's last output time the timers were armed for
+    armed_for: std::sync::Mutex<Instant>,
     timeout_timers: std::sync::Mutex<Vec<TimeoutTimer>>,
 }
 
@@ -35,7 +39,13 @@ impl RuleProcessor for DiffTimeout {
                             self.reset_timeout_activity().await;
                         }
                         Err(broadcast::error::RecvError::Lagged(skipped)) => {
-                            tracing::warn!("DiffTimeout receiver lagged, skipped {} messages", skipped);
+                            // Skipped output is still output, so it resets the timers
+                            tracing::warn!(
+                                "⚠️ Agent {} diff_timeout monitor fell behind, {} output chunks skipped",
+                                self.agent.get_id(),
+                                skipped
+                            );
+                            self.stats.record_dropped_output(skipped);
                             self.reset_timeout_activity().await;
                         }
                         Err(broadcast::error::RecvError::Closed) => {
@@ -60,10 +70,16 @@ impl RuleProcessor for DiffTimeout {
 impl DiffTimeout {
     pub fn new(rules: Vec<Rule>, agent: Arc<Agent>) -> Self {
         // Filter to only keep DiffTimeout rules and extract durations and actions
-        let diff_timeout_rules: Vec<Rule> = rules
+        let (rule_indices, diff_timeout_rules): (Vec<usize>, Vec<Rule>) = rules
             .into_iter()
-            .filter(|rule| matches!(rule.rule_type, RuleType::DiffTimeout(_)))
-            .collect();
+            .enumerate()
+            .filter(|(_, rule)| matches!(rule.rule_type, RuleType::DiffTimeout(_)))
+            .unzip();
+
+        let stats = agent.get_rule_stats();
+        for (index, rule) in rule_indices.iter().zip(&diff_timeout_rules) {
+            stats.register(*index, rule.describe());
+        }
 
         let durations: Vec<Duration> = diff_timeout_rules
             .iter()
@@ -76,6 +92,11 @@ impl DiffTimeout {
             })
             .collect();
 
+        let groups: Vec<Option<String>> = diff_timeout_rules
+            .iter()
+            .map(|rule| rule.group.clone())
+            .collect();
+
         let actions: Vec<ActionType> = diff_timeout_rules
             .into_iter()
             .map(|rule| rule.action)
@@ -92,26 +113,24 @@ impl DiffTimeout {
         Self {
             durations,
             actions,
+            rule_indices,
+            groups,
+            stats,
+            armed_for: std::sync::Mutex::new(agent.get_last_output()),
             agent,
-            last_activity: std::sync::Mutex::new(Instant::now()),
             timeout_timers: std::sync::Mutex::new(timers),
         }
     }
 
-    /// Reset timeout activity (called when terminal output is received)
+    /// Reset timeout activity (called when terminal output is received).
+    /// Timeouts count from the agent's last output, shared with its status
+    /// monitor, so this may get to it first.
     async fn reset_timeout_activity(&self) {
-        if let (Ok(mut last_activity), Ok(mut timers)) =
-            (self.last_activity.lock(), self.timeout_timers.lock())
-        {
-            *last_activity = Instant::now();
-            for timer in timers.iter_mut() {
-                timer.triggered = false;
-            }
-        }
+        self.agent.record_output(Instant::now());
     }
 
     async fn process_timeout_rules(&self) -> Result<()> {
-        if !self.agent.is_active().await {
+        if !self.agent.is_active() {
             return Ok(());
         }
 
@@ -126,7 +145,15 @@ impl DiffTimeout {
                 idx,
                 duration
             );
-            tracing::info!("⏰ Executing timeout rule action: {:?}", action);
+            if self.agent.are_rules_paused() {
+                tracing::info!(
+                    "⏸️ Agent {} rules paused, skipped timeout action: {}",
+                    self.agent.get_id(),
+                    action.describe()
+                );
+                continue;
+            }
+            tracing::info!("⏰ Executing timeout rule action: {}", action.describe());
 
             if let Err(e) = execute_rule_action(action, &self.agent, "🤖 Rule action").await {
                 tracing::error!("❌ Error executing timeout rule action: {}", e);
@@ -137,22 +164,37 @@ impl DiffTimeout {
     }
 
     pub(crate) fn find_triggered_timeout_indices(&self) -> Vec<usize> {
-        let Ok(last_activity) = self.last_activity.lock() else {
-            return Vec::new();
-        };
         let Ok(mut timers) = self.timeout_timers.lock() else {
             return Vec::new();
         };
 
-        let elapsed = last_activity.elapsed();
-        drop(last_activity);
+        // Output since the timers fired arms them again
+        let last_output = self.agent.get_last_output();
+        if let Ok(mut armed_for) = self.armed_for.lock()
+            && *armed_for != last_output
+        {
+            *armed_for = last_output;
+            for timer in timers.iter_mut() {
+                timer.triggered = false;
+            }
+        }
+        let elapsed = last_output.elapsed();
+
+        let rule_groups = self.agent.get_rule_groups();
 
         timers
             .iter_mut()
             .enumerate()
             .filter_map(|(i, timer)| {
-                if elapsed >= timer.duration && !timer.triggered {
+                if elapsed >= timer.duration
+                    && !timer.triggered
+                    && rule_groups.is_enabled(self.groups[i].as_deref())
+                {
                     timer.triggered = true;
+                    self.stats.record(
+                        self.rule_indices[i],
+                        &format!("no output for {}s", elapsed.as_secs()),
+                    );
                     Some(i)
                 } else {
                     None
@@ -175,10 +217,10 @@ mod tests {
     use crate::config::rules_config::RuleType;
 
     fn create_timeout_rule(duration_str: &str, keys: Vec<String>) -> Rule {
-        Rule {
-            rule_type: RuleType::DiffTimeout(parse_duration(duration_str)),
-            action: ActionType::SendKeys(keys),
-        }
+        Rule::new(
+            RuleType::DiffTimeout(parse_duration(duration_str)),
+            ActionType::SendKeys(keys),
+        )
     }
 
     fn parse_duration(duration_str: &str) -> Duration {
@@ -210,9 +252,9 @@ mod tests {
         let diff_timeout = DiffTimeout::new(rules.clone(), agent);
 
         // Simulate 1.5 seconds elapsed
-        if let Ok(mut last_activity) = diff_timeout.last_activity.lock() {
-            *last_activity = Instant::now() - Duration::from_millis(1500);
-        }
+        diff_timeout
+            .agent
+            .record_output(Instant::now() - Duration::from_millis(1500));
 
         let indices = diff_timeout.find_triggered_timeout_indices();
         let actions: Vec<ActionType> = indices
@@ -242,12 +284,12 @@ mod tests {
             create_timeout_rule("2s", vec!["long_timeout".to_string()]),
         ];
 
-        let diff_timeout = DiffTimeout::new(rules.clone(), agent);
+        let diff_timeout = DiffTimeout::new(rules.clone(), Arc::clone(&agent));
 
         // Simulate 2.5 seconds elapsed
-        if let Ok(mut last_activity) = diff_timeout.last_activity.lock() {
-            *last_activity = Instant::now() - Duration::from_millis(2500);
-        }
+        diff_timeout
+            .agent
+            .record_output(Instant::now() - Duration::from_millis(2500));
 
         let indices = diff_timeout.find_triggered_timeout_indices();
         let actions: Vec<ActionType> = indices
@@ -263,6 +305,11 @@ mod tests {
             actions[1],
             ActionType::SendKeys(vec!["long_timeout".to_string()])
         );
+
+        let hits = agent.get_rule_stats().snapshot();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.match_count == 1));
+        assert_eq!(hits[0].rule, "diff_timeout: 1s");
     }
 
     #[tokio::test]
@@ -284,9 +331,9 @@ mod tests {
         let diff_timeout = DiffTimeout::new(rules.clone(), agent);
 
         // First timeout trigger
-        if let Ok(mut last_activity) = diff_timeout.last_activity.lock() {
-            *last_activity = Instant::now() - Duration::from_millis(1500);
-        }
+        diff_timeout
+            .agent
+            .record_output(Instant::now() - Duration::from_millis(1500));
         let indices = diff_timeout.find_triggered_timeout_indices();
         let actions: Vec<ActionType> = indices
             .into_iter()
@@ -310,9 +357,9 @@ mod tests {
         assert_eq!(actions.len(), 0);
 
         // Second timeout trigger after reset
-        if let Ok(mut last_activity) = diff_timeout.last_activity.lock() {
-            *last_activity = Instant::now() - Duration::from_millis(1500);
-        }
+        diff_timeout
+            .agent
+            .record_output(Instant::now() - Duration::from_millis(1500));
         let indices = diff_timeout.find_triggered_timeout_indices();
         let actions: Vec<ActionType> = indices
             .into_iter()
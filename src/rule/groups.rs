@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+/// Per-agent set of active rule groups
+///
+/// Ungrouped rules are always evaluated; grouped rules only while their
+/// group is active. The set returns to its defaults when the agent goes Idle.
+#[derive(Debug, Default)]
+pub struct RuleGroups {
+    defaults: BTreeSet<String>,
+    active: RwLock<BTreeSet<String>>,
+}
+
+impl RuleGroups {
+    pub fn new(defaults: BTreeSet<String>) -> Self {
+        Self {
+            active: RwLock::new(defaults.clone()),
+            defaults,
+        }
+    }
+
+    /// Check whether a rule in the given group should be evaluated
+    pub fn is_enabled(&self, group: Option<&str>) -> bool {
+        match group {
+            None => true,
+            Some(name) => self
+                .active
+                .read()
+                .map(|active| active.contains(name))
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn activate(&self, name: &str) {
+        if let Ok(mut active) = self.active.write() {
+            active.insert(name.to_string());
+        }
+    }
+
+    pub fn deactivate(&self, name: &str) {
+        if let Ok(mut active) = self.active.write() {
+            active.remove(name);
+        }
+    }
+
+    /// Restore the default set of active groups
+    pub fn reset(&self) {
+        if let Ok(mut active) = self.active.write() {
+            *active = self.defaults.clone();
+        }
+    }
+
+    /// Currently active group names
+    pub fn active(&self) -> Vec<String> {
+        self.active
+            .read()
+            .map(|active| active.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ungrouped_rules_always_enabled() {
+        let groups = RuleGroups::default();
+        assert!(groups.is_enabled(None));
+        assert!(!groups.is_enabled(Some("commit")));
+    }
+
+    #[test]
+    fn test_activate_deactivate_and_reset() {
+        let groups = RuleGroups::new(BTreeSet::from(["base".to_string()]));
+        assert!(groups.is_enabled(Some("base")));
+
+        groups.activate("commit");
+        groups.deactivate("base");
+        assert!(groups.is_enabled(Some("commit")));
+        assert!(!groups.is_enabled(Some("base")));
+        assert_eq!(groups.active(), vec!["commit"]);
+
+        groups.reset();
+        assert_eq!(groups.active(), vec!["base"]);
+    }
+}
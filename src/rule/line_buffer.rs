@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+/// Quiet period after which an incomplete line is evaluated anyway, so
+/// prompts without a trailing newline still get matched
+pub const FLUSH_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Maximum size of the pending incomplete line before it is force-flushed
+pub const MAX_PENDING_BYTES: usize = 8 * 1024;
+
+/// Reassembles PTY output chunks into complete lines
+///
+/// Lines end at `\n` or `\r`. The trailing incomplete segment of a chunk is
+/// kept until the next chunk completes it, it grows past `MAX_PENDING_BYTES`,
+/// or no output arrives for `FLUSH_TIMEOUT`.
+#[derive(Debug)]
+pub struct LineBuffer {
+    pending: String,
+    last_data_at: Instant,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self {
+            pending: String::new(),
+            last_data_at: Instant::now(),
+        }
+    }
+
+    /// Append a chunk and return the lines it completed (blank lines are skipped)
+    pub fn push(&mut self, chunk: &str, now: Instant) -> Vec<String> {
+        self.last_data_at = now;
+        self.pending.push_str(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.find(['\n', '\r']) {
+            let line = self.pending[..end].to_string();
+            self.pending.drain(..=end);
+            if !line.trim().is_empty() {
+                lines.push(line);
+            }
+        }
+
+        if self.pending.len() > MAX_PENDING_BYTES {
+            lines.extend(self.take_pending());
+        }
+
+        lines
+    }
+
+    /// Return the incomplete line once output has been quiet for `FLUSH_TIMEOUT`
+    pub fn flush_if_idle(&mut self, now: Instant) -> Option<String> {
+        if now.duration_since(self.last_data_at) < FLUSH_TIMEOUT {
+            return None;
+        }
+        self.take_pending()
+    }
+
+    /// Return the incomplete line regardless of timing
+    pub fn take_pending(&mut self) -> Option<String> {
+        let line = std::mem::take(&mut self.pending);
+        (!line.trim().is_empty()).then_some(line)
+    }
+
+    /// Drop any incomplete line
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Replace the incomplete line with `partial`, e.g. the cursor's line on
+    /// screen after output chunks were lost
+    pub fn reset_to(&mut self, partial: &str, now: Instant) {
+        self.last_data_at = now;
+        self.pending = partial.to_string();
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_complete_lines() {
+        let mut buffer = LineBuffer::new();
+        let lines = buffer.push("one\r\ntwo\n\n  \rthree\n", Instant::now());
+        assert_eq!(lines, vec!["one", "two", "three"]);
+        assert!(buffer.take_pending().is_none());
+    }
+
+    #[test]
+    fn test_line_split_across_three_chunks() {
+        let mut buffer = LineBuffer::new();
+        let now = Instant::now();
+        assert!(buffer.push("Do you ", now).is_empty());
+        assert!(buffer.push("want to ", now).is_empty());
+        assert_eq!(
+            buffer.push("proceed?\nnext", now),
+            vec!["Do you want to proceed?"]
+        );
+        assert_eq!(buffer.take_pending().as_deref(), Some("next"));
+    }
+
+    #[test]
+    fn test_flush_if_idle() {
+        let mut buffer = LineBuffer::new();
+        let start = Instant::now();
+        assert!(buffer.push("> Continue? (y/n) ", start).is_empty());
+
+        assert!(
+            buffer
+                .flush_if_idle(start + Duration::from_millis(50))
+                .is_none()
+        );
+        assert_eq!(
+            buffer.flush_if_idle(start + FLUSH_TIMEOUT).as_deref(),
+            Some("> Continue? (y/n) ")
+        );
+        // Already flushed
+        assert!(buffer.flush_if_idle(start + FLUSH_TIMEOUT * 2).is_none());
+    }
+
+    #[test]
+    fn test_new_data_delays_flush() {
+        let mut buffer = LineBuffer::new();
+        let start = Instant::now();
+        buffer.push("partial", start);
+        buffer.push(" more", start + Duration::from_millis(150));
+
+        assert!(
+            buffer
+                .flush_if_idle(start + Duration::from_millis(250))
+                .is_none()
+        );
+        assert_eq!(
+            buffer
+                .flush_if_idle(start + Duration::from_millis(350))
+                .as_deref(),
+            Some("partial more")
+        );
+    }
+
+    #[test]
+    fn test_pending_size_cap() {
+        let mut buffer = LineBuffer::new();
+        let long = "x".repeat(MAX_PENDING_BYTES + 1);
+        let lines = buffer.push(&long, Instant::now());
+        assert_eq!(lines, vec![long]);
+        assert!(buffer.take_pending().is_none());
+    }
+}
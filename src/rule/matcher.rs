@@ -0,0 +1,306 @@
+use regex::{Regex, RegexSet};
+
+use crate::config::helper::ActionType;
+use crate::config::rules_config::{Rule, RuleType, ScreenMode};
+use crate::terminal::ansi::strip_ansi;
+
+/// A `when` rule that matched a line of output
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    /// Position of the rule in the config's rule list
+    pub rule_index: usize,
+    /// Pattern of the rule that matched
+    pub pattern: String,
+    /// Capture groups (`${0}` is the whole match); unmatched groups are `None`
+    pub captures: Vec<Option<String>>,
+    /// ANSI-stripped line the rule matched against
+    pub line: String,
+    /// Unresolved action of the rule
+    pub action: ActionType,
+}
+
+/// Agent-independent matching of output lines against `when` rules
+///
+/// Shared by the `When` monitor and the `test-rule` subcommand so both use
+/// exactly the same matching behavior.
+pub struct WhenMatcher {
+    regexes: Vec<Regex>,
+    actions: Vec<ActionType>,
+    strip_ansi: Vec<bool>,
+    rule_indices: Vec<usize>,
+    descriptions: Vec<String>,
+    groups: Vec<Option<String>>,
+    screens: Vec<ScreenMode>,
+    /// All patterns in one set, so a line is scanned once to find candidate rules
+    prefilter: Option<RegexSet>,
+    /// Whether any rule matches against the raw (ANSI-carrying) line
+    has_raw_rules: bool,
+}
+
+impl WhenMatcher {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        // Keep only When rules, remembering their position in the config
+        let mut matcher = Self {
+            regexes: Vec::new(),
+            actions: Vec::new(),
+            strip_ansi: Vec::new(),
+            rule_indices: Vec::new(),
+            descriptions: Vec::new(),
+            groups: Vec::new(),
+            screens: Vec::new(),
+            prefilter: None,
+            has_raw_rules: false,
+        };
+
+        for (index, rule) in rules.into_iter().enumerate() {
+            let description = rule.describe();
+            if let RuleType::When(regex) = rule.rule_type {
+                matcher.regexes.push(regex);
+                matcher.actions.push(rule.action);
+                matcher.strip_ansi.push(rule.strip_ansi);
+                matcher.rule_indices.push(index);
+                matcher.descriptions.push(description);
+                matcher.groups.push(rule.group);
+                matcher.screens.push(rule.screen);
+            }
+        }
+
+        matcher.has_raw_rules = matcher.strip_ansi.iter().any(|strip| !strip);
+        matcher.prefilter = match RegexSet::new(matcher.regexes.iter().map(Regex::as_str)) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                tracing::warn!("Rule pre-filter disabled, matching rules one by one: {}", e);
+                None
+            }
+        };
+
+        matcher
+    }
+
+    /// Config indices and descriptions of the `when` rules, in priority order
+    pub fn rules(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.rule_indices
+            .iter()
+            .copied()
+            .zip(self.descriptions.iter().map(String::as_str))
+    }
+
+    /// Find the first rule matching a raw terminal line, skipping rules whose
+    /// group is not enabled or that don't apply on the screen the line was
+    /// shown on (`None` when unknown, which skips no rule). Each rule matches
+    /// against either the raw line or its ANSI-stripped form.
+    pub fn find_match(
+        &self,
+        raw: &str,
+        alternate_screen: Option<bool>,
+        is_enabled: impl Fn(Option<&str>) -> bool,
+    ) -> Option<RuleMatch> {
+        let clean = strip_ansi(raw);
+        let enabled = |i: &usize| {
+            alternate_screen.is_none_or(|alternate| self.screens[*i].matches(alternate))
+                && is_enabled(self.groups[*i].as_deref())
+        };
+
+        self.candidates(raw, &clean)
+            .filter(enabled)
+            .find_map(|i| self.capture(i, raw, &clean))
+    }
+
+    /// Rules that can match the line, in rule order: the ones the pre-filter
+    /// reports, or every rule when there is no pre-filter
+    fn candidates(&self, raw: &str, clean: &str) -> impl Iterator<Item = usize> {
+        // Ask the set which rules can match, so only those are captured
+        let hits = self.prefilter.as_ref().map(|prefilter| {
+            let clean_hits = prefilter.matches(clean);
+            // Raw rules need a second scan only when stripping changed the line
+            let raw_hits = (self.has_raw_rules && clean != raw).then(|| prefilter.matches(raw));
+            (clean_hits, raw_hits)
+        });
+
+        (0..self.regexes.len()).filter(move |&i| match &hits {
+            None => true,
+            Some((clean_hits, raw_hits)) => match (raw_hits, self.strip_ansi[i]) {
+                (Some(raw_hits), false) => raw_hits.matched(i),
+                _ => clean_hits.matched(i),
+            },
+        })
+    }
+
+    /// Run rule `i` against the raw or clean line and collect its captures
+    fn capture(&self, i: usize, raw: &str, clean: &str) -> Option<RuleMatch> {
+        let regex = &self.regexes[i];
+        let text = if self.strip_ansi[i] { clean } else { raw };
+        let captures = regex.captures(text)?;
+
+        Some(RuleMatch {
+            rule_index: self.rule_indices[i],
+            pattern: regex.as_str().to_string(),
+            captures: captures
+                .iter()
+                .map(|group| group.map(|m| m.as_str().to_string()))
+                .collect(),
+            line: clean.to_string(),
+            action: self.actions[i].clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::rules_config::RuleType;
+    use std::time::Duration;
+
+    fn rule(rule_type: RuleType, keys: &[&str]) -> Rule {
+        Rule::new(
+            rule_type,
+            ActionType::SendKeys(keys.iter().map(|k| k.to_string()).collect()),
+        )
+    }
+
+    #[test]
+    fn test_find_match_keeps_config_index() {
+        let matcher = WhenMatcher::new(vec![
+            rule(RuleType::DiffTimeout(Duration::from_secs(5)), &["t"]),
+            rule(
+                RuleType::When(Regex::new(r"issue (\d+)").unwrap()),
+                &["${1}"],
+            ),
+        ]);
+
+        let found = matcher
+            .find_match("\x1b[1missue 7\x1b[0m", None, |_| true)
+            .unwrap();
+        assert_eq!(found.rule_index, 1);
+        assert_eq!(found.pattern, r"issue (\d+)");
+        assert_eq!(
+            found.captures,
+            vec![Some("issue 7".to_string()), Some("7".to_string())]
+        );
+        assert_eq!(found.line, "issue 7");
+        assert_eq!(
+            matcher.rules().collect::<Vec<_>>(),
+            vec![(1, r"when: issue (\d+)")]
+        );
+    }
+
+    #[test]
+    fn test_find_match_none() {
+        let matcher = WhenMatcher::new(vec![rule(
+            RuleType::When(Regex::new("resume").unwrap()),
+            &[],
+        )]);
+        assert!(matcher.find_match("nothing here", None, |_| true).is_none());
+    }
+
+    #[test]
+    fn test_find_match_respects_screen_mode() {
+        let mut menu = rule(RuleType::When(Regex::new("Continue").unwrap()), &["\r"]);
+        menu.screen = ScreenMode::Alternate;
+        let mut prompt = rule(RuleType::When(Regex::new("Continue").unwrap()), &["y"]);
+        prompt.screen = ScreenMode::Primary;
+        let matcher = WhenMatcher::new(vec![menu, prompt]);
+
+        let on = |alternate_screen| {
+            matcher
+                .find_match("Continue? [y/n]", alternate_screen, |_| true)
+                .map(|found| found.rule_index)
+        };
+        assert_eq!(on(Some(true)), Some(0));
+        assert_eq!(on(Some(false)), Some(1));
+        // Without screen information the first rule wins
+        assert_eq!(on(None), Some(0));
+    }
+
+    /// Reference implementation: try every rule in order without the pre-filter
+    fn find_match_naive(matcher: &WhenMatcher, raw: &str) -> Option<RuleMatch> {
+        let clean = strip_ansi(raw);
+        (0..matcher.regexes.len()).find_map(|i| matcher.capture(i, raw, &clean))
+    }
+
+    fn mixed_rules() -> Vec<Rule> {
+        let mut raw_rule = rule(
+            RuleType::When(Regex::new(r"\x1b\[31m(\w+)").unwrap()),
+            &["raw ${1}"],
+        );
+        raw_rule.strip_ansi = false;
+
+        vec![
+            rule(RuleType::When(Regex::new(r"^exit$").unwrap()), &["exit"]),
+            raw_rule,
+            rule(RuleType::DiffTimeout(Duration::from_secs(1)), &["t"]),
+            rule(
+                RuleType::When(Regex::new(r"issue (\d+)").unwrap()),
+                &["${1}"],
+            ),
+            rule(
+                RuleType::When(Regex::new(r"(\w+) (\d+)").unwrap()),
+                &["${2}"],
+            ),
+            rule(
+                RuleType::When(Regex::new(r"こんにちは|Hello").unwrap()),
+                &["q"],
+            ),
+            rule(RuleType::When(Regex::new(r"(a)?b").unwrap()), &["${1}"]),
+        ]
+    }
+
+    #[test]
+    fn test_prefilter_matches_naive_loop() {
+        let matcher = WhenMatcher::new(mixed_rules());
+        assert!(matcher.prefilter.is_some());
+
+        let lines = [
+            "exit",
+            " exit",
+            "issue 42",
+            "\x1b[31missue 42\x1b[0m",
+            "\x1b[31mERROR\x1b[0m",
+            "ERROR",
+            "page 3",
+            "⏺ こんにちは！",
+            "b",
+            "ab",
+            "",
+            "no match at all",
+        ];
+        for line in lines {
+            assert_eq!(
+                matcher.find_match(line, None, |_| true),
+                find_match_naive(&matcher, line),
+                "mismatch for {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefilter_fast_path_150_rules() {
+        let rules: Vec<Rule> = (0..150)
+            .map(|i| {
+                rule(
+                    RuleType::When(Regex::new(&format!(r"unique_pattern_{}\s+(\d+)", i)).unwrap()),
+                    &["${1}"],
+                )
+            })
+            .collect();
+        let matcher = WhenMatcher::new(rules);
+
+        // Lines no rule matches run none of the 150 regexes
+        for i in 0..2_000 {
+            let line = format!("streaming output line {} with nothing interesting", i);
+            assert_eq!(matcher.candidates(&line, &line).count(), 0);
+            assert!(matcher.find_match(&line, None, |_| true).is_none());
+        }
+
+        // A matching line runs only the rule that matches
+        let line = "unique_pattern_149 7";
+        assert_eq!(
+            matcher.candidates(line, line).collect::<Vec<_>>(),
+            vec![149]
+        );
+        let found = matcher.find_match(line, None, |_| true).unwrap();
+        assert_eq!(found.rule_index, 149);
+        assert_eq!(found.captures[1].as_deref(), Some("7"));
+    }
+}
@@ -1,12 +1,24 @@
+pub mod debounce;
+pub mod delayed;
 pub mod diff_timeout;
+pub mod groups;
+pub mod line_buffer;
+pub mod matcher;
+pub mod rate_limit;
+pub mod stats;
+pub mod tester;
+pub mod title;
 pub mod when;
 
 // Re-export for convenience
 pub use diff_timeout::DiffTimeout;
+pub use stats::{RuleHit, RuleStats};
+pub use title::Title;
 pub use when::When;
 
 use crate::agent::Agent;
 use crate::config::helper::ActionType;
+use crate::terminal::secret;
 use anyhow::Result;
 use tokio::sync::broadcast;
 // use tokio::time::Duration as TokioDuration; // Removed: sleep no longer used
@@ -18,15 +30,28 @@ pub trait RuleProcessor {
 
 /// Execute an action for rules with delay between keys
 pub async fn execute_rule_action(action: &ActionType, agent: &Agent, context: &str) -> Result<()> {
-    let ActionType::SendKeys(keys) = action;
+    let keys = match action {
+        ActionType::SendKeys(keys) => keys,
+        _ => return crate::action::execute_non_key_action(action, agent, context).await,
+    };
 
     if keys.is_empty() {
         tracing::debug!("{}: No keys to send", context);
         return Ok(());
     }
 
+    if agent.is_dry_run() {
+        tracing::info!("{}: WOULD SEND: {:?}", context, secret::redact_keys(keys));
+        return Ok(());
+    }
+
+    if let Err(e) = agent.take_command_quota() {
+        tracing::debug!("{}: {:#}", context, e);
+        return Ok(());
+    }
+
     tracing::info!("{}: Sending {} keys", context, keys.len());
-    tracing::debug!("{}: Keys: {:?}", context, keys);
+    tracing::debug!("{}: Keys: {:?}", context, secret::redact_keys(keys));
 
     for (i, key) in keys.iter().enumerate() {
         agent.send_keys(key).await?;
@@ -164,6 +189,184 @@ mod tests {
         assert!(result.is_ok(), "Function should work with empty context");
     }
 
+    #[tokio::test]
+    async fn test_execute_rule_action_dry_run_sends_nothing() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.dry_run = true;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let action = ActionType::SendKeys(vec!["echo".to_string(), "\r".to_string()]);
+        let result = execute_rule_action(&action, &agent, "Dry run").await;
+
+        assert!(result.is_ok());
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let action = ActionType::AppendFile {
+            path: path.to_str().unwrap().to_string(),
+            template: "line".to_string(),
+        };
+        execute_rule_action(&action, &agent, "Dry run")
+            .await
+            .unwrap();
+        assert!(!path.exists());
+    }
+
+    /// Everything logged while `run` runs, at every level
+    async fn capture_logs(run: impl Future<Output = ()>) -> String {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        // The test runtime runs every task on this thread
+        let _guard = tracing::subscriber::set_default(subscriber);
+        run.await;
+        String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_secret_keys_are_sent_but_never_logged() {
+        use crate::config::helper::{ActionParams, parse_action};
+        use crate::template::{TemplateContext, resolve_action};
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let params = ActionParams {
+            secret: true,
+            ..ActionParams::default()
+        };
+        let keys = [
+            "${env.CARGO_PKG_NAME}-rule-secret-5e0b".to_string(),
+            "\r".to_string(),
+        ];
+        let action = parse_action(&Some("send_keys".to_string()), &keys, &params).unwrap();
+        let action = resolve_action(&action, &TemplateContext::for_agent(&agent));
+        let secret = format!("{}-rule-secret-5e0b", env!("CARGO_PKG_NAME"));
+
+        config.dry_run = true;
+        let dry_run_agent = Agent::new_with_process(1, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+
+        let logs = capture_logs(async {
+            execute_rule_action(&action, &agent, "Login").await.unwrap();
+            execute_rule_action(&action, &dry_run_agent, "Login")
+                .await
+                .unwrap();
+            tracing::info!("Resolved: {}", action.describe());
+        })
+        .await;
+
+        assert_eq!(mock_pty.get_sent_inputs(), [secret.as_str(), "\r"]);
+        assert!(logs.contains(r#"Login: Keys: ["***", "***"]"#), "{}", logs);
+        assert!(logs.contains(r#"Login: WOULD SEND: ["***", "***"]"#));
+        assert!(logs.contains(r#"Resolved: send_keys ["***", "***"]"#));
+        assert!(!logs.contains("rule-secret-5e0b"), "{}", logs);
+    }
+
+    #[tokio::test]
+    async fn test_execute_rule_action_respects_command_quota() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.max_commands_per_hour = Some(2);
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        for key in ["a", "b", "c"] {
+            let action = ActionType::SendKeys(vec![key.to_string()]);
+            execute_rule_action(&action, &agent, "Quota").await.unwrap();
+        }
+        assert_eq!(mock_pty.get_sent_inputs(), ["a", "b"]);
+        assert_eq!(agent.get_command_usage().unwrap().used, 2);
+
+        // Keys typed by hand don't count
+        agent.send_keys("d").await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), ["a", "b", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_rule_action_signal() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+        use nix::sys::signal::Signal;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let action = ActionType::Signal(Signal::SIGINT);
+        execute_rule_action(&action, &agent, "Signal")
+            .await
+            .unwrap();
+        assert_eq!(mock_pty.get_sent_signals(), [Signal::SIGINT]);
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        config.dry_run = true;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        execute_rule_action(&action, &agent, "Dry run")
+            .await
+            .unwrap();
+        assert!(mock_pty.get_sent_signals().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_rule_action_append_file() {
+        let agent = create_test_agent().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent-0.log");
+
+        let action = ActionType::AppendFile {
+            path: path.to_str().unwrap().to_string(),
+            template: "error: disk full".to_string(),
+        };
+        execute_rule_action(&action, &agent, "Append test")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "error: disk full\n"
+        );
+    }
+
     #[tokio::test]
     async fn test_execute_rule_action_with_mock_pty() {
         use crate::terminal::pty_process_trait::{MockPtyProcess, PtyProcessTrait};
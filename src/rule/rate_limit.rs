@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+/// Length of the fixed rate limiting window
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Fixed-window limiter for rule actions fired on a single agent
+#[derive(Debug)]
+pub struct ActionRateLimiter {
+    /// Maximum actions per window (0 disables limiting)
+    limit: u32,
+    window_start: Instant,
+    count: u32,
+    warned: bool,
+}
+
+impl ActionRateLimiter {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            window_start: Instant::now(),
+            count: 0,
+            warned: false,
+        }
+    }
+
+    /// Try to take a slot for one action. Returns false if the action must be dropped.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+
+        if now.duration_since(self.window_start) >= WINDOW {
+            self.window_start = now;
+            self.count = 0;
+            self.warned = false;
+        }
+
+        if self.count < self.limit {
+            self.count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true the first time it's called in a suppressed window, so the
+    /// caller logs a single warning per window
+    pub fn should_warn(&mut self) -> bool {
+        !std::mem::replace(&mut self.warned, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_allows_up_to_limit() {
+        let mut limiter = ActionRateLimiter::new(3);
+        let now = Instant::now();
+        assert!(limiter.try_acquire(now));
+        assert!(limiter.try_acquire(now));
+        assert!(limiter.try_acquire(now));
+        assert!(!limiter.try_acquire(now));
+    }
+
+    #[test]
+    fn test_limiter_resets_after_window() {
+        let mut limiter = ActionRateLimiter::new(1);
+        let now = Instant::now();
+        assert!(limiter.try_acquire(now));
+        assert!(!limiter.try_acquire(now + Duration::from_secs(30)));
+        assert!(limiter.try_acquire(now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_limiter_zero_disables() {
+        let mut limiter = ActionRateLimiter::new(0);
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert!(limiter.try_acquire(now));
+        }
+    }
+
+    #[test]
+    fn test_limiter_warns_once_per_window() {
+        let mut limiter = ActionRateLimiter::new(1);
+        let now = Instant::now();
+        limiter.try_acquire(now);
+        assert!(!limiter.try_acquire(now));
+        assert!(limiter.should_warn());
+        assert!(!limiter.should_warn());
+
+        // A new window re-arms the warning
+        assert!(limiter.try_acquire(now + Duration::from_secs(60)));
+        assert!(!limiter.try_acquire(now + Duration::from_secs(60)));
+        assert!(limiter.should_warn());
+    }
+}
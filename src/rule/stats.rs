@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metrics;
+
+/// Maximum number of characters kept from the last matched text
+const MAX_MATCHED_TEXT_CHARS: usize = 100;
+
+/// Hit counters for a single rule on a single agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleHit {
+    /// Position of the rule in the config's rule list
+    pub rule_index: usize,
+    /// Human readable description of the rule (e.g. "when: ^exit$")
+    pub rule: String,
+    pub match_count: u64,
+    /// Unix timestamp (seconds) of the last match
+    pub last_matched_at: Option<u64>,
+    pub last_matched_text: Option<String>,
+}
+
+/// Per-agent rule hit statistics shared between rule monitors
+#[derive(Debug, Default)]
+pub struct RuleStats {
+    /// Agent the rules run for, labeling the rule match metric
+    agent_id: String,
+    hits: Mutex<BTreeMap<usize, RuleHit>>,
+    dropped_actions: AtomicU64,
+    dropped_output: AtomicU64,
+}
+
+impl RuleStats {
+    /// Statistics of the rules of the agent `agent_id`
+    pub fn for_agent(agent_id: &str) -> Self {
+        Self {
+            agent_id: agent_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Register a rule so it shows up in statistics even before it fires
+    pub fn register(&self, rule_index: usize, rule: String) {
+        if let Ok(mut hits) = self.hits.lock() {
+            hits.entry(rule_index).or_insert_with(|| RuleHit {
+                rule_index,
+                rule,
+                match_count: 0,
+                last_matched_at: None,
+                last_matched_text: None,
+            });
+        }
+    }
+
+    /// Record a match for a registered rule
+    pub fn record(&self, rule_index: usize, matched_text: &str) {
+        let Ok(mut hits) = self.hits.lock() else {
+            return;
+        };
+        let Some(hit) = hits.get_mut(&rule_index) else {
+            tracing::debug!("Ignoring hit for unregistered rule #{}", rule_index);
+            return;
+        };
+
+        metrics::metrics().record_rule_match(&self.agent_id, &hit.rule);
+        hit.match_count += 1;
+        hit.last_matched_at = Some(unix_now());
+        hit.last_matched_text = Some(matched_text.chars().take(MAX_MATCHED_TEXT_CHARS).collect());
+    }
+
+    /// Record a rule action that was suppressed by the rate limit
+    pub fn record_dropped_action(&self) {
+        self.dropped_actions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of rule actions suppressed by the rate limit
+    pub fn dropped_actions(&self) -> u64 {
+        self.dropped_actions.load(Ordering::Relaxed)
+    }
+
+    /// Record `chunks` of output a rule monitor missed by falling behind
+    pub fn record_dropped_output(&self, chunks: u64) {
+        metrics::metrics().record_dropped_output(&self.agent_id, chunks);
+        self.dropped_output.fetch_add(chunks, Ordering::Relaxed);
+    }
+
+    /// Number of output chunks rule monitors missed by falling behind
+    pub fn dropped_output(&self) -> u64 {
+        self.dropped_output.load(Ordering::Relaxed)
+    }
+
+    /// Get a copy of all rule hits ordered by rule index
+    pub fn snapshot(&self) -> Vec<RuleHit> {
+        self.hits
+            .lock()
+            .map(|hits| hits.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Current time as Unix seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_creates_zero_hit_entry() {
+        let stats = RuleStats::default();
+        stats.register(2, "when: foo".to_string());
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].rule_index, 2);
+        assert_eq!(snapshot[0].match_count, 0);
+        assert!(snapshot[0].last_matched_at.is_none());
+    }
+
+    #[test]
+    fn test_record_updates_counters() {
+        let stats = RuleStats::default();
+        stats.register(0, "when: foo".to_string());
+        stats.record(0, "first foo");
+        stats.record(0, "second foo");
+
+        let hit = &stats.snapshot()[0];
+        assert_eq!(hit.match_count, 2);
+        assert_eq!(hit.last_matched_text.as_deref(), Some("second foo"));
+        assert!(hit.last_matched_at.is_some());
+    }
+
+    #[test]
+    fn test_record_truncates_text() {
+        let stats = RuleStats::default();
+        stats.register(0, "when: x".to_string());
+        stats.record(0, &"x".repeat(250));
+
+        let text = stats.snapshot()[0].last_matched_text.clone().unwrap();
+        assert_eq!(text.chars().count(), MAX_MATCHED_TEXT_CHARS);
+    }
+
+    #[test]
+    fn test_dropped_actions_counter() {
+        let stats = RuleStats::default();
+        assert_eq!(stats.dropped_actions(), 0);
+        stats.record_dropped_action();
+        stats.record_dropped_action();
+        assert_eq!(stats.dropped_actions(), 2);
+
+        stats.record_dropped_output(5);
+        assert_eq!(stats.dropped_output(), 5);
+    }
+
+    #[test]
+    fn test_record_unregistered_rule_is_ignored() {
+        let stats = RuleStats::default();
+        stats.record(5, "text");
+        assert!(stats.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::config::helper::ActionType;
+use crate::config::rules_config::Rule;
+use crate::rule::groups::RuleGroups;
+use crate::rule::line_buffer::LineBuffer;
+use crate::rule::matcher::{RuleMatch, WhenMatcher};
+use crate::template::{TemplateContext, resolve_action};
+use std::time::Instant;
+
+/// Agent id/index used to resolve agent variables when testing rules offline
+const SIMULATED_AGENT_INDEX: usize = 0;
+
+/// A rule match found while replaying sample output
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleTestHit {
+    /// 1-based number of the chunk the line arrived in
+    pub chunk: usize,
+    pub rule_match: RuleMatch,
+    /// Action with all placeholders resolved
+    pub resolved_action: ActionType,
+}
+
+/// Replay sample output through the same line buffering and matching path the
+/// `When` monitor uses. With `chunk_size`, the text is fed in pieces of at most
+/// that many bytes, as if it had arrived from the PTY in separate reads. A
+/// trailing line without newline is evaluated as if output then went quiet.
+/// Hits from that final flush report the last chunk number. Matched
+/// activate_group / deactivate_group actions update `groups` as they would
+/// on a live agent.
+pub fn test_rules(
+    rules: Vec<Rule>,
+    groups: &RuleGroups,
+    text: &str,
+    chunk_size: Option<usize>,
+) -> Vec<RuleTestHit> {
+    let matcher = WhenMatcher::new(rules);
+    let chunks = match chunk_size {
+        Some(size) => split_chunks(text, size),
+        None => vec![text],
+    };
+
+    let mut buffer = LineBuffer::new();
+    let now = Instant::now();
+    let mut lines = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        lines.extend(
+            buffer
+                .push(chunk, now)
+                .into_iter()
+                .map(|line| (i + 1, line)),
+        );
+    }
+    lines.extend(buffer.take_pending().map(|line| (chunks.len(), line)));
+
+    let mut hits = Vec::new();
+    for (chunk, line) in lines {
+        let Some(rule_match) = matcher.find_match(&line, None, |group| groups.is_enabled(group))
+        else {
+            continue;
+        };
+
+        let context = TemplateContext::for_agent_id(
+            format!("agent-{}", SIMULATED_AGENT_INDEX),
+            SIMULATED_AGENT_INDEX,
+        )
+        .with_captures(rule_match.captures.clone())
+        .with_rule_pattern(&rule_match.pattern);
+        let resolved_action = resolve_action(&rule_match.action, &context);
+
+        match &resolved_action {
+            ActionType::ActivateGroup(group) => groups.activate(group),
+            ActionType::DeactivateGroup(group) => groups.deactivate(group),
+            _ => {}
+        }
+
+        hits.push(RuleTestHit {
+            chunk,
+            rule_match,
+            resolved_action,
+        });
+    }
+
+    hits
+}
+
+/// Split text into chunks of at most `size` bytes without breaking UTF-8 characters
+pub fn split_chunks(text: &str, size: usize) -> Vec<&str> {
+    let size = size.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    chunks
+}
+
+/// Format test results for display
+pub fn format_hits(hits: &[RuleTestHit]) -> String {
+    if hits.is_empty() {
+        return "No rules matched\n".to_string();
+    }
+
+    let mut output = String::new();
+    for hit in hits {
+        let rule_match = &hit.rule_match;
+        output.push_str(&format!(
+            "chunk {}: rule #{} ({}) matched {:?}\n",
+            hit.chunk, rule_match.rule_index, rule_match.pattern, rule_match.line
+        ));
+        for (group, value) in rule_match.captures.iter().enumerate() {
+            if let Some(value) = value {
+                output.push_str(&format!("    ${{{}}} = {:?}\n", group, value));
+            }
+        }
+        output.push_str(&format!("    action: {}\n", hit.resolved_action.describe()));
+    }
+    output.push_str(&format!("{} match(es)\n", hits.len()));
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::rules_config::RuleType;
+    use regex::Regex;
+
+    fn rules() -> Vec<Rule> {
+        vec![Rule::new(
+            RuleType::When(Regex::new(r"issue (\d+)").unwrap()),
+            ActionType::SendKeys(vec!["open ${1} on ${agent_id}".to_string()]),
+        )]
+    }
+
+    #[test]
+    fn test_rules_whole_text() {
+        let hits = test_rules(
+            rules(),
+            &RuleGroups::default(),
+            "start\n\x1b[32missue 12\x1b[0m\nend\n",
+            None,
+        );
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk, 1);
+        assert_eq!(hits[0].rule_match.line, "issue 12");
+        assert_eq!(
+            hits[0].resolved_action,
+            ActionType::SendKeys(vec!["open 12 on agent-0".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_rules_reassembles_chunked_lines() {
+        // "issue 12" is cut into "iss" / "ue 12" and reassembled by the line buffer
+        let hits = test_rules(rules(), &RuleGroups::default(), "xxissue 12", Some(5));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rule_match.captures[1].as_deref(), Some("12"));
+
+        let hits = test_rules(
+            rules(),
+            &RuleGroups::default(),
+            "issue 12\nissue 3",
+            Some(9),
+        );
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].chunk, 2);
+    }
+
+    #[test]
+    fn test_rules_follow_group_activation() {
+        let mut grouped = rules();
+        grouped[0].group = Some("commit".to_string());
+        grouped.push(Rule::new(
+            RuleType::When(Regex::new("All tests passed").unwrap()),
+            ActionType::ActivateGroup("commit".to_string()),
+        ));
+
+        let groups = RuleGroups::default();
+        let hits = test_rules(
+            grouped,
+            &groups,
+            "issue 1\nAll tests passed\nissue 2\n",
+            None,
+        );
+        let lines: Vec<&str> = hits.iter().map(|h| h.rule_match.line.as_str()).collect();
+        assert_eq!(lines, vec!["All tests passed", "issue 2"]);
+        assert_eq!(groups.active(), vec!["commit"]);
+    }
+
+    #[test]
+    fn test_split_chunks_respects_char_boundaries() {
+        assert_eq!(split_chunks("abcde", 2), vec!["ab", "cd", "e"]);
+        assert_eq!(split_chunks("こんにちは", 4), vec!["こん", "にち", "は"]);
+        assert!(split_chunks("", 3).is_empty());
+    }
+
+    #[test]
+    fn test_format_hits() {
+        assert_eq!(format_hits(&[]), "No rules matched\n");
+
+        let output = format_hits(&test_rules(
+            rules(),
+            &RuleGroups::default(),
+            "issue 12",
+            None,
+        ));
+        assert!(output.contains(r"rule #0 (issue (\d+)) matched"));
+        assert!(output.contains(r#"${1} = "12""#));
+        assert!(output.contains(r#"action: send_keys ["open 12 on agent-0"]"#));
+        assert!(output.ends_with("1 match(es)\n"));
+    }
+}
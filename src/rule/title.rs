@@ -0,0 +1,192 @@
+use anyhow::Result;
+use regex::Regex;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::agent::Agent;
+use crate::config::helper::ActionType;
+use crate::config::rules_config::{Rule, RuleType, ScreenMode};
+use crate::rule::{RuleStats, execute_rule_action};
+use crate::template::{TemplateContext, resolve_action};
+
+/// Title condition processor matching the window title as it changes
+pub struct Title {
+    regexes: Vec<Regex>,
+    actions: Vec<ActionType>,
+    rule_indices: Vec<usize>,
+    groups: Vec<Option<String>>,
+    screens: Vec<ScreenMode>,
+    stats: Arc<RuleStats>,
+    agent: Arc<Agent>,
+}
+
+impl Title {
+    pub fn new(rules: Vec<Rule>, agent: Arc<Agent>) -> Self {
+        let stats = agent.get_rule_stats();
+        let mut title = Self {
+            regexes: Vec::new(),
+            actions: Vec::new(),
+            rule_indices: Vec::new(),
+            groups: Vec::new(),
+            screens: Vec::new(),
+            stats,
+            agent,
+        };
+
+        // Keep only Title rules, remembering their position in the config
+        for (index, rule) in rules.into_iter().enumerate() {
+            let description = rule.describe();
+            if let RuleType::Title(regex) = rule.rule_type {
+                title.stats.register(index, description);
+                title.regexes.push(regex);
+                title.actions.push(rule.action);
+                title.rule_indices.push(index);
+                title.groups.push(rule.group);
+                title.screens.push(rule.screen);
+            }
+        }
+
+        title
+    }
+
+    /// Match each new title until the agent is dropped. Titles changing
+    /// faster than they are read (e.g. spinners) are only seen as the last.
+    pub async fn start_monitoring(&self, mut titles: watch::Receiver<String>) -> Result<()> {
+        let mut previous = titles.borrow_and_update().clone();
+
+        while titles.changed().await.is_ok() {
+            let title = titles.borrow_and_update().clone();
+            if self.agent.is_active()
+                && let Some(action) = self.decide(&previous, &title)
+            {
+                self.run_action(&action).await;
+            }
+            previous = title;
+        }
+
+        Ok(())
+    }
+
+    /// Find the first enabled rule matching `title` and resolve its action.
+    /// A rule that already matched the `previous` title doesn't fire again,
+    /// so an animated title can't repeat an action on every frame.
+    fn decide(&self, previous: &str, title: &str) -> Option<ActionType> {
+        let groups = self.agent.get_rule_groups();
+        let alternate_screen = self.agent.is_alternate_screen();
+        let (i, captures) = (0..self.regexes.len())
+            .filter(|&i| self.screens[i].matches(alternate_screen))
+            .filter(|&i| groups.is_enabled(self.groups[i].as_deref()))
+            .find_map(|i| {
+                self.regexes[i]
+                    .captures(title)
+                    .map(|captures| (i, captures))
+            })?;
+        if self.regexes[i].is_match(previous) {
+            return None;
+        }
+
+        self.stats.record(self.rule_indices[i], title);
+
+        let context = TemplateContext::for_agent(&self.agent)
+            .with_captures(
+                captures
+                    .iter()
+                    .map(|group| group.map(|m| m.as_str().to_string()))
+                    .collect(),
+            )
+            .with_rule_pattern(self.regexes[i].as_str());
+        Some(resolve_action(&self.actions[i], &context))
+    }
+
+    async fn run_action(&self, action: &ActionType) {
+        if self.agent.are_rules_paused() {
+            tracing::info!(
+                "⏸️ Agent {} rules paused, skipped title action: {}",
+                self.agent.get_id(),
+                action.describe()
+            );
+            return;
+        }
+        if let Err(e) = execute_rule_action(action, &self.agent, "🪟 Title rule action").await {
+            tracing::error!("❌ Error executing title rule action: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentStatus;
+    use crate::config::Config;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use std::time::Duration;
+
+    fn title_rule(pattern: &str, keys: &[&str]) -> Rule {
+        Rule::new(
+            RuleType::Title(Regex::new(pattern).unwrap()),
+            ActionType::SendKeys(keys.iter().map(|key| key.to_string()).collect()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_title_rule_fires_when_title_starts_matching() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+            .await
+            .unwrap();
+        agent.set_status(AgentStatus::Active);
+
+        // Output rules leave the title alone
+        let rules = vec![
+            Rule {
+                rule_type: RuleType::When(Regex::new("Approve").unwrap()),
+                ..title_rule("", &["n"])
+            },
+            title_rule(r"^Approve (\w+)\?$", &["y ${1}"]),
+        ];
+        let title = Arc::new(Title::new(rules, Arc::clone(&agent)));
+        let monitor = tokio::spawn({
+            let title = Arc::clone(&title);
+            let titles = agent.subscribe_title();
+            async move { title.start_monitoring(titles).await }
+        });
+
+        let settle = || tokio::time::sleep(Duration::from_millis(50));
+        for set in [
+            "✳ Working",
+            "Approve deploy?",
+            "Approve deploy?",
+            "✳ Working",
+        ] {
+            mock.set_title(set);
+            settle().await;
+        }
+        mock.set_title("Approve rollback?");
+        settle().await;
+
+        assert_eq!(agent.get_title(), "Approve rollback?");
+        assert_eq!(mock.get_sent_inputs(), ["y deploy", "y rollback"]);
+        let hits = agent.get_rule_stats().snapshot();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rule_index, 1);
+        assert_eq!(hits[0].match_count, 2);
+        monitor.abort();
+    }
+
+    #[tokio::test]
+    async fn test_title_rule_skips_matching_title_again() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+        let title = Title::new(vec![title_rule("Thinking", &["\r"])], agent);
+
+        assert!(title.decide("", "✻ Thinking").is_some());
+        // Spinner frames of the same title
+        assert!(title.decide("✻ Thinking", "✽ Thinking").is_none());
+        assert!(title.decide("✽ Thinking", "Done").is_none());
+    }
+}
@@ -1,43 +1,69 @@
 use anyhow::Result;
-use regex::Regex;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::sync::broadcast;
 
 use crate::agent::Agent;
 use crate::config::helper::ActionType;
-use crate::config::rules_config::{Rule, RuleType};
-use crate::rule::{RuleProcessor, execute_rule_action};
+use crate::config::rules_config::{Rule, RuleDelay};
+use crate::rule::debounce::OutputDebounce;
+use crate::rule::delayed::{DelayedActions, PendingAction};
+use crate::rule::line_buffer::LineBuffer;
+use crate::rule::matcher::WhenMatcher;
+use crate::rule::rate_limit::ActionRateLimiter;
+use crate::rule::{RuleProcessor, RuleStats, execute_rule_action};
+use crate::template::{TemplateContext, resolve_action};
+use crate::terminal::ansi::strip_ansi;
 
 /// Delay between PTY output checks to prevent busy waiting
 const MONITORING_INTERVAL_MS: u64 = 10;
 
 /// When condition processor for PTY output pattern matching
 pub struct When {
-    regexes: Vec<Regex>,
-    actions: Vec<ActionType>,
+    matcher: WhenMatcher,
+    /// Grace periods of delayed rules, by config rule index
+    delays: HashMap<usize, RuleDelay>,
+    delayed: Mutex<DelayedActions>,
+    stats: Arc<RuleStats>,
+    rate_limiter: Mutex<ActionRateLimiter>,
     agent: Arc<Agent>,
 }
 
 impl RuleProcessor for When {
     async fn start_monitoring(&self, mut receiver: broadcast::Receiver<String>) -> Result<()> {
+        // Chunks of the current burst of output, not yet looked at
+        let settings = &self.agent.get_config().rules;
+        let mut debounce = OutputDebounce::new(settings.debounce(), settings.debounce_max_bytes);
+        // Partial line carried over between batches
+        let mut buffer = LineBuffer::new();
+
         loop {
-            while let Ok(pty_output) = receiver.try_recv() {
-                if self.agent.is_active().await {
-                    let lines = self.get_normalized_lines(&pty_output);
-
-                    // Check each line for pattern matching
-                    for line in lines {
-                        let action = self.decide_action(&line);
-
-                        if !matches!(action, ActionType::SendKeys(ref keys) if keys.is_empty()) {
-                            if let Err(e) =
-                                execute_rule_action(&action, &self.agent, "Rule action").await
-                            {
-                                tracing::error!("Error executing rule action: {}", e);
-                            }
-                        }
-                    }
-                }
+            if !self
+                .drain_output(&mut receiver, &mut debounce, &mut buffer)
+                .await
+            {
+                tracing::info!(
+                    "Agent {} when monitor output closed, stopping",
+                    self.agent.get_id()
+                );
+                return Ok(());
+            }
+
+            if let Some(batch) = debounce.flush_if_quiet(Instant::now()) {
+                self.process_batch(&batch, &mut buffer).await;
+            }
+
+            // Evaluate prompts that never got a trailing newline
+            if let Some(line) = buffer.flush_if_idle(Instant::now()) {
+                self.process_line(&line).await;
+            }
+
+            // Run delayed actions whose grace period passed; drop them once Idle
+            if self.agent.is_active() {
+                self.fire_due_actions(Instant::now()).await;
+            } else {
+                self.drop_delayed_actions();
             }
 
             // Small delay to prevent busy waiting
@@ -48,69 +74,247 @@ impl RuleProcessor for When {
 
 impl When {
     pub fn new(rules: Vec<Rule>, agent: Arc<Agent>) -> Self {
-        // Filter to only keep When rules and extract regexes and actions
-        let when_rules: Vec<Rule> = rules
-            .into_iter()
-            .filter(|rule| matches!(rule.rule_type, RuleType::When(_)))
-            .collect();
-
-        let regexes: Vec<Regex> = when_rules
+        let delays = rules
             .iter()
-            .map(|rule| {
-                if let RuleType::When(regex) = &rule.rule_type {
-                    regex.clone()
-                } else {
-                    panic!("Only When rules should be present")
-                }
-            })
+            .enumerate()
+            .filter_map(|(index, rule)| rule.delay.clone().map(|delay| (index, delay)))
             .collect();
 
-        let actions: Vec<ActionType> = when_rules.into_iter().map(|rule| rule.action).collect();
+        // Only When rules are kept by the matcher
+        let matcher = WhenMatcher::new(rules);
+
+        let stats = agent.get_rule_stats();
+        for (index, description) in matcher.rules() {
+            stats.register(index, description.to_string());
+        }
+
+        let rate_limiter = Mutex::new(ActionRateLimiter::new(
+            agent.get_config().rules.max_actions_per_minute,
+        ));
 
         Self {
-            regexes,
-            actions,
+            matcher,
+            delays,
+            delayed: Mutex::new(DelayedActions::new()),
+            stats,
+            rate_limiter,
             agent,
         }
     }
 
-    /// Decides what action to take based on a terminal output capture
-    fn decide_action(&self, capture: &str) -> ActionType {
-        for (i, regex) in self.regexes.iter().enumerate() {
-            if let Some(captures) = regex.captures(capture) {
-                let mut action = self.actions[i].clone();
-
-                // Handle capture group substitution
-                let ActionType::SendKeys(ref mut keys) = action;
-                for key in keys.iter_mut() {
-                    // Replace ${1}, ${2}, etc. with capture groups
-                    for (j, capture_group) in captures.iter().enumerate().skip(1) {
-                        if let Some(matched) = capture_group {
-                            *key = key.replace(&format!("${{{}}}", j), matched.as_str());
+    /// Add all output waiting in `receiver` to the current burst, matching
+    /// the lines of a burst that grew past `debounce_max_bytes`. Returns
+    /// false once the output closed.
+    async fn drain_output(
+        &self,
+        receiver: &mut broadcast::Receiver<String>,
+        debounce: &mut OutputDebounce,
+        buffer: &mut LineBuffer,
+    ) -> bool {
+        let mut lagged = false;
+        let active = self.agent.is_active();
+        loop {
+            match receiver.try_recv() {
+                Ok(pty_output) => {
+                    if !active {
+                        debounce.clear();
+                        buffer.clear();
+                    } else if let Some(batch) = debounce.push(&pty_output, Instant::now()) {
+                        self.process_batch(&batch, buffer).await;
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "⚠️ Agent {} when monitor fell behind, {} output chunks skipped",
+                        self.agent.get_id(),
+                        skipped
+                    );
+                    self.stats.record_dropped_output(skipped);
+                    // Output up to the gap is still matched, the rest of
+                    // its partial line was lost
+                    if let Some(batch) = debounce.take() {
+                        self.process_batch(&batch, buffer).await;
+                    }
+                    buffer.clear();
+                    lagged = true;
+                }
+                Err(broadcast::error::TryRecvError::Empty) => {
+                    if lagged {
+                        // Lines completed after the gap are matched, the
+                        // partial line is then taken from the screen
+                        if let Some(batch) = debounce.take() {
+                            self.process_batch(&batch, buffer).await;
                         }
+                        self.resync(buffer).await;
                     }
+                    return true;
                 }
+                Err(broadcast::error::TryRecvError::Closed) => return false,
+            }
+        }
+    }
+
+    /// Catch up once output chunks were lost to a full channel and the rest
+    /// is read. Lines completed in the lost chunks can't be matched anymore,
+    /// but the partial line is taken from the cursor's line on screen, so a
+    /// prompt that is waiting there still matches.
+    async fn resync(&self, buffer: &mut LineBuffer) {
+        let Ok(screen) = self.agent.get_process().get_screen_text().await else {
+            return;
+        };
+        let (row, col) = screen.cursor;
+        if let Some(line) = screen.lines.get(row as usize) {
+            // Snapshot lines lose the blanks before the cursor, e.g. after "(y/n)"
+            let partial = format!("{:<width$}", line, width = col as usize);
+            buffer.reset_to(&partial, Instant::now());
+        }
+    }
+
+    /// Match each line a batch of output completed
+    async fn process_batch(&self, batch: &str, buffer: &mut LineBuffer) {
+        for line in buffer.push(batch, Instant::now()) {
+            self.process_line(&line).await;
+        }
+    }
+
+    /// Match a complete line and execute (or schedule) the resulting action, if any
+    async fn process_line(&self, line: &str) {
+        self.cancel_delayed_actions(line);
+
+        let Some((rule_index, action)) = self.decide(line) else {
+            return;
+        };
+
+        match self.delays.get(&rule_index) {
+            Some(delay) => self.schedule_delayed_action(rule_index, action, delay),
+            None => self.run_action(&action, "Rule action").await,
+        }
+    }
 
-                return action;
+    /// Execute a resolved action, subject to the rate limit and paused rules
+    async fn run_action(&self, action: &ActionType, context: &str) {
+        if self.agent.are_rules_paused() {
+            tracing::info!(
+                "⏸️ Agent {} rules paused, skipped action: {}",
+                self.agent.get_id(),
+                action.describe()
+            );
+            return;
+        }
+        if !matches!(action, ActionType::SendKeys(keys) if keys.is_empty())
+            && self.allow_action()
+            && let Err(e) = execute_rule_action(action, &self.agent, context).await
+        {
+            tracing::error!("Error executing rule action: {}", e);
+        }
+    }
+
+    fn schedule_delayed_action(&self, rule_index: usize, action: ActionType, delay: &RuleDelay) {
+        let Ok(mut delayed) = self.delayed.lock() else {
+            return;
+        };
+        let scheduled = delayed.schedule(PendingAction {
+            rule_index,
+            action,
+            due: Instant::now() + delay.duration,
+            cancel_on: delay.cancel_on.clone(),
+        });
+        if scheduled {
+            tracing::info!(
+                "⏳ Agent {} rule #{} action scheduled in {:?}",
+                self.agent.get_id(),
+                rule_index,
+                delay.duration
+            );
+        }
+    }
+
+    /// Cancel pending actions whose `cancel_on` pattern matches this line
+    fn cancel_delayed_actions(&self, line: &str) {
+        let Ok(mut delayed) = self.delayed.lock() else {
+            return;
+        };
+        for rule_index in delayed.cancel_matching(&strip_ansi(line)) {
+            tracing::info!(
+                "🚫 Agent {} rule #{} delayed action cancelled",
+                self.agent.get_id(),
+                rule_index
+            );
+        }
+    }
+
+    /// Execute delayed actions whose grace period has passed
+    async fn fire_due_actions(&self, now: Instant) {
+        let due = match self.delayed.lock() {
+            Ok(mut delayed) => delayed.take_due(now),
+            Err(_) => return,
+        };
+        for pending in due {
+            self.run_action(&pending.action, "Delayed rule action")
+                .await;
+        }
+    }
+
+    /// Drop all pending delayed actions (e.g. when the agent goes Idle)
+    fn drop_delayed_actions(&self) {
+        if let Ok(mut delayed) = self.delayed.lock() {
+            let dropped = delayed.clear();
+            if dropped > 0 {
+                tracing::info!(
+                    "Agent {} is idle, dropped {} delayed action(s)",
+                    self.agent.get_id(),
+                    dropped
+                );
             }
         }
+    }
+
+    /// Apply the per-agent rate limit, counting and reporting dropped actions
+    fn allow_action(&self) -> bool {
+        let Ok(mut limiter) = self.rate_limiter.lock() else {
+            return true;
+        };
+        if limiter.try_acquire(Instant::now()) {
+            return true;
+        }
+
+        self.stats.record_dropped_action();
+        if limiter.should_warn() {
+            tracing::warn!(
+                "⚠️ Agent {} exceeded max_actions_per_minute, suppressing rule actions for the rest of the minute",
+                self.agent.get_id()
+            );
+        }
+        false
+    }
 
-        // Return empty action if no rule matches
-        ActionType::SendKeys(vec![])
+    /// Decides what action to take based on a raw terminal output line.
+    /// Returns an empty action if no rule matches.
+    #[cfg(test)]
+    fn decide_action(&self, raw: &str) -> ActionType {
+        self.decide(raw)
+            .map(|(_, action)| action)
+            .unwrap_or_else(|| ActionType::SendKeys(vec![]))
     }
 
-    /// Normalize PTY output into clean lines for pattern matching
-    fn get_normalized_lines(&self, pty_output: &str) -> Vec<String> {
-        // Remove ANSI escape sequences for cleaner pattern matching
-        let ansi_regex = regex::Regex::new(r"\x1b\[[0-9;]*[mGKHF]").unwrap();
-        let clean_output = ansi_regex.replace_all(pty_output, "");
+    /// Find the rule matching a raw terminal output line and resolve its action.
+    /// Each rule matches against either the raw line or its ANSI-stripped form.
+    fn decide(&self, raw: &str) -> Option<(usize, ActionType)> {
+        let groups = self.agent.get_rule_groups();
+        let found =
+            self.matcher
+                .find_match(raw, Some(self.agent.is_alternate_screen()), |group| {
+                    groups.is_enabled(group)
+                })?;
+
+        self.stats.record(found.rule_index, &found.line);
 
-        // Split by both \n and \r for better handling of carriage returns
-        clean_output
-            .split(['\n', '\r'])
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.to_string())
-            .collect()
+        // Resolve ${0} (whole match), ${1}, ${2}, etc. and agent/rule variables
+        let context = TemplateContext::for_agent(&self.agent)
+            .with_captures(found.captures)
+            .with_rule_pattern(&found.pattern);
+
+        Some((found.rule_index, resolve_action(&found.action, &context)))
     }
 }
 
@@ -119,12 +323,13 @@ mod tests {
     use super::*;
     use crate::config::rules_config::RuleType;
     use regex::Regex;
+    use std::time::Duration;
 
     fn create_test_rule(pattern: &str, keys: Vec<String>) -> Rule {
-        Rule {
-            rule_type: RuleType::When(Regex::new(pattern).unwrap()),
-            action: ActionType::SendKeys(keys),
-        }
+        Rule::new(
+            RuleType::When(Regex::new(pattern).unwrap()),
+            ActionType::SendKeys(keys),
+        )
     }
 
     async fn create_test_agent() -> Arc<Agent> {
@@ -222,6 +427,334 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_decide_action_whole_match_placeholder() {
+        use crate::config::rules_config::{MatchMode, compile_pattern};
+
+        let rules = vec![Rule::new(
+            RuleType::When(compile_pattern("Do you want to proceed?", MatchMode::Literal).unwrap()),
+            ActionType::SendKeys(vec!["echo '${0}'".to_string()]),
+        )];
+
+        let when = When::new(rules, create_test_agent().await);
+        let action = when.decide_action("? Do you want to proceed? (y/n)");
+        assert_eq!(
+            action,
+            ActionType::SendKeys(vec!["echo 'Do you want to proceed?'".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decide_action_raw_vs_stripped() {
+        let mut raw_rule = create_test_rule(r"\x1b\[31mERROR", vec!["raw".to_string()]);
+        raw_rule.strip_ansi = false;
+        let rules = vec![
+            raw_rule,
+            create_test_rule(r"^WARN", vec!["stripped".to_string()]),
+        ];
+
+        let when = When::new(rules, create_test_agent().await);
+        assert_eq!(
+            when.decide_action("\x1b[31mERROR\x1b[0m"),
+            ActionType::SendKeys(vec!["raw".to_string()])
+        );
+        assert_eq!(
+            when.decide_action("\x1b[33mWARN\x1b[0m"),
+            ActionType::SendKeys(vec!["stripped".to_string()])
+        );
+        // Plain text never matches the raw color rule
+        assert_eq!(when.decide_action("ERROR"), ActionType::SendKeys(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_decide_action_records_stats() {
+        let rules = vec![
+            create_test_rule(r"never", vec!["x".to_string()]),
+            create_test_rule(r"issue\s+(\d+)", vec!["open".to_string()]),
+        ];
+
+        let agent = create_test_agent().await;
+        let when = When::new(rules, Arc::clone(&agent));
+        when.decide_action("issue 42");
+        when.decide_action("issue 43");
+
+        let hits = agent.get_rule_stats().snapshot();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].match_count, 0);
+        assert_eq!(hits[1].rule_index, 1);
+        assert_eq!(hits[1].rule, r"when: issue\s+(\d+)");
+        assert_eq!(hits[1].match_count, 2);
+        assert_eq!(hits[1].last_matched_text.as_deref(), Some("issue 43"));
+    }
+
+    #[tokio::test]
+    async fn test_allow_action_rate_limit() {
+        use crate::agent::Agent;
+        use crate::config::Config;
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.rules.max_actions_per_minute = 2;
+        let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+            .await
+            .unwrap();
+
+        let when = When::new(vec![], Arc::clone(&agent));
+        assert!(when.allow_action());
+        assert!(when.allow_action());
+        assert!(!when.allow_action());
+        assert!(!when.allow_action());
+        assert_eq!(agent.get_rule_stats().dropped_actions(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_decide_action_agent_variables() {
+        let rules = vec![create_test_rule(
+            r"error: (\w+)",
+            vec!["echo ${agent_id} ${agent_index} ${1} '${rule_pattern}'".to_string()],
+        )];
+
+        let when = When::new(rules, create_test_agent().await);
+        let action = when.decide_action("error: disk");
+        assert_eq!(
+            action,
+            ActionType::SendKeys(vec![r"echo agent-0 0 disk 'error: (\w+)'".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decide_action_append_file() {
+        let rules = vec![Rule::new(
+            RuleType::When(Regex::new(r"error: (.+)").unwrap()),
+            ActionType::AppendFile {
+                path: "logs/${agent_id}.log".to_string(),
+                template: "${agent_id} ${0}".to_string(),
+            },
+        )];
+
+        let when = When::new(rules, create_test_agent().await);
+        assert_eq!(
+            when.decide_action("\x1b[31merror: disk full\x1b[0m"),
+            ActionType::AppendFile {
+                path: "logs/agent-0.log".to_string(),
+                template: "agent-0 error: disk full".to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decide_action_skips_inactive_groups() {
+        let mut grouped = create_test_rule(r"Commit\?", vec!["y".to_string()]);
+        grouped.group = Some("commit".to_string());
+        let rules = vec![
+            grouped,
+            create_test_rule(r"Commit\?", vec!["n".to_string()]),
+        ];
+
+        let agent = create_test_agent().await;
+        let when = When::new(rules, Arc::clone(&agent));
+        assert_eq!(
+            when.decide_action("Commit?"),
+            ActionType::SendKeys(vec!["n".to_string()])
+        );
+
+        agent.get_rule_groups().activate("commit");
+        assert_eq!(
+            when.decide_action("Commit?"),
+            ActionType::SendKeys(vec!["y".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_buffered_lines_match_across_chunks() {
+        use crate::rule::line_buffer::FLUSH_TIMEOUT;
+
+        let rules = vec![
+            create_test_rule(r"Do you want to proceed\?", vec!["1".to_string()]),
+            create_test_rule(r"\(y/n\) $", vec!["y".to_string()]),
+        ];
+        let when = When::new(rules, create_test_agent().await);
+        let mut buffer = LineBuffer::new();
+        let start = Instant::now();
+
+        // Pattern split across three chunks only matches once the line completes
+        let mut actions = Vec::new();
+        for chunk in ["Do you w", "ant to pro", "ceed?\r\n"] {
+            for line in buffer.push(chunk, start) {
+                actions.push(when.decide_action(&line));
+            }
+        }
+        assert_eq!(actions, vec![ActionType::SendKeys(vec!["1".to_string()])]);
+
+        // Prompt without trailing newline is evaluated after a quiet period
+        assert!(buffer.push("Overwrite file? (y/n) ", start).is_empty());
+        assert!(buffer.flush_if_idle(start).is_none());
+        let line = buffer.flush_if_idle(start + FLUSH_TIMEOUT).unwrap();
+        assert_eq!(
+            when.decide_action(&line),
+            ActionType::SendKeys(vec!["y".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lagging_monitor_resyncs_from_screen() {
+        use crate::config::Config;
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        // The prompt the lost chunks ended with is still on screen
+        mock_pty.set_screen(&["building...", "Overwrite file? (y/n) "]);
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        agent.set_status(crate::agent::AgentStatus::Active);
+        let rules = vec![create_test_rule(r"\(y/n\) $", vec!["y".to_string()])];
+        let when = When::new(rules, agent);
+
+        let (sender, mut receiver) = broadcast::channel(2);
+        for chunk in ["Over", "write ", "file? ", "(y/", "n) "] {
+            sender.send(chunk.to_string()).unwrap();
+        }
+        let mut debounce = OutputDebounce::new(Duration::from_millis(20), 8 * 1024);
+        let mut buffer = LineBuffer::new();
+        assert!(
+            when.drain_output(&mut receiver, &mut debounce, &mut buffer)
+                .await
+        );
+
+        assert_eq!(when.stats.dropped_output(), 3);
+        // Only "(y/" and "n) " arrived; the screen has the whole line
+        assert_eq!(
+            buffer.take_pending().as_deref(),
+            Some("Overwrite file? (y/n) ")
+        );
+
+        assert!(debounce.take().is_none());
+
+        drop(sender);
+        assert!(
+            !when
+                .drain_output(&mut receiver, &mut debounce, &mut buffer)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_prompt_matched_once_burst_ends() {
+        use crate::config::Config;
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        agent.set_status(crate::agent::AgentStatus::Active);
+        let rules = vec![create_test_rule(
+            r"^Do you want to proceed\?",
+            vec!["1".to_string()],
+        )];
+        let when = When::new(rules, agent);
+
+        // 500 writes of a single character, as claude streams tokens
+        let text = "Thinking about the change.\r\n".repeat(17) + "Do you want to proceed?\n";
+        let chunks: Vec<String> = text.chars().map(String::from).collect();
+        assert_eq!(chunks.len(), 500);
+
+        let (sender, mut receiver) = broadcast::channel(1024);
+        let mut debounce = OutputDebounce::new(Duration::from_millis(20), 8 * 1024);
+        let mut buffer = LineBuffer::new();
+        for chunk in &chunks {
+            sender.send(chunk.clone()).unwrap();
+        }
+        assert!(
+            when.drain_output(&mut receiver, &mut debounce, &mut buffer)
+                .await
+        );
+        // Nothing is looked at while the burst lasts
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        let batch = debounce
+            .flush_if_quiet(Instant::now() + Duration::from_millis(20))
+            .unwrap();
+        assert_eq!(batch, text);
+        when.process_batch(&batch, &mut buffer).await;
+        assert_eq!(mock_pty.get_sent_inputs(), ["1"]);
+    }
+
+    async fn create_delayed_when(
+        cancel_on: Option<&str>,
+    ) -> (
+        When,
+        Arc<crate::terminal::pty_process_trait::MockPtyProcess>,
+    ) {
+        use crate::config::Config;
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let mut rule = create_test_rule(r"Compacting conversation", vec!["\x1b".to_string()]);
+        rule.delay = Some(RuleDelay {
+            duration: Duration::from_secs(10),
+            cancel_on: cancel_on.map(|p| Regex::new(p).unwrap()),
+        });
+        (When::new(vec![rule], agent), mock_pty)
+    }
+
+    #[tokio::test]
+    async fn test_paused_rules_send_nothing() {
+        let (when, mock_pty) = create_delayed_when(None).await;
+        when.agent.pause_rules().unwrap();
+
+        when.process_line("Compacting conversation...").await;
+        when.fire_due_actions(Instant::now() + Duration::from_secs(11))
+            .await;
+        assert!(mock_pty.get_sent_inputs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delayed_action_fires_after_delay() {
+        let (when, mock_pty) = create_delayed_when(Some("Compacted")).await;
+
+        when.process_line("Compacting conversation...").await;
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        when.fire_due_actions(Instant::now() + Duration::from_secs(5))
+            .await;
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        when.fire_due_actions(Instant::now() + Duration::from_secs(11))
+            .await;
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["\x1b"]);
+    }
+
+    #[tokio::test]
+    async fn test_delayed_action_cancelled() {
+        let (when, mock_pty) = create_delayed_when(Some("Compacted")).await;
+
+        when.process_line("Compacting conversation...").await;
+        when.process_line("✓ Compacted conversation").await;
+        when.fire_due_actions(Instant::now() + Duration::from_secs(11))
+            .await;
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        // Pending actions are also dropped when the agent goes Idle
+        when.process_line("Compacting conversation...").await;
+        when.drop_delayed_actions();
+        when.fire_due_actions(Instant::now() + Duration::from_secs(11))
+            .await;
+        assert!(mock_pty.get_sent_inputs().is_empty());
+    }
+
     #[tokio::test]
     async fn test_performance_100_rules() {
         use std::time::Instant;
@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::config::dedupe_config::DedupeConfig;
+use crate::rule::stats::unix_now;
+
+/// Seen lines per trigger name, each with the Unix timestamp it was last seen
+type SeenLines = BTreeMap<String, BTreeMap<String, u64>>;
+
+/// Store installed by the running process; entries fall back to an in-memory one
+static DEDUPE_STORE: OnceLock<DedupeStore> = OnceLock::new();
+
+/// Remembers source lines of `dedupe: true` entries across executions.
+/// With a file, the store is re-read before every update so that
+/// `ccauto dedupe clear` takes effect in a running process.
+pub struct DedupeStore {
+    path: Option<PathBuf>,
+    max_entries: usize,
+    ttl: Duration,
+    seen: Mutex<SeenLines>,
+}
+
+impl DedupeStore {
+    /// Store persisted at `config.file`
+    pub fn from_config(config: &DedupeConfig) -> Result<Self> {
+        Ok(Self {
+            path: Some(config.file.clone()),
+            max_entries: config.max_entries,
+            ttl: config.ttl()?,
+            seen: Mutex::new(SeenLines::new()),
+        })
+    }
+
+    /// Store kept only for the lifetime of the process
+    pub fn in_memory(max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            path: None,
+            max_entries,
+            ttl,
+            seen: Mutex::new(SeenLines::new()),
+        }
+    }
+
+    /// Keep the lines not seen within `ttl` (the store's TTL when None) for
+    /// `trigger` and remember them. Expired lines are pruned here, lazily.
+    pub fn retain_new(
+        &self,
+        trigger: &str,
+        lines: Vec<String>,
+        ttl: Option<Duration>,
+    ) -> Result<Vec<String>> {
+        self.retain_new_at(trigger, lines, ttl, unix_now())
+    }
+
+    /// Whether `line` was not seen for `trigger` within `ttl`, without
+    /// remembering it; see [`Self::mark_seen`]
+    pub fn is_new(&self, trigger: &str, line: &str, ttl: Option<Duration>) -> Result<bool> {
+        self.is_new_at(trigger, line, ttl, unix_now())
+    }
+
+    /// Remember `line` for `trigger`, once its action was sent
+    pub fn mark_seen(&self, trigger: &str, line: &str, ttl: Option<Duration>) -> Result<()> {
+        self.retain_new(trigger, vec![line.to_string()], ttl)
+            .map(drop)
+    }
+
+    fn is_new_at(
+        &self,
+        trigger: &str,
+        line: &str,
+        ttl: Option<Duration>,
+        now: u64,
+    ) -> Result<bool> {
+        let mut seen = self.lock();
+        if let Some(path) = &self.path {
+            *seen = read_seen(path)?;
+        }
+        let expires_before = now.saturating_sub(ttl.unwrap_or(self.ttl).as_secs());
+        Ok(seen
+            .get(trigger)
+            .and_then(|entries| entries.get(line))
+            .is_none_or(|seen_at| *seen_at < expires_before))
+    }
+
+    fn retain_new_at(
+        &self,
+        trigger: &str,
+        lines: Vec<String>,
+        ttl: Option<Duration>,
+        now: u64,
+    ) -> Result<Vec<String>> {
+        let mut seen = self.lock();
+        if let Some(path) = &self.path {
+            *seen = read_seen(path)?;
+        }
+
+        let ttl = ttl.unwrap_or(self.ttl);
+        let expires_before = now.saturating_sub(ttl.as_secs());
+        let entries = seen.entry(trigger.to_string()).or_default();
+        entries.retain(|_, seen_at| *seen_at >= expires_before);
+
+        let mut new_lines = Vec::new();
+        for line in lines {
+            if !entries.contains_key(&line) {
+                entries.insert(line.clone(), now);
+                new_lines.push(line);
+            }
+        }
+
+        // Forget the oldest lines beyond the limit
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, seen_at)| **seen_at)
+                .map(|(line, _)| line.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+
+        if let Some(path) = &self.path {
+            write_seen(path, &seen)?;
+        }
+        Ok(new_lines)
+    }
+
+    /// Forget all lines of `trigger`, returning how many were removed
+    pub fn clear(&self, trigger: &str) -> Result<usize> {
+        let mut seen = self.lock();
+        if let Some(path) = &self.path {
+            *seen = read_seen(path)?;
+        }
+
+        let removed = seen.remove(trigger).map_or(0, |entries| entries.len());
+        if let Some(path) = &self.path {
+            write_seen(path, &seen)?;
+        }
+        Ok(removed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SeenLines> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Install the store used by all entries; only the first call has an effect
+pub fn install(store: DedupeStore) {
+    if DEDUPE_STORE.set(store).is_err() {
+        tracing::warn!("Dedupe store already installed");
+    }
+}
+
+/// The installed store, or an in-memory store with default limits
+pub fn store() -> &'static DedupeStore {
+    DEDUPE_STORE.get_or_init(|| {
+        let config = DedupeConfig::default();
+        DedupeStore::in_memory(
+            config.max_entries,
+            config.ttl().unwrap_or(Duration::from_secs(7 * 24 * 3600)),
+        )
+    })
+}
+
+/// [`DedupeStore::is_new`] on the installed store, run on a blocking thread
+/// since a file-backed store reads the file
+pub async fn is_new(trigger: &str, line: &str, ttl: Option<Duration>) -> Result<bool> {
+    let (trigger, line) = (trigger.to_string(), line.to_string());
+    tokio::task::spawn_blocking(move || store().is_new(&trigger, &line, ttl)).await?
+}
+
+/// [`DedupeStore::mark_seen`] on the installed store, run on a blocking
+/// thread since a file-backed store rewrites the file
+pub async fn mark_seen(trigger: &str, line: &str, ttl: Option<Duration>) -> Result<()> {
+    let (trigger, line) = (trigger.to_string(), line.to_string());
+    tokio::task::spawn_blocking(move || store().mark_seen(&trigger, &line, ttl)).await?
+}
+
+fn read_seen(path: &Path) -> Result<SeenLines> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse dedupe file {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SeenLines::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read dedupe file {}", path.display())),
+    }
+}
+
+/// Write atomically (temp file + rename), like the runtime state file
+fn write_seen(path: &Path, seen: &SeenLines) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create dedupe directory {}", parent.display()))?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_string_pretty(seen)?)
+        .with_context(|| format!("Failed to write dedupe file {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace dedupe file {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn file_store(path: &Path, max_entries: usize) -> DedupeStore {
+        DedupeStore::from_config(&DedupeConfig {
+            file: path.to_path_buf(),
+            max_entries,
+            ttl: "1h".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_retain_new_persists_across_stores() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".ccauto").join("dedupe.json");
+
+        let store = file_store(&path, 100);
+        assert_eq!(
+            store
+                .retain_new("issues", lines(&["#1", "#2"]), None)
+                .unwrap(),
+            lines(&["#1", "#2"])
+        );
+
+        // A restarted process only sees the new line
+        let restarted = file_store(&path, 100);
+        assert_eq!(
+            restarted
+                .retain_new("issues", lines(&["#1", "#2", "#3"]), None)
+                .unwrap(),
+            lines(&["#3"])
+        );
+        // Other triggers are tracked separately
+        assert_eq!(
+            restarted.retain_new("prs", lines(&["#1"]), None).unwrap(),
+            lines(&["#1"])
+        );
+    }
+
+    #[test]
+    fn test_retain_new_expires_lines() {
+        let store = DedupeStore::in_memory(100, Duration::from_secs(60));
+        assert_eq!(
+            store.retain_new_at("t", lines(&["a"]), None, 1000).unwrap(),
+            lines(&["a"])
+        );
+        assert!(
+            store
+                .retain_new_at("t", lines(&["a"]), None, 1060)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            store.retain_new_at("t", lines(&["a"]), None, 1061).unwrap(),
+            lines(&["a"])
+        );
+    }
+
+    #[test]
+    fn test_retain_new_trigger_ttl_overrides_store_ttl() {
+        let store = DedupeStore::in_memory(100, Duration::from_secs(3600));
+        let ttl = Some(Duration::from_secs(5));
+        store
+            .retain_new_at("reopened", lines(&["#7"]), ttl, 100)
+            .unwrap();
+
+        assert!(
+            store
+                .retain_new_at("reopened", lines(&["#7"]), ttl, 105)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            store
+                .retain_new_at("reopened", lines(&["#7"]), ttl, 111)
+                .unwrap(),
+            lines(&["#7"])
+        );
+    }
+
+    #[test]
+    fn test_is_new_does_not_remember_lines() {
+        let store = DedupeStore::in_memory(10, Duration::from_secs(60));
+        assert!(store.is_new_at("t", "a", None, 1000).unwrap());
+        assert!(store.is_new_at("t", "a", None, 1000).unwrap());
+
+        store.retain_new_at("t", lines(&["a"]), None, 1000).unwrap();
+        assert!(!store.is_new_at("t", "a", None, 1060).unwrap());
+        assert!(store.is_new_at("t", "a", None, 1061).unwrap());
+    }
+
+    #[test]
+    fn test_retain_new_forgets_oldest_beyond_limit() {
+        let store = DedupeStore::in_memory(2, Duration::from_secs(3600));
+        store.retain_new_at("t", lines(&["a"]), None, 1).unwrap();
+        store
+            .retain_new_at("t", lines(&["b", "c"]), None, 2)
+            .unwrap();
+
+        assert_eq!(
+            store
+                .retain_new_at("t", lines(&["a", "b"]), None, 3)
+                .unwrap(),
+            lines(&["a"])
+        );
+    }
+
+    #[test]
+    fn test_clear_trigger() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dedupe.json");
+        let store = file_store(&path, 100);
+        store
+            .retain_new("issues", lines(&["#1", "#2"]), None)
+            .unwrap();
+        store.retain_new("prs", lines(&["#9"]), None).unwrap();
+
+        // Cleared from another invocation, the running store picks it up
+        assert_eq!(file_store(&path, 100).clear("issues").unwrap(), 2);
+        assert_eq!(file_store(&path, 100).clear("missing").unwrap(), 0);
+        assert_eq!(
+            store.retain_new("issues", lines(&["#1"]), None).unwrap(),
+            lines(&["#1"])
+        );
+        assert!(
+            store
+                .retain_new("prs", lines(&["#9"]), None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
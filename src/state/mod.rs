@@ -0,0 +1,370 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::agent::Agents;
+use crate::agent::agents::AgentSummary;
+use crate::agent::resources::format_bytes;
+use crate::rule::RuleHit;
+use crate::rule::stats::unix_now;
+use crate::trigger::history::TriggerRun;
+
+pub mod dedupe;
+
+/// Default location of the runtime state file, relative to the working directory
+pub const DEFAULT_STATE_FILE: &str = ".ccauto/state.json";
+
+/// How often the running process refreshes the state file
+const STATE_WRITE_INTERVAL_MS: u64 = 1000;
+
+/// Snapshot of runtime information written by a running ccauto process
+/// so that other invocations (e.g. `ccauto show`) can inspect it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeState {
+    pub pid: u32,
+    /// Unix timestamp (seconds) of the last refresh
+    pub updated_at: u64,
+    /// Rule hit statistics keyed by agent ID
+    pub rule_stats: BTreeMap<String, Vec<RuleHit>>,
+    /// Rule actions suppressed by the rate limit, keyed by agent ID
+    #[serde(default)]
+    pub dropped_actions: BTreeMap<String, u64>,
+    /// Output chunks rule monitors missed by falling behind, keyed by agent ID
+    #[serde(default)]
+    pub dropped_output: BTreeMap<String, u64>,
+    /// Recent runs of each trigger entry, keyed by trigger name
+    #[serde(default)]
+    pub trigger_history: BTreeMap<String, Vec<TriggerRun>>,
+    /// Entries paused with `ccauto trigger pause`
+    #[serde(default)]
+    pub paused_triggers: BTreeSet<String>,
+    /// Status and resource usage keyed by agent ID
+    #[serde(default)]
+    pub agents: BTreeMap<String, AgentSummary>,
+}
+
+impl RuntimeState {
+    /// Collect the current state from the agent pool
+    pub fn collect(agents: &Agents) -> Self {
+        Self {
+            pid: std::process::id(),
+            updated_at: unix_now(),
+            rule_stats: agents.rule_stats(),
+            dropped_actions: agents.dropped_actions(),
+            dropped_output: agents.dropped_output(),
+            trigger_history: agents.trigger_runs(),
+            paused_triggers: agents.paused_triggers().snapshot(),
+            agents: agents.summaries(),
+        }
+    }
+
+    /// Write state atomically (temp file + rename) so readers never see partial JSON
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create state directory {}", parent.display())
+            })?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&tmp_path, content)
+            .with_context(|| format!("Failed to write state file {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace state file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Read state written by a running process
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read state file {} (is ccauto running?)",
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state file {}", path.display()))
+    }
+
+    /// Render rule statistics as human readable text
+    pub fn format_rule_stats(&self) -> String {
+        let mut out = String::new();
+        let now = unix_now();
+
+        for (agent_id, hits) in &self.rule_stats {
+            out.push_str(&format!("{}\n", agent_id));
+            if let Some(dropped) = self.dropped_actions.get(agent_id).filter(|d| **d > 0) {
+                out.push_str(&format!("  ({} actions dropped by rate limit)\n", dropped));
+            }
+            if let Some(missed) = self.dropped_output.get(agent_id).filter(|d| **d > 0) {
+                out.push_str(&format!(
+                    "  ({} output chunks missed by lagging rule monitors)\n",
+                    missed
+                ));
+            }
+            if hits.is_empty() {
+                out.push_str("  (no rules)\n");
+            }
+            for hit in hits {
+                let last = match hit.last_matched_at {
+                    Some(at) => format!("{}s ago", now.saturating_sub(at)),
+                    None => "never".to_string(),
+                };
+                out.push_str(&format!(
+                    "  #{:<3} {:>6} hits  last: {:<10} {}\n",
+                    hit.rule_index, hit.match_count, last, hit.rule
+                ));
+                if let Some(text) = &hit.last_matched_text {
+                    out.push_str(&format!("        matched: {:?}\n", text));
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Render agent status and resource usage as human readable text
+    pub fn format_agents(&self) -> String {
+        let now = unix_now();
+        self.agents
+            .iter()
+            .map(|(agent_id, summary)| {
+                let times = &summary.times;
+                let mut notes = format!(
+                    "  for {}s  active {}s in {} tasks  idle {}s",
+                    times.state_secs, times.active_secs, times.activations, times.idle_secs
+                );
+                if !summary.tags.is_empty() {
+                    notes.push_str(&format!("  tags {}", summary.tags.join(",")));
+                }
+                if summary.rules_paused {
+                    notes.push_str("  rules paused");
+                }
+                let restarts = &summary.restarts;
+                if restarts.restarts > 0 || restarts.last_exit_reason.is_some() {
+                    let mut details = Vec::new();
+                    if let Some(at) = restarts.last_restart_at {
+                        details.push(format!("last {}s ago", now.saturating_sub(at)));
+                    }
+                    if let Some(reason) = &restarts.last_exit_reason {
+                        details.push(reason.clone());
+                    }
+                    notes.push_str(&format!(
+                        "  restarts {} ({})",
+                        restarts.restarts,
+                        details.join(", ")
+                    ));
+                }
+                if let Some(commands) = &summary.commands {
+                    notes.push_str(&format!(
+                        "  commands {}/{} this hour",
+                        commands.used, commands.limit
+                    ));
+                }
+                if !summary.term.is_empty() {
+                    notes.push_str(&format!("  TERM {}", summary.term));
+                }
+                if summary.pending_input > 0 {
+                    notes.push_str(&format!("  {} inputs pending", summary.pending_input));
+                }
+                if let Some(command) = &summary.current_command {
+                    notes.push_str(&format!("  running: {}", command));
+                }
+                if !summary.title.is_empty() {
+                    notes.push_str(&format!("  title: {:?}", summary.title));
+                }
+                if summary.alternate_screen {
+                    notes.push_str("  alternate screen");
+                }
+                let mut out = format!(
+                    "{:<10} {:<8} cpu {:>5.1}%  mem {:>10}  children {}{}\n",
+                    agent_id,
+                    summary.status,
+                    summary.resources.cpu_percent,
+                    format_bytes(summary.resources.rss_bytes),
+                    summary.resources.child_count,
+                    notes
+                );
+                for process in &summary.processes {
+                    let indent = "  ".repeat(process.depth);
+                    out.push_str(&format!("  {}{} {}\n", indent, process.pid, process.name));
+                }
+                out
+            })
+            .collect()
+    }
+}
+
+/// Periodically write the runtime state file until the task is aborted
+pub fn start_state_writer(agents: Arc<Agents>, path: PathBuf) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(STATE_WRITE_INTERVAL_MS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = RuntimeState::collect(&agents).write_to_file(&path) {
+                tracing::warn!("Failed to write runtime state: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::quota::CommandUsage;
+    use crate::agent::resources::ResourceUsage;
+    use crate::agent::restarts::RestartCounters;
+    use crate::agent::state_times::StateDurations;
+    use crate::terminal::process_tree::ProcessInfo;
+
+    fn sample_state() -> RuntimeState {
+        let mut rule_stats = BTreeMap::new();
+        rule_stats.insert(
+            "agent-0".to_string(),
+            vec![
+                RuleHit {
+                    rule_index: 0,
+                    rule: "when: proceed".to_string(),
+                    match_count: 3,
+                    last_matched_at: Some(unix_now()),
+                    last_matched_text: Some("Do you want to proceed?".to_string()),
+                },
+                RuleHit {
+                    rule_index: 1,
+                    rule: "diff_timeout: 60s".to_string(),
+                    match_count: 0,
+                    last_matched_at: None,
+                    last_matched_text: None,
+                },
+            ],
+        );
+        let mut dropped_actions = BTreeMap::new();
+        dropped_actions.insert("agent-0".to_string(), 7);
+        RuntimeState {
+            pid: 42,
+            updated_at: unix_now(),
+            rule_stats,
+            dropped_actions,
+            dropped_output: BTreeMap::from([("agent-0".to_string(), 12)]),
+            trigger_history: BTreeMap::new(),
+            paused_triggers: BTreeSet::from(["poller".to_string()]),
+            agents: BTreeMap::from([(
+                "agent-0".to_string(),
+                AgentSummary {
+                    status: "Active".to_string(),
+                    tags: vec!["claude".to_string(), "gpu".to_string()],
+                    rules_paused: true,
+                    current_command: Some("claude --resume".to_string()),
+                    title: "✳ Fix the tests".to_string(),
+                    alternate_screen: true,
+                    processes: vec![
+                        ProcessInfo {
+                            pid: 4242,
+                            name: "claude".to_string(),
+                            depth: 1,
+                        },
+                        ProcessInfo {
+                            pid: 4250,
+                            name: "cargo".to_string(),
+                            depth: 2,
+                        },
+                    ],
+                    restarts: RestartCounters {
+                        restarts: 3,
+                        last_restart_at: Some(unix_now() - 30),
+                        last_exit_reason: Some("exit code 1".to_string()),
+                    },
+                    times: StateDurations {
+                        state_secs: 75,
+                        active_secs: 1200,
+                        idle_secs: 300,
+                        activations: 4,
+                    },
+                    last_output_secs: 12,
+                    commands: Some(CommandUsage {
+                        used: 48,
+                        limit: 50,
+                    }),
+                    term: "screen-256color".to_string(),
+                    pending_input: 3,
+                    port: Some(9990),
+                    resources: ResourceUsage {
+                        cpu_percent: 87.5,
+                        rss_bytes: 300 * 1024 * 1024,
+                        child_count: 2,
+                    },
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_state_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+
+        let state = sample_state();
+        state.write_to_file(&path).unwrap();
+
+        let loaded = RuntimeState::read_from_file(&path).unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn test_read_missing_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = RuntimeState::read_from_file(&dir.path().join("missing.json"));
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("is ccauto running")
+        );
+    }
+
+    #[test]
+    fn test_format_rule_stats() {
+        let output = sample_state().format_rule_stats();
+        assert!(output.contains("agent-0"));
+        assert!(output.contains("when: proceed"));
+        assert!(output.contains("3 hits"));
+        assert!(output.contains("never"));
+        assert!(output.contains("Do you want to proceed?"));
+        assert!(output.contains("7 actions dropped"));
+        assert!(output.contains("12 output chunks missed"));
+    }
+
+    #[test]
+    fn test_format_agents() {
+        let output = sample_state().format_agents();
+        assert!(output.starts_with("agent-0"), "{}", output);
+        assert!(output.contains("Active"));
+        assert!(output.contains("cpu  87.5%"));
+        assert!(output.contains("300.0 MiB"));
+        assert!(output.contains(
+            "children 2  for 75s  active 1200s in 4 tasks  idle 300s  tags claude,gpu  rules paused"
+        ));
+        assert!(output.contains("  restarts 3 (last 3"), "{}", output);
+        assert!(output.contains("s ago, exit code 1)"));
+        assert!(
+            output.contains("  commands 48/50 this hour  TERM screen-256color  3 inputs pending")
+        );
+        assert!(
+            output
+                .contains("running: claude --resume  title: \"✳ Fix the tests\"  alternate screen")
+        );
+        assert!(
+            output.contains("\n    4242 claude\n      4250 cargo\n"),
+            "{}",
+            output
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use chrono::{DateTime, SecondsFormat};
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+use crate::agent::Agent;
+use crate::config::helper::ActionType;
+use crate::rule::stats::unix_now;
+
+/// Matches `${name}` placeholders (numbers for capture groups, identifiers for
+/// variables, `.path` for JSON fields, `env.NAME` for environment
+/// variables), optionally with an escaping filter such as `${1|sh}`
+static PLACEHOLDER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\$\{([A-Za-z_][A-Za-z0-9_]*|[0-9]+|\.[A-Za-z0-9_.-]*|env\.[A-Za-z_][A-Za-z0-9_]*)(?:\|([a-z]+))?\}",
+    )
+    .unwrap()
+});
+
+/// Values available to `${...}` placeholders in action keys
+///
+/// Supported variables:
+/// - `${0}`, `${1}`, ...: capture groups (rules) or the source line (triggers);
+///   with `split`, `${0}` is the line and `${1}`, `${2}`, ... its fields
+/// - `${.field}`, `${.a.b.0}`: fields of the JSON element (`format: json`);
+///   `${.}` is the whole element
+/// - `${agent_id}`, `${agent_index}`: the agent executing the action
+/// - `${timestamp}`: current UTC time in ISO 8601 format
+/// - `${trigger_name}`: name of the trigger being executed
+/// - `${rule_pattern}`: pattern of the rule that matched
+/// - `${env.NAME}`: the environment variable `NAME` of ccauto, e.g. a token
+///   sent by a `secret: true` action
+///
+/// Values are inserted as is unless a filter is given: `${1|sh}` quotes the
+/// value as a single shell word, `${1|json}` as a JSON string literal.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    pub captures: Vec<Option<String>>,
+    pub agent_id: Option<String>,
+    pub agent_index: Option<usize>,
+    pub timestamp: Option<String>,
+    pub trigger_name: Option<String>,
+    pub rule_pattern: Option<String>,
+    /// Resolve missing capture indexes to "" (split fields) instead of leaving them verbatim
+    pub blank_missing_captures: bool,
+    /// Current element of a `format: json` source
+    pub json: Option<serde_json::Value>,
+}
+
+impl TemplateContext {
+    /// Create a context for an agent, stamped with the current time
+    pub fn for_agent(agent: &Agent) -> Self {
+        Self::for_agent_id(agent.get_id(), agent.get_index())
+    }
+
+    /// Create a context for an agent identified by id and index, stamped with the current time
+    pub fn for_agent_id(agent_id: String, agent_index: usize) -> Self {
+        Self {
+            agent_id: Some(agent_id),
+            agent_index: Some(agent_index),
+            timestamp: Some(format_timestamp(unix_now())),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_captures(mut self, captures: Vec<Option<String>>) -> Self {
+        self.captures = captures;
+        self
+    }
+
+    /// Use the whole line as ${0} and its fields as ${1}, ${2}, ...
+    pub fn with_fields(mut self, line: &str, fields: Vec<&str>) -> Self {
+        self.captures = std::iter::once(line)
+            .chain(fields)
+            .map(|value| Some(value.to_string()))
+            .collect();
+        self.blank_missing_captures = true;
+        self
+    }
+
+    pub fn with_json(mut self, json: serde_json::Value) -> Self {
+        self.json = Some(json);
+        self
+    }
+
+    pub fn with_trigger_name(mut self, name: &str) -> Self {
+        self.trigger_name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_rule_pattern(mut self, pattern: &str) -> Self {
+        self.rule_pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Look up the value of a placeholder name, if known
+    fn lookup(&self, name: &str) -> Option<String> {
+        if let Ok(index) = name.parse::<usize>() {
+            let value = self.captures.get(index).cloned().flatten();
+            if value.is_none() && self.blank_missing_captures {
+                tracing::debug!(
+                    "Field ${{{}}} is out of range, using an empty string",
+                    index
+                );
+                return Some(String::new());
+            }
+            return value;
+        }
+
+        if let Some(path) = name.strip_prefix('.') {
+            return self.lookup_json(path);
+        }
+        if let Some(var) = name.strip_prefix("env.") {
+            return std::env::var(var).ok();
+        }
+        match name {
+            "agent_id" => self.agent_id.clone(),
+            "agent_index" => self.agent_index.map(|i| i.to_string()),
+            "timestamp" => self.timestamp.clone(),
+            "trigger_name" => self.trigger_name.clone(),
+            "rule_pattern" => self.rule_pattern.clone(),
+            _ => None,
+        }
+    }
+
+    /// Resolve a dotted path (e.g. "issue.title") in the JSON element.
+    /// Missing fields resolve to "" and strings are inserted without quotes.
+    fn lookup_json(&self, path: &str) -> Option<String> {
+        let json = self.json.as_ref()?;
+        let pointer = if path.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", path.replace('.', "/"))
+        };
+        match json.pointer(&pointer) {
+            Some(serde_json::Value::String(value)) => Some(value.clone()),
+            Some(value) => Some(value.to_string()),
+            None => {
+                tracing::debug!("JSON field .{} not found, using an empty string", path);
+                Some(String::new())
+            }
+        }
+    }
+}
+
+/// Escaping applied to a placeholder value (`${1|sh}`, `${1|json}`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    /// Single-quoted shell word, safe inside `sh -c` and typed commands
+    Sh,
+    /// Quoted JSON string, e.g. for webhook bodies
+    Json,
+}
+
+impl Escape {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sh" => Some(Self::Sh),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: &str) -> String {
+        match self {
+            // Inside single quotes only ' itself is special: close the
+            // quotes, add an escaped quote and reopen them
+            Self::Sh => format!("'{}'", value.replace('\'', r"'\''")),
+            Self::Json => serde_json::Value::from(value).to_string(),
+        }
+    }
+}
+
+/// Substitute `${...}` placeholders in a string. Unknown or unavailable
+/// placeholders, and unknown filters, are left verbatim.
+pub fn resolve_template(template: &str, context: &TemplateContext) -> String {
+    PLACEHOLDER_REGEX
+        .replace_all(template, |caps: &Captures| {
+            let escape = match caps.get(2) {
+                Some(filter) => match Escape::parse(filter.as_str()) {
+                    Some(escape) => Some(escape),
+                    None => return caps[0].to_string(),
+                },
+                None => None,
+            };
+            match (context.lookup(&caps[1]), escape) {
+                (Some(value), Some(escape)) => escape.apply(&value),
+                (Some(value), None) => value,
+                (None, _) => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Substitute placeholders in every templated field of an action
+pub fn resolve_action(action: &ActionType, context: &TemplateContext) -> ActionType {
+    match action {
+        ActionType::SendKeys(keys) => ActionType::SendKeys(
+            keys.iter()
+                .map(|key| resolve_template(key, context))
+                .collect(),
+        ),
+        ActionType::AppendFile { path, template } => ActionType::AppendFile {
+            path: resolve_template(path, context),
+            template: resolve_template(template, context),
+        },
+        ActionType::Notify { title, body } => ActionType::Notify {
+            title: resolve_template(title, context),
+            body: resolve_template(body, context),
+        },
+        ActionType::Webhook { target, message } => ActionType::Webhook {
+            target: target.clone(),
+            message: resolve_template(message, context),
+        },
+        ActionType::Enqueue { queue, item } => ActionType::Enqueue {
+            queue: queue.clone(),
+            item: resolve_template(item, context),
+        },
+        ActionType::ActivateGroup(_) | ActionType::DeactivateGroup(_) | ActionType::Signal(_) => {
+            action.clone()
+        }
+    }
+}
+
+/// Format Unix seconds as an ISO 8601 UTC timestamp (e.g. 2024-01-31T12:00:00Z)
+pub fn format_timestamp(unix_secs: u64) -> String {
+    i64::try_from(unix_secs)
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext {
+        TemplateContext {
+            captures: vec![Some("issue 42".to_string()), Some("42".to_string()), None],
+            agent_id: Some("agent-3".to_string()),
+            agent_index: Some(3),
+            timestamp: Some("2024-01-31T12:00:00Z".to_string()),
+            trigger_name: Some("nightly".to_string()),
+            rule_pattern: Some(r"issue (\d+)".to_string()),
+            blank_missing_captures: false,
+            json: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_capture_groups() {
+        assert_eq!(resolve_template("${0}", &context()), "issue 42");
+        assert_eq!(resolve_template("open ${1}", &context()), "open 42");
+    }
+
+    #[test]
+    fn test_resolve_unmatched_capture_group_left_verbatim() {
+        assert_eq!(resolve_template("${2}", &context()), "${2}");
+        assert_eq!(resolve_template("${9}", &context()), "${9}");
+    }
+
+    #[test]
+    fn test_resolve_agent_id() {
+        assert_eq!(
+            resolve_template("${agent_id} matched", &context()),
+            "agent-3 matched"
+        );
+    }
+
+    #[test]
+    fn test_resolve_agent_index() {
+        assert_eq!(resolve_template("#${agent_index}", &context()), "#3");
+    }
+
+    #[test]
+    fn test_resolve_timestamp() {
+        assert_eq!(
+            resolve_template("at ${timestamp}", &context()),
+            "at 2024-01-31T12:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_resolve_trigger_name() {
+        assert_eq!(resolve_template("${trigger_name}", &context()), "nightly");
+    }
+
+    #[test]
+    fn test_resolve_rule_pattern() {
+        assert_eq!(
+            resolve_template("${rule_pattern}", &context()),
+            r"issue (\d+)"
+        );
+    }
+
+    #[test]
+    fn test_resolve_split_fields() {
+        let context =
+            TemplateContext::default().with_fields("123\tfix bug", vec!["123", "fix bug"]);
+        assert_eq!(
+            resolve_template("${0} | #${1} ${2} [${3}]", &context),
+            "123\tfix bug | #123 fix bug []"
+        );
+    }
+
+    #[test]
+    fn test_resolve_json_fields() {
+        let context = TemplateContext::default().with_json(serde_json::json!({
+            "number": 42,
+            "title": "Crash on start",
+            "labels": [{"name": "bug"}],
+        }));
+        assert_eq!(
+            resolve_template("#${.number} ${.title} [${.labels.0.name}]", &context),
+            "#42 Crash on start [bug]"
+        );
+        assert_eq!(resolve_template("(${.missing})", &context), "()");
+        assert_eq!(
+            resolve_template("${.}", &context.clone().with_json(serde_json::json!([1]))),
+            "[1]"
+        );
+        // Without a JSON element the placeholder is left alone
+        assert_eq!(
+            resolve_template("${.title}", &TemplateContext::default()),
+            "${.title}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_unknown_variable_left_verbatim() {
+        assert_eq!(
+            resolve_template("${unknown} $HOME ${", &context()),
+            "${unknown} $HOME ${"
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_variables() {
+        // Set by cargo for the tests it runs
+        assert_eq!(
+            resolve_template("${env.CARGO_PKG_NAME}|${env.CARGO_PKG_NAME|sh}", &context()),
+            format!("{0}|'{0}'", env!("CARGO_PKG_NAME"))
+        );
+        assert_eq!(
+            resolve_template("${env.CCAUTO_UNSET_VARIABLE} ${env.}", &context()),
+            "${env.CCAUTO_UNSET_VARIABLE} ${env.}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_missing_variable_left_verbatim() {
+        let context = TemplateContext::default();
+        assert_eq!(
+            resolve_template("${trigger_name}", &context),
+            "${trigger_name}"
+        );
+    }
+
+    #[test]
+    fn test_substituted_values_are_not_expanded_again() {
+        let context = TemplateContext {
+            captures: vec![None, Some("${agent_id}".to_string())],
+            agent_id: Some("agent-0".to_string()),
+            ..TemplateContext::default()
+        };
+        assert_eq!(resolve_template("${1}", &context), "${agent_id}");
+    }
+
+    #[test]
+    fn test_resolve_action() {
+        let action = ActionType::SendKeys(vec!["echo ${agent_id}".to_string(), "\r".to_string()]);
+        assert_eq!(
+            resolve_action(&action, &context()),
+            ActionType::SendKeys(vec!["echo agent-3".to_string(), "\r".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_706_702_400), "2024-01-31T12:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    }
+
+    fn line(value: &str) -> TemplateContext {
+        TemplateContext::default().with_captures(vec![None, Some(value.to_string())])
+    }
+
+    /// Values that break naive quoting
+    const TRICKY: [&str; 6] = [
+        "it's \"quoted\"",
+        "`rm -rf ~` $(whoami) $HOME",
+        "line one\nline two",
+        "naïve ✓ 日本語",
+        "back\\slash",
+        "",
+    ];
+
+    #[test]
+    fn test_resolve_sh_escape() {
+        assert_eq!(resolve_template("${1|sh}", &line("a b")), "'a b'");
+        assert_eq!(resolve_template("${1|sh}", &line("it's")), r"'it'\''s'");
+
+        // The shell must see each value as one literal word
+        for value in TRICKY {
+            let command = resolve_template("printf %s ${1|sh}", &line(value));
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .output()
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                value,
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_json_escape() {
+        assert_eq!(
+            resolve_template(r#"{"text": ${1|json}}"#, &line("say \"hi\"\n")),
+            r#"{"text": "say \"hi\"\n"}"#
+        );
+
+        for value in TRICKY {
+            let body = resolve_template(r#"{"text": ${1|json}}"#, &line(value));
+            let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(parsed["text"], value);
+        }
+    }
+
+    #[test]
+    fn test_resolve_escape_filters_edge_cases() {
+        // Unescaped stays the default
+        assert_eq!(resolve_template("${1}", &line("$(x)")), "$(x)");
+        // Unknown filters and missing values are left verbatim
+        assert_eq!(resolve_template("${1|html}", &line("x")), "${1|html}");
+        assert_eq!(
+            resolve_template("${trigger_name|sh}", &TemplateContext::default()),
+            "${trigger_name|sh}"
+        );
+        assert_eq!(resolve_template("${agent_id|sh}", &context()), "'agent-3'");
+        assert_eq!(
+            resolve_template(
+                "${.title|json}",
+                &TemplateContext::default().with_json(serde_json::json!({"title": "a\tb"}))
+            ),
+            r#""a\tb""#
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use vte::{Params, Parser, Perform};
+
+/// Most spaces one cursor-forward sequence expands to, wider than common
+/// terminals; `ESC[65535C` would otherwise flood the matched text and logs
+const MAX_CURSOR_FORWARD: u16 = 256;
+
+/// Collects the printable text of a byte stream, dropping every control
+/// sequence the parser recognizes (CSI, OSC, DCS, charset selection, ...)
+#[derive(Default)]
+struct TextCollector {
+    text: String,
+}
+
+impl Perform for TextCollector {
+    fn print(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        // Line structure is kept for the line-based rules, other C0 controls
+        // (BEL, BS, SI/SO, ...) have no textual meaning
+        if matches!(byte, b'\n' | b'\r' | b'\t') {
+            self.text.push(byte as char);
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        // TUIs like claude move the cursor right instead of printing spaces
+        if action == 'C' && intermediates.is_empty() && !ignore {
+            let count = params
+                .iter()
+                .next()
+                .and_then(|param| param.first().copied())
+                .unwrap_or(1)
+                .clamp(1, MAX_CURSOR_FORWARD);
+            self.text.extend(std::iter::repeat_n(' ', count as usize));
+        }
+    }
+}
+
+/// Remove ANSI escape sequences from PTY output for cleaner pattern matching
+pub fn strip_ansi(text: &str) -> String {
+    let mut parser = Parser::new();
+    let mut collector = TextCollector::default();
+    for byte in text.bytes() {
+        parser.advance(&mut collector, byte);
+    }
+    collector.text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::sync::LazyLock;
+
+    /// The stripping rules matched against before the VTE parser, kept to
+    /// show where the two differ
+    static LEGACY_ANSI_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*[mGKHF]").unwrap());
+
+    fn strip_ansi_legacy(text: &str) -> String {
+        LEGACY_ANSI_REGEX.replace_all(text, "").into_owned()
+    }
+
+    /// Fragments of captured claude sessions, with the text they hold
+    const CLAUDE_CORPUS: &[(&str, &str)] = &[
+        // Colored prompt
+        (
+            "\x1b[38;2;215;119;87m✻\x1b[39m Welcome to \x1b[1mClaude Code\x1b[22m!\r\n",
+            "✻ Welcome to Claude Code!\r\n",
+        ),
+        // Line redraw of the spinner
+        (
+            "\x1b[2K\x1b[1G\x1b[38;5;174m· Thinking…\x1b[39m",
+            "· Thinking…",
+        ),
+        // Window title
+        (
+            "\x1b]0;✳ Fix the tests\x07Do you want to proceed?",
+            "Do you want to proceed?",
+        ),
+        // Title terminated by ST, bracketed paste and hidden cursor
+        (
+            "\x1b]2;claude\x1b\\\x1b[?2004h\x1b[?25l> Try \"refactor\"\x1b[?25h",
+            "> Try \"refactor\"",
+        ),
+        // Charset selection after a reset
+        (
+            "\x1b[0m\x1b(B\x1b)0Esc to interrupt\r\n",
+            "Esc to interrupt\r\n",
+        ),
+        // Spacing by cursor moves, relative line moves and a bell
+        ("\x1b[1A\x1b[2C│\x1b[1C>\x1b[1CYes\x07\x1b[3B", "  │ > Yes"),
+    ];
+
+    #[test]
+    fn test_strip_ansi_removes_colors() {
+        assert_eq!(strip_ansi("\x1b[31mred\x1b[0m text"), "red text");
+    }
+
+    #[test]
+    fn test_strip_ansi_plain_text_unchanged() {
+        assert_eq!(strip_ansi("plain text"), "plain text");
+        assert_eq!(strip_ansi("a\tb\r\nこんにちは\n"), "a\tb\r\nこんにちは\n");
+    }
+
+    #[test]
+    fn test_strip_ansi_caps_cursor_forward() {
+        assert_eq!(strip_ansi("a\x1b[3Cb"), "a   b");
+        let text = strip_ansi("a\x1b[65535Cb");
+        assert_eq!(text.len(), MAX_CURSOR_FORWARD as usize + 2);
+        assert!(text.starts_with("a ") && text.ends_with(" b"));
+    }
+
+    #[test]
+    fn test_strip_ansi_matches_legacy_on_what_it_handled() {
+        for (raw, text) in &CLAUDE_CORPUS[..2] {
+            assert_eq!(strip_ansi(raw), *text);
+            assert_eq!(strip_ansi(raw), strip_ansi_legacy(raw));
+        }
+    }
+
+    #[test]
+    fn test_strip_ansi_removes_what_legacy_leaked() {
+        for (raw, text) in &CLAUDE_CORPUS[2..] {
+            assert_eq!(strip_ansi(raw), *text);
+            assert_ne!(strip_ansi_legacy(raw), *text, "{:?}", raw);
+        }
+        assert!(strip_ansi_legacy(CLAUDE_CORPUS[2].0).contains("]0;"));
+    }
+}
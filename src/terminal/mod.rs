@@ -1,4 +1,11 @@
+pub mod ansi;
+pub mod paste;
+pub mod process_tree;
 pub mod pty_process;
 pub mod pty_process_trait;
 pub mod pty_session;
 pub mod pty_terminal;
+pub mod screen;
+pub mod secret;
+pub mod shell_integration;
+pub mod utf8;
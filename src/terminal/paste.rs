@@ -0,0 +1,49 @@
+/// Markers around pasted text in bracketed paste mode (xterm mode 2004)
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// Text of a key sequence written as `<paste>...</paste>`, the whole
+/// sequence being the token
+pub fn paste_token(keys: &str) -> Option<&str> {
+    keys.strip_prefix("<paste>")?.strip_suffix("</paste>")
+}
+
+/// Input a terminal sends when `text` is pasted: line breaks as Enter, and
+/// the whole text between paste markers if the program asked for them, so
+/// it doesn't run each line as it arrives. Markers in the text itself are
+/// dropped, so it can't end the paste early.
+pub fn paste_input(text: &str, bracketed: bool) -> String {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if !bracketed {
+        return text;
+    }
+    let text = text.replace(PASTE_START, "").replace(PASTE_END, "");
+    format!("{}{}{}", PASTE_START, text, PASTE_END)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_token() {
+        assert_eq!(
+            paste_token("<paste>fix\nthe tests</paste>"),
+            Some("fix\nthe tests")
+        );
+        assert_eq!(paste_token("<paste></paste>"), Some(""));
+        assert_eq!(paste_token("<paste>unterminated"), None);
+        assert_eq!(paste_token("say <paste>x</paste>"), None);
+    }
+
+    #[test]
+    fn test_paste_input() {
+        assert_eq!(
+            paste_input("line 1\nline 2\r\n", true),
+            "\x1b[200~line 1\rline 2\r\x1b[201~"
+        );
+        assert_eq!(paste_input("a\x1b[201~\nb", true), "\x1b[200~a\rb\x1b[201~");
+        // Without bracketed paste mode the program sees typed lines
+        assert_eq!(paste_input("a\nb", false), "a\rb");
+    }
+}
@@ -0,0 +1,372 @@
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The fields of /proc/<pid>/stat needed to walk a shell's processes
+#[derive(Debug, Clone, PartialEq)]
+struct ProcEntry {
+    pid: u32,
+    name: String,
+    ppid: u32,
+    session: u32,
+    zombie: bool,
+}
+
+/// A process started from an agent's shell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// Command name from /proc/<pid>/stat
+    pub name: String,
+    /// 1 for children of the shell (and orphans adopted by init), 2 for
+    /// their children, ...
+    pub depth: usize,
+}
+
+/// Processes started from the shell `shell_pid`, without the shell itself:
+/// the members of its session (the PTY makes the shell a session leader,
+/// and job control moves each command into its own process group) plus
+/// descendants that left the session
+pub fn shell_processes(shell_pid: u32) -> Vec<u32> {
+    collect_shell_processes(&read_proc_entries(), shell_pid)
+}
+
+fn collect_shell_processes(entries: &[ProcEntry], shell_pid: u32) -> Vec<u32> {
+    let mut found: BTreeSet<u32> = entries
+        .iter()
+        .filter(|entry| entry.session == shell_pid && entry.pid != shell_pid)
+        .map(|entry| entry.pid)
+        .collect();
+
+    // Descendants of the shell and of every process found so far
+    let mut parents = vec![shell_pid];
+    parents.extend(found.iter().copied());
+    while let Some(parent) = parents.pop() {
+        for entry in entries.iter().filter(|entry| entry.ppid == parent) {
+            if entry.pid != shell_pid && found.insert(entry.pid) {
+                parents.push(entry.pid);
+            }
+        }
+    }
+
+    found.into_iter().collect()
+}
+
+/// Running processes started from the shell `shell_pid`, as found by
+/// shell_processes, in tree order. Processes whose parent exited stay in
+/// the shell's session, so they still count, at depth 1.
+pub fn process_tree(shell_pid: u32) -> Vec<ProcessInfo> {
+    collect_process_tree(&read_proc_entries(), shell_pid)
+}
+
+fn collect_process_tree(entries: &[ProcEntry], shell_pid: u32) -> Vec<ProcessInfo> {
+    let found: BTreeMap<u32, &ProcEntry> = collect_shell_processes(entries, shell_pid)
+        .into_iter()
+        .filter_map(|pid| entries.iter().find(|entry| entry.pid == pid))
+        .filter(|entry| !entry.zombie)
+        .map(|entry| (entry.pid, entry))
+        .collect();
+
+    // Depth-first from the processes whose parent is not in the tree
+    let mut tree = Vec::new();
+    let mut stack: Vec<(&ProcEntry, usize)> = found
+        .values()
+        .rev()
+        .filter(|entry| !found.contains_key(&entry.ppid))
+        .map(|entry| (*entry, 1))
+        .collect();
+    while let Some((entry, depth)) = stack.pop() {
+        tree.push(ProcessInfo {
+            pid: entry.pid,
+            name: entry.name.clone(),
+            depth,
+        });
+        stack.extend(
+            found
+                .values()
+                .rev()
+                .filter(|child| child.ppid == entry.pid)
+                .map(|child| (*child, depth + 1)),
+        );
+    }
+    tree
+}
+
+/// Running processes started from the shell `shell_pid` in the order to
+/// signal them: children before their parents, so a parent can't react to
+/// a child's death by starting another. Without `recursive` only the
+/// processes at depth 1 of process_tree, e.g. the shell's direct children.
+pub fn kill_order(shell_pid: u32, recursive: bool) -> Vec<u32> {
+    collect_kill_order(&read_proc_entries(), shell_pid, recursive)
+}
+
+fn collect_kill_order(entries: &[ProcEntry], shell_pid: u32, recursive: bool) -> Vec<u32> {
+    let mut tree = collect_process_tree(entries, shell_pid);
+    if !recursive {
+        tree.retain(|process| process.depth == 1);
+    }
+    // Deepest first; the sort is stable, so siblings stay in tree order
+    tree.sort_by_key(|process| std::cmp::Reverse(process.depth));
+    tree.into_iter().map(|process| process.pid).collect()
+}
+
+/// Whether `pid` still runs; zombies only wait to be reaped
+pub fn is_alive(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| parse_entry(&stat))
+        .is_some_and(|entry| !entry.zombie)
+}
+
+/// Send `signal` to each of `pids`, ignoring processes that already exited.
+/// Returns how many processes were signaled.
+pub fn signal_all(pids: &[u32], signal: Signal) -> usize {
+    let mut signaled = 0;
+    for pid in pids {
+        match kill(Pid::from_raw(*pid as i32), signal) {
+            Ok(()) => signaled += 1,
+            Err(nix::errno::Errno::ESRCH) => {}
+            Err(e) => tracing::warn!("Failed to send {} to process {}: {}", signal, pid, e),
+        }
+    }
+    signaled
+}
+
+/// Command line of the most recently started of `child_pids`, the command
+/// in the foreground of an interactive shell. `start_time` orders the
+/// children, with PIDs breaking ties (and standing in where start times are
+/// unknown); `cmdline` resolves the winner.
+pub fn newest_command(
+    child_pids: &[u32],
+    start_time: impl Fn(u32) -> Option<u64>,
+    cmdline: impl Fn(u32) -> Option<String>,
+) -> Option<String> {
+    let newest = child_pids
+        .iter()
+        .copied()
+        .max_by_key(|pid| (start_time(*pid), *pid))?;
+    cmdline(newest)
+}
+
+/// Start time of `pid` in clock ticks since boot, from /proc/<pid>/stat
+pub fn start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Field 22 of the line; the first field after ')' is field 3
+    stat.get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()
+}
+
+/// Command line of `pid` with its arguments separated by spaces, read from
+/// /proc or, where there is no /proc (macOS), from `ps`
+pub fn cmdline(pid: u32) -> Option<String> {
+    let command = match std::fs::read(format!("/proc/{}/cmdline", pid)) {
+        Ok(raw) => parse_cmdline(&raw),
+        Err(_) if !std::path::Path::new("/proc/self").exists() => {
+            let output = std::process::Command::new("ps")
+                .args(["-o", "command=", "-p", &pid.to_string()])
+                .output()
+                .ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        Err(_) => return None,
+    };
+    // Zombies and kernel threads have no command line
+    (!command.is_empty()).then_some(command)
+}
+
+fn parse_cmdline(raw: &[u8]) -> String {
+    raw.split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn read_proc_entries() -> Vec<ProcEntry> {
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    dir.filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().parse::<u32>().is_ok())
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("stat")).ok())
+        .filter_map(|stat| parse_entry(&stat))
+        .collect()
+}
+
+/// Parse /proc/<pid>/stat. The command name in parentheses may contain
+/// spaces, so fields are counted after its closing parenthesis.
+fn parse_entry(stat: &str) -> Option<ProcEntry> {
+    let pid = stat.split_whitespace().next()?.parse().ok()?;
+    let name = stat.get(stat.find('(')? + 1..stat.rfind(')')?)?.to_string();
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    Some(ProcEntry {
+        pid,
+        name,
+        zombie: *fields.first()? == "Z",
+        ppid: fields.get(1)?.parse().ok()?,
+        session: fields.get(3)?.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: u32, ppid: u32, session: u32) -> ProcEntry {
+        ProcEntry {
+            pid,
+            name: format!("cmd-{}", pid),
+            ppid,
+            session,
+            zombie: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let stat = "4242 (my (odd) cmd) Z 17 4242 99 0 -1 4194304";
+        assert_eq!(
+            parse_entry(stat),
+            Some(ProcEntry {
+                pid: 4242,
+                name: "my (odd) cmd".to_string(),
+                ppid: 17,
+                session: 99,
+                zombie: true,
+            })
+        );
+        assert_eq!(parse_entry("garbage"), None);
+    }
+
+    #[test]
+    fn test_collect_shell_processes() {
+        let entries = vec![
+            entry(1, 0, 1),
+            // The shell and a job in its session
+            entry(100, 1, 100),
+            entry(101, 100, 100),
+            // A grandchild that called setsid, and its child
+            entry(102, 101, 102),
+            entry(103, 102, 102),
+            // Orphaned into init but still in the session
+            entry(104, 1, 100),
+            // Another agent's shell
+            entry(200, 1, 200),
+            entry(201, 200, 200),
+        ];
+        assert_eq!(collect_shell_processes(&entries, 100), [101, 102, 103, 104]);
+        assert_eq!(collect_shell_processes(&entries, 200), [201]);
+    }
+
+    #[test]
+    fn test_collect_process_tree() {
+        let mut zombie = entry(105, 100, 100);
+        zombie.zombie = true;
+        let entries = vec![
+            entry(1, 0, 1),
+            entry(100, 1, 100),
+            entry(101, 100, 100),
+            entry(102, 101, 102),
+            entry(103, 102, 102),
+            // Its parent exited, so init adopted it
+            entry(104, 1, 100),
+            zombie,
+            entry(106, 101, 100),
+        ];
+        let tree: Vec<(u32, usize)> = collect_process_tree(&entries, 100)
+            .iter()
+            .map(|process| (process.pid, process.depth))
+            .collect();
+        assert_eq!(tree, [(101, 1), (102, 2), (103, 3), (106, 2), (104, 1)]);
+        assert_eq!(collect_process_tree(&entries, 100)[0].name, "cmd-101");
+    }
+
+    #[test]
+    fn test_collect_kill_order() {
+        let entries = vec![
+            entry(1, 0, 1),
+            entry(100, 1, 100),
+            entry(101, 100, 100),
+            entry(102, 101, 102),
+            entry(103, 102, 102),
+            entry(104, 1, 100),
+            entry(106, 101, 100),
+        ];
+        // Leaves first, the shell itself never
+        assert_eq!(
+            collect_kill_order(&entries, 100, true),
+            [103, 102, 106, 101, 104]
+        );
+        assert_eq!(collect_kill_order(&entries, 100, false), [101, 104]);
+    }
+
+    #[test]
+    fn test_process_tree_finds_grandchildren() {
+        // The `; true` keeps each shell from exec'ing its last command
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sh -c 'sleep 30; true'; true"])
+            .spawn()
+            .unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let tree = loop {
+            let tree = process_tree(child.id());
+            if tree.iter().any(|process| process.name == "sleep") {
+                break tree;
+            }
+            assert!(std::time::Instant::now() < deadline, "{:?}", tree);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        };
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let pids: Vec<u32> = tree.iter().map(|process| process.pid).collect();
+        signal_all(&pids, Signal::SIGKILL);
+
+        let names: Vec<(&str, usize)> = tree
+            .iter()
+            .map(|process| (process.name.as_str(), process.depth))
+            .collect();
+        assert_eq!(names, [("sh", 1), ("sleep", 2)]);
+    }
+
+    #[test]
+    fn test_own_process_is_alive() {
+        assert!(is_alive(std::process::id()));
+        assert!(!is_alive(u32::MAX));
+    }
+
+    #[test]
+    fn test_newest_command() {
+        let cmdline = |pid: u32| Some(format!("cmd-{}", pid));
+        // The latest start wins, even over a higher (wrapped around) PID
+        let start_time = |pid: u32| Some(if pid == 300 { 50 } else { 100 });
+        assert_eq!(
+            newest_command(&[300, 12, 7], start_time, cmdline),
+            Some("cmd-12".to_string())
+        );
+        // Unknown start times fall back to PID order
+        assert_eq!(
+            newest_command(&[12, 300, 7], |_| None, cmdline),
+            Some("cmd-300".to_string())
+        );
+        assert_eq!(newest_command(&[], |_| None, cmdline), None);
+        assert_eq!(newest_command(&[5], |_| None, |_| None), None);
+    }
+
+    #[test]
+    fn test_parse_cmdline() {
+        assert_eq!(
+            parse_cmdline(b"claude\0--resume\0my task\0"),
+            "claude --resume my task"
+        );
+        assert_eq!(parse_cmdline(b""), "");
+        assert!(cmdline(std::process::id()).is_some());
+        assert!(start_time(std::process::id()).is_some());
+    }
+}
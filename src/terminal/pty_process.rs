@@ -1,13 +1,21 @@
+use super::process_tree;
 use super::pty_process_trait::PtyProcessTrait;
 use super::pty_session::{PtyCommand, PtyEvent, PtyEventData, PtySession};
+use super::pty_terminal::InputQueueFull;
+use super::screen::ScreenSnapshot;
+use super::secret;
 use crate::config::Config;
+use crate::config::terminal_config::TerminalConfig;
 use anyhow::Result;
+use bytes::Bytes;
+use nix::sys::signal::Signal;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
-use tokio::sync::{Mutex, broadcast, mpsc};
-use tracing::{error, info, warn};
+use tokio::sync::{Mutex, broadcast, mpsc, watch};
+use tracing::{error, info, trace, warn};
 
 #[derive(Debug, Error)]
 pub enum PtyProcessError {
@@ -17,10 +25,13 @@ pub enum PtyProcessError {
     CommunicationError(String),
     #[error("PTY process not running")]
     NotRunning,
+    #[error(transparent)]
+    InputQueueFull(#[from] InputQueueFull),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum PtyMessage {
@@ -47,6 +58,8 @@ pub struct PtyProcessConfig {
     pub shell_command: Option<String>,
     pub cols: u16,
     pub rows: u16,
+    /// Environment of the shell, e.g. TERM, and buffering of its PTY
+    pub terminal: TerminalConfig,
 }
 
 impl Default for PtyProcessConfig {
@@ -55,18 +68,20 @@ impl Default for PtyProcessConfig {
             shell_command: Some(std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string())),
             cols: 80,
             rows: 24,
+            terminal: TerminalConfig::default(),
         }
     }
 }
 
 impl PtyProcessConfig {
-    /// Create PtyProcessConfig from Config
-    pub fn from_config(config: &Config) -> Self {
+    /// Create PtyProcessConfig from Config for the agent `agent_id`
+    pub fn from_config(config: &Config, agent_id: &str) -> Self {
         let (cols, rows) = (config.web_ui.cols, config.web_ui.rows);
         Self {
             shell_command: Some(std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string())),
             cols,
             rows,
+            terminal: config.terminal_for(agent_id),
         }
     }
 }
@@ -74,25 +89,53 @@ impl PtyProcessConfig {
 pub struct PtyProcess {
     config: PtyProcessConfig,
     session: Arc<Mutex<Option<Arc<PtySession>>>>,
-    event_rx: Arc<Mutex<Option<broadcast::Receiver<PtyEvent>>>>,
     response_tx: Arc<Mutex<Option<mpsc::UnboundedSender<PtyResponse>>>>,
     response_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<PtyResponse>>>>,
+    // Channels outliving a single shell session, so rule monitors and web UI
+    // clients keep receiving after a restart
+    string_tx: broadcast::Sender<String>,
+    bytes_tx: broadcast::Sender<Bytes>,
+    /// Events ending a session (see `PtyEventData::exit_reason`), `init`
+    /// events of all sessions, `resize` events and OSC 133 command marks
+    lifecycle_tx: broadcast::Sender<PtyEvent>,
+    /// Window title of the current shell; a watch channel, so a flood of
+    /// title changes (e.g. a spinner) can't push exit events out
+    title_tx: watch::Sender<String>,
+    /// Whether the current shell shows the alternate screen (full-screen TUIs)
+    alternate_screen_tx: watch::Sender<bool>,
+    /// Set by `start`; the channels stay usable while the shell is stopped
+    started: AtomicBool,
+    /// Current terminal size (cols, rows), starting from the config's
+    size: std::sync::Mutex<(u16, u16)>,
+    /// Input queue of the current shell, for `pending_input`
+    input_queue: std::sync::Mutex<Option<mpsc::WeakSender<Bytes>>>,
 }
 
+/// Buffered lifecycle events per subscriber
+const LIFECYCLE_CHANNEL_CAPACITY: usize = 16;
+
 impl PtyProcess {
     pub fn new(config: PtyProcessConfig) -> Self {
         Self {
-            config,
             session: Arc::new(Mutex::new(None)),
-            event_rx: Arc::new(Mutex::new(None)),
             response_tx: Arc::new(Mutex::new(None)),
             response_rx: Arc::new(Mutex::new(None)),
+            // A zero capacity would panic
+            string_tx: broadcast::channel(config.terminal.output_channel_capacity.max(1)).0,
+            bytes_tx: broadcast::channel(config.terminal.output_channel_capacity.max(1)).0,
+            lifecycle_tx: broadcast::channel(LIFECYCLE_CHANNEL_CAPACITY).0,
+            title_tx: watch::channel(String::new()).0,
+            alternate_screen_tx: watch::channel(false).0,
+            started: AtomicBool::new(false),
+            size: std::sync::Mutex::new((config.cols, config.rows)),
+            input_queue: std::sync::Mutex::new(None),
+            config,
         }
     }
 
-    /// Create PtyProcess directly from Config
-    pub fn from_config(config: &Config) -> Self {
-        let pty_config = PtyProcessConfig::from_config(config);
+    /// Create PtyProcess directly from Config for the agent `agent_id`
+    pub fn from_config(config: &Config, agent_id: &str) -> Self {
+        let pty_config = PtyProcessConfig::from_config(config, agent_id);
         Self::new(pty_config)
     }
 
@@ -106,67 +149,200 @@ impl PtyProcess {
 
         info!("Starting PTY process with config: {:?}", self.config);
 
-        let shell = self.config.shell_command.as_deref().unwrap_or("bash");
-        let session = Arc::new(
-            PtySession::new(
-                shell.to_string(),
-                self.config.cols as usize,
-                self.config.rows as usize,
-            )
+        *session_lock = Some(self.spawn_session().await?);
+        self.started.store(true, Ordering::SeqCst);
+
+        info!("PTY process started successfully");
+        Ok(())
+    }
+
+    /// Replace the shell with a fresh one, e.g. after it exited. Receivers
+    /// keep working and lifecycle subscribers get an `init` event with the
+    /// new screen.
+    pub async fn restart(&self) -> Result<(), PtyProcessError> {
+        let mut session_lock = self.session.lock().await;
+        session_lock.take();
+
+        info!("Restarting PTY process with config: {:?}", self.config);
+
+        let session = self.spawn_session().await?;
+        *session_lock = Some(session.clone());
+        drop(session_lock);
+
+        let initial_data = session.get_screen_contents().await.unwrap_or_default();
+        let (cols, rows) = *self.size.lock().unwrap();
+        let init_event = PtyEvent {
+            event_type: "init".to_string(),
+            time: 0.0,
+            data: PtyEventData::Init {
+                cols: cols as usize,
+                rows: rows as usize,
+                initial_data,
+                initial_seq: String::new(),
+            },
+        };
+        // No subscribers is fine (e.g. no web UI client connected)
+        let _ = self.lifecycle_tx.send(init_event);
+
+        info!("PTY process restarted successfully");
+        Ok(())
+    }
+
+    /// Hang up the shell until the next `restart`. Lifecycle subscribers
+    /// get its `exit` event like for any other exit.
+    pub async fn stop(&self) -> Result<(), PtyProcessError> {
+        let session = self
+            .session
+            .lock()
             .await
-            .map_err(|e| PtyProcessError::StartupFailure(e.to_string()))?,
-        );
+            .take()
+            .ok_or(PtyProcessError::NotRunning)?;
+
+        // The event processor holds the session until the shell exits
+        if let Ok(Some(shell_pid)) = session.get_shell_pid().await {
+            info!("Stopping PTY process (shell pid {})", shell_pid);
+            process_tree::signal_all(&[shell_pid], Signal::SIGHUP);
+        }
+        Ok(())
+    }
+
+    /// Resize the terminal of the current shell, if any, and of the shells
+    /// started later. Lifecycle subscribers get a `resize` event.
+    pub async fn resize(&self, cols: u16, rows: u16) -> Result<(), PtyProcessError> {
+        // Held across the session's resize so concurrent calls end in order
+        let session_lock = self.session.lock().await;
+        if let Some(session) = session_lock.as_ref() {
+            session
+                .handle_command(PtyCommand::Resize {
+                    cols: cols as usize,
+                    rows: rows as usize,
+                })
+                .await
+                .map_err(|e| PtyProcessError::CommunicationError(e.to_string()))?;
+        }
+        *self.size.lock().unwrap() = (cols, rows);
+        drop(session_lock);
+
+        let _ = self.lifecycle_tx.send(PtyEvent {
+            event_type: "resize".to_string(),
+            time: 0.0,
+            data: PtyEventData::Resize {
+                cols: cols as usize,
+                rows: rows as usize,
+            },
+        });
+        Ok(())
+    }
+
+    /// Start a shell session and connect it to the process-wide channels.
+    /// Lifecycle subscribers get a `spawnFailed` event if it can't be started.
+    async fn spawn_session(&self) -> Result<Arc<PtySession>, PtyProcessError> {
+        let shell = self.config.shell_command.as_deref().unwrap_or("bash");
+        let argv: Vec<String> = shell.split_whitespace().map(str::to_string).collect();
+        let (cols, rows) = *self.size.lock().unwrap();
+        let session =
+            match PtySession::new(&argv, cols as usize, rows as usize, &self.config.terminal).await
+            {
+                Ok(session) => Arc::new(session),
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    let _ = self.lifecycle_tx.send(PtyEvent {
+                        event_type: "spawnFailed".to_string(),
+                        time: 0.0,
+                        data: PtyEventData::SpawnFailed {
+                            error: error.clone(),
+                        },
+                    });
+                    return Err(PtyProcessError::StartupFailure(error));
+                }
+            };
 
         let event_rx = session.subscribe().await;
         let (response_tx, response_rx) = mpsc::unbounded_channel();
+        // The title and screen mode of the previous shell are gone with its terminal
+        self.title_tx.send_replace(String::new());
+        self.alternate_screen_tx.send_replace(false);
 
-        *session_lock = Some(session.clone());
-        *self.event_rx.lock().await = Some(event_rx);
         *self.response_tx.lock().await = Some(response_tx.clone());
         *self.response_rx.lock().await = Some(response_rx);
 
         tokio::spawn(event_processor(
             session.clone(),
-            self.event_rx.clone(),
+            event_rx,
             response_tx,
+            self.lifecycle_tx.clone(),
+            self.title_tx.clone(),
+            self.alternate_screen_tx.clone(),
         ));
 
-        info!("PTY process started successfully");
-        Ok(())
+        let string_rx = session
+            .get_pty_string_receiver()
+            .await
+            .map_err(|e| PtyProcessError::StartupFailure(e.to_string()))?;
+        let bytes_rx = session
+            .get_pty_bytes_receiver()
+            .await
+            .map_err(|e| PtyProcessError::StartupFailure(e.to_string()))?;
+        tokio::spawn(forward_output(string_rx, self.string_tx.clone()));
+        tokio::spawn(forward_output(bytes_rx, self.bytes_tx.clone()));
+        *self.input_queue.lock().unwrap() = Some(session.input_queue());
+
+        Ok(session)
     }
 
     pub async fn send_input(&self, input: String) -> Result<(), PtyProcessError> {
-        info!("🔍 send_input called with: {:?}", input);
+        trace!(
+            "🔍 send_input called with: {:?}",
+            secret::registry().redact(&input)
+        );
 
-        info!("🔐 Attempting to acquire session lock...");
-        let session_lock = self.session.lock().await;
-        info!("✅ Session lock acquired");
+        // Not held while writing: waiting for a full input queue mustn't
+        // keep the shell from being stopped or restarted
+        let session = self.session.lock().await.clone();
 
-        if let Some(session) = session_lock.as_ref() {
+        if let Some(session) = session {
             let command = PtyCommand::Input { payload: input };
-            info!("📨 About to call session.handle_command");
-            session
-                .handle_command(command)
-                .await
-                .map_err(|e| PtyProcessError::CommunicationError(e.to_string()))?;
-            info!("✅ session.handle_command completed");
+            trace!("📨 About to call session.handle_command");
+            session.handle_command(command).await.map_err(|e| {
+                match e.downcast::<InputQueueFull>() {
+                    Ok(full) => PtyProcessError::InputQueueFull(full),
+                    Err(e) => PtyProcessError::CommunicationError(e.to_string()),
+                }
+            })?;
+            trace!("✅ session.handle_command completed");
             Ok(())
         } else {
             Err(PtyProcessError::NotRunning)
         }
     }
 
+    /// Send `signal` to the program in the foreground of the shell
+    pub async fn send_signal(&self, signal: Signal) -> Result<(), PtyProcessError> {
+        let session = self.session.lock().await.clone();
+        match session {
+            Some(session) => session
+                .send_signal(signal)
+                .await
+                .map_err(|e| PtyProcessError::CommunicationError(format!("{:#}", e))),
+            None => Err(PtyProcessError::NotRunning),
+        }
+    }
+
+    /// Writes queued for the current shell that it hasn't read yet
+    pub fn pending_input(&self) -> usize {
+        let queue = self.input_queue.lock().unwrap();
+        match queue.as_ref().and_then(mpsc::WeakSender::upgrade) {
+            Some(sender) => sender.max_capacity() - sender.capacity(),
+            None => 0,
+        }
+    }
+
     /// Get direct access to PTY raw bytes receiver for WebSocket streaming
     pub async fn get_pty_bytes_receiver(
         &self,
     ) -> Result<tokio::sync::broadcast::Receiver<bytes::Bytes>, PtyProcessError> {
-        let session_lock = self.session.lock().await;
-
-        if let Some(session) = session_lock.as_ref() {
-            session
-                .get_pty_bytes_receiver()
-                .await
-                .map_err(|e| PtyProcessError::CommunicationError(e.to_string()))
+        if self.started.load(Ordering::SeqCst) {
+            Ok(self.bytes_tx.subscribe())
         } else {
             Err(PtyProcessError::NotRunning)
         }
@@ -176,18 +352,29 @@ impl PtyProcess {
     pub async fn get_pty_string_receiver(
         &self,
     ) -> Result<tokio::sync::broadcast::Receiver<String>, PtyProcessError> {
-        let session_lock = self.session.lock().await;
-
-        if let Some(session) = session_lock.as_ref() {
-            session
-                .get_pty_string_receiver()
-                .await
-                .map_err(|e| PtyProcessError::CommunicationError(e.to_string()))
+        if self.started.load(Ordering::SeqCst) {
+            Ok(self.string_tx.subscribe())
         } else {
             Err(PtyProcessError::NotRunning)
         }
     }
 
+    /// Get the events ending the shell, `init` and `resize` events, across
+    /// restarts
+    pub fn get_pty_event_receiver(&self) -> broadcast::Receiver<PtyEvent> {
+        self.lifecycle_tx.subscribe()
+    }
+
+    /// Get the window title of the current shell, across restarts
+    pub fn get_title_receiver(&self) -> watch::Receiver<String> {
+        self.title_tx.subscribe()
+    }
+
+    /// Get whether the current shell shows the alternate screen, across restarts
+    pub fn get_alternate_screen_receiver(&self) -> watch::Receiver<bool> {
+        self.alternate_screen_tx.subscribe()
+    }
+
     /// Get current screen contents for WebSocket initial state
     pub async fn get_screen_contents(&self) -> Result<String, PtyProcessError> {
         let session_lock = self.session.lock().await;
@@ -202,6 +389,16 @@ impl PtyProcess {
         }
     }
 
+    /// Get the current screen as plain text
+    pub async fn get_screen_text(&self) -> Result<ScreenSnapshot, PtyProcessError> {
+        let session_lock = self.session.lock().await;
+
+        match session_lock.as_ref() {
+            Some(session) => Ok(session.get_screen_text().await),
+            None => Err(PtyProcessError::NotRunning),
+        }
+    }
+
     /// Get the PID of the shell process
     pub async fn get_shell_pid(&self) -> Result<Option<u32>, PtyProcessError> {
         let session_lock = self.session.lock().await;
@@ -216,9 +413,37 @@ impl PtyProcess {
         }
     }
 
-    /// Get child processes of the shell process
+    /// Signal the processes started from the shell, leaving the shell and
+    /// its PTY alone: the whole tree leaf-first, or only the shell's direct
+    /// children without `recursive`. Returns how many were signaled.
+    pub async fn kill_children(
+        &self,
+        signal: Signal,
+        recursive: bool,
+    ) -> Result<usize, PtyProcessError> {
+        let Some(shell_pid) = self.get_shell_pid().await? else {
+            return Ok(0);
+        };
+        let pids = if std::path::Path::new("/proc/self").exists() {
+            process_tree::kill_order(shell_pid, recursive)
+        } else {
+            // Without /proc only the direct children are known
+            self.get_child_processes().await?
+        };
+        let signaled = process_tree::signal_all(&pids, signal);
+        info!("Sent {} to {} processes of the shell", signal, signaled);
+        Ok(signaled)
+    }
+
+    /// Get the processes started from the shell: its whole process tree,
+    /// including processes whose parent already exited. Without /proc
+    /// (macOS) only direct children are found.
     pub async fn get_child_processes(&self) -> Result<Vec<u32>, PtyProcessError> {
         if let Ok(Some(shell_pid)) = self.get_shell_pid().await {
+            if std::path::Path::new("/proc/self").exists() {
+                let tree = process_tree::process_tree(shell_pid);
+                return Ok(tree.iter().map(|process| process.pid).collect());
+            }
             let output = Command::new("pgrep")
                 .arg("-P")
                 .arg(shell_pid.to_string())
@@ -240,50 +465,58 @@ impl PtyProcess {
     }
 }
 
+/// Relay a session's output as responses, its title and screen mode changes
+/// to their watch channels and its command marks and exit to lifecycle subscribers. The session is held until
+/// its shell exits.
 async fn event_processor(
-    session: Arc<PtySession>,
-    event_rx: Arc<Mutex<Option<broadcast::Receiver<PtyEvent>>>>,
+    _session: Arc<PtySession>,
+    mut rx: broadcast::Receiver<PtyEvent>,
     response_tx: mpsc::UnboundedSender<PtyResponse>,
+    lifecycle_tx: broadcast::Sender<PtyEvent>,
+    title_tx: watch::Sender<String>,
+    alternate_screen_tx: watch::Sender<bool>,
 ) {
-    let mut rx = {
-        let guard = event_rx.lock().await;
-        if let Some(rx) = guard.as_ref() {
-            rx.resubscribe()
-        } else {
-            return;
-        }
-    };
-
-    while let Ok(event) = rx.recv().await {
-        match event.event_type.as_str() {
-            "output" => {
-                if let PtyEventData::Output { data } = event.data {
-                    info!(
-                        "🎉 Processing Output event: {} bytes: {:?}",
-                        data.len(),
-                        data
-                    );
-
-                    // Send the output data to the terminal's output channel
-                    if let Err(e) = session.send_output_data(&data).await {
-                        error!("Failed to send output to terminal: {}", e);
-                    } else {
-                        info!("✅ Output data sent to terminal successfully");
-                    }
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match event.data {
+            PtyEventData::Output { data } => {
+                trace!(
+                    "🎉 Processing Output event: {} bytes: {:?}",
+                    data.len(),
+                    data
+                );
 
-                    let response = PtyResponse::Output {
-                        response_type: "output".to_string(),
-                        data,
-                    };
+                let response = PtyResponse::Output {
+                    response_type: "output".to_string(),
+                    data,
+                };
 
-                    if response_tx.send(response).is_err() {
-                        error!("❌ Failed to send output response");
-                        break;
-                    } else {
-                        info!("✅ Output response sent successfully");
-                    }
+                if response_tx.send(response).is_err() {
+                    error!("❌ Failed to send output response");
+                    break;
+                } else {
+                    trace!("✅ Output response sent successfully");
                 }
             }
+            PtyEventData::TitleChanged { title } => {
+                title_tx.send_replace(title);
+            }
+            PtyEventData::ScreenModeChanged { alternate } => {
+                alternate_screen_tx.send_replace(alternate);
+            }
+            PtyEventData::CommandStarted {} | PtyEventData::CommandFinished { .. } => {
+                let _ = lifecycle_tx.send(event);
+            }
+            ref data if data.exit_reason().is_some() => {
+                info!("🔚 Shell exited: {:?}", data);
+                // Release the session so a restart can drop it
+                let _ = lifecycle_tx.send(event);
+                break;
+            }
             _ => {
                 // Ignore other event types for now
             }
@@ -291,6 +524,24 @@ async fn event_processor(
     }
 }
 
+/// Forward one session's output into a process-wide channel until the
+/// session's channel closes
+async fn forward_output<T: Clone + Send + 'static>(
+    mut rx: broadcast::Receiver<T>,
+    tx: broadcast::Sender<T>,
+) {
+    loop {
+        match rx.recv().await {
+            Ok(item) => {
+                // No subscribers is fine (e.g. no web UI client connected)
+                let _ = tx.send(item);
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
 impl Drop for PtyProcess {
     fn drop(&mut self) {
         if let Ok(mut session) = self.session.try_lock() {
@@ -315,13 +566,622 @@ impl PtyProcessTrait for PtyProcess {
         self.get_child_processes().await
     }
 
+    async fn get_current_command(&self) -> Result<Option<String>, PtyProcessError> {
+        let children = self.get_child_processes().await?;
+        Ok(process_tree::newest_command(
+            &children,
+            process_tree::start_time,
+            process_tree::cmdline,
+        ))
+    }
+
     async fn get_screen_contents(&self) -> Result<String, PtyProcessError> {
         self.get_screen_contents().await
     }
 
+    async fn get_screen_text(&self) -> Result<ScreenSnapshot, PtyProcessError> {
+        self.get_screen_text().await
+    }
+
     async fn get_pty_bytes_receiver(
         &self,
     ) -> Result<broadcast::Receiver<bytes::Bytes>, PtyProcessError> {
         self.get_pty_bytes_receiver().await
     }
+
+    async fn get_pty_event_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<PtyEvent>, PtyProcessError> {
+        Ok(self.get_pty_event_receiver())
+    }
+
+    async fn get_title_receiver(&self) -> Result<watch::Receiver<String>, PtyProcessError> {
+        Ok(self.get_title_receiver())
+    }
+
+    async fn get_alternate_screen_receiver(
+        &self,
+    ) -> Result<watch::Receiver<bool>, PtyProcessError> {
+        Ok(self.get_alternate_screen_receiver())
+    }
+
+    async fn restart(&self) -> Result<(), PtyProcessError> {
+        self.restart().await
+    }
+
+    async fn stop(&self) -> Result<(), PtyProcessError> {
+        self.stop().await
+    }
+
+    async fn resize(&self, cols: u16, rows: u16) -> Result<(), PtyProcessError> {
+        self.resize(cols, rows).await
+    }
+
+    async fn get_shell_pid(&self) -> Result<Option<u32>, PtyProcessError> {
+        self.get_shell_pid().await
+    }
+
+    async fn send_signal(&self, signal: Signal) -> Result<(), PtyProcessError> {
+        self.send_signal(signal).await
+    }
+
+    async fn kill_children(
+        &self,
+        signal: Signal,
+        recursive: bool,
+    ) -> Result<usize, PtyProcessError> {
+        self.kill_children(signal, recursive).await
+    }
+
+    fn pending_input(&self) -> usize {
+        self.pending_input()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::terminal_config::InputFull;
+
+    #[tokio::test]
+    async fn test_spawn_failure_is_reported() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("/nonexistent/shell".to_string()),
+            ..Default::default()
+        });
+        let mut events = process.get_pty_event_receiver();
+
+        assert!(process.restart().await.is_err());
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event_type, "spawnFailed");
+        assert!(matches!(event.data, PtyEventData::SpawnFailed { .. }));
+    }
+
+    /// Read `output` until it contains `expected`, failing after 5 seconds
+    async fn read_until(output: &mut broadcast::Receiver<String>, expected: &str) {
+        let mut seen = String::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !seen.contains(expected) {
+                seen.push_str(&output.recv().await.unwrap());
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no {:?} in {:?}", expected, seen));
+    }
+
+    /// Whether a `sleep` runs in the shell, once it changes to `running`
+    async fn wait_for_sleep(process: &PtyProcess, running: bool) {
+        let shell_pid = process.get_shell_pid().await.unwrap().unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let tree = process_tree::process_tree(shell_pid);
+                if tree.iter().any(|process| process.name == "sleep") == running {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("sleep never became running={}", running));
+    }
+
+    #[tokio::test]
+    async fn test_kill_children_spares_the_shell() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut output = process.get_pty_string_receiver().await.unwrap();
+
+        // A background job with a child of its own; only the job is a
+        // child of the shell
+        process
+            .send_input("sh -c 'sleep 30; true' &\r".to_string())
+            .await
+            .unwrap();
+        wait_for_sleep(&process, true).await;
+        assert_eq!(
+            process.kill_children(Signal::SIGKILL, false).await.unwrap(),
+            1
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let shell_pid = process.get_shell_pid().await.unwrap().unwrap();
+        let left = process_tree::process_tree(shell_pid);
+        assert_eq!(
+            left.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+            ["sleep"],
+        );
+
+        // The orphan is still found, and the shell keeps working
+        assert_eq!(
+            process.kill_children(Signal::SIGKILL, true).await.unwrap(),
+            1
+        );
+        wait_for_sleep(&process, false).await;
+        process
+            .send_input("echo alive\r".to_string())
+            .await
+            .unwrap();
+        read_until(&mut output, "alive\r\n").await;
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signal_reaches_program_with_isig_off() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+
+        process
+            .send_input("stty -isig; sleep 30\r".to_string())
+            .await
+            .unwrap();
+        wait_for_sleep(&process, true).await;
+
+        // With ISIG off, C-c is just another byte of input
+        process.send_input("\x03".to_string()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        wait_for_sleep(&process, true).await;
+
+        process.send_signal(Signal::SIGINT).await.unwrap();
+        wait_for_sleep(&process, false).await;
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signal_runs_trap_of_foreground_program() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut output = process.get_pty_string_receiver().await.unwrap();
+
+        process
+            .send_input(
+                "stty -isig; sh -c 'trap \"echo trapped-$((6*7)); exit\" INT; \
+                 while :; do sleep 0.1; done'\r"
+                    .to_string(),
+            )
+            .await
+            .unwrap();
+        wait_for_sleep(&process, true).await;
+
+        process.send_signal(Signal::SIGINT).await.unwrap();
+        read_until(&mut output, "trapped-42").await;
+
+        // Only the foreground program got the signal, the shell carries on
+        process
+            .send_input("echo alive-$((6*7))\r".to_string())
+            .await
+            .unwrap();
+        read_until(&mut output, "alive-42").await;
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resize_reaches_the_shell() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut events = process.get_pty_event_receiver();
+        let mut output = process.get_pty_string_receiver().await.unwrap();
+
+        process.resize(132, 43).await.unwrap();
+        assert!(matches!(
+            events.try_recv().unwrap().data,
+            PtyEventData::Resize {
+                cols: 132,
+                rows: 43
+            }
+        ));
+
+        process.send_input("stty size\r".to_string()).await.unwrap();
+        let mut seen = String::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !seen.contains("43 132") {
+                seen.push_str(&output.recv().await.unwrap());
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no size in {:?}", seen));
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_character_split_across_reads() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut output = process.get_pty_string_receiver().await.unwrap();
+
+        // こ is e3 81 93; the pause makes the PTY deliver it in two reads
+        process
+            .send_input("printf '[\\343\\201'; sleep 0.3; printf '\\223]\\n'\r".to_string())
+            .await
+            .unwrap();
+        let mut seen = String::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !seen.contains("[こ]") && !seen.contains('\u{fffd}') {
+                seen.push_str(&output.recv().await.unwrap());
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no output in {:?}", seen));
+        assert!(seen.contains("[こ]"), "{:?}", seen);
+        assert!(!seen.contains('\u{fffd}'), "{:?}", seen);
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_capped_by_read_buf_size() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            terminal: TerminalConfig {
+                read_buf_size: 256,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut output = process.get_pty_bytes_receiver().await.unwrap();
+
+        process
+            .send_input("head -c 3000 /dev/zero | tr '\\0' x; echo\r".to_string())
+            .await
+            .unwrap();
+        let mut received = 0;
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while received < 3000 {
+                let chunk = output.recv().await.unwrap();
+                assert!(chunk.len() <= 256, "chunk of {} bytes", chunk.len());
+                received += chunk.iter().filter(|&&byte| byte == b'x').count();
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("only {} bytes arrived", received));
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_many_shells_dont_starve_the_runtime() {
+        const SHELLS: usize = 16;
+        const LINES: usize = 300;
+
+        // A timer on the same (single-threaded) runtime as the readers: a
+        // read blocking the runtime would stop it for as long as it waits
+        let probe = tokio::spawn(async {
+            let mut longest = std::time::Duration::ZERO;
+            let mut last = std::time::Instant::now();
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                longest = longest.max(last.elapsed());
+                last = std::time::Instant::now();
+                if longest > std::time::Duration::from_secs(1) {
+                    return longest;
+                }
+            }
+        });
+
+        let mut shells = Vec::new();
+        for _ in 0..SHELLS {
+            let process = PtyProcess::new(PtyProcessConfig {
+                shell_command: Some("sh".to_string()),
+                ..Default::default()
+            });
+            process.start().await.unwrap();
+            let output = process.get_pty_string_receiver().await.unwrap();
+            process
+                .send_input(format!(
+                    "i=0; while [ $i -lt {} ]; do echo \"#$i\"; i=$((i+1)); done\r",
+                    LINES
+                ))
+                .await
+                .unwrap();
+            shells.push((process, output));
+        }
+
+        let line = regex::Regex::new(r"#(\d+)").unwrap();
+        for (process, mut output) in shells {
+            let mut numbers = Vec::new();
+            let mut text = String::new();
+            tokio::time::timeout(std::time::Duration::from_secs(30), async {
+                while numbers.len() < LINES {
+                    text.push_str(&output.recv().await.unwrap());
+                    // Only complete lines, a number may still be cut off
+                    let end = text.rfind('\n').map_or(0, |end| end + 1);
+                    numbers.extend(
+                        line.captures_iter(&text[..end])
+                            .map(|captures| captures[1].parse::<usize>().unwrap()),
+                    );
+                    text.drain(..end);
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("only {} lines arrived", numbers.len()));
+            // Every line, in the order the shell wrote them
+            assert_eq!(numbers, (0..LINES).collect::<Vec<_>>());
+            process.stop().await.unwrap();
+        }
+
+        assert!(
+            !probe.is_finished(),
+            "runtime stalled for {:?}",
+            probe.await
+        );
+        probe.abort();
+    }
+
+    /// Shell busy with `sleep` and not reading its input, so the writer
+    /// stalls once the PTY's own buffer is full. Canonical mode would drop
+    /// the input of an overlong line instead.
+    async fn stalled_shell(on_input_full: InputFull) -> PtyProcess {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            terminal: TerminalConfig {
+                input_channel_bound: 4,
+                on_input_full,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        process
+            .send_input("stty -icanon -echo; sleep 30\r".to_string())
+            .await
+            .unwrap();
+        process
+    }
+
+    #[tokio::test]
+    async fn test_full_input_queue_fails_fast() {
+        let process = stalled_shell(InputFull::Fail).await;
+
+        let chunk = "x".repeat(8192);
+        let error = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                if let Err(e) = process.send_input(chunk.clone()).await {
+                    break e;
+                }
+            }
+        })
+        .await
+        .expect("input was never refused");
+
+        assert!(
+            matches!(
+                error,
+                PtyProcessError::InputQueueFull(InputQueueFull { pending: 4 })
+            ),
+            "{:?}",
+            error
+        );
+        assert_eq!(process.pending_input(), 4);
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_input_queue_waits() {
+        let process = Arc::new(stalled_shell(InputFull::Wait).await);
+
+        let chunk = "x".repeat(8192);
+        let sender = tokio::spawn({
+            let process = Arc::clone(&process);
+            async move {
+                loop {
+                    process.send_input(chunk.clone()).await?;
+                }
+            }
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while process.pending_input() < 4 {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("input queue never filled");
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!sender.is_finished());
+        assert_eq!(process.pending_input(), 4);
+
+        // A waiting write doesn't keep the shell from being stopped, and
+        // fails once the shell is gone
+        process.stop().await.unwrap();
+        let result: Result<(), PtyProcessError> =
+            tokio::time::timeout(std::time::Duration::from_secs(10), sender)
+                .await
+                .expect("write still waiting after stop")
+                .unwrap();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_title_follows_osc_sequences() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut title = process.get_title_receiver();
+        let wait_for = |title: &mut watch::Receiver<String>, expected: &'static str| {
+            let mut title = title.clone();
+            async move {
+                tokio::time::timeout(
+                    std::time::Duration::from_secs(5),
+                    title.wait_for(|title| title == expected),
+                )
+                .await
+                .unwrap_or_else(|_| panic!("title never became {:?}", expected))
+                .unwrap();
+            }
+        };
+
+        // OSC 0 ended by BEL, between output on the same line
+        process
+            .send_input("printf 'a\\033]0;✳ Working\\007b\\n'\r".to_string())
+            .await
+            .unwrap();
+        wait_for(&mut title, "✳ Working").await;
+
+        // OSC 2 ended by ST; OSC 1 only names the icon
+        process
+            .send_input("printf '\\033]2;Done\\033\\\\\\033]1;icon\\007'\r".to_string())
+            .await
+            .unwrap();
+        wait_for(&mut title, "Done").await;
+        let screen = process.get_screen_text().await.unwrap();
+        assert!(screen.lines.iter().any(|line| line == "ab"), "{:?}", screen);
+
+        // A new shell starts without a title
+        process.restart().await.unwrap();
+        assert_eq!(*title.borrow_and_update(), "");
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shell_integration_marks_become_events() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let mut events = process.get_pty_event_receiver();
+
+        // What an integrated shell prints around a failing command
+        process
+            .send_input(
+                "printf '\\033]133;C\\007oops\\033]133;D;2\\007\\033]133;A\\007'\r".to_string(),
+            )
+            .await
+            .unwrap();
+        let mut marks = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while marks.len() < 2 {
+                let event = events.recv().await.unwrap();
+                match event.data {
+                    PtyEventData::CommandStarted {} => marks.push((event.event_type, None)),
+                    PtyEventData::CommandFinished { exit_code } => {
+                        marks.push((event.event_type, exit_code))
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("command marks never arrived");
+        assert_eq!(
+            marks,
+            [
+                ("commandStarted".to_string(), None),
+                ("commandFinished".to_string(), Some(2))
+            ]
+        );
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alternate_screen_follows_mode_switches() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        process.start().await.unwrap();
+        let alternate = process.get_alternate_screen_receiver();
+        let wait_for = |expected: bool| {
+            let mut alternate = alternate.clone();
+            async move {
+                tokio::time::timeout(
+                    std::time::Duration::from_secs(5),
+                    alternate.wait_for(|alternate| *alternate == expected),
+                )
+                .await
+                .unwrap_or_else(|_| panic!("alternate screen never became {}", expected))
+                .unwrap();
+            }
+        };
+
+        // What a full-screen program like less sends on start
+        process
+            .send_input("printf '\\033[?1049h\\033[Hmenu'\r".to_string())
+            .await
+            .unwrap();
+        wait_for(true).await;
+        assert!(process.get_screen_text().await.unwrap().alternate_screen);
+
+        // ... and on exit
+        process
+            .send_input("printf '\\033[?1049l'\r".to_string())
+            .await
+            .unwrap();
+        wait_for(false).await;
+
+        // A new shell starts on the primary screen
+        process
+            .send_input("printf '\\033[?1049h'\r".to_string())
+            .await
+            .unwrap();
+        wait_for(true).await;
+        process.restart().await.unwrap();
+        assert!(!*alternate.borrow());
+        process.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_screen_text_shows_output() {
+        let process = PtyProcess::new(PtyProcessConfig {
+            shell_command: Some("sh".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            process.get_screen_text().await,
+            Err(PtyProcessError::NotRunning)
+        ));
+        process.start().await.unwrap();
+
+        process
+            .send_input("printf '\\033[1mscreen\\033[0m %s\\n' text\r".to_string())
+            .await
+            .unwrap();
+        let snapshot = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let snapshot = process.get_screen_text().await.unwrap();
+                if snapshot.lines.iter().any(|line| line == "screen text") {
+                    return snapshot;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!((snapshot.cols, snapshot.rows), (80, 24));
+        assert_eq!(snapshot.lines.len(), 24);
+        process.stop().await.unwrap();
+    }
 }
@@ -1,5 +1,10 @@
+use crate::terminal::pty_session::{PtyEvent, PtyEventData};
+use crate::terminal::screen::ScreenSnapshot;
 use anyhow::Result;
-use tokio::sync::broadcast;
+use nix::sys::signal::Signal;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{broadcast, watch};
 
 /// Trait for PTY process operations to enable mocking
 #[async_trait::async_trait]
@@ -17,9 +22,171 @@ pub trait PtyProcessTrait: Send + Sync {
     async fn get_screen_contents(
         &self,
     ) -> Result<String, crate::terminal::pty_process::PtyProcessError>;
+    /// What the terminal currently shows, as plain text. Only locks the
+    /// screen for a copy, so it's fine to call several times per second.
+    async fn get_screen_text(
+        &self,
+    ) -> Result<ScreenSnapshot, crate::terminal::pty_process::PtyProcessError>;
     async fn get_pty_bytes_receiver(
         &self,
     ) -> Result<broadcast::Receiver<bytes::Bytes>, crate::terminal::pty_process::PtyProcessError>;
+    /// Events ending the shell, `init` and `resize` events and OSC 133
+    /// command marks, across restarts
+    async fn get_pty_event_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<PtyEvent>, crate::terminal::pty_process::PtyProcessError>;
+    /// Window title set by programs in the shell (OSC 0/2), empty for a new
+    /// shell
+    async fn get_title_receiver(
+        &self,
+    ) -> Result<watch::Receiver<String>, crate::terminal::pty_process::PtyProcessError>;
+    /// Whether programs in the shell switched to the alternate screen, false
+    /// for a new shell
+    async fn get_alternate_screen_receiver(
+        &self,
+    ) -> Result<watch::Receiver<bool>, crate::terminal::pty_process::PtyProcessError>;
+    /// Replace the shell with a fresh one
+    async fn restart(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError>;
+    /// Shut the shell down until the next restart
+    async fn stop(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError>;
+    /// Change the terminal size, of the current shell and of later ones
+    async fn resize(
+        &self,
+        cols: u16,
+        rows: u16,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError>;
+    async fn get_shell_pid(
+        &self,
+    ) -> Result<Option<u32>, crate::terminal::pty_process::PtyProcessError>;
+    /// Send `signal` to the program in the foreground of the shell
+    async fn send_signal(
+        &self,
+        signal: Signal,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError>;
+    /// Send `signal` to the processes started from the shell, without
+    /// touching the shell and its PTY: leaf-first through the whole process
+    /// tree, or only the shell's children unless `recursive`. Returns how
+    /// many processes were signaled.
+    async fn kill_children(
+        &self,
+        signal: Signal,
+        recursive: bool,
+    ) -> Result<usize, crate::terminal::pty_process::PtyProcessError>;
+    /// Writes queued for the shell that it hasn't read yet
+    fn pending_input(&self) -> usize;
+    /// Command line of the newest child process, None while the shell
+    /// itself is in the foreground
+    async fn get_current_command(
+        &self,
+    ) -> Result<Option<String>, crate::terminal::pty_process::PtyProcessError>;
+}
+
+/// Allow sharing a process between an agent and its owner (e.g. a test
+/// that inspects a mock after handing it to an agent)
+#[async_trait::async_trait]
+impl<T: PtyProcessTrait + ?Sized> PtyProcessTrait for std::sync::Arc<T> {
+    async fn send_input(
+        &self,
+        input: String,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        (**self).send_input(input).await
+    }
+
+    async fn get_pty_string_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<String>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_pty_string_receiver().await
+    }
+
+    async fn get_child_processes(
+        &self,
+    ) -> Result<Vec<u32>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_child_processes().await
+    }
+
+    async fn get_screen_contents(
+        &self,
+    ) -> Result<String, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_screen_contents().await
+    }
+
+    async fn get_screen_text(
+        &self,
+    ) -> Result<ScreenSnapshot, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_screen_text().await
+    }
+
+    async fn get_pty_bytes_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<bytes::Bytes>, crate::terminal::pty_process::PtyProcessError>
+    {
+        (**self).get_pty_bytes_receiver().await
+    }
+
+    async fn get_pty_event_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<PtyEvent>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_pty_event_receiver().await
+    }
+
+    async fn get_title_receiver(
+        &self,
+    ) -> Result<watch::Receiver<String>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_title_receiver().await
+    }
+
+    async fn get_alternate_screen_receiver(
+        &self,
+    ) -> Result<watch::Receiver<bool>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_alternate_screen_receiver().await
+    }
+
+    async fn restart(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        (**self).restart().await
+    }
+
+    async fn stop(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        (**self).stop().await
+    }
+
+    async fn resize(
+        &self,
+        cols: u16,
+        rows: u16,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        (**self).resize(cols, rows).await
+    }
+
+    async fn get_shell_pid(
+        &self,
+    ) -> Result<Option<u32>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_shell_pid().await
+    }
+
+    async fn send_signal(
+        &self,
+        signal: Signal,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        (**self).send_signal(signal).await
+    }
+
+    async fn kill_children(
+        &self,
+        signal: Signal,
+        recursive: bool,
+    ) -> Result<usize, crate::terminal::pty_process::PtyProcessError> {
+        (**self).kill_children(signal, recursive).await
+    }
+
+    fn pending_input(&self) -> usize {
+        (**self).pending_input()
+    }
+
+    async fn get_current_command(
+        &self,
+    ) -> Result<Option<String>, crate::terminal::pty_process::PtyProcessError> {
+        (**self).get_current_command().await
+    }
 }
 
 /// Mock implementation for testing
@@ -27,6 +194,17 @@ pub trait PtyProcessTrait: Send + Sync {
 pub struct MockPtyProcess {
     pub sent_inputs: std::sync::Mutex<Vec<String>>,
     pub should_fail: bool,
+    pub restarts: AtomicUsize,
+    pub stops: AtomicUsize,
+    events: broadcast::Sender<PtyEvent>,
+    children: std::sync::Mutex<Vec<u32>>,
+    cmdlines: std::sync::Mutex<HashMap<u32, String>>,
+    screen: std::sync::Mutex<ScreenSnapshot>,
+    title: watch::Sender<String>,
+    alternate_screen: watch::Sender<bool>,
+    output: broadcast::Sender<bytes::Bytes>,
+    signals: std::sync::Mutex<Vec<Signal>>,
+    kills: std::sync::Mutex<Vec<(Signal, bool)>>,
 }
 
 #[allow(dead_code)]
@@ -35,6 +213,17 @@ impl MockPtyProcess {
         Self {
             sent_inputs: std::sync::Mutex::new(Vec::new()),
             should_fail: false,
+            restarts: AtomicUsize::new(0),
+            stops: AtomicUsize::new(0),
+            events: broadcast::channel(16).0,
+            children: std::sync::Mutex::new(Vec::new()),
+            cmdlines: std::sync::Mutex::new(HashMap::new()),
+            screen: std::sync::Mutex::new(mock_screen()),
+            title: watch::channel(String::new()).0,
+            alternate_screen: watch::channel(false).0,
+            output: broadcast::channel(100).0,
+            signals: std::sync::Mutex::new(Vec::new()),
+            kills: std::sync::Mutex::new(Vec::new()),
         }
     }
 
@@ -42,12 +231,119 @@ impl MockPtyProcess {
         Self {
             sent_inputs: std::sync::Mutex::new(Vec::new()),
             should_fail: true,
+            restarts: AtomicUsize::new(0),
+            stops: AtomicUsize::new(0),
+            events: broadcast::channel(16).0,
+            children: std::sync::Mutex::new(Vec::new()),
+            cmdlines: std::sync::Mutex::new(HashMap::new()),
+            screen: std::sync::Mutex::new(mock_screen()),
+            title: watch::channel(String::new()).0,
+            alternate_screen: watch::channel(false).0,
+            output: broadcast::channel(100).0,
+            signals: std::sync::Mutex::new(Vec::new()),
+            kills: std::sync::Mutex::new(Vec::new()),
         }
     }
 
     pub fn get_sent_inputs(&self) -> Vec<String> {
         self.sent_inputs.lock().unwrap().clone()
     }
+
+    pub fn get_sent_signals(&self) -> Vec<Signal> {
+        self.signals.lock().unwrap().clone()
+    }
+
+    /// Signal and `recursive` flag of each kill_children call
+    pub fn get_kill_calls(&self) -> Vec<(Signal, bool)> {
+        self.kills.lock().unwrap().clone()
+    }
+
+    /// Simulate the shell exiting with `exit_code`
+    pub fn exit(&self, exit_code: u32) {
+        let _ = self.events.send(PtyEvent {
+            event_type: "exit".to_string(),
+            time: 0.0,
+            data: PtyEventData::ChildExited {
+                exit_code,
+                signal: None,
+            },
+        });
+    }
+
+    pub fn restart_count(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    pub fn stop_count(&self) -> usize {
+        self.stops.load(Ordering::SeqCst)
+    }
+
+    /// Simulate the terminal showing `lines`, with the cursor after the
+    /// last one
+    pub fn set_screen(&self, lines: &[&str]) {
+        let mut screen = self.screen.lock().unwrap();
+        let rows = screen.rows as usize;
+        screen.lines = lines.iter().map(|line| line.to_string()).collect();
+        screen.lines.resize(rows.max(lines.len()), String::new());
+        let last = lines.len().saturating_sub(1);
+        screen.cursor = (
+            last as u16,
+            lines.last().map_or(0, |line| line.len() as u16),
+        );
+    }
+
+    /// Simulate a program switching bracketed paste mode on or off
+    pub fn set_bracketed_paste(&self, enabled: bool) {
+        self.screen.lock().unwrap().bracketed_paste = enabled;
+    }
+
+    /// Simulate a program setting the window title
+    pub fn set_title(&self, title: &str) {
+        self.title.send_replace(title.to_string());
+    }
+
+    /// Simulate an OSC 133 command mark of the shell
+    pub fn mark_command(&self, data: PtyEventData) {
+        let _ = self.events.send(PtyEvent {
+            event_type: "command".to_string(),
+            time: 0.0,
+            data,
+        });
+    }
+
+    /// Simulate the shell writing `bytes` to the terminal
+    pub fn emit_output(&self, bytes: &[u8]) {
+        let _ = self.output.send(bytes::Bytes::copy_from_slice(bytes));
+    }
+
+    /// Simulate a full-screen program entering or leaving the alternate screen
+    pub fn set_alternate_screen(&self, alternate: bool) {
+        self.screen.lock().unwrap().alternate_screen = alternate;
+        self.alternate_screen.send_replace(alternate);
+    }
+
+    /// Simulate child processes of the shell, with their command lines
+    pub fn set_children(&self, children: &[(u32, &str)]) {
+        *self.children.lock().unwrap() = children.iter().map(|(pid, _)| *pid).collect();
+        *self.cmdlines.lock().unwrap() = children
+            .iter()
+            .map(|(pid, cmdline)| (*pid, cmdline.to_string()))
+            .collect();
+    }
+}
+
+/// An 80x24 screen showing the mock's screen contents
+fn mock_screen() -> ScreenSnapshot {
+    let mut lines = vec![String::new(); 24];
+    lines[0] = "Mock screen contents".to_string();
+    ScreenSnapshot {
+        lines,
+        cursor: (0, 20),
+        cols: 80,
+        rows: 24,
+        bracketed_paste: false,
+        alternate_screen: false,
+    }
 }
 
 #[async_trait::async_trait]
@@ -80,8 +376,8 @@ impl PtyProcessTrait for MockPtyProcess {
     async fn get_child_processes(
         &self,
     ) -> Result<Vec<u32>, crate::terminal::pty_process::PtyProcessError> {
-        // Return empty vec to simulate idle state
-        Ok(vec![])
+        // Empty unless set_children simulates a running command
+        Ok(self.children.lock().unwrap().clone())
     }
 
     async fn get_screen_contents(
@@ -97,13 +393,134 @@ impl PtyProcessTrait for MockPtyProcess {
         Ok("Mock screen contents".to_string())
     }
 
+    async fn get_screen_text(
+        &self,
+    ) -> Result<ScreenSnapshot, crate::terminal::pty_process::PtyProcessError> {
+        if self.should_fail {
+            return Err(
+                crate::terminal::pty_process::PtyProcessError::CommunicationError(
+                    "Mock screen text failure".to_string(),
+                ),
+            );
+        }
+        Ok(self.screen.lock().unwrap().clone())
+    }
+
     async fn get_pty_bytes_receiver(
         &self,
     ) -> Result<broadcast::Receiver<bytes::Bytes>, crate::terminal::pty_process::PtyProcessError>
     {
-        let (tx, rx) = broadcast::channel(100);
-        // Send some mock bytes for testing
-        let _ = tx.send(bytes::Bytes::from(b"mock bytes output".to_vec()));
-        Ok(rx)
+        Ok(self.output.subscribe())
+    }
+
+    async fn get_pty_event_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<PtyEvent>, crate::terminal::pty_process::PtyProcessError> {
+        Ok(self.events.subscribe())
+    }
+
+    async fn get_title_receiver(
+        &self,
+    ) -> Result<watch::Receiver<String>, crate::terminal::pty_process::PtyProcessError> {
+        Ok(self.title.subscribe())
+    }
+
+    async fn get_alternate_screen_receiver(
+        &self,
+    ) -> Result<watch::Receiver<bool>, crate::terminal::pty_process::PtyProcessError> {
+        Ok(self.alternate_screen.subscribe())
+    }
+
+    async fn restart(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        self.restarts.fetch_add(1, Ordering::SeqCst);
+        if self.should_fail {
+            return Err(
+                crate::terminal::pty_process::PtyProcessError::StartupFailure(
+                    "Mock restart failure".to_string(),
+                ),
+            );
+        }
+        let _ = self.events.send(PtyEvent {
+            event_type: "init".to_string(),
+            time: 0.0,
+            data: PtyEventData::Init {
+                cols: 80,
+                rows: 24,
+                initial_data: String::new(),
+                initial_seq: String::new(),
+            },
+        });
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        self.stops.fetch_add(1, Ordering::SeqCst);
+        // Like a hung up shell
+        let _ = self.events.send(PtyEvent {
+            event_type: "exit".to_string(),
+            time: 0.0,
+            data: PtyEventData::ChildExited {
+                exit_code: 1,
+                signal: Some("Hangup".to_string()),
+            },
+        });
+        Ok(())
+    }
+
+    async fn resize(
+        &self,
+        cols: u16,
+        rows: u16,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        let _ = self.events.send(PtyEvent {
+            event_type: "resize".to_string(),
+            time: 0.0,
+            data: PtyEventData::Resize {
+                cols: cols as usize,
+                rows: rows as usize,
+            },
+        });
+        Ok(())
+    }
+
+    async fn get_shell_pid(
+        &self,
+    ) -> Result<Option<u32>, crate::terminal::pty_process::PtyProcessError> {
+        // No real shell behind the mock
+        Ok(None)
+    }
+
+    async fn send_signal(
+        &self,
+        signal: Signal,
+    ) -> Result<(), crate::terminal::pty_process::PtyProcessError> {
+        self.signals.lock().unwrap().push(signal);
+        Ok(())
+    }
+
+    async fn kill_children(
+        &self,
+        signal: Signal,
+        recursive: bool,
+    ) -> Result<usize, crate::terminal::pty_process::PtyProcessError> {
+        self.kills.lock().unwrap().push((signal, recursive));
+        Ok(self.children.lock().unwrap().len())
+    }
+
+    fn pending_input(&self) -> usize {
+        // Inputs are recorded right away
+        0
+    }
+
+    async fn get_current_command(
+        &self,
+    ) -> Result<Option<String>, crate::terminal::pty_process::PtyProcessError> {
+        let children = self.get_child_processes().await?;
+        let cmdlines = self.cmdlines.lock().unwrap();
+        Ok(crate::terminal::process_tree::newest_command(
+            &children,
+            |_| None,
+            |pid| cmdlines.get(&pid).cloned(),
+        ))
     }
 }
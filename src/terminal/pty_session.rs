@@ -1,4 +1,7 @@
 use super::pty_terminal::PtyTerminal;
+use super::screen::ScreenSnapshot;
+use super::secret;
+use crate::config::terminal_config::TerminalConfig;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -43,25 +46,91 @@ pub enum PtyEventData {
         cols: usize,
         rows: usize,
     },
+    /// A program set the window title (OSC 0 or 2)
+    TitleChanged {
+        title: String,
+    },
+    /// A program switched to or from the alternate screen (`ESC[?1049h/l`)
+    ScreenModeChanged {
+        alternate: bool,
+    },
+    /// The shell exited; no more output follows
+    ChildExited {
+        #[serde(rename = "exitCode")]
+        exit_code: u32,
+        /// Signal that killed the shell, e.g. "Hangup"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signal: Option<String>,
+    },
+    /// The PTY could not be read anymore while the shell's exit status was
+    /// unknown, e.g. after a read error
+    ReaderClosed {
+        reason: String,
+    },
+    /// No shell could be started
+    SpawnFailed {
+        error: String,
+    },
+    /// Shell integration (OSC 133;D): a command finished
+    CommandFinished {
+        #[serde(rename = "commandExitCode")]
+        exit_code: Option<i32>,
+    },
+    /// Shell integration (OSC 133;C): the shell started a command
+    CommandStarted {},
+}
+
+impl PtyEventData {
+    /// Why the terminal is dead, for the events that end it
+    pub fn exit_reason(&self) -> Option<String> {
+        match self {
+            PtyEventData::ChildExited {
+                signal: Some(signal),
+                ..
+            } => Some(format!("killed ({})", signal)),
+            PtyEventData::ChildExited { exit_code, .. } => Some(format!("exit code {}", exit_code)),
+            PtyEventData::ReaderClosed { reason } => Some(format!("PTY closed: {}", reason)),
+            PtyEventData::SpawnFailed { error } => Some(format!("failed to start: {}", error)),
+            _ => None,
+        }
+    }
 }
 
 pub struct PtySession {
     terminal: Arc<PtyTerminal>,
     event_tx: broadcast::Sender<PtyEvent>,
+    /// Subscribed before the shell started, so the first subscriber also
+    /// gets the events of a shell that exits right away
+    first_rx: std::sync::Mutex<Option<broadcast::Receiver<PtyEvent>>>,
     start_time: Instant,
 }
 
 impl PtySession {
-    pub async fn new(command: String, cols: usize, rows: usize) -> Result<Self> {
-        let (event_tx, _) = broadcast::channel(1024);
+    /// Start `argv` (the default shell when empty) on a new PTY
+    pub async fn new(
+        argv: &[String],
+        cols: usize,
+        rows: usize,
+        config: &TerminalConfig,
+    ) -> Result<Self> {
+        let (event_tx, first_rx) = broadcast::channel(config.output_channel_capacity.max(1));
         let now = Instant::now();
         let terminal = Arc::new(
-            PtyTerminal::new(command, cols as u16, rows as u16, event_tx.clone(), now).await?,
+            PtyTerminal::new(
+                argv,
+                cols as u16,
+                rows as u16,
+                config,
+                event_tx.clone(),
+                now,
+            )
+            .await?,
         );
 
         let session = Self {
             terminal: terminal.clone(),
             event_tx: event_tx.clone(),
+            first_rx: std::sync::Mutex::new(Some(first_rx)),
             start_time: now,
         };
 
@@ -71,18 +140,19 @@ impl PtySession {
     }
 
     pub async fn handle_command(&self, command: PtyCommand) -> Result<()> {
-        use tracing::info;
-        info!("🎯 handle_command called with: {:?}", command);
+        use tracing::trace;
+        // The payload is logged redacted below, not as part of the command
+        trace!("🎯 handle_command called");
 
         match command {
             PtyCommand::Input { payload } => {
-                info!(
+                trace!(
                     "🔄 Processing Input command: {} bytes: {:?}",
                     payload.len(),
-                    payload
+                    secret::registry().redact(&payload)
                 );
                 self.terminal.write_input(payload.as_bytes()).await?;
-                info!("✅ Input written to terminal successfully");
+                trace!("✅ Input written to terminal successfully");
             }
             PtyCommand::SendKeys { keys } => {
                 for key in keys {
@@ -98,8 +168,14 @@ impl PtySession {
         Ok(())
     }
 
+    /// Receive the session's events; the first call gets them from the
+    /// start of the shell, later ones from now on
     pub async fn subscribe(&self) -> broadcast::Receiver<PtyEvent> {
-        self.event_tx.subscribe()
+        self.first_rx
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_else(|| self.event_tx.subscribe())
     }
 
     async fn emit_resize_event(&self, cols: usize, rows: usize) {
@@ -116,14 +192,6 @@ impl PtySession {
         elapsed.as_secs_f64()
     }
 
-    /// Send output data directly to the terminal's output channel
-    pub async fn send_output_data(&self, data: &str) -> Result<()> {
-        use bytes::Bytes;
-        self.terminal
-            .write_output(Bytes::from(data.to_string()))
-            .await
-    }
-
     /// Get direct access to PTY raw bytes receiver for WebSocket streaming (asciinema)
     pub async fn get_pty_bytes_receiver(
         &self,
@@ -143,13 +211,30 @@ impl PtySession {
         self.terminal.get_screen_contents().await
     }
 
+    /// Get the current screen as plain text
+    pub async fn get_screen_text(&self) -> ScreenSnapshot {
+        self.terminal.get_screen_text().await
+    }
+
     /// Get the PID of the shell process
     pub async fn get_shell_pid(&self) -> Result<Option<u32>> {
         self.terminal.get_shell_pid().await
     }
+
+    /// Signal the terminal's foreground process group, see
+    /// `PtyTerminal::send_signal`
+    pub async fn send_signal(&self, signal: nix::sys::signal::Signal) -> Result<()> {
+        self.terminal.send_signal(signal).await
+    }
+
+    /// Handle on the shell's input queue, see `PtyTerminal::input_queue`
+    pub fn input_queue(&self) -> tokio::sync::mpsc::WeakSender<bytes::Bytes> {
+        self.terminal.input_queue()
+    }
 }
 
-fn parse_key(key: &str) -> Vec<u8> {
+/// Bytes of a key sequence of a send_keys action, e.g. `C-c` or `Enter`
+pub fn parse_key(key: &str) -> Vec<u8> {
     let bytes: &[u8] = match key {
         "C-@" | "C-Space" | "^@" => b"\x00",
         "C-[" | "Escape" | "^[" => b"\x1b",
@@ -216,3 +301,64 @@ fn parse_ctrl_key(key: &str) -> Option<u8> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_events_of_a_command_exiting_right_away() {
+        let argv = ["echo".to_string(), "hello".to_string()];
+        let session = PtySession::new(&argv, 80, 24, &TerminalConfig::default())
+            .await
+            .unwrap();
+        let mut events = session.subscribe().await;
+
+        let mut output = String::new();
+        let ended = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match events.recv().await.unwrap().data {
+                    PtyEventData::Output { data } => output.push_str(&data),
+                    data => return data,
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(output.contains("hello"), "{:?}", output);
+        assert!(
+            matches!(
+                ended,
+                PtyEventData::ChildExited {
+                    exit_code: 0,
+                    signal: None
+                }
+            ),
+            "{:?}",
+            ended
+        );
+        assert_eq!(ended.exit_reason().as_deref(), Some("exit code 0"));
+    }
+
+    #[test]
+    fn test_exit_reason() {
+        let killed = PtyEventData::ChildExited {
+            exit_code: 1,
+            signal: Some("Hangup".to_string()),
+        };
+        assert_eq!(killed.exit_reason().as_deref(), Some("killed (Hangup)"));
+        let failed = PtyEventData::SpawnFailed {
+            error: "No such file".to_string(),
+        };
+        assert_eq!(
+            failed.exit_reason().as_deref(),
+            Some("failed to start: No such file")
+        );
+        let output = PtyEventData::Output {
+            data: String::new(),
+        };
+        assert_eq!(output.exit_reason(), None);
+    }
+}
@@ -1,21 +1,41 @@
+use crate::config::terminal_config::{InputFull, TerminalConfig};
 use crate::terminal::pty_session::{PtyEvent, PtyEventData};
+use crate::terminal::screen::ScreenSnapshot;
+use crate::terminal::secret;
+use crate::terminal::shell_integration::{CommandMark, ShellMarks};
+use crate::terminal::utf8::take_utf8;
 use anyhow::{Context, Result};
 use bytes::Bytes;
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
 use portable_pty::{Child, CommandBuilder, NativePtySystem, PtySize, PtySystem};
 use std::io::Write;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::{Mutex, broadcast, mpsc};
-use tracing::{error, info};
-
-const READ_BUF_SIZE: usize = 4096;
+use tokio::sync::{Mutex, broadcast, mpsc, watch};
+use tracing::{error, info, trace};
+
+/// How long the reader waits for the shell's exit status after EOF
+const EXIT_WAIT_POLLS: u32 = 50;
+const EXIT_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Input refused because the shell's queue is full and
+/// `terminal.on_input_full` is `fail`
+#[derive(Debug, thiserror::Error)]
+#[error("PTY input queue is full ({pending} writes pending)")]
+pub struct InputQueueFull {
+    pub pending: usize,
+}
 
 pub struct PtyTerminal {
     master_pty: Arc<Mutex<Box<dyn portable_pty::MasterPty + Send>>>,
     child_process: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>>,
     reader_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-    writer_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-    input_tx: mpsc::UnboundedSender<Bytes>,
+    input_tx: mpsc::Sender<Bytes>,
+    on_input_full: InputFull,
+    /// Set once nothing is read from the PTY anymore, ending waits for room
+    /// in the input queue
+    reader_done: watch::Sender<bool>,
     // Raw bytes output for WebSocket (asciinema)
     output_tx: broadcast::Sender<Bytes>,
     // String output for rule matching
@@ -27,10 +47,13 @@ pub struct PtyTerminal {
 }
 
 impl PtyTerminal {
+    /// Start `argv` (the default shell when empty) on a new PTY, with the
+    /// environment and buffer sizes of `config`
     pub async fn new(
-        command: String,
+        argv: &[String],
         cols: u16,
         rows: u16,
+        config: &TerminalConfig,
         event_tx: broadcast::Sender<PtyEvent>,
         start_time: Instant,
     ) -> Result<Self> {
@@ -48,28 +71,23 @@ impl PtyTerminal {
             .openpty(pty_size)
             .context("Failed to create PTY")?;
 
-        let mut cmd = CommandBuilder::new_default_prog();
-        cmd.env("TERM", "xterm-256color");
+        let mut cmd = match argv.split_first() {
+            Some((program, args)) => {
+                let mut cmd = CommandBuilder::new(program);
+                cmd.args(args);
+                cmd
+            }
+            None => CommandBuilder::new_default_prog(),
+        };
+        for (key, value) in config.env() {
+            cmd.env(key, value);
+        }
 
         // Set current working directory to the project root
         if let Ok(current_dir) = std::env::current_dir() {
             cmd.cwd(current_dir);
         }
 
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if !parts.is_empty() {
-            cmd = CommandBuilder::new(parts[0]);
-            for arg in &parts[1..] {
-                cmd.arg(arg);
-            }
-            cmd.env("TERM", "xterm-256color");
-
-            // Set current working directory for the command as well
-            if let Ok(current_dir) = std::env::current_dir() {
-                cmd.cwd(current_dir);
-            }
-        }
-
         let child = pair
             .slave
             .spawn_command(cmd)
@@ -82,9 +100,11 @@ impl PtyTerminal {
         // Give the shell a moment to initialize
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-        let (input_tx, mut input_rx) = mpsc::unbounded_channel::<Bytes>();
-        let (output_tx, _rx) = broadcast::channel(1024);
-        let (string_output_tx, _string_rx) = broadcast::channel(1024);
+        // Zero capacities would panic
+        let (input_tx, mut input_rx) = mpsc::channel::<Bytes>(config.input_channel_bound.max(1));
+        let (output_tx, _rx) = broadcast::channel(config.output_channel_capacity.max(1));
+        let (string_output_tx, _string_rx) =
+            broadcast::channel(config.output_channel_capacity.max(1));
 
         // Keep persistent receivers alive to prevent broadcast channels from failing
         let persistent_rx = output_tx.subscribe();
@@ -103,36 +123,106 @@ impl PtyTerminal {
         let output_tx_clone = output_tx.clone();
         let string_output_tx_clone = string_output_tx.clone();
         let event_tx_clone = event_tx.clone();
-        let reader_handle = tokio::spawn(async move {
+        // Store child process to keep it alive
+        let child_process = Arc::new(Mutex::new(Some(child)));
+        let child_for_reader = child_process.clone();
+        let reader_done = watch::Sender::new(false);
+        let reader_done_tx = reader_done.clone();
+        // Reads block, so they run on a blocking thread: inside a task they
+        // would stall a runtime worker, and with it everything on a
+        // single-CPU machine
+        let (chunk_tx, mut chunk_rx) = mpsc::channel::<std::io::Result<Vec<u8>>>(64);
+        let read_buf_size = config.read_buf_size.max(1);
+        tokio::task::spawn_blocking(move || {
             use std::io::Read;
             let mut reader = reader;
-            let mut buf = vec![0u8; READ_BUF_SIZE];
-
+            let mut buf = vec![0u8; read_buf_size];
+            loop {
+                let chunk = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => Ok(buf[..n].to_vec()),
+                    Err(e) => Err(e),
+                };
+                let failed = chunk.is_err();
+                if chunk_tx.blocking_send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let reader_handle = tokio::spawn(async move {
             info!("🔍 PTY reader task started, entering read loop");
 
+            // Set when something else than EOF ended the loop
+            let mut read_error = None;
+            // Start of a character whose other bytes are still to be read
+            let mut pending = Vec::new();
+            let mut title = String::new();
+            let mut alternate_screen = false;
+            let mut marks = ShellMarks::new();
             loop {
-                info!("🔄 PTY reader: attempting to read from PTY...");
-                match reader.read(&mut buf) {
-                    Ok(0) => {
+                trace!("🔄 PTY reader: attempting to read from PTY...");
+                match chunk_rx.recv().await {
+                    None => {
                         info!("🔚 PTY reader: read 0 bytes, EOF reached, breaking");
                         break;
                     }
-                    Ok(n) => {
-                        let data = &buf[..n];
-                        info!(
+                    Some(Ok(data)) => {
+                        let n = data.len();
+                        let data = &data[..];
+                        trace!(
                             "📥 PTY reader: read {} bytes from PTY: {:?}",
                             n,
-                            String::from_utf8_lossy(data)
+                            secret::registry().redact(&String::from_utf8_lossy(data))
                         );
 
                         // Process through vt100 parser for structured access
                         let mut term = terminal_clone.lock().await;
                         term.process(data);
+                        let title_changed = term.screen().title() != title;
+                        if title_changed {
+                            title = term.screen().title().to_string();
+                        }
+                        let mode_changed = term.screen().alternate_screen() != alternate_screen;
+                        if mode_changed {
+                            alternate_screen = !alternate_screen;
+                        }
                         drop(term);
+                        if title_changed {
+                            let _ = event_tx_clone.send(PtyEvent {
+                                event_type: "title".to_string(),
+                                time: start_time.elapsed().as_secs_f64(),
+                                data: PtyEventData::TitleChanged {
+                                    title: title.clone(),
+                                },
+                            });
+                        }
+                        if mode_changed {
+                            let _ = event_tx_clone.send(PtyEvent {
+                                event_type: "screenMode".to_string(),
+                                time: start_time.elapsed().as_secs_f64(),
+                                data: PtyEventData::ScreenModeChanged {
+                                    alternate: alternate_screen,
+                                },
+                            });
+                        }
+                        for mark in marks.scan(data) {
+                            let (event_type, data) = match mark {
+                                CommandMark::Started => {
+                                    ("commandStarted", PtyEventData::CommandStarted {})
+                                }
+                                CommandMark::Finished { exit_code } => (
+                                    "commandFinished",
+                                    PtyEventData::CommandFinished { exit_code },
+                                ),
+                            };
+                            let _ = event_tx_clone.send(PtyEvent {
+                                event_type: event_type.to_string(),
+                                time: start_time.elapsed().as_secs_f64(),
+                                data,
+                            });
+                        }
 
-                        let raw_str = String::from_utf8_lossy(data);
-
-                        info!(
+                        trace!(
                             "📤 PTY reader: broadcasting {} bytes to output channel",
                             data.len()
                         );
@@ -141,87 +231,120 @@ impl PtyTerminal {
                             error!(
                                 "❌ PTY reader: failed to broadcast to output channel, breaking"
                             );
+                            read_error = Some("output channel closed".to_string());
                             break;
                         }
-                        info!("✅ PTY reader: successfully broadcast to output channel");
+                        trace!("✅ PTY reader: successfully broadcast to output channel");
+
+                        // Only complete characters are decoded, so one split
+                        // across reads doesn't turn into two replacement
+                        // characters
+                        pending.extend_from_slice(data);
+                        let raw_str = take_utf8(&mut pending);
+                        if raw_str.is_empty() {
+                            continue;
+                        }
 
                         // Send string to rule matching channel
-                        if string_output_tx_clone.send(raw_str.to_string()).is_err() {
+                        if string_output_tx_clone.send(raw_str.clone()).is_err() {
                             error!(
                                 "❌ PTY reader: failed to broadcast to string output channel, breaking"
                             );
+                            read_error = Some("output channel closed".to_string());
                             break;
                         }
-                        info!("✅ PTY reader: successfully broadcast to string output channel");
+                        trace!("✅ PTY reader: successfully broadcast to string output channel");
 
+                        trace!(
+                            "📡 PTY reader: emitting output event with {} bytes",
+                            raw_str.len()
+                        );
                         // Also emit the output event directly
                         let output_event = PtyEvent {
                             event_type: "output".to_string(),
                             time: start_time.elapsed().as_secs_f64(),
-                            data: PtyEventData::Output {
-                                data: raw_str.to_string(),
-                            },
+                            data: PtyEventData::Output { data: raw_str },
                         };
 
-                        info!(
-                            "📡 PTY reader: emitting output event with {} bytes",
-                            raw_str.len()
-                        );
-                        if event_tx_clone.send(output_event).is_err() {
-                            error!("❌ PTY reader: failed to send output event, breaking");
-                            break;
+                        // The session may not be subscribed yet; the raw
+                        // channels above already carry the output
+                        if event_tx_clone.send(output_event).is_ok() {
+                            trace!("✅ PTY reader: successfully emitted output event");
                         }
-                        info!("✅ PTY reader: successfully emitted output event");
                     }
-                    Err(e) => {
+                    Some(Err(e)) => {
                         error!("❌ PTY reader: Error reading from PTY: {}", e);
                         // Add a small delay before breaking to see if this is a temporary issue
                         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                        read_error = Some(e.to_string());
                         break;
                     }
                 }
             }
             info!("🔚 PTY reader task terminating");
+            reader_done_tx.send_replace(true);
+
+            // Whatever ended the loop, nothing will be read anymore: report
+            // it so the owner can respawn the shell
+            let (event_type, data) = match wait_for_exit(&child_for_reader).await {
+                Some(status) => (
+                    "exit",
+                    PtyEventData::ChildExited {
+                        exit_code: status.exit_code(),
+                        signal: status.signal().map(str::to_string),
+                    },
+                ),
+                None => (
+                    "readerClosed",
+                    PtyEventData::ReaderClosed {
+                        reason: read_error
+                            .unwrap_or_else(|| "EOF while the shell was running".to_string()),
+                    },
+                ),
+            };
+            let _ = event_tx_clone.send(PtyEvent {
+                event_type: event_type.to_string(),
+                time: start_time.elapsed().as_secs_f64(),
+                data,
+            });
         });
 
-        let writer_clone = Arc::new(Mutex::new(writer));
-        let writer_for_task = writer_clone.clone();
-
-        let writer_handle = tokio::spawn(async move {
+        // Writes block while the shell doesn't read its input, and a write
+        // stuck on a PTY whose shell is gone never returns. The thread is
+        // left to end with the input queue rather than tie up the runtime.
+        std::thread::spawn(move || {
             info!("🔍 PTY writer task started");
-            while let Some(data) = input_rx.recv().await {
-                info!(
+            let mut writer = writer;
+            while let Some(data) = input_rx.blocking_recv() {
+                trace!(
                     "📝 PTY writer: Writing {} bytes to PTY: {:?}",
                     data.len(),
-                    String::from_utf8_lossy(&data)
+                    secret::registry().redact(&String::from_utf8_lossy(&data))
                 );
-                let mut writer = writer_for_task.lock().await;
                 if let Err(e) = writer.write_all(data.as_ref()) {
                     error!("❌ PTY writer: Error writing to PTY: {}", e);
                     break;
                 } else {
-                    info!("✅ PTY writer: Data written to PTY, flushing...");
+                    trace!("✅ PTY writer: Data written to PTY, flushing...");
                     // Flush to ensure data is sent immediately
                     if let Err(e) = writer.flush() {
                         error!("❌ PTY writer: Error flushing PTY: {}", e);
                         break;
                     } else {
-                        info!("✅ PTY writer: Successfully flushed PTY");
+                        trace!("✅ PTY writer: Successfully flushed PTY");
                     }
                 }
             }
             info!("🔚 PTY writer task terminating");
         });
 
-        // Store child process to keep it alive
-        let child_process = Arc::new(Mutex::new(Some(child)));
-
         let pty_terminal = PtyTerminal {
             master_pty: Arc::new(Mutex::new(pair.master)),
             child_process,
             reader_handle: Arc::new(Mutex::new(Some(reader_handle))),
-            writer_handle: Arc::new(Mutex::new(Some(writer_handle))),
             input_tx,
+            on_input_full: config.on_input_full,
+            reader_done,
             output_tx,
             string_output_tx,
             _persistent_rx: persistent_rx,
@@ -232,17 +355,36 @@ impl PtyTerminal {
         Ok(pty_terminal)
     }
 
+    /// Queue `data` for the shell. Once `input_channel_bound` writes are
+    /// pending this waits for the shell to catch up, or fails with
+    /// `InputQueueFull` if `on_input_full` is `fail`.
     pub async fn write_input(&self, data: &[u8]) -> Result<()> {
-        self.input_tx
-            .send(Bytes::from(data.to_vec()))
-            .context("Failed to send input")?;
-        Ok(())
+        let data = Bytes::from(data.to_vec());
+        match self.on_input_full {
+            InputFull::Wait => {
+                let mut reader_done = self.reader_done.subscribe();
+                tokio::select! {
+                    result = self.input_tx.send(data) => result.context("Failed to send input"),
+                    _ = reader_done.wait_for(|done| *done) => {
+                        anyhow::bail!("Shell exited before reading its input")
+                    }
+                }
+            }
+            InputFull::Fail => match self.input_tx.try_send(data) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(_)) => Err(InputQueueFull {
+                    pending: self.input_tx.max_capacity(),
+                }
+                .into()),
+                Err(mpsc::error::TrySendError::Closed(_)) => anyhow::bail!("Failed to send input"),
+            },
+        }
     }
 
-    /// Write output data directly to the output channel (for external data injection)
-    pub async fn write_output(&self, data: Bytes) -> Result<()> {
-        self.output_tx.send(data).context("Failed to send output")?;
-        Ok(())
+    /// Handle on the input queue for reading its depth; it doesn't keep the
+    /// queue open
+    pub fn input_queue(&self) -> mpsc::WeakSender<Bytes> {
+        self.input_tx.downgrade()
     }
 
     /// Get a new broadcast receiver for raw bytes output (for WebSocket/asciinema)
@@ -280,6 +422,11 @@ impl PtyTerminal {
         Ok(String::from_utf8_lossy(&formatted_bytes).to_string())
     }
 
+    /// Get the current screen as plain text
+    pub async fn get_screen_text(&self) -> ScreenSnapshot {
+        ScreenSnapshot::from_screen(self.terminal.lock().await.screen())
+    }
+
     /// Get the PID of the shell process
     pub async fn get_shell_pid(&self) -> Result<Option<u32>> {
         let child_guard = self.child_process.lock().await;
@@ -289,13 +436,67 @@ impl PtyTerminal {
             Ok(None)
         }
     }
+
+    /// Send `signal` to the terminal's foreground process group, the command
+    /// the shell is running or the shell itself. Unlike typing `C-c`, this
+    /// also reaches programs that turned ISIG off. Where the group can't be
+    /// read, INT, QUIT and TSTP fall back to their control byte.
+    pub async fn send_signal(&self, signal: Signal) -> Result<()> {
+        let group = self.master_pty.lock().await.process_group_leader();
+        match group {
+            Some(group) if group > 0 => {
+                info!("Sending {} to process group {}", signal, group);
+                killpg(Pid::from_raw(group), signal)
+                    .with_context(|| format!("Failed to send {} to group {}", signal, group))
+            }
+            _ => {
+                let Some(byte) = control_byte(signal) else {
+                    anyhow::bail!("No foreground process group to send {} to", signal);
+                };
+                info!("Typing the control byte of {}", signal);
+                self.write_input(&[byte]).await
+            }
+        }
+    }
+}
+
+/// Byte the terminal turns into `signal` while ISIG is on
+fn control_byte(signal: Signal) -> Option<u8> {
+    match signal {
+        Signal::SIGINT => Some(0x03),
+        Signal::SIGQUIT => Some(0x1c),
+        Signal::SIGTSTP => Some(0x1a),
+        _ => None,
+    }
+}
+
+/// Exit status of the shell once it exited. The PTY reaches EOF just before
+/// the shell is reaped, so it gets a moment; a shell that's still running
+/// after that (e.g. one that closed its terminal) yields None.
+async fn wait_for_exit(
+    child: &Mutex<Option<Box<dyn Child + Send + Sync>>>,
+) -> Option<portable_pty::ExitStatus> {
+    for _ in 0..EXIT_WAIT_POLLS {
+        match child.lock().await.as_mut()?.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => {}
+            Err(_) => return None,
+        }
+        tokio::time::sleep(EXIT_WAIT_INTERVAL).await;
+    }
+    None
 }
 
 impl Drop for PtyTerminal {
     fn drop(&mut self) {
         // Properly terminate child process first
-        if let Ok(mut child_guard) = self.child_process.try_lock() {
-            if let Some(mut child) = child_guard.take() {
+        if let Ok(mut child_guard) = self.child_process.try_lock()
+            && let Some(mut child) = child_guard.take()
+        {
+            // The shell may already have exited on its own
+            if let Ok(Some(_)) = child.try_wait() {
+                info!("✅ Child process already exited");
+            } else {
                 info!("🔄 Terminating child process gracefully");
                 // Try to kill the child process gracefully
                 if let Err(e) = child.kill() {
@@ -309,16 +510,11 @@ impl Drop for PtyTerminal {
             }
         }
 
-        // Abort reader/writer tasks
-        if let Ok(mut handle) = self.reader_handle.try_lock() {
-            if let Some(h) = handle.take() {
-                h.abort();
-            }
-        }
-        if let Ok(mut handle) = self.writer_handle.try_lock() {
-            if let Some(h) = handle.take() {
-                h.abort();
-            }
+        // Abort the reader task; the writer thread ends with the input queue
+        if let Ok(mut handle) = self.reader_handle.try_lock()
+            && let Some(h) = handle.take()
+        {
+            h.abort();
         }
     }
 }
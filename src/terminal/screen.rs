@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+/// What a terminal currently shows, as plain text
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScreenSnapshot {
+    /// One entry per row, without colors and trailing blanks
+    pub lines: Vec<String>,
+    /// Cursor position as (row, col), both from 0
+    pub cursor: (u16, u16),
+    pub cols: u16,
+    pub rows: u16,
+    /// Whether the program asked for bracketed paste (mode 2004)
+    pub bracketed_paste: bool,
+    /// Whether a full-screen program switched to the alternate screen
+    pub alternate_screen: bool,
+}
+
+impl ScreenSnapshot {
+    pub fn from_screen(screen: &vt100::Screen) -> Self {
+        let (rows, cols) = screen.size();
+        Self {
+            lines: screen
+                .rows(0, cols)
+                .map(|line| line.trim_end().to_string())
+                .collect(),
+            cursor: screen.cursor_position(),
+            cols,
+            rows,
+            bracketed_paste: screen.bracketed_paste(),
+            alternate_screen: screen.alternate_screen(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_of_screen() {
+        let mut parser = vt100::Parser::new(5, 20, 0);
+        parser.process(b"\x1b[31mred\x1b[0m text   \r\n$ ls\r\n\x1b[2;10Hx");
+        let snapshot = ScreenSnapshot::from_screen(parser.screen());
+
+        assert_eq!(snapshot.lines, ["red text", "$ ls     x", "", "", ""]);
+        assert_eq!(snapshot.cursor, (1, 10));
+        assert_eq!((snapshot.cols, snapshot.rows), (20, 5));
+        assert!(!snapshot.bracketed_paste);
+
+        parser.process(b"\x1b[?2004h");
+        assert!(ScreenSnapshot::from_screen(parser.screen()).bracketed_paste);
+        assert!(!snapshot.alternate_screen);
+
+        // A full-screen program takes over, then gives the screen back
+        parser.process(b"\x1b[?1049hmenu");
+        let snapshot = ScreenSnapshot::from_screen(parser.screen());
+        assert!(snapshot.alternate_screen);
+        assert_eq!(snapshot.lines[0], "menu");
+        parser.process(b"\x1b[?1049l");
+        let snapshot = ScreenSnapshot::from_screen(parser.screen());
+        assert!(!snapshot.alternate_screen);
+        assert_eq!(snapshot.lines[0], "red text");
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+/// What a secret is replaced with in logs, recordings and history
+pub const REDACTED: &str = "***";
+
+/// Secrets shorter than this (in characters) are not redacted: a value like
+/// `y` would turn every `y` in the output into [`REDACTED`]
+pub const MIN_SECRET_LEN: usize = 4;
+
+/// Secrets remembered at once; the least recently sent are forgotten first
+pub const MAX_SECRETS: usize = 256;
+
+static SECRETS: OnceLock<Secrets> = OnceLock::new();
+
+/// Key sequence written as `<secret>...</secret>` by `secret: true` actions
+pub fn secret_keys(keys: &str) -> String {
+    format!("<secret>{}</secret>", keys)
+}
+
+/// Keys of a sequence written as `<secret>...</secret>`, the whole sequence
+/// being the token
+pub fn secret_token(keys: &str) -> Option<&str> {
+    keys.strip_prefix("<secret>")?.strip_suffix("</secret>")
+}
+
+/// `keys` as they may be logged: secret sequences become [`REDACTED`]
+pub fn redact_keys(keys: &[String]) -> Vec<String> {
+    keys.iter()
+        .map(|key| match secret_token(key) {
+            Some(_) => REDACTED.to_string(),
+            None => key.clone(),
+        })
+        .collect()
+}
+
+/// Values sent as secrets, looked for in everything written about a session
+#[derive(Default)]
+pub struct Secrets {
+    values: Mutex<SecretValues>,
+}
+
+#[derive(Default)]
+struct SecretValues {
+    /// Least recently sent first
+    recent: VecDeque<String>,
+    /// Longest first, so a secret containing another is replaced whole
+    by_length: Vec<String>,
+}
+
+impl Secrets {
+    /// Redact `value` from now on. Values made only of control characters
+    /// and whitespace, like the Enter sent after a password, would redact
+    /// every line and are left alone, as are values shorter than
+    /// [`MIN_SECRET_LEN`].
+    pub fn remember(&self, value: &str) {
+        if value.chars().all(|c| c.is_control() || c.is_whitespace()) {
+            return;
+        }
+        let len = value.chars().count();
+        if len < MIN_SECRET_LEN {
+            tracing::warn!(
+                "Secret of {} characters is too short to be redacted (minimum {})",
+                len,
+                MIN_SECRET_LEN
+            );
+            return;
+        }
+        let Ok(mut values) = self.values.lock() else {
+            return;
+        };
+        values.recent.retain(|known| known != value);
+        values.recent.push_back(value.to_string());
+        if values.recent.len() > MAX_SECRETS {
+            values.recent.pop_front();
+        }
+        let mut by_length: Vec<String> = values.recent.iter().cloned().collect();
+        by_length.sort_by_key(|known| std::cmp::Reverse(known.len()));
+        values.by_length = by_length;
+    }
+
+    /// `text` with every remembered secret replaced by [`REDACTED`]
+    pub fn redact(&self, text: &str) -> String {
+        let Ok(values) = self.values.lock() else {
+            return text.to_string();
+        };
+        values
+            .by_length
+            .iter()
+            .fold(text.to_string(), |text, value| {
+                text.replace(value, REDACTED)
+            })
+    }
+
+    /// Length of the longest end of `text` that a secret starts with
+    fn partial_len(&self, text: &str) -> usize {
+        let Ok(values) = self.values.lock() else {
+            return 0;
+        };
+        values
+            .by_length
+            .iter()
+            .filter_map(|value| {
+                (1..value.len())
+                    .rev()
+                    .filter(|&len| value.is_char_boundary(len))
+                    .find(|&len| text.ends_with(&value[..len]))
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// The process-wide secrets, remembered as secret keys are sent
+pub fn registry() -> &'static Secrets {
+    SECRETS.get_or_init(Secrets::default)
+}
+
+/// Redacts secrets from a stream of output chunks. The end of a chunk that
+/// may be the start of a secret is held back until the next chunk shows
+/// whether it is one.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    held: String,
+}
+
+impl Redactor {
+    /// Redacted text of `chunk` and what was held back before it
+    pub fn redact(&mut self, chunk: &str) -> String {
+        let secrets = registry();
+        let mut text = secrets.redact(&(std::mem::take(&mut self.held) + chunk));
+        let partial = secrets.partial_len(&text);
+        self.held = text.split_off(text.len() - partial);
+        text
+    }
+
+    /// Text held back so far, e.g. once the stream ended. It is only the
+    /// start of a secret, so it can be written as is.
+    pub fn take_held(&mut self) -> String {
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_token() {
+        assert_eq!(secret_token(&secret_keys("hunter2")), Some("hunter2"));
+        assert_eq!(secret_token("hunter2"), None);
+        assert_eq!(
+            redact_keys(&[secret_keys("hunter2"), "\r".to_string()]),
+            ["***", "\r"]
+        );
+    }
+
+    #[test]
+    fn test_secrets_redact_remembered_values() {
+        let secrets = Secrets::default();
+        secrets.remember("tok-1234");
+        secrets.remember("tok-1234-long");
+        secrets.remember("\r");
+        assert_eq!(
+            secrets.redact("a tok-1234 and tok-1234-long\r\n"),
+            "a *** and ***\r\n"
+        );
+        assert_eq!(secrets.partial_len("prompt> tok-1"), 5);
+        assert_eq!(secrets.partial_len("prompt> "), 0);
+    }
+
+    #[test]
+    fn test_secrets_ignore_short_values() {
+        let secrets = Secrets::default();
+        secrets.remember("y");
+        secrets.remember("123");
+        assert_eq!(secrets.redact("y 1234 yes"), "y 1234 yes");
+        assert_eq!(secrets.partial_len("yes 12"), 0);
+    }
+
+    #[test]
+    fn test_secrets_forget_least_recently_sent() {
+        let secrets = Secrets::default();
+        for i in 0..=MAX_SECRETS {
+            secrets.remember(&format!("secret-{:04}", i));
+        }
+        // Sending a value again makes it recent
+        secrets.remember("secret-0001");
+        secrets.remember("secret-new!");
+        assert_eq!(secrets.redact("secret-0000"), "secret-0000");
+        assert_eq!(secrets.redact("secret-0001"), "***");
+        assert_eq!(secrets.redact("secret-0002"), "secret-0002");
+        assert_eq!(secrets.redact("secret-new!"), "***");
+    }
+
+    #[test]
+    fn test_redactor_holds_back_secrets_split_across_chunks() {
+        registry().remember("redactor-secret-7f3a");
+        let mut redactor = Redactor::default();
+        let mut written = redactor.redact("Password: redactor-se");
+        assert_eq!(written, "Password: ");
+        written += &redactor.redact("cret-7f3a\r\n$ redac");
+        written += &redactor.redact("ted\r\n");
+        written += &redactor.take_held();
+        assert_eq!(written, "Password: ***\r\n$ redacted\r\n");
+    }
+}
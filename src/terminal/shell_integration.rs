@@ -0,0 +1,98 @@
+use vte::{Parser, Perform};
+
+/// Command boundary reported by a shell with OSC 133 shell integration
+/// (the FinalTerm marks iTerm2, VS Code and others use)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandMark {
+    /// `OSC 133;C`: the command line was accepted and the command runs
+    Started,
+    /// `OSC 133;D[;code]`: the command finished, with its exit code if the
+    /// shell sent one
+    Finished { exit_code: Option<i32> },
+}
+
+/// Collects the OSC 133 marks of a byte stream, ignoring everything else
+#[derive(Default)]
+struct MarkCollector {
+    marks: Vec<CommandMark>,
+}
+
+impl Perform for MarkCollector {
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        let [b"133", kind, rest @ ..] = params else {
+            return;
+        };
+        match *kind {
+            b"C" => self.marks.push(CommandMark::Started),
+            b"D" => {
+                let exit_code = rest
+                    .first()
+                    .and_then(|code| std::str::from_utf8(code).ok())
+                    .and_then(|code| code.parse().ok());
+                self.marks.push(CommandMark::Finished { exit_code });
+            }
+            // Prompt start (A) and end (B) don't change what the shell runs
+            _ => {}
+        }
+    }
+}
+
+/// Finds OSC 133 marks in PTY output, including marks split across reads
+#[derive(Default)]
+pub struct ShellMarks {
+    parser: Parser,
+}
+
+impl ShellMarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks completed by `data`, in order
+    pub fn scan(&mut self, data: &[u8]) -> Vec<CommandMark> {
+        let mut collector = MarkCollector::default();
+        for byte in data {
+            self.parser.advance(&mut collector, *byte);
+        }
+        collector.marks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_command_marks() {
+        let mut marks = ShellMarks::new();
+        // What the bash snippet of the README prints around `false`
+        let output = b"\x1b]133;A\x07$ \x1b]133;B\x07false\r\n\x1b]133;C\x07\
+            \x1b]133;D;1\x07\x1b]133;A\x07$ \x1b]133;B\x07";
+        assert_eq!(
+            marks.scan(output),
+            [
+                CommandMark::Started,
+                CommandMark::Finished { exit_code: Some(1) }
+            ]
+        );
+        // ST terminated, and without an exit code
+        assert_eq!(
+            marks.scan(b"\x1b]133;D\x1b\\"),
+            [CommandMark::Finished { exit_code: None }]
+        );
+        assert!(marks.scan(b"\x1b]0;title\x07\x1b[31mred\x1b[0m").is_empty());
+    }
+
+    #[test]
+    fn test_scan_finds_marks_split_across_reads() {
+        let mut marks = ShellMarks::new();
+        assert!(marks.scan(b"output\x1b]13").is_empty());
+        assert!(marks.scan(b"3;D;1").is_empty());
+        assert_eq!(
+            marks.scan(b"27\x07$ "),
+            [CommandMark::Finished {
+                exit_code: Some(127)
+            }]
+        );
+    }
+}
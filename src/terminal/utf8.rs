@@ -0,0 +1,76 @@
+/// Take the valid UTF-8 from the start of `bytes`, leaving a character
+/// split across reads for the next one. Invalid bytes are replaced.
+pub fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    let complete = bytes.len() - incomplete_tail(bytes);
+    let text = String::from_utf8_lossy(&bytes[..complete]).into_owned();
+    bytes.drain(..complete);
+    text
+}
+
+/// Number of bytes at the end of `bytes` that start a character whose
+/// other bytes are still to come, whatever precedes them
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    // A character has at most 4 bytes, so an unfinished one has at most 3
+    let start = bytes.len().saturating_sub(3);
+    (start..bytes.len())
+        .find(|&i| {
+            matches!(std::str::from_utf8(&bytes[i..]),
+                Err(e) if e.valid_up_to() == 0 && e.error_len().is_none())
+        })
+        .map_or(0, |i| bytes.len() - i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_utf8_keeps_split_characters() {
+        let mut bytes = "aé".as_bytes().to_vec();
+        bytes.pop();
+        assert_eq!(take_utf8(&mut bytes), "a");
+        assert_eq!(bytes, [0xc3]);
+
+        bytes.push(0xa9);
+        assert_eq!(take_utf8(&mut bytes), "é");
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_take_utf8_byte_by_byte() {
+        let input = "こんにちは, wörld";
+        let mut pending = Vec::new();
+        let mut chunks = Vec::new();
+        for byte in input.bytes() {
+            pending.push(byte);
+            let text = take_utf8(&mut pending);
+            if !text.is_empty() {
+                chunks.push(text);
+            }
+        }
+
+        assert!(pending.is_empty());
+        assert_eq!(chunks.concat(), input);
+        // Every character came out whole, once its last byte arrived
+        assert_eq!(chunks.len(), input.chars().count());
+        assert!(!chunks.concat().contains('\u{fffd}'));
+    }
+
+    #[test]
+    fn test_take_utf8_replaces_invalid_bytes() {
+        let mut bytes = vec![b'a', 0xff, b'b'];
+        assert_eq!(take_utf8(&mut bytes), "a\u{fffd}b");
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_take_utf8_keeps_split_character_after_invalid_byte() {
+        let mut bytes = vec![0xff, 0xe3, 0x81];
+        assert_eq!(take_utf8(&mut bytes), "\u{fffd}");
+        assert_eq!(bytes, [0xe3, 0x81]);
+
+        bytes.push(0x82);
+        assert_eq!(take_utf8(&mut bytes), "あ");
+        assert!(bytes.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, timeout};
+
+use crate::agent::Agents;
+use crate::config::triggers_config::{Trigger, TriggerType};
+
+/// Quiet period after the last event before a batch of changes is reported
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A debounced change to a watched file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub deleted: bool,
+}
+
+/// Watches a directory and reports debounced changes of files matching a glob
+pub struct FileWatcher {
+    // Dropping the watcher stops the events
+    _watcher: RecommendedWatcher,
+    root: PathBuf,
+    glob: GlobMatcher,
+    ignore_deleted: bool,
+    receiver: mpsc::UnboundedReceiver<notify::Result<Event>>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path, glob: &str, ignore_deleted: bool) -> Result<Self> {
+        let root = path
+            .canonicalize()
+            .with_context(|| format!("Cannot watch {}", path.display()))?;
+        let glob = Glob::new(glob)
+            .with_context(|| format!("Invalid glob pattern: {}", glob))?
+            .compile_matcher();
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            root,
+            glob,
+            ignore_deleted,
+            receiver,
+        })
+    }
+
+    /// Wait for the next non-empty batch of changes, settled for `DEBOUNCE`.
+    /// Returns None once the watcher stops.
+    pub async fn next_changes(&mut self) -> Option<Vec<FileChange>> {
+        loop {
+            // Later events for the same path win (e.g. delete then re-create)
+            let mut changes = BTreeMap::new();
+            let event = self.receiver.recv().await?;
+            self.collect(event, &mut changes);
+
+            while let Ok(Some(event)) = timeout(DEBOUNCE, self.receiver.recv()).await {
+                self.collect(event, &mut changes);
+            }
+
+            if !changes.is_empty() {
+                return Some(
+                    changes
+                        .into_iter()
+                        .map(|(path, deleted)| FileChange { path, deleted })
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    fn collect(&self, event: notify::Result<Event>, changes: &mut BTreeMap<PathBuf, bool>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("File watch error on {}: {}", self.root.display(), e);
+                return;
+            }
+        };
+
+        let deleted = match event.kind {
+            EventKind::Create(_) | EventKind::Modify(_) => false,
+            EventKind::Remove(_) => true,
+            _ => return,
+        };
+        if deleted && self.ignore_deleted {
+            return;
+        }
+
+        for path in event.paths {
+            if self.matches(&path) && (deleted || path.is_file()) {
+                changes.insert(path, deleted);
+            }
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        self.glob.is_match(relative)
+    }
+}
+
+/// File watch task manager responsible for handling watch entries
+pub struct FileWatch {
+    pub entries: Vec<Trigger>,
+    pub agents: Arc<Agents>,
+}
+
+impl FileWatch {
+    pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
+        Self { entries, agents }
+    }
+
+    /// Start one watcher task per entry and return their handles
+    pub fn start_all_tasks(&self) -> Vec<JoinHandle<()>> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let TriggerType::FileWatch {
+                    path,
+                    glob,
+                    read_content,
+                    ignore_deleted,
+                } = &entry.trigger
+                else {
+                    return None;
+                };
+
+                let mut watcher = match FileWatcher::new(path, glob, *ignore_deleted) {
+                    Ok(watcher) => watcher,
+                    Err(e) => {
+                        tracing::error!("Failed to start watch entry '{}': {:#}", entry.name, e);
+                        return None;
+                    }
+                };
+                let read_content = *read_content;
+                let entry = entry.clone();
+                let agents = Arc::clone(&self.agents);
+
+                Some(tokio::spawn(async move {
+                    tracing::debug!("Starting watch entry: {}", entry.name);
+
+                    while let Some(changes) = watcher.next_changes().await {
+                        for change in changes {
+                            let input = match read_input(&change, read_content).await {
+                                Ok(Some(input)) => input,
+                                Ok(None) => continue,
+                                Err(e) => {
+                                    tracing::error!(
+                                        "Watch entry '{}' failed to read {}: {}",
+                                        entry.name,
+                                        change.path.display(),
+                                        e
+                                    );
+                                    continue;
+                                }
+                            };
+
+                            let agent = match agents.get_next_idle_agent(entry.tag.as_deref()).await
+                            {
+                                Some(agent) => agent,
+                                None => agents.get_next_agent(entry.tag.as_deref()),
+                            };
+                            if let Err(e) =
+                                entry.run_with_input_recorded(&agent, &agents, &input).await
+                            {
+                                tracing::error!(
+                                    "Error executing watch entry '{}': {}",
+                                    entry.name,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                }))
+            })
+            .collect()
+    }
+}
+
+/// The value passed as ${1}: the file path, or its content with `read_content`.
+/// Deleted files have no content, so they are skipped in that mode.
+async fn read_input(change: &FileChange, read_content: bool) -> Result<Option<String>> {
+    if !read_content {
+        return Ok(Some(change.path.display().to_string()));
+    }
+    if change.deleted {
+        tracing::debug!("Skipping deleted file {}", change.path.display());
+        return Ok(None);
+    }
+    Ok(Some(tokio::fs::read_to_string(&change.path).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next_changes(watcher: &mut FileWatcher) -> Vec<FileChange> {
+        timeout(Duration::from_secs(5), watcher.next_changes())
+            .await
+            .expect("no file changes reported")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_file_watcher_reports_matching_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = FileWatcher::new(dir.path(), "*.md", false).unwrap();
+
+        std::fs::write(dir.path().join("task.md"), "fix the build").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].path.ends_with("task.md"));
+        assert!(!changes[0].deleted);
+
+        assert_eq!(
+            read_input(&changes[0], true).await.unwrap(),
+            Some("fix the build".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_watcher_deleted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("task.md");
+        std::fs::write(&path, "done").unwrap();
+
+        let mut watcher = FileWatcher::new(dir.path(), "*.md", false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].deleted);
+        assert_eq!(read_input(&changes[0], true).await.unwrap(), None);
+        assert_eq!(
+            read_input(&changes[0], false).await.unwrap(),
+            Some(changes[0].path.display().to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_watcher_ignore_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.md");
+        std::fs::write(&old, "done").unwrap();
+
+        let mut watcher = FileWatcher::new(dir.path(), "*.md", true).unwrap();
+        std::fs::remove_file(&old).unwrap();
+        std::fs::write(dir.path().join("new.md"), "next").unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].path.ends_with("new.md"));
+    }
+
+    #[test]
+    fn test_file_watcher_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(FileWatcher::new(&dir.path().join("missing"), "*", false).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Mutex;
+
+use crate::terminal::secret;
+
+/// Runs kept per trigger when not configured
+pub const DEFAULT_HISTORY_SIZE: usize = 20;
+
+/// Outcome of one run of a trigger entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriggerRun {
+    /// Unix timestamp (seconds) when the run started
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Source lines read, including previously seen ones
+    pub lines_produced: usize,
+    /// Lines the action ran for
+    pub lines_processed: usize,
+    pub error: Option<String>,
+    /// Runs left for entries with `max_runs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_runs: Option<u32>,
+}
+
+/// Ring buffer of the last runs of every trigger entry, keyed by name
+pub struct TriggerHistory {
+    capacity: usize,
+    runs: Mutex<BTreeMap<String, VecDeque<TriggerRun>>>,
+}
+
+impl TriggerHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            runs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a run, dropping the oldest one beyond the capacity. Secrets
+    /// sent so far are redacted from its error.
+    pub fn record(&self, trigger: &str, mut run: TriggerRun) {
+        if self.capacity == 0 {
+            return;
+        }
+        run.error = run.error.map(|error| secret::registry().redact(&error));
+        let Ok(mut runs) = self.runs.lock() else {
+            return;
+        };
+        let runs = runs.entry(trigger.to_string()).or_default();
+        if runs.len() >= self.capacity {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+    }
+
+    /// Attach the run budget left to the latest run of `trigger`
+    pub fn set_remaining_runs(&self, trigger: &str, remaining: u32) {
+        if let Ok(mut runs) = self.runs.lock()
+            && let Some(run) = runs.get_mut(trigger).and_then(|runs| runs.back_mut())
+        {
+            run.remaining_runs = Some(remaining);
+        }
+    }
+
+    /// Copy of the recorded runs, oldest first
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<TriggerRun>> {
+        self.runs
+            .lock()
+            .map(|runs| {
+                runs.iter()
+                    .map(|(name, runs)| (name.clone(), runs.iter().cloned().collect()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Render trigger runs as human readable text, newest run first.
+/// Paused entries are listed even before their first run.
+pub fn format_history(
+    history: &BTreeMap<String, Vec<TriggerRun>>,
+    paused: &BTreeSet<String>,
+    now: u64,
+) -> String {
+    let mut out = String::new();
+
+    let names: BTreeSet<&String> = history.keys().chain(paused).collect();
+    for name in names {
+        let runs = history.get(name).map(Vec::as_slice).unwrap_or_default();
+        let failed = runs.iter().filter(|run| run.error.is_some()).count();
+        let budget = match runs.last().and_then(|run| run.remaining_runs) {
+            Some(0) => ", retired".to_string(),
+            Some(remaining) => format!(", {} left", remaining),
+            None => String::new(),
+        };
+        let paused = if paused.contains(name) {
+            ", paused"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "{} ({} runs, {} failed{}{})\n",
+            name,
+            runs.len(),
+            failed,
+            budget,
+            paused
+        ));
+        for run in runs.iter().rev() {
+            let status = match &run.error {
+                Some(error) => format!("error: {}", error),
+                None => "ok".to_string(),
+            };
+            out.push_str(&format!(
+                "  {:>8} ago  {:>7.2}s  {:>4} produced  {:>4} processed  {}\n",
+                format!("{}s", now.saturating_sub(run.started_at)),
+                run.duration_ms as f64 / 1000.0,
+                run.lines_produced,
+                run.lines_processed,
+                status
+            ));
+        }
+    }
+
+    if out.is_empty() {
+        out.push_str("No trigger runs recorded yet\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(started_at: u64, error: Option<&str>) -> TriggerRun {
+        TriggerRun {
+            started_at,
+            duration_ms: 1500,
+            lines_produced: 5,
+            lines_processed: 3,
+            error: error.map(str::to_string),
+            remaining_runs: None,
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_last_runs() {
+        let history = TriggerHistory::new(2);
+        history.record("poller", run(1, None));
+        history.record("poller", run(2, Some("boom")));
+        history.record("poller", run(3, None));
+        history.record("startup", run(1, None));
+
+        let snapshot = history.snapshot();
+        let started: Vec<u64> = snapshot["poller"].iter().map(|r| r.started_at).collect();
+        assert_eq!(started, vec![2, 3]);
+        assert_eq!(snapshot["startup"].len(), 1);
+
+        history.set_remaining_runs("poller", 4);
+        history.set_remaining_runs("missing", 4);
+        let snapshot = history.snapshot();
+        assert_eq!(snapshot["poller"][1].remaining_runs, Some(4));
+        assert_eq!(snapshot["poller"][0].remaining_runs, None);
+        assert!(!snapshot.contains_key("missing"));
+        assert!(
+            format_history(&snapshot, &BTreeSet::new(), 10)
+                .starts_with("poller (2 runs, 1 failed, 4 left)\n")
+        );
+    }
+
+    #[test]
+    fn test_history_redacts_secrets_from_errors() {
+        secret::registry().remember("history-secret-c41d");
+        let history = TriggerHistory::new(2);
+        history.record(
+            "login",
+            run(1, Some("login failed: history-secret-c41d rejected")),
+        );
+        assert_eq!(
+            history.snapshot()["login"][0].error.as_deref(),
+            Some("login failed: *** rejected")
+        );
+    }
+
+    #[test]
+    fn test_history_disabled() {
+        let history = TriggerHistory::new(0);
+        history.record("poller", run(1, None));
+        assert!(history.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_format_history() {
+        let mut history = BTreeMap::new();
+        history.insert(
+            "poller".to_string(),
+            vec![run(100, Some("Source command failed")), run(160, None)],
+        );
+
+        let output = format_history(&history, &BTreeSet::new(), 200);
+        assert!(output.starts_with("poller (2 runs, 1 failed)\n"));
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[1].contains("40s ago") && lines[1].ends_with("ok"));
+        assert!(lines[1].contains("1.50s"));
+        assert!(lines[1].contains("5 produced") && lines[1].contains("3 processed"));
+        assert!(lines[2].contains("100s ago") && lines[2].contains("error: Source command failed"));
+
+        assert_eq!(
+            format_history(&BTreeMap::new(), &BTreeSet::new(), 200),
+            "No trigger runs recorded yet\n"
+        );
+
+        let paused = BTreeSet::from(["cleanup".to_string(), "poller".to_string()]);
+        let output = format_history(&history, &paused, 200);
+        assert!(output.starts_with(
+            "cleanup (0 runs, 0 failed, paused)\npoller (2 runs, 1 failed, paused)\n"
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant, timeout_at};
+
+use crate::agent::{AgentStatus, Agents};
+use crate::config::triggers_config::{Trigger, TriggerType};
+
+/// Idle task manager responsible for handling on_idle entries
+pub struct Idle {
+    pub entries: Vec<Trigger>,
+    pub agents: Arc<Agents>,
+}
+
+impl Idle {
+    pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
+        Self { entries, agents }
+    }
+
+    /// Start one task per agent and entry, returning their handles
+    pub fn start_all_tasks(&self) -> Vec<JoinHandle<()>> {
+        let mut handles = Vec::new();
+
+        for entry in &self.entries {
+            let TriggerType::OnIdle { min_idle } = entry.trigger else {
+                continue;
+            };
+
+            for agent in self.agents.tagged(entry.tag.as_deref()) {
+                let mut receiver = agent.subscribe_status();
+                let entry = entry.clone();
+                let agents = Arc::clone(&self.agents);
+
+                handles.push(tokio::spawn(async move {
+                    tracing::debug!(
+                        "Starting idle entry '{}' for agent {}",
+                        entry.name,
+                        agent.get_id()
+                    );
+
+                    loop {
+                        match receiver.recv().await {
+                            Ok(AgentStatus::Idle) => {
+                                if !stays_idle(&mut receiver, min_idle).await || agent.is_active() {
+                                    tracing::debug!(
+                                        "Agent {} became active again, skipping idle entry '{}'",
+                                        agent.get_id(),
+                                        entry.name
+                                    );
+                                    continue;
+                                }
+
+                                if let Err(e) = entry.run_recorded(&agent, &agents).await {
+                                    tracing::error!(
+                                        "Error executing idle entry '{}': {}",
+                                        entry.name,
+                                        e
+                                    );
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                tracing::warn!(
+                                    "Idle entry '{}' lagged, skipped {} status changes",
+                                    entry.name,
+                                    skipped
+                                );
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        }
+                    }
+                }));
+            }
+        }
+
+        handles
+    }
+}
+
+/// Wait for `min_idle`, returning false if the agent leaves Idle meanwhile
+async fn stays_idle(receiver: &mut broadcast::Receiver<AgentStatus>, min_idle: Duration) -> bool {
+    let deadline = Instant::now() + min_idle;
+
+    loop {
+        match timeout_at(deadline, receiver.recv()).await {
+            Err(_) => return true,
+            Ok(Ok(AgentStatus::Idle)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => {}
+            Ok(Ok(_)) => return false,
+            Ok(Err(broadcast::error::RecvError::Closed)) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+
+    #[tokio::test]
+    async fn test_stays_idle_without_activity() {
+        let (_sender, mut receiver) = broadcast::channel(4);
+        assert!(stays_idle(&mut receiver, Duration::from_millis(20)).await);
+        assert!(stays_idle(&mut receiver, Duration::ZERO).await);
+    }
+
+    #[tokio::test]
+    async fn test_stays_idle_interrupted_by_active() {
+        let (sender, mut receiver) = broadcast::channel(4);
+        sender.send(AgentStatus::Active).unwrap();
+        assert!(!stays_idle(&mut receiver, Duration::from_secs(5)).await);
+    }
+
+    #[tokio::test]
+    async fn test_idle_starts_task_per_agent() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.pool = 2;
+        let agents = Agents::new_with_mock(vec![], &config).await.unwrap();
+
+        let entries = vec![
+            Trigger {
+                name: "next_task".to_string(),
+                trigger: TriggerType::OnIdle {
+                    min_idle: Duration::from_secs(5),
+                },
+                action: ActionType::SendKeys(vec!["next".to_string()]),
+                ..Default::default()
+            },
+            Trigger {
+                name: "startup".to_string(),
+                trigger: TriggerType::OnStart,
+                action: ActionType::SendKeys(vec!["hello".to_string()]),
+                ..Default::default()
+            },
+        ];
+
+        let idle = Idle::new(entries, Arc::new(agents));
+        let handles = idle.start_all_tasks();
+        assert_eq!(handles.len(), 2);
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+}
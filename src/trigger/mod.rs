@@ -1,18 +1,42 @@
-use anyhow::Result;
-use std::process::Command;
+use anyhow::{Context, Result};
 use std::sync::Arc;
+use tokio::process::Command;
 use tokio::task::JoinHandle;
 use tokio::time::Duration;
 
+use crate::agent::agents::AgentSelector;
 use crate::agent::{Agent, Agents};
 use crate::config;
-use crate::config::triggers_config::{Trigger, TriggerType};
-
+use crate::config::triggers_config::{
+    DEFAULT_STICKY_KEY, Distribution, SourceFormat, Trigger, TriggerType,
+};
+use crate::rule::stats::unix_now;
+use crate::state::dedupe;
+use crate::template::{TemplateContext, resolve_action, resolve_template};
+use crate::terminal::secret;
+
+pub mod file_watch;
+pub mod history;
+pub mod idle;
+pub mod pause;
 pub mod periodic;
+pub mod queue;
+pub mod shutdown;
+pub mod source;
 pub mod startup;
+pub mod stdin;
+pub mod wait;
+pub mod webhook;
 
+pub use file_watch::FileWatch;
+use history::TriggerRun;
+pub use idle::Idle;
 pub use periodic::Periodic;
+pub use queue::QueueConsumer;
+pub use shutdown::Shutdown;
+pub use source::{SourceStream, parse_json_items};
 pub use startup::Startup;
+pub use stdin::StdinReader;
 
 /// Result of executing a shell command
 #[derive(Debug)]
@@ -38,24 +62,41 @@ impl CommandResult {
     }
 }
 
-/// Execute a shell command and return structured result
-pub async fn execute_shell_command(command: &str) -> Result<CommandResult> {
+/// Lines processed and skipped during one source run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SourceCounts {
+    pub processed: usize,
+    /// Lines skipped as previously seen (dedupe)
+    pub seen: usize,
+}
+
+impl SourceCounts {
+    /// Lines read from the source
+    pub fn produced(&self) -> usize {
+        self.processed + self.seen
+    }
+}
+
+/// Execute a shell command and return structured result.
+/// The command is killed if it runs longer than `timeout`.
+pub async fn execute_shell_command(command: &str, timeout: Duration) -> Result<CommandResult> {
     tracing::debug!("Executing shell command: {}", command);
 
-    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .kill_on_drop(true)
+        .output();
+    // Dropping the timed out future kills the child
+    let output = match tokio::time::timeout(timeout, child).await {
+        Ok(output) => output?,
+        Err(_) => anyhow::bail!("Command '{}' timed out after {:?}", command, timeout),
+    };
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
     let success = output.status.success();
 
-    if !success {
-        tracing::warn!(
-            "Command '{}' failed with stderr: {}",
-            command,
-            stderr.trim()
-        );
-    }
-
     Ok(CommandResult {
         success,
         stdout,
@@ -64,80 +105,441 @@ pub async fn execute_shell_command(command: &str) -> Result<CommandResult> {
 }
 
 impl config::triggers_config::Trigger {
-    /// Execute this trigger using the provided agent
-    pub async fn execute(&self, agent: &Agent) -> Result<()> {
-        tracing::info!("📦 Executing entry '{}': {:?}", self.name, self.action);
+    /// Execute this trigger using the provided agent.
+    /// Returns the source line counts (zero without a source).
+    pub async fn execute(&self, agent: &Agent) -> Result<SourceCounts> {
+        tracing::info!(
+            "📦 Executing entry '{}': {}",
+            self.name,
+            self.action.describe()
+        );
 
         if let Some(source) = &self.source {
-            self.execute_source_command(source, agent).await
+            self.execute_source_command(source, agent, None).await
         } else {
-            self.execute_action(agent, &format!("Entry '{}'", self.name))
-                .await
+            let context = TemplateContext::for_agent(agent).with_trigger_name(&self.name);
+            let action = resolve_action(&self.action, &context);
+            self.send_action(&action, agent, &format!("Entry '{}'", self.name))
+                .await?;
+            Ok(SourceCounts::default())
+        }
+    }
+
+    /// Execute this trigger starting at `agent`, spreading source lines over
+    /// the pool according to `distribution` (or over spawned agents with
+    /// `spawn_agent`)
+    pub async fn execute_in_pool(&self, agent: &Agent, agents: &Agents) -> Result<SourceCounts> {
+        match &self.source {
+            Some(source) if self.distribution != Distribution::Single || self.spawn_agent => {
+                tracing::info!(
+                    "📦 Executing entry '{}' ({:?}): {}",
+                    self.name,
+                    self.distribution,
+                    self.action.describe()
+                );
+                self.execute_source_command(source, agent, Some(agents))
+                    .await
+            }
+            _ => self.execute(agent).await,
         }
     }
 
-    /// Execute a source command and process its output
-    async fn execute_source_command(&self, source: &str, agent: &Agent) -> Result<()> {
-        let result = execute_shell_command(source).await?;
+    /// Run via `execute_in_pool` and record the outcome in the pool's trigger history
+    pub async fn run_recorded(&self, agent: &Agent, agents: &Agents) -> Result<SourceCounts> {
+        let run = self.with_max_runtime(agent, self.execute_in_pool(agent, agents));
+        record_run(self, agents, run).await
+    }
 
-        if !result.success {
-            anyhow::bail!(
-                "Source command failed: {} (stderr: {})",
-                source,
-                result.stderr.trim()
+    /// Run via `execute_with_input` and record the outcome in the pool's trigger history
+    pub async fn run_with_input_recorded(
+        &self,
+        agent: &Agent,
+        agents: &Agents,
+        input: &str,
+    ) -> Result<()> {
+        let run = async {
+            self.execute_with_input(agent, input).await?;
+            Ok(SourceCounts {
+                processed: 1,
+                seen: 0,
+            })
+        };
+        let run = self.with_max_runtime(agent, run);
+        record_run(self, agents, run).await.map(|_| ())
+    }
+
+    /// Process one line read from stdin like a source line and record it in
+    /// the pool's trigger history. `counts` carries over between lines;
+    /// returns false once `max_lines` is reached.
+    pub async fn run_line_recorded(
+        &self,
+        agent: &Agent,
+        agents: &Agents,
+        line: &str,
+        counts: &mut SourceCounts,
+    ) -> Result<bool> {
+        let mut more = true;
+        let run = async {
+            let before = *counts;
+            more = self
+                .process_source_item(line, None, agent, Some(agents), counts)
+                .await?;
+            Ok(SourceCounts {
+                processed: counts.processed - before.processed,
+                seen: counts.seen - before.seen,
+            })
+        };
+        let run = self.with_max_runtime(agent, run);
+        record_run(self, agents, run).await?;
+        Ok(more)
+    }
+
+    /// Run `run` within `max_runtime`. On expiry it is dropped, which kills
+    /// the source command and stops sending keys, and `on_timeout` is sent
+    /// to `agent`. With `spawn_agent` it bounds each spawned agent instead.
+    async fn with_max_runtime(
+        &self,
+        agent: &Agent,
+        run: impl Future<Output = Result<SourceCounts>>,
+    ) -> Result<SourceCounts> {
+        let Some(max_runtime) = self.max_runtime.filter(|_| !self.spawn_agent) else {
+            return run.await;
+        };
+        if let Ok(result) = tokio::time::timeout(max_runtime, run).await {
+            return result;
+        }
+
+        tracing::warn!(
+            "Entry '{}' exceeded max_runtime ({:?}), aborted",
+            self.name,
+            max_runtime
+        );
+        if let Some(on_timeout) = &self.on_timeout {
+            let context = TemplateContext::for_agent(agent).with_trigger_name(&self.name);
+            let action = resolve_action(on_timeout, &context);
+            let label = format!("Entry '{}' on_timeout", self.name);
+            if let Err(e) = execute_action_with_agent(&action, agent, &label).await {
+                tracing::error!("{} failed: {:#}", label, e);
+            }
+        }
+        anyhow::bail!(
+            "Entry '{}' exceeded max_runtime of {:?}",
+            self.name,
+            max_runtime
+        )
+    }
+
+    /// Execute this trigger with external input (e.g. a webhook body) available as ${1}
+    pub async fn execute_with_input(&self, agent: &Agent, input: &str) -> Result<()> {
+        tracing::info!(
+            "📦 Executing entry '{}' with input: {}",
+            self.name,
+            self.action.describe()
+        );
+
+        let action = resolve_action(&self.action, &self.line_context(agent, input));
+        self.send_action(&action, agent, &format!("Entry '{}'", self.name))
+            .await
+    }
+
+    /// Send `action` to `agent` once the `wait_for` condition holds
+    async fn send_action(
+        &self,
+        action: &config::helper::ActionType,
+        agent: &Agent,
+        context: &str,
+    ) -> Result<()> {
+        if let Some(wait_for) = &self.wait_for {
+            wait::wait_for(agent, wait_for)
+                .await
+                .with_context(|| format!("{}: action not sent", context))?;
+        }
+        if matches!(action, config::helper::ActionType::SendKeys(keys) if !keys.is_empty())
+            && !agent.is_dry_run()
+        {
+            agent.take_command_quota()?;
+        }
+        execute_action_with_agent(action, agent, context).await
+    }
+
+    /// Execute a source command and process its output, retrying failed
+    /// runs per `retry`. A run that already processed lines is not retried,
+    /// so no line is sent twice. Lines are only distributed when the agent
+    /// pool is given.
+    async fn execute_source_command(
+        &self,
+        source: &str,
+        agent: &Agent,
+        agents: Option<&Agents>,
+    ) -> Result<SourceCounts> {
+        let attempts = self.retry.map_or(1, |retry| retry.attempts);
+        let mut attempt = 1;
+
+        loop {
+            let mut counts = SourceCounts::default();
+            let error = match self
+                .run_source_command(source, agent, agents, &mut counts)
+                .await
+            {
+                Ok(()) => return Ok(counts),
+                Err(e) => e,
+            };
+
+            if counts.processed > 0 {
+                return Err(error.context(format!(
+                    "{} lines were processed before the failure",
+                    counts.processed
+                )));
+            }
+            let Some(retry) = self.retry.filter(|_| attempt < attempts) else {
+                return Err(error);
+            };
+            let delay = retry.delay(attempt);
+            tracing::warn!(
+                "Source of entry '{}' failed (attempt {}/{}), retrying in {:?}: {:#}",
+                self.name,
+                attempt,
+                attempts,
+                delay,
+                error
             );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Run the source command once and process its output line by line as it
+    /// arrives (or element by element with `format: json`)
+    async fn run_source_command(
+        &self,
+        source: &str,
+        agent: &Agent,
+        agents: Option<&Agents>,
+        counts: &mut SourceCounts,
+    ) -> Result<()> {
+        let mut stream = SourceStream::spawn(source, self.source_timeout)?;
+
+        match self.format {
+            SourceFormat::Lines => {
+                while let Some(line) = stream.next_line().await? {
+                    if !self
+                        .process_source_item(&line, None, agent, agents, counts)
+                        .await?
+                    {
+                        stream.kill().await;
+                        return Ok(());
+                    }
+                }
+                stream.finish().await?;
+            }
+            SourceFormat::Json => {
+                // A JSON document can only be parsed once the command is done
+                let mut output = String::new();
+                while let Some(line) = stream.next_line().await? {
+                    output.push_str(&line);
+                    output.push('\n');
+                }
+                stream.finish().await?;
+
+                let items = parse_json_items(&output).with_context(|| {
+                    format!("Entry '{}' expects JSON from its source", self.name)
+                })?;
+                for item in items {
+                    let line = item.to_string();
+                    if !self
+                        .process_source_item(&line, Some(item), agent, agents, counts)
+                        .await?
+                    {
+                        return Ok(());
+                    }
+                }
+            }
         }
 
-        let lines = result.stdout_lines();
-        if lines.is_empty() {
+        if counts.seen > 0 {
+            tracing::info!(
+                "Skipped {} previously seen lines for '{}'",
+                counts.seen,
+                self.name
+            );
+        }
+        if counts.processed == 0 && counts.seen == 0 {
             tracing::info!("Source command '{}' produced no output", source);
-            return Ok(());
         }
+        Ok(())
+    }
 
-        tracing::info!("Source command '{}' produced {} lines", source, lines.len());
+    /// Process one source line (or serialized JSON element), returning false
+    /// once `max_lines` is reached
+    async fn process_source_item(
+        &self,
+        line: &str,
+        json: Option<serde_json::Value>,
+        agent: &Agent,
+        agents: Option<&Agents>,
+        counts: &mut SourceCounts,
+    ) -> Result<bool> {
+        if let Some(options) = &self.dedupe
+            && !dedupe::is_new(&self.name, line, options.ttl).await?
+        {
+            counts.seen += 1;
+            return Ok(true);
+        }
 
-        // Process each line from the source command
-        for (i, line) in lines.iter().enumerate() {
-            let resolved_action = resolve_placeholders(&self.action, line);
-            let context = format!("Source line {}/{}", i + 1, lines.len());
+        let spawned = match agents {
+            Some(agents) if self.spawn_agent => Some(
+                agents
+                    .spawner()
+                    .spawn(&self.name, self.max_concurrent_spawned)
+                    .await?,
+            ),
+            _ => None,
+        };
+        let target = match (&spawned, agents) {
+            (Some(spawned), _) => Some(Arc::clone(spawned.agent())),
+            (None, Some(agents)) => {
+                self.agent_for_line(counts.processed, line, json.as_ref(), agent, agents)
+                    .await
+            }
+            (None, None) => None,
+        };
+        let agent = target.as_deref().unwrap_or(agent);
 
-            tracing::debug!(
-                "{}: {}",
-                context,
-                line.chars().take(100).collect::<String>()
-            );
+        let mut template_context = self.line_context(agent, line);
+        if let Some(json) = json {
+            template_context = template_context.with_json(json);
+        }
+        let resolved_action = resolve_action(&self.action, &template_context);
+        counts.processed += 1;
+        let context = format!("Source line {}", counts.processed);
+
+        tracing::info!(
+            "{} → {}: {}",
+            context,
+            agent.get_id(),
+            line.chars().take(100).collect::<String>()
+        );
 
-            if let Err(e) = execute_action_with_agent(&resolved_action, agent, &context).await {
-                tracing::error!("Failed to process {}: {}", context, e);
+        // A line counts as seen once its action was sent, so a line whose
+        // send failed is tried again on the next run
+        match self.send_action(&resolved_action, agent, &context).await {
+            Ok(()) => {
+                if let Some(options) = &self.dedupe {
+                    dedupe::mark_seen(&self.name, line, options.ttl).await?;
+                }
             }
+            Err(e) => tracing::error!("Failed to process {}: {}", context, e),
+        }
+        if let Some(spawned) = spawned {
+            tokio::spawn(spawned.retire_when_idle(self.max_runtime));
+        }
 
-            // Small delay between lines to prevent overwhelming the system
-            tokio::time::sleep(Duration::from_millis(100)).await;
+        // Small delay between lines to prevent overwhelming the system
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        if let Some(max_lines) = self.max_lines
+            && counts.processed >= max_lines
+        {
+            tracing::info!(
+                "Entry '{}' reached max_lines ({}), stopping source command",
+                self.name,
+                max_lines
+            );
+            return Ok(false);
         }
+        Ok(true)
+    }
 
-        Ok(())
+    /// Placeholders for a source line or input: ${1} is the whole line, or
+    /// ${1}, ${2}, ... its fields with `split`
+    fn line_context(&self, agent: &Agent, line: &str) -> TemplateContext {
+        let context = TemplateContext::for_agent(agent).with_trigger_name(&self.name);
+        match &self.split {
+            Some(split) => context.with_fields(line, split.fields(line)),
+            None => context.with_captures(vec![Some(line.to_string()), Some(line.to_string())]),
+        }
     }
 
-    /// Execute this trigger's action using the provided agent
-    async fn execute_action(&self, agent: &Agent, context: &str) -> Result<()> {
-        execute_action_with_agent(&self.action, agent, context).await
+    /// Pick the agent for the i-th source line; None keeps the entry's agent
+    async fn agent_for_line(
+        &self,
+        i: usize,
+        line: &str,
+        json: Option<&serde_json::Value>,
+        agent: &Agent,
+        agents: &Agents,
+    ) -> Option<Arc<Agent>> {
+        let key;
+        let selector = match self.distribution {
+            Distribution::Single => return None,
+            Distribution::RoundRobin => AgentSelector::RoundRobin {
+                start: agent.get_index(),
+                offset: i,
+            },
+            Distribution::LeastBusy => AgentSelector::LeastBusy,
+            Distribution::Sticky => {
+                let mut context = self.line_context(agent, line);
+                if let Some(json) = json {
+                    context = context.with_json(json.clone());
+                }
+                let template = self.sticky_key.as_deref().unwrap_or(DEFAULT_STICKY_KEY);
+                key = resolve_template(template, &context);
+                AgentSelector::Sticky(&key)
+            }
+        };
+        Some(agents.select_agent(selector, self.tag.as_deref()).await)
     }
 }
 
+/// Time `run` and record its outcome for `entry` in the pool's trigger history
+async fn record_run(
+    entry: &Trigger,
+    agents: &Agents,
+    run: impl Future<Output = Result<SourceCounts>>,
+) -> Result<SourceCounts> {
+    let started_at = unix_now();
+    let started = std::time::Instant::now();
+    let result = run.await;
+
+    let counts = result.as_ref().copied().unwrap_or_default();
+    let duration = started.elapsed();
+    crate::metrics::metrics().record_trigger_run(&entry.name, duration, result.is_ok());
+    agents.trigger_history().record(
+        &entry.name,
+        TriggerRun {
+            started_at,
+            duration_ms: duration.as_millis() as u64,
+            lines_produced: counts.produced(),
+            lines_processed: counts.processed,
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            remaining_runs: None,
+        },
+    );
+    result
+}
+
 /// Execute an action with consistent 100ms delay between keys
-async fn execute_action_with_agent(
+pub(crate) async fn execute_action_with_agent(
     action: &config::helper::ActionType,
     agent: &Agent,
     context: &str,
 ) -> Result<()> {
-    let config::helper::ActionType::SendKeys(keys) = action;
+    let keys = match action {
+        config::helper::ActionType::SendKeys(keys) => keys,
+        _ => return crate::action::execute_non_key_action(action, agent, context).await,
+    };
     if keys.is_empty() {
         tracing::debug!("{}: No keys to send", context);
         return Ok(());
     }
 
+    if agent.is_dry_run() {
+        tracing::info!("{}: WOULD SEND: {:?}", context, secret::redact_keys(keys));
+        return Ok(());
+    }
+
     tracing::info!("{}: Sending {} keys", context, keys.len());
-    tracing::debug!("{}: Keys: {:?}", context, keys);
+    tracing::debug!("{}: Keys: {:?}", context, secret::redact_keys(keys));
 
     for (i, key) in keys.iter().enumerate() {
         if i > 0 {
@@ -149,20 +551,7 @@ async fn execute_action_with_agent(
     Ok(())
 }
 
-/// Resolve ${1} placeholders in action with source line content
-fn resolve_placeholders(
-    action: &config::helper::ActionType,
-    line: &str,
-) -> config::helper::ActionType {
-    match action {
-        config::helper::ActionType::SendKeys(keys) => {
-            let resolved_keys = keys.iter().map(|key| key.replace("${1}", line)).collect();
-            config::helper::ActionType::SendKeys(resolved_keys)
-        }
-    }
-}
-
-/// Triggers responsible for managing startup and periodic entries
+/// Triggers responsible for managing startup, periodic, idle, watch and queue entries
 pub struct Triggers {
     triggers: Vec<Trigger>,
     agents: Arc<Agents>,
@@ -173,15 +562,27 @@ impl Triggers {
         Self { triggers, agents }
     }
 
-    /// Start all triggers: execute startup entries then start periodic tasks
+    /// Start all triggers: execute startup entries then start the event-driven tasks
     pub async fn start_all(&self) -> Result<Vec<JoinHandle<()>>> {
         // 1. Execute startup entries
         self.execute_startup_entries().await?;
 
         // 2. Start periodic tasks
-        let periodic_handles = self.start_periodic_tasks().await;
+        let mut handles = self.start_periodic_tasks().await;
+
+        // 3. Start idle tasks
+        handles.extend(self.start_idle_tasks());
+
+        // 4. Start file watchers
+        handles.extend(self.start_file_watch_tasks());
+
+        // 5. Start queue consumers
+        handles.extend(self.start_queue_tasks());
+
+        // 6. Read lines piped into stdin
+        handles.extend(self.start_stdin_task());
 
-        Ok(periodic_handles)
+        Ok(handles)
     }
 
     async fn execute_startup_entries(&self) -> Result<()> {
@@ -195,6 +596,37 @@ impl Triggers {
         let periodic_manager = Periodic::new(periodic_entries, Arc::clone(&self.agents));
         periodic_manager.start_all_tasks()
     }
+
+    fn start_idle_tasks(&self) -> Vec<JoinHandle<()>> {
+        let idle_entries = get_idle_triggers(&self.triggers);
+        let idle_manager = Idle::new(idle_entries, Arc::clone(&self.agents));
+        idle_manager.start_all_tasks()
+    }
+
+    fn start_file_watch_tasks(&self) -> Vec<JoinHandle<()>> {
+        let watch_entries = get_file_watch_triggers(&self.triggers);
+        let watch_manager = FileWatch::new(watch_entries, Arc::clone(&self.agents));
+        watch_manager.start_all_tasks()
+    }
+
+    /// Run the shutdown entries; called on Ctrl+C before tasks are aborted
+    pub async fn execute_shutdown_entries(&self) {
+        let shutdown_entries = get_shutdown_triggers(&self.triggers);
+        let shutdown_manager = Shutdown::new(shutdown_entries, Arc::clone(&self.agents));
+        shutdown_manager.execute_all_entries().await
+    }
+
+    fn start_queue_tasks(&self) -> Vec<JoinHandle<()>> {
+        let queue_entries = get_queue_triggers(&self.triggers);
+        let queue_manager = QueueConsumer::new(queue_entries, Arc::clone(&self.agents));
+        queue_manager.start_all_tasks()
+    }
+
+    fn start_stdin_task(&self) -> Vec<JoinHandle<()>> {
+        let stdin_entries = get_stdin_triggers(&self.triggers);
+        let stdin_reader = StdinReader::new(stdin_entries, Arc::clone(&self.agents));
+        stdin_reader.start_all_tasks()
+    }
 }
 
 /// Get startup triggers from a list of triggers
@@ -206,11 +638,70 @@ pub fn get_startup_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
         .collect()
 }
 
-/// Get periodic triggers from a list of triggers
+/// Get periodic and cron triggers from a list of triggers
 pub fn get_periodic_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
     triggers
         .iter()
-        .filter(|trigger| matches!(trigger.trigger, TriggerType::Periodic { .. }))
+        .filter(|trigger| {
+            matches!(
+                trigger.trigger,
+                TriggerType::Periodic { .. } | TriggerType::Cron { .. }
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+/// Get idle triggers from a list of triggers
+pub fn get_idle_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| matches!(trigger.trigger, TriggerType::OnIdle { .. }))
+        .cloned()
+        .collect()
+}
+
+/// Get webhook triggers from a list of triggers
+pub fn get_webhook_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| matches!(trigger.trigger, TriggerType::Webhook { .. }))
+        .cloned()
+        .collect()
+}
+
+/// Get file watch triggers from a list of triggers
+pub fn get_file_watch_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| matches!(trigger.trigger, TriggerType::FileWatch { .. }))
+        .cloned()
+        .collect()
+}
+
+/// Get shutdown triggers from a list of triggers
+pub fn get_shutdown_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| trigger.trigger == TriggerType::OnShutdown)
+        .cloned()
+        .collect()
+}
+
+/// Get queue triggers from a list of triggers
+pub fn get_queue_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| matches!(trigger.trigger, TriggerType::Queue { .. }))
+        .cloned()
+        .collect()
+}
+
+/// Get stdin triggers from a list of triggers
+pub fn get_stdin_triggers(triggers: &[Trigger]) -> Vec<Trigger> {
+    triggers
+        .iter()
+        .filter(|trigger| trigger.trigger == TriggerType::Stdin)
         .cloned()
         .collect()
 }
@@ -220,12 +711,16 @@ mod tests {
     use super::*;
     use crate::config::Config;
     use crate::config::helper::ActionType;
-    use crate::config::triggers_config::{Trigger, TriggerType};
+    use crate::config::triggers_config::{
+        DEFAULT_SOURCE_TIMEOUT, Distribution, PeriodicOptions, SourceFormat, Trigger, TriggerType,
+    };
     use tokio::time::Duration as TokioDuration;
 
     #[tokio::test]
     async fn test_execute_shell_command_success() {
-        let result = execute_shell_command("echo hello").await.unwrap();
+        let result = execute_shell_command("echo hello", DEFAULT_SOURCE_TIMEOUT)
+            .await
+            .unwrap();
         assert!(result.success);
         assert_eq!(result.stdout_lines(), vec!["hello"]);
         assert!(result.has_output());
@@ -233,20 +728,30 @@ mod tests {
 
     #[tokio::test]
     async fn test_execute_shell_command_failure() {
-        let result = execute_shell_command("false").await.unwrap();
+        let result = execute_shell_command("false", DEFAULT_SOURCE_TIMEOUT)
+            .await
+            .unwrap();
         assert!(!result.success);
         assert!(!result.has_output());
     }
 
     #[tokio::test]
     async fn test_execute_shell_command_with_stderr() {
-        let result = execute_shell_command("echo error >&2; false")
+        let result = execute_shell_command("echo error >&2; false", DEFAULT_SOURCE_TIMEOUT)
             .await
             .unwrap();
         assert!(!result.success);
         assert!(result.stderr.contains("error"));
     }
 
+    #[tokio::test]
+    async fn test_execute_shell_command_timeout() {
+        let started = std::time::Instant::now();
+        let result = execute_shell_command("sleep 5", TokioDuration::from_millis(100)).await;
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+        assert!(started.elapsed() < TokioDuration::from_secs(2));
+    }
+
     #[test]
     fn test_command_result_stdout_lines() {
         let result = CommandResult {
@@ -281,24 +786,22 @@ mod tests {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "periodic1".to_string(),
                 trigger: TriggerType::Periodic {
                     interval: TokioDuration::from_secs(1),
+                    options: PeriodicOptions::default(),
                 },
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup2".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
         ];
 
@@ -315,26 +818,33 @@ mod tests {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "periodic1".to_string(),
                 trigger: TriggerType::Periodic {
                     interval: TokioDuration::from_secs(1),
+                    options: PeriodicOptions::default(),
                 },
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "periodic2".to_string(),
                 trigger: TriggerType::Periodic {
                     interval: TokioDuration::from_secs(2),
+                    options: PeriodicOptions::default(),
                 },
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
+            },
+            Trigger {
+                name: "idle1".to_string(),
+                trigger: TriggerType::OnIdle {
+                    min_idle: TokioDuration::from_secs(5),
+                },
+                action: ActionType::SendKeys(vec!["test".to_string()]),
+                ..Default::default()
             },
         ];
 
@@ -342,6 +852,10 @@ mod tests {
         assert_eq!(periodic_triggers.len(), 2);
         assert_eq!(periodic_triggers[0].name, "periodic1");
         assert_eq!(periodic_triggers[1].name, "periodic2");
+
+        let idle_triggers = get_idle_triggers(&triggers);
+        assert_eq!(idle_triggers.len(), 1);
+        assert_eq!(idle_triggers[0].name, "idle1");
     }
 
     #[tokio::test]
@@ -368,17 +882,30 @@ mod tests {
     }
 
     #[test]
-    fn test_resolve_placeholders() {
+    fn test_resolve_action_with_source_line() {
         let action = ActionType::SendKeys(vec![
             "echo".to_string(),
             "${1}".to_string(),
             "done".to_string(),
         ]);
 
-        let resolved = resolve_placeholders(&action, "test_value");
-
-        let ActionType::SendKeys(keys) = resolved;
-        assert_eq!(keys, vec!["echo", "test_value", "done"]);
+        let context = TemplateContext {
+            captures: vec![
+                Some("test_value".to_string()),
+                Some("test_value".to_string()),
+            ],
+            ..TemplateContext::default()
+        };
+        let resolved = resolve_action(&action, &context);
+
+        assert_eq!(
+            resolved,
+            ActionType::SendKeys(vec![
+                "echo".to_string(),
+                "test_value".to_string(),
+                "done".to_string()
+            ])
+        );
     }
 
     #[tokio::test]
@@ -392,8 +919,7 @@ mod tests {
             name: "test_trigger".to_string(),
             trigger: TriggerType::OnStart,
             action: ActionType::SendKeys(vec!["echo".to_string(), "test".to_string()]),
-            source: None,
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = trigger.execute(&agent).await;
@@ -412,7 +938,7 @@ mod tests {
             trigger: TriggerType::OnStart,
             action: ActionType::SendKeys(vec!["echo".to_string(), "${1}".to_string()]),
             source: Some("echo hello".to_string()),
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = trigger.execute(&agent).await;
@@ -431,7 +957,7 @@ mod tests {
             trigger: TriggerType::OnStart,
             action: ActionType::SendKeys(vec!["echo".to_string(), "${1}".to_string()]),
             source: Some("false".to_string()),
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = trigger.execute(&agent).await;
@@ -450,11 +976,456 @@ mod tests {
             trigger: TriggerType::OnStart,
             action: ActionType::SendKeys(vec!["echo".to_string(), "${1}".to_string()]),
             source: Some("true".to_string()), // succeeds but produces no output
-            dedupe: false,
+            dedupe: None,
+            ..Default::default()
+        };
+
+        let result = trigger.execute(&agent).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_dry_run_sends_nothing() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.dry_run = true;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            name: "dry_run_trigger".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["process".to_string(), "${1}".to_string()]),
+            source: Some("printf 'a\\nb\\n'".to_string()),
+            ..Default::default()
         };
 
         let result = trigger.execute(&agent).await;
         assert!(result.is_ok());
+        assert!(mock_pty.get_sent_inputs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_refused_over_command_quota() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.max_commands_per_hour = Some(1);
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            name: "claude".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["claude\r".to_string()]),
+            ..Default::default()
+        };
+
+        trigger.execute(&agent).await.unwrap();
+        let error = trigger.execute(&agent).await.unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("max_commands_per_hour"),
+            "{:#}",
+            error
+        );
+        assert_eq!(mock_pty.get_sent_inputs(), ["claude\r"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_resolves_agent_variables() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(2, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            name: "audit".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec![
+                "${trigger_name} ${agent_id} ${agent_index} ${1}".to_string(),
+            ]),
+            source: Some("echo item".to_string()),
+            ..Default::default()
+        };
+
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["audit agent-2 2 item"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_dedupe_skips_seen_lines() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let mut trigger = Trigger {
+            name: "dedupe_execute_test".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("printf 'a\\nb\\n'".to_string()),
+            dedupe: Some(Default::default()),
+            ..Default::default()
+        };
+        trigger.execute(&agent).await.unwrap();
+
+        trigger.source = Some("printf 'b\\nc\\n'".to_string());
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_dedupe_retries_unsent_lines() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.max_commands_per_hour = Some(1);
+        let trigger = Trigger {
+            name: "dedupe_retry_test".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("printf 'a\\nb\\n'".to_string()),
+            dedupe: Some(Default::default()),
+            ..Default::default()
+        };
+
+        // The quota refuses "b", which stays new
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["a"]);
+
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_streams_and_caps_lines() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        // The command would hang after two lines, the cap stops it first
+        let trigger = Trigger {
+            name: "capped".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("echo a; echo b; sleep 30; echo c".to_string()),
+            max_lines: Some(2),
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        trigger.execute(&agent).await.unwrap();
+        assert!(started.elapsed() < TokioDuration::from_secs(5));
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_split_fields() {
+        use crate::config::triggers_config::{FieldSplit, SplitSetting};
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            name: "issues".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["fix #${1}: ${2}${3}".to_string()]),
+            source: Some("printf '123\\tfix login bug\\n'".to_string()),
+            split: Some(FieldSplit::parse(&SplitSetting::Separator("\t".to_string())).unwrap()),
+            ..Default::default()
+        };
+
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["fix #123: fix login bug"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_waits_for_condition() {
+        use crate::agent::AgentStatus;
+        use crate::config::triggers_config::{WaitCondition, WaitFor};
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            name: "start-claude".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["claude".to_string()]),
+            wait_for: Some(WaitFor {
+                condition: WaitCondition::Idle,
+                timeout: TokioDuration::from_millis(200),
+            }),
+            ..Default::default()
+        };
+
+        // Times out while the agent stays busy, without sending anything
+        agent.set_status(AgentStatus::Active);
+        let error = trigger.execute(&agent).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("action not sent"));
+        assert!(mock_pty.get_sent_inputs().is_empty());
+
+        agent.set_status(AgentStatus::Idle);
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["claude"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_recorded_aborts_after_max_runtime() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let agents = Agents::from_agents(vec![], vec![Arc::clone(&agent)]);
+
+        let trigger = Trigger {
+            name: "stuck".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("echo first; sleep 5; echo second".to_string()),
+            max_runtime: Some(TokioDuration::from_millis(500)),
+            on_timeout: Some(ActionType::SendKeys(vec!["\u{3}".to_string()])),
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        let error = trigger.run_recorded(&agent, &agents).await.unwrap_err();
+        assert!(started.elapsed() < TokioDuration::from_secs(3));
+        assert!(
+            error.to_string().contains("exceeded max_runtime"),
+            "{}",
+            error
+        );
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["first", "\u{3}"]);
+
+        let history = agents.trigger_runs();
+        assert!(history["stuck"][0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_json_source() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        let mut trigger = Trigger {
+            name: "json_source_test".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["fix #${.number}: ${.title}".to_string()]),
+            source: Some(
+                r#"echo '[{"number": 1, "title": "Crash"}, {"number": 2, "title": "Typo"}]'"#
+                    .to_string(),
+            ),
+            dedupe: Some(Default::default()),
+            format: SourceFormat::Json,
+            ..Default::default()
+        };
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(
+            mock_pty.get_sent_inputs(),
+            vec!["fix #1: Crash", "fix #2: Typo"]
+        );
+
+        // Dedupe keys on the serialized element
+        trigger.source = Some(r#"echo '{"number": 2, "title": "Typo"}'"#.to_string());
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs().len(), 2);
+
+        trigger.source = Some("echo 'not json'".to_string());
+        let error = trigger.execute(&agent).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("expects JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_retries_failed_source() {
+        use crate::config::triggers_config::RetryPolicy;
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+
+        // Fails on the first run only
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("failed-once");
+        let mut trigger = Trigger {
+            name: "flaky".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some(format!(
+                "test -f {0} && echo ok || {{ touch {0}; exit 1; }}",
+                marker.display()
+            )),
+            retry: Some(RetryPolicy {
+                attempts: 2,
+                backoff: TokioDuration::from_millis(10),
+            }),
+            ..Default::default()
+        };
+        trigger.execute(&agent).await.unwrap();
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["ok"]);
+
+        // Exhausted attempts surface the error
+        trigger.source = Some("echo unavailable >&2; exit 1".to_string());
+        let error = trigger.execute(&agent).await.unwrap_err();
+        assert!(error.to_string().contains("unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_in_pool_round_robin() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mut mocks = Vec::new();
+        let mut pool = Vec::new();
+        for index in 0..2 {
+            let mock_pty = Arc::new(MockPtyProcess::new());
+            let agent = Agent::new_with_process(index, &config, Box::new(Arc::clone(&mock_pty)))
+                .await
+                .unwrap();
+            mocks.push(mock_pty);
+            pool.push(agent);
+        }
+        let agents = Agents::from_agents(vec![], pool);
+
+        let trigger = Trigger {
+            name: "issues".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("printf 'a\\nb\\nc\\n'".to_string()),
+            distribution: Distribution::RoundRobin,
+            ..Default::default()
+        };
+
+        let agent = agents.get_agent_by_index(1);
+        trigger.execute_in_pool(&agent, &agents).await.unwrap();
+        assert_eq!(mocks[0].get_sent_inputs(), vec!["b"]);
+        assert_eq!(mocks[1].get_sent_inputs(), vec!["a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_in_pool_spawns_agent_per_line() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let agents = Agents::from_agents(vec![], vec![Arc::clone(&agent)]);
+
+        let trigger = Trigger {
+            name: "tasks".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some("printf 'a\\nb\\n'".to_string()),
+            spawn_agent: true,
+            ..Default::default()
+        };
+
+        trigger.execute_in_pool(&agent, &agents).await.unwrap();
+        assert!(mock_pty.get_sent_inputs().is_empty());
+        let spawned = agents.spawner().spawned_mocks();
+        assert_eq!(spawned.len(), 2);
+        assert_eq!(spawned[0].get_sent_inputs(), vec!["a"]);
+        assert_eq!(spawned[1].get_sent_inputs(), vec!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_execute_in_pool_sticky() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mut mocks = Vec::new();
+        let mut pool = Vec::new();
+        for index in 0..3 {
+            let mock_pty = Arc::new(MockPtyProcess::new());
+            let agent = Agent::new_with_process(index, &config, Box::new(Arc::clone(&mock_pty)))
+                .await
+                .unwrap();
+            mocks.push(mock_pty);
+            pool.push(agent);
+        }
+        let agents = Agents::from_agents(vec![], pool);
+
+        let trigger = Trigger {
+            name: "issues".to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec!["${0}".to_string()]),
+            source: Some("printf '1 a\\n2 b\\n1 c\\n2 d\\n1 e\\n'".to_string()),
+            split: Some(crate::config::triggers_config::FieldSplit::Separator(
+                " ".to_string(),
+            )),
+            distribution: Distribution::Sticky,
+            sticky_key: Some("issue-${1}".to_string()),
+            ..Default::default()
+        };
+
+        let agent = agents.get_agent_by_index(0);
+        trigger.execute_in_pool(&agent, &agents).await.unwrap();
+        // Lines of the same issue end up on one agent
+        let owner = |line: &str| {
+            mocks
+                .iter()
+                .position(|mock| mock.get_sent_inputs().iter().any(|sent| sent == line))
+                .unwrap()
+        };
+        assert_eq!(owner("1 a"), owner("1 c"));
+        assert_eq!(owner("1 a"), owner("1 e"));
+        assert_eq!(owner("2 b"), owner("2 d"));
     }
 
     #[tokio::test]
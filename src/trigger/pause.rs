@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// Names of periodic and cron entries paused at runtime; their ticks are
+/// skipped until resumed
+#[derive(Default)]
+pub struct PausedTriggers {
+    names: Mutex<BTreeSet<String>>,
+}
+
+impl PausedTriggers {
+    /// Pause `name`; returns false if it already was
+    pub fn pause(&self, name: &str) -> bool {
+        self.lock().insert(name.to_string())
+    }
+
+    /// Resume `name`; returns false if it wasn't paused
+    pub fn resume(&self, name: &str) -> bool {
+        self.lock().remove(name)
+    }
+
+    pub fn is_paused(&self, name: &str) -> bool {
+        self.lock().contains(name)
+    }
+
+    /// Pause all `names` (paused state restored from a previous run)
+    pub fn restore(&self, names: impl IntoIterator<Item = String>) {
+        self.lock().extend(names);
+    }
+
+    pub fn snapshot(&self) -> BTreeSet<String> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeSet<String>> {
+        self.names.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_resume() {
+        let paused = PausedTriggers::default();
+        assert!(!paused.is_paused("poller"));
+
+        assert!(paused.pause("poller"));
+        assert!(!paused.pause("poller"));
+        assert!(paused.is_paused("poller"));
+
+        paused.restore(["cleanup".to_string()]);
+        assert_eq!(
+            paused.snapshot().into_iter().collect::<Vec<_>>(),
+            vec!["cleanup", "poller"]
+        );
+
+        assert!(paused.resume("poller"));
+        assert!(!paused.resume("poller"));
+        assert!(!paused.is_paused("poller"));
+    }
+}
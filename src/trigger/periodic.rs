@@ -1,13 +1,14 @@
 use anyhow::Result;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
-use tokio::time::interval;
+use tokio::time::{Duration, Instant, sleep_until};
 
 use super::execute_shell_command;
-use crate::agent::Agents;
-use crate::config::triggers_config::{Trigger, TriggerType};
+use crate::agent::{Agent, Agents};
+use crate::config::cron_schedule::CronSchedule;
+use crate::config::triggers_config::{PeriodicOptions, Trigger, TriggerType};
 
-/// Periodic task manager responsible for handling periodic entries
+/// Periodic task manager responsible for handling periodic and cron entries
 pub struct Periodic {
     pub entries: Vec<Trigger>,
     pub agents: Arc<Agents>,
@@ -21,65 +22,251 @@ impl Periodic {
     pub fn start_all_tasks(&self) -> Vec<JoinHandle<()>> {
         self.entries
             .iter()
-            .filter_map(|entry| {
-                if let TriggerType::Periodic { interval: period } = entry.trigger {
+            .filter_map(|entry| match &entry.trigger {
+                TriggerType::Periodic {
+                    interval: period,
+                    options,
+                } => {
+                    let period = *period;
+                    let options = options.clone();
                     let entry = entry.clone();
                     let agents = Arc::clone(&self.agents);
 
                     Some(tokio::spawn(async move {
                         tracing::debug!("Starting periodic entry: {}", entry.name);
 
-                        // Execute immediately on startup (only if agent is idle)
-                        if let Some(agent) = agents.get_next_idle_agent().await {
-                            if let Err(e) = entry.execute(&agent).await {
+                        let mut skipped = 0;
+                        let mut budget = RunBudget::new(options.max_runs);
+                        let mut next = Instant::now();
+                        if let Some(delay) = options.initial_delay {
+                            // Skip the startup execution and start ticking after the delay
+                            next += delay;
+                        } else if !is_paused(&entry, &agents)
+                            && let Some(agent) =
+                                pick_agent(&entry, &agents, &options, &mut skipped).await
+                        {
+                            // Execute immediately on startup (only if agent is idle)
+                            if let Err(e) = entry.run_recorded(&agent, &agents).await {
                                 tracing::error!(
                                     "Error executing periodic entry '{}': {}",
                                     entry.name,
                                     e
                                 );
                             }
-                        } else {
-                            tracing::debug!("No idle agents available for startup execution of '{}'", entry.name);
+                            budget.count_run(&entry, &agents);
                         }
 
                         // Continue with periodic execution
-                        let mut timer = interval(period);
-                        loop {
-                            timer.tick().await;
-
-                            match has_data_to_process(&entry).await {
-                                Ok(true) => {
-                                    if let Some(agent) = agents.get_next_idle_agent().await {
-                                        if let Err(e) = entry.execute(&agent).await {
-                                            tracing::error!(
-                                                "Error executing periodic entry '{}': {}",
-                                                entry.name,
-                                                e
-                                            );
-                                        }
-                                    } else {
-                                        tracing::debug!("No idle agents available for periodic execution of '{}'", entry.name);
-                                    }
+                        while !budget.is_spent() {
+                            let at = match options.jitter {
+                                Some(jitter) => {
+                                    let at = apply_jitter(next, jitter);
+                                    tracing::info!(
+                                        "Periodic entry '{}' scheduled in {:?} (jitter ±{:?})",
+                                        entry.name,
+                                        at.saturating_duration_since(Instant::now()),
+                                        jitter
+                                    );
+                                    at
                                 }
-                                Ok(false) => {
-                                    tracing::debug!("No data to process for entry: {}", entry.name)
-                                }
-                                Err(e) => tracing::error!(
-                                    "Error checking data for entry '{}': {}",
-                                    entry.name,
-                                    e
-                                ),
+                                None => next,
+                            };
+                            sleep_until(at).await;
+                            if run_if_data(&entry, &agents, &options, &mut skipped).await {
+                                budget.count_run(&entry, &agents);
                             }
+                            next += period;
                         }
+                        tracing::info!(
+                            "Periodic entry '{}' retired after {} runs",
+                            entry.name,
+                            options.max_runs.unwrap_or_default()
+                        );
                     }))
-                } else {
-                    None
                 }
+                TriggerType::Cron { schedule } => {
+                    let schedule = schedule.clone();
+                    let entry = entry.clone();
+                    let agents = Arc::clone(&self.agents);
+
+                    Some(tokio::spawn(async move {
+                        tracing::debug!(
+                            "Starting cron entry: {} ({})",
+                            entry.name,
+                            schedule.expression()
+                        );
+
+                        let options = PeriodicOptions::default();
+                        let mut skipped = 0;
+                        while wait_for_next_run(&schedule).await {
+                            run_if_data(&entry, &agents, &options, &mut skipped).await;
+                        }
+                        tracing::warn!("Cron entry '{}' has no further runs", entry.name);
+                    }))
+                }
+                _ => None,
             })
             .collect()
     }
 }
 
+/// Runs left for a `max_runs` entry; unlimited without it
+struct RunBudget {
+    remaining: Option<u32>,
+}
+
+impl RunBudget {
+    fn new(max_runs: Option<u32>) -> Self {
+        Self {
+            remaining: max_runs,
+        }
+    }
+
+    /// Count one execution and publish the runs left in the trigger history
+    fn count_run(&mut self, entry: &Trigger, agents: &Agents) {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(1);
+            agents
+                .trigger_history()
+                .set_remaining_runs(&entry.name, *remaining);
+        }
+    }
+
+    fn is_spent(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
+
+/// Shift a scheduled time by a random offset within ±jitter
+fn apply_jitter(at: Instant, jitter: Duration) -> Instant {
+    let range = jitter.as_millis() as i64;
+    let offset = fastrand::i64(-range..=range);
+    if offset >= 0 {
+        at + Duration::from_millis(offset as u64)
+    } else {
+        at.checked_sub(Duration::from_millis(offset.unsigned_abs()))
+            .unwrap_or(at)
+    }
+}
+
+/// Sleep until the next cron fire time. Returns false if there is none.
+async fn wait_for_next_run(schedule: &CronSchedule) -> bool {
+    let Some(next) = schedule.next() else {
+        return false;
+    };
+    let delay = (next.to_utc() - chrono::Utc::now())
+        .to_std()
+        .unwrap_or_default();
+    tracing::debug!("Next cron run at {} (in {:?})", next, delay);
+    tokio::time::sleep(delay).await;
+    true
+}
+
+/// Format the next `count` runs of every cron entry (for `ccauto show --schedule`)
+pub fn format_cron_schedule(
+    entries: &[Trigger],
+    after: chrono::DateTime<chrono::Utc>,
+    count: usize,
+) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        let TriggerType::Cron { schedule } = &entry.trigger else {
+            continue;
+        };
+        out.push_str(&format!(
+            "{}: {} ({})\n",
+            entry.name,
+            schedule.expression(),
+            schedule.timezone_name()
+        ));
+        for run in schedule.upcoming_after(after, count) {
+            out.push_str(&format!("  {}\n", run.format("%a %Y-%m-%d %H:%M:%S %:z")));
+        }
+    }
+
+    if out.is_empty() {
+        out.push_str("No cron entries configured\n");
+    }
+    out
+}
+
+/// Execute the entry on an idle agent if its source has data.
+/// `skipped` counts runs dropped because every agent was busy.
+/// Returns whether the entry was executed (successfully or not).
+async fn run_if_data(
+    entry: &Trigger,
+    agents: &Agents,
+    options: &PeriodicOptions,
+    skipped: &mut u64,
+) -> bool {
+    if is_paused(entry, agents) {
+        return false;
+    }
+    match has_data_to_process(entry).await {
+        Ok(true) => {
+            let Some(agent) = pick_agent(entry, agents, options, skipped).await else {
+                return false;
+            };
+            if let Err(e) = entry.run_recorded(&agent, agents).await {
+                tracing::error!("Error executing periodic entry '{}': {}", entry.name, e);
+            }
+            true
+        }
+        Ok(false) => {
+            tracing::debug!("No data to process for entry: {}", entry.name);
+            false
+        }
+        Err(e) => {
+            tracing::error!("Error checking data for entry '{}': {}", entry.name, e);
+            false
+        }
+    }
+}
+
+/// Whether the entry was paused with `ccauto trigger pause`, skipping this tick
+fn is_paused(entry: &Trigger, agents: &Agents) -> bool {
+    let paused = agents.paused_triggers().is_paused(&entry.name);
+    if paused {
+        tracing::debug!("Periodic entry '{}' is paused, skipping run", entry.name);
+    }
+    paused
+}
+
+/// Pick an idle agent for a periodic run, waiting for one with `queue_if_active`
+async fn pick_agent(
+    entry: &Trigger,
+    agents: &Agents,
+    options: &PeriodicOptions,
+    skipped: &mut u64,
+) -> Option<Arc<Agent>> {
+    if let Some(agent) = agents.get_next_idle_agent(entry.tag.as_deref()).await {
+        return Some(agent);
+    }
+
+    if options.queue_if_active {
+        tracing::info!(
+            "All agents busy, deferring periodic entry '{}' until an agent is idle",
+            entry.name
+        );
+        return agents.wait_for_idle_agent(entry.tag.as_deref()).await;
+    }
+
+    *skipped += 1;
+    if options.skip_if_active {
+        tracing::info!(
+            "All agents busy, skipped periodic entry '{}' ({} runs skipped so far)",
+            entry.name,
+            skipped
+        );
+    } else {
+        tracing::debug!(
+            "No idle agents available for periodic execution of '{}'",
+            entry.name
+        );
+    }
+    None
+}
+
 /// Check if a periodic entry will produce data to process
 async fn has_data_to_process(entry: &Trigger) -> Result<bool> {
     // If there's no source command, we consider it as having data to process
@@ -88,7 +275,14 @@ async fn has_data_to_process(entry: &Trigger) -> Result<bool> {
     };
 
     // Execute the source command
-    let result = execute_shell_command(source).await?;
+    let result = execute_shell_command(source, entry.source_timeout).await?;
+    if !result.success {
+        tracing::warn!(
+            "Source check of entry '{}' failed with stderr: {}",
+            entry.name,
+            result.stderr.trim()
+        );
+    }
 
     // Return false if command failed or has no output
     Ok(result.success && result.has_output())
@@ -97,9 +291,11 @@ async fn has_data_to_process(entry: &Trigger) -> Result<bool> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::AgentStatus;
     use crate::config::Config;
     use crate::config::helper::ActionType;
-    use crate::config::triggers_config::{Trigger, TriggerType};
+    use crate::config::triggers_config::{PeriodicOptions, Trigger, TriggerType};
+    use crate::terminal::pty_process_trait::MockPtyProcess;
     use tokio::time::Duration as TokioDuration;
 
     #[tokio::test]
@@ -136,34 +332,41 @@ mod tests {
                 name: "periodic1".to_string(),
                 trigger: TriggerType::Periodic {
                     interval: TokioDuration::from_millis(100),
+                    options: PeriodicOptions::default(),
                 },
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "periodic2".to_string(),
                 trigger: TriggerType::Periodic {
                     interval: TokioDuration::from_millis(200),
+                    options: PeriodicOptions::default(),
                 },
                 action: ActionType::SendKeys(vec!["test2".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
+            },
+            Trigger {
+                name: "cron1".to_string(),
+                trigger: TriggerType::Cron {
+                    schedule: Box::new(CronSchedule::parse("0 9 * * 1-5", None).unwrap()),
+                },
+                action: ActionType::SendKeys(vec!["test3".to_string()]),
+                ..Default::default()
             },
         ];
 
         let periodic = Periodic::new(entries, Arc::new(agents));
         let handles = periodic.start_all_tasks();
 
-        // Should only start periodic tasks, not startup tasks
-        assert_eq!(handles.len(), 2);
+        // Should only start periodic and cron tasks, not startup tasks
+        assert_eq!(handles.len(), 3);
 
         // Clean up handles
         for handle in handles {
@@ -171,16 +374,149 @@ mod tests {
         }
     }
 
+    async fn create_busy_agents() -> (Agents, Vec<Arc<MockPtyProcess>>) {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+
+        let mut agents = Vec::new();
+        let mut mocks = Vec::new();
+        for index in 0..2 {
+            let mock_pty = Arc::new(MockPtyProcess::new());
+            let agent = Agent::new_with_process(index, &config, Box::new(Arc::clone(&mock_pty)))
+                .await
+                .unwrap();
+            agent.set_status(AgentStatus::Active);
+            agents.push(agent);
+            mocks.push(mock_pty);
+        }
+        (Agents::from_agents(vec![], agents), mocks)
+    }
+
+    fn create_busy_entry(options: PeriodicOptions) -> Trigger {
+        Trigger {
+            name: "poll".to_string(),
+            trigger: TriggerType::Periodic {
+                interval: TokioDuration::from_secs(60),
+                options,
+            },
+            action: ActionType::SendKeys(vec!["poll".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_if_active_sends_nothing() {
+        let (agents, mocks) = create_busy_agents().await;
+        let options = PeriodicOptions {
+            skip_if_active: true,
+            ..PeriodicOptions::default()
+        };
+        let entry = create_busy_entry(options.clone());
+
+        let mut skipped = 0;
+        run_if_data(&entry, &agents, &options, &mut skipped).await;
+        run_if_data(&entry, &agents, &options, &mut skipped).await;
+
+        assert_eq!(skipped, 2);
+        assert!(mocks.iter().all(|mock| mock.get_sent_inputs().is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_queue_if_active_runs_when_idle() {
+        let (agents, mocks) = create_busy_agents().await;
+        let agents = Arc::new(agents);
+        let options = PeriodicOptions {
+            queue_if_active: true,
+            ..PeriodicOptions::default()
+        };
+        let entry = create_busy_entry(options.clone());
+
+        let run = {
+            let agents = Arc::clone(&agents);
+            tokio::spawn(async move {
+                let mut skipped = 0;
+                run_if_data(&entry, &agents, &options, &mut skipped).await;
+                skipped
+            })
+        };
+
+        tokio::time::sleep(TokioDuration::from_millis(50)).await;
+        assert!(mocks.iter().all(|mock| mock.get_sent_inputs().is_empty()));
+
+        agents.get_agent_by_index(1).set_status(AgentStatus::Idle);
+        let skipped = tokio::time::timeout(TokioDuration::from_secs(2), run)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(skipped, 0);
+        assert!(mocks[0].get_sent_inputs().is_empty());
+        assert_eq!(mocks[1].get_sent_inputs(), vec!["poll"]);
+    }
+
+    #[tokio::test]
+    async fn test_paused_entry_skips_runs() {
+        let (agents, mocks) = create_busy_agents().await;
+        agents.get_agent_by_index(0).set_status(AgentStatus::Idle);
+        let options = PeriodicOptions::default();
+        let entry = create_busy_entry(options.clone());
+
+        let mut skipped = 0;
+        agents.paused_triggers().pause("poll");
+        assert!(!run_if_data(&entry, &agents, &options, &mut skipped).await);
+        assert!(mocks[0].get_sent_inputs().is_empty());
+
+        agents.paused_triggers().resume("poll");
+        assert!(run_if_data(&entry, &agents, &options, &mut skipped).await);
+        assert_eq!(mocks[0].get_sent_inputs(), vec!["poll"]);
+    }
+
+    #[test]
+    fn test_apply_jitter_within_range() {
+        let base = Instant::now() + Duration::from_secs(60);
+        let jitter = Duration::from_secs(5);
+
+        for _ in 0..100 {
+            let at = apply_jitter(base, jitter);
+            assert!(at >= base - jitter && at <= base + jitter);
+        }
+        assert_eq!(apply_jitter(base, Duration::ZERO), base);
+    }
+
+    #[test]
+    fn test_format_cron_schedule() {
+        use chrono::TimeZone;
+
+        let entries = vec![Trigger {
+            name: "standup".to_string(),
+            trigger: TriggerType::Cron {
+                schedule: Box::new(CronSchedule::parse("0 9 * * 1-5", Some("UTC")).unwrap()),
+            },
+            action: ActionType::SendKeys(vec!["standup".to_string()]),
+            ..Default::default()
+        }];
+        let after = chrono::Utc.with_ymd_and_hms(2024, 3, 8, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            format_cron_schedule(&entries, after, 3),
+            "standup: 0 9 * * 1-5 (UTC)\n  Mon 2024-03-11 09:00:00 +00:00\n  Tue 2024-03-12 09:00:00 +00:00\n  Wed 2024-03-13 09:00:00 +00:00\n"
+        );
+        assert_eq!(
+            format_cron_schedule(&[], after, 3),
+            "No cron entries configured\n"
+        );
+    }
+
     #[tokio::test]
     async fn test_has_data_to_process_no_source() {
         let trigger = Trigger {
             name: "test".to_string(),
             trigger: TriggerType::Periodic {
                 interval: TokioDuration::from_secs(1),
+                options: PeriodicOptions::default(),
             },
             action: ActionType::SendKeys(vec!["test".to_string()]),
-            source: None,
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = has_data_to_process(&trigger).await.unwrap();
@@ -193,10 +529,11 @@ mod tests {
             name: "test".to_string(),
             trigger: TriggerType::Periodic {
                 interval: TokioDuration::from_secs(1),
+                options: PeriodicOptions::default(),
             },
             action: ActionType::SendKeys(vec!["test".to_string()]),
             source: Some("echo hello".to_string()),
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = has_data_to_process(&trigger).await.unwrap();
@@ -209,10 +546,11 @@ mod tests {
             name: "test".to_string(),
             trigger: TriggerType::Periodic {
                 interval: TokioDuration::from_secs(1),
+                options: PeriodicOptions::default(),
             },
             action: ActionType::SendKeys(vec!["test".to_string()]),
             source: Some("false".to_string()),
-            dedupe: false,
+            ..Default::default()
         };
 
         let result = has_data_to_process(&trigger).await.unwrap();
@@ -225,13 +563,70 @@ mod tests {
             name: "test".to_string(),
             trigger: TriggerType::Periodic {
                 interval: TokioDuration::from_secs(1),
+                options: PeriodicOptions::default(),
             },
             action: ActionType::SendKeys(vec!["test".to_string()]),
             source: Some("true".to_string()), // succeeds but produces no output
-            dedupe: false,
+            dedupe: None,
+            ..Default::default()
         };
 
         let result = has_data_to_process(&trigger).await.unwrap();
         assert!(!result); // Should return false when no output
     }
+
+    #[tokio::test]
+    async fn test_has_data_to_process_with_hung_source() {
+        let trigger = Trigger {
+            name: "hung".to_string(),
+            trigger: TriggerType::Periodic {
+                interval: TokioDuration::from_secs(1),
+                options: PeriodicOptions::default(),
+            },
+            action: ActionType::SendKeys(vec!["test".to_string()]),
+            source: Some("sleep 5".to_string()),
+            source_timeout: TokioDuration::from_millis(100),
+            ..Default::default()
+        };
+
+        // Fails instead of blocking, so the next tick runs again
+        assert!(has_data_to_process(&trigger).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_max_runs_retires_entry() {
+        let (agents, mocks) = create_busy_agents().await;
+        for index in 0..2 {
+            agents
+                .get_agent_by_index(index)
+                .set_status(AgentStatus::Idle);
+        }
+        let mut entry = create_busy_entry(PeriodicOptions::default());
+        entry.trigger = TriggerType::Periodic {
+            interval: TokioDuration::from_millis(50),
+            options: PeriodicOptions {
+                max_runs: Some(2),
+                ..PeriodicOptions::default()
+            },
+        };
+
+        let agents = Arc::new(agents);
+        let periodic = Periodic::new(vec![entry], Arc::clone(&agents));
+        let handle = periodic.start_all_tasks().remove(0);
+
+        // The task ends by itself after the second run
+        tokio::time::timeout(TokioDuration::from_secs(2), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        let sent: usize = mocks.iter().map(|mock| mock.get_sent_inputs().len()).sum();
+        assert_eq!(sent, 2);
+
+        let history = agents.trigger_runs();
+        let remaining: Vec<_> = history["poll"]
+            .iter()
+            .map(|run| run.remaining_runs)
+            .collect();
+        assert_eq!(remaining, vec![Some(1), Some(0)]);
+    }
 }
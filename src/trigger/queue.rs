@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::agent::Agents;
+use crate::config::triggers_config::{FailedItems, Trigger, TriggerType};
+use crate::queue::QueueManager;
+
+/// Pause after a batch with requeued items, so a persistent failure
+/// doesn't retry in a tight loop
+const REQUEUE_DELAY: Duration = Duration::from_secs(1);
+
+/// Queue task manager responsible for handling queue entries
+pub struct QueueConsumer {
+    pub entries: Vec<Trigger>,
+    pub agents: Arc<Agents>,
+    queues: Arc<QueueManager>,
+}
+
+impl QueueConsumer {
+    /// Consume the queues of `agents`
+    pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
+        let queues = Arc::clone(agents.queues());
+        Self {
+            entries,
+            agents,
+            queues,
+        }
+    }
+
+    /// Start one consumer task per entry and return their handles
+    pub fn start_all_tasks(&self) -> Vec<JoinHandle<()>> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.trigger, TriggerType::Queue { .. }))
+            .map(|entry| {
+                let entry = entry.clone();
+                let agents = Arc::clone(&self.agents);
+                let queues = Arc::clone(&self.queues);
+                tokio::spawn(async move { consume(&entry, &agents, &queues).await })
+            })
+            .collect()
+    }
+}
+
+/// Pop batches from the entry's queue whenever it has items and run the
+/// action once per item
+async fn consume(entry: &Trigger, agents: &Agents, queues: &QueueManager) {
+    let TriggerType::Queue {
+        name,
+        batch,
+        failed_items,
+    } = &entry.trigger
+    else {
+        return;
+    };
+    tracing::debug!("Starting queue entry '{}' on queue {}", entry.name, name);
+
+    let mut enqueued = queues.subscribe();
+    loop {
+        // Mark the current state as seen before popping, so an item enqueued
+        // right after an empty pop still wakes us up
+        enqueued.borrow_and_update();
+        let items = queues.pop_batch(name, *batch);
+        if items.is_empty() {
+            if enqueued.changed().await.is_err() {
+                return;
+            }
+            continue;
+        }
+        tracing::debug!(
+            "Queue entry '{}' popped {} items from {} ({} left)",
+            entry.name,
+            items.len(),
+            name,
+            queues.len(name)
+        );
+
+        let agent = match agents.get_next_idle_agent(entry.tag.as_deref()).await {
+            Some(agent) => agent,
+            None => agents.get_next_agent(entry.tag.as_deref()),
+        };
+        let mut requeued = false;
+        for item in items {
+            let Err(e) = entry.run_with_input_recorded(&agent, agents, &item).await else {
+                continue;
+            };
+            tracing::error!("Error executing queue entry '{}': {:#}", entry.name, e);
+            match failed_items {
+                FailedItems::Drop => {}
+                FailedItems::Requeue => {
+                    queues.enqueue(name, item);
+                    requeued = true;
+                }
+                FailedItems::FailedQueue => queues.enqueue(&format!("{}.failed", name), item),
+            }
+        }
+        if requeued {
+            tokio::time::sleep(REQUEUE_DELAY).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+
+    fn queue_entry(batch: usize, failed_items: FailedItems) -> Trigger {
+        Trigger {
+            name: "reviews".to_string(),
+            trigger: TriggerType::Queue {
+                name: "reviews".to_string(),
+                batch,
+                failed_items,
+            },
+            action: ActionType::SendKeys(vec!["review ${1}".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    async fn start(entry: Trigger, mock_pty: &Arc<MockPtyProcess>) -> Arc<QueueManager> {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(mock_pty)))
+            .await
+            .unwrap();
+        let agents = Arc::new(Agents::from_agents(vec![], vec![agent]));
+
+        let queues = Arc::clone(agents.queues());
+        QueueConsumer::new(vec![entry], agents).start_all_tasks();
+        queues
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_entry_drains_items() {
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let queues = start(queue_entry(2, FailedItems::Drop), &mock_pty).await;
+
+        for item in ["#1", "#2", "#3"] {
+            queues.enqueue("reviews", item.to_string());
+        }
+        wait_until(|| mock_pty.get_sent_inputs().len() == 3).await;
+        assert_eq!(
+            mock_pty.get_sent_inputs(),
+            vec!["review #1", "review #2", "review #3"]
+        );
+        assert_eq!(queues.len("reviews"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_entry_moves_failed_items() {
+        let mock_pty = Arc::new(MockPtyProcess::with_failure());
+        let queues = start(queue_entry(1, FailedItems::FailedQueue), &mock_pty).await;
+
+        queues.enqueue("reviews", "#7".to_string());
+        wait_until(|| queues.len("reviews.failed") == 1).await;
+        assert_eq!(queues.pop_batch("reviews.failed", 1), vec!["#7"]);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_action_feeds_the_pool_queues() {
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let agents = Arc::new(Agents::from_agents(vec![], vec![Arc::clone(&agent)]));
+        QueueConsumer::new(vec![queue_entry(1, FailedItems::Drop)], agents).start_all_tasks();
+
+        let action = ActionType::Enqueue {
+            queue: "reviews".to_string(),
+            item: "#9".to_string(),
+        };
+        crate::action::execute_non_key_action(&action, &agent, "test")
+            .await
+            .unwrap();
+        wait_until(|| mock_pty.get_sent_inputs().len() == 1).await;
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["review #9"]);
+    }
+}
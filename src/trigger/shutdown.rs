@@ -0,0 +1,84 @@
+use futures_util::future::join_all;
+use std::sync::Arc;
+
+use crate::agent::Agents;
+use crate::config::triggers_config::Trigger;
+
+/// Shutdown task manager responsible for handling on_shutdown entries
+pub struct Shutdown {
+    pub entries: Vec<Trigger>,
+    pub agents: Arc<Agents>,
+}
+
+impl Shutdown {
+    pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
+        Self { entries, agents }
+    }
+
+    /// Run each shutdown entry on every agent at once, entry after entry.
+    /// Failures are logged so that the remaining cleanup still runs.
+    pub async fn execute_all_entries(&self) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        tracing::info!("Executing on_shutdown entries...");
+
+        for entry in &self.entries {
+            let agents = self.agents.tagged(entry.tag.as_deref());
+            let runs = agents.into_iter().map(|agent| async move {
+                if let Err(e) = entry.run_recorded(&agent, &self.agents).await {
+                    tracing::error!(
+                        "Error executing shutdown entry '{}' on agent {}: {:#}",
+                        entry.name,
+                        agent.get_id(),
+                        e
+                    );
+                }
+            });
+            join_all(runs).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+    use crate::config::triggers_config::TriggerType;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+
+    #[tokio::test]
+    async fn test_shutdown_entries_run_on_every_agent() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+
+        let mut agents = Vec::new();
+        let mut mocks = Vec::new();
+        for index in 0..2 {
+            let mock_pty = Arc::new(MockPtyProcess::new());
+            agents.push(
+                Agent::new_with_process(index, &config, Box::new(Arc::clone(&mock_pty)))
+                    .await
+                    .unwrap(),
+            );
+            mocks.push(mock_pty);
+        }
+
+        let entry = Trigger {
+            name: "exit-claude".to_string(),
+            trigger: TriggerType::OnShutdown,
+            action: ActionType::SendKeys(vec!["/exit".to_string(), "\r".to_string()]),
+            ..Default::default()
+        };
+
+        let shutdown = Shutdown::new(vec![entry], Arc::new(Agents::from_agents(vec![], agents)));
+        shutdown.execute_all_entries().await;
+
+        for mock in mocks {
+            assert_eq!(mock.get_sent_inputs(), vec!["/exit", "\r"]);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, timeout};
+
+/// Output of a running source command, read line by line as it arrives
+pub struct SourceStream {
+    command: String,
+    child: Child,
+    stdout: Lines<BufReader<ChildStdout>>,
+    // Drained concurrently so a chatty stderr can't block the command
+    stderr: JoinHandle<String>,
+    timeout: Duration,
+}
+
+impl SourceStream {
+    /// Start `command` with `sh -c`. The command is killed when no output
+    /// arrives within `timeout`, and when the stream is dropped.
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self> {
+        tracing::debug!("Streaming shell command: {}", command);
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start source command: {}", command))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .context("Source command has no stdout")?;
+        let mut stderr = child
+            .stderr
+            .take()
+            .context("Source command has no stderr")?;
+        let stderr = tokio::spawn(async move {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output).await;
+            output
+        });
+
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            stdout: BufReader::new(stdout).lines(),
+            stderr,
+            timeout,
+        })
+    }
+
+    /// Next non-empty line, or None once the command closes its output
+    pub async fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            let line = match timeout(self.timeout, self.stdout.next_line()).await {
+                Ok(line) => line?,
+                Err(_) => {
+                    self.kill().await;
+                    anyhow::bail!(
+                        "Command '{}' produced no output for {:?}, killed",
+                        self.command,
+                        self.timeout
+                    );
+                }
+            };
+
+            match line {
+                Some(line) if line.trim().is_empty() => continue,
+                line => return Ok(line),
+            }
+        }
+    }
+
+    /// Stop the command early (e.g. when enough lines were processed)
+    pub async fn kill(&mut self) {
+        if let Err(e) = self.child.kill().await {
+            tracing::debug!("Failed to kill source command '{}': {}", self.command, e);
+        }
+    }
+
+    /// Wait for the command to exit, failing with its stderr on a non-zero status
+    pub async fn finish(mut self) -> Result<()> {
+        let status = match timeout(self.timeout, self.child.wait()).await {
+            Ok(status) => status?,
+            Err(_) => {
+                self.kill().await;
+                anyhow::bail!(
+                    "Command '{}' did not exit within {:?}, killed",
+                    self.command,
+                    self.timeout
+                );
+            }
+        };
+        if status.success() {
+            return Ok(());
+        }
+
+        let stderr = timeout(self.timeout, &mut self.stderr)
+            .await
+            .ok()
+            .and_then(|output| output.ok())
+            .unwrap_or_default();
+        anyhow::bail!(
+            "Source command failed: {} (stderr: {})",
+            self.command,
+            stderr.trim()
+        )
+    }
+}
+
+/// Items of a `format: json` source: the elements of an array, or the
+/// whole document otherwise
+pub fn parse_json_items(output: &str) -> Result<Vec<serde_json::Value>> {
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str(output).context("Failed to parse source output as JSON")? {
+        serde_json::Value::Array(items) => Ok(items),
+        item => Ok(vec![item]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(stream: &mut SourceStream) -> Vec<String> {
+        let mut lines = Vec::new();
+        while let Some(line) = stream.next_line().await.unwrap() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[tokio::test]
+    async fn test_source_stream_skips_empty_lines() {
+        let mut stream =
+            SourceStream::spawn("printf 'a\\n\\n  \\nb\\n'", Duration::from_secs(5)).unwrap();
+        assert_eq!(collect(&mut stream).await, vec!["a", "b"]);
+        assert!(stream.finish().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_source_stream_failure_reports_stderr() {
+        let mut stream = SourceStream::spawn(
+            "echo partial; echo broken >&2; exit 3",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(collect(&mut stream).await, vec!["partial"]);
+
+        let error = stream.finish().await.unwrap_err().to_string();
+        assert!(error.contains("broken"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_json_items() {
+        let items = parse_json_items(r#"[{"number": 1}, {"number": 2}]"#).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1]["number"], 2);
+
+        // A single object is one item
+        let items = parse_json_items("{\n  \"number\": 7\n}\n").unwrap();
+        assert_eq!(items, vec![serde_json::json!({"number": 7})]);
+
+        assert!(parse_json_items("").unwrap().is_empty());
+        assert!(parse_json_items("[{\"number\": ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_source_stream_times_out_without_output() {
+        let mut stream =
+            SourceStream::spawn("echo first; sleep 5", Duration::from_millis(200)).unwrap();
+        assert_eq!(stream.next_line().await.unwrap(), Some("first".to_string()));
+
+        let started = std::time::Instant::now();
+        assert!(stream.next_line().await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
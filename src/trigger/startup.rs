@@ -1,8 +1,10 @@
 use anyhow::Result;
 use std::sync::Arc;
 
+use std::collections::HashMap;
+
 use crate::agent::Agents;
-use crate::config::triggers_config::Trigger;
+use crate::config::triggers_config::{AfterFailure, Trigger, startup_order};
 
 /// Startup task manager responsible for handling on_start entries
 pub struct Startup {
@@ -14,7 +16,7 @@ impl Startup {
     pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
         Self { entries, agents }
     }
-    /// Execute all startup entries
+    /// Execute all startup entries, each after the entry named in its `after:`
     pub async fn execute_all_entries(&self) -> Result<()> {
         if self.entries.is_empty() {
             return Ok(());
@@ -22,14 +24,36 @@ impl Startup {
 
         tracing::info!("Executing on_start entries...");
 
-        for (i, entry) in self.entries.iter().enumerate() {
-            let agent = self.agents.get_agent_by_index(i % self.agents.size());
+        // Whether each entry succeeded; skipped entries count as failed
+        let mut succeeded: HashMap<&str, bool> = HashMap::new();
+        for i in startup_order(&self.entries)? {
+            let entry = &self.entries[i];
+            if let Some(after) = &entry.after
+                && !succeeded.get(after.as_str()).copied().unwrap_or(true)
+                && entry.after_failure == AfterFailure::Skip
+            {
+                tracing::warn!(
+                    "Skipping startup entry '{}' because '{}' failed",
+                    entry.name,
+                    after
+                );
+                succeeded.insert(&entry.name, false);
+                continue;
+            }
+
+            let candidates = self.agents.tagged(entry.tag.as_deref());
+            let agent = Arc::clone(&candidates[i % candidates.len()]);
             tracing::info!(
                 "Executing startup entry '{}' on agent {}",
                 entry.name,
                 agent.get_id()
             );
-            entry.execute(&agent).await?;
+            // A failing entry must not keep the others from running
+            let result = entry.run_recorded(&agent, &self.agents).await;
+            if let Err(e) = &result {
+                tracing::error!("Error executing startup entry '{}': {:#}", entry.name, e);
+            }
+            succeeded.insert(&entry.name, result.is_ok());
         }
 
         Ok(())
@@ -41,7 +65,7 @@ mod tests {
     use super::*;
     use crate::config::Config;
     use crate::config::helper::ActionType;
-    use crate::config::triggers_config::{Trigger, TriggerType};
+    use crate::config::triggers_config::{AfterFailure, Trigger, TriggerType};
 
     #[tokio::test]
     async fn test_startup_new() {
@@ -77,15 +101,13 @@ mod tests {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["echo".to_string(), "test1".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup2".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["echo".to_string(), "test2".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
         ];
 
@@ -94,6 +116,40 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_startup_failing_entry_does_not_stop_others() {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent =
+            crate::agent::Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+                .await
+                .unwrap();
+        let agents = Agents::from_agents(vec![], vec![agent]);
+
+        let entries = vec![
+            Trigger {
+                name: "broken".to_string(),
+                trigger: TriggerType::OnStart,
+                action: ActionType::SendKeys(vec!["${1}".to_string()]),
+                source: Some("exit 1".to_string()),
+                ..Default::default()
+            },
+            Trigger {
+                name: "greeting".to_string(),
+                trigger: TriggerType::OnStart,
+                action: ActionType::SendKeys(vec!["hello".to_string()]),
+                ..Default::default()
+            },
+        ];
+
+        let startup = Startup::new(entries, Arc::new(agents));
+        assert!(startup.execute_all_entries().await.is_ok());
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["hello"]);
+    }
+
     #[tokio::test]
     async fn test_startup_execute_with_single_agent() {
         let mut config = Config::default();
@@ -107,22 +163,19 @@ mod tests {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test1".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup2".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test2".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup3".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test3".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
         ];
 
@@ -143,22 +196,19 @@ mod tests {
                 name: "startup1".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test1".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup2".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test2".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
             Trigger {
                 name: "startup3".to_string(),
                 trigger: TriggerType::OnStart,
                 action: ActionType::SendKeys(vec!["test3".to_string()]),
-                source: None,
-                dedupe: false,
+                ..Default::default()
             },
         ];
 
@@ -166,4 +216,58 @@ mod tests {
         let result = startup.execute_all_entries().await;
         assert!(result.is_ok());
     }
+
+    fn entry(name: &str, source: Option<&str>, after: Option<&str>) -> Trigger {
+        Trigger {
+            name: name.to_string(),
+            trigger: TriggerType::OnStart,
+            action: ActionType::SendKeys(vec![name.to_string()]),
+            source: source.map(str::to_string),
+            after: after.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    async fn run_with_mock(entries: Vec<Trigger>) -> Vec<String> {
+        use crate::terminal::pty_process_trait::MockPtyProcess;
+
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent =
+            crate::agent::Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+                .await
+                .unwrap();
+        let agents = Agents::from_agents(vec![], vec![agent]);
+
+        let startup = Startup::new(entries, Arc::new(agents));
+        assert!(startup.execute_all_entries().await.is_ok());
+        mock_pty.get_sent_inputs()
+    }
+
+    #[tokio::test]
+    async fn test_startup_runs_entries_after_prerequisites() {
+        let sent = run_with_mock(vec![
+            entry("claude", None, Some("pull")),
+            entry("pull", None, None),
+        ])
+        .await;
+        assert_eq!(sent, vec!["pull", "claude"]);
+    }
+
+    #[tokio::test]
+    async fn test_startup_after_failure() {
+        let mut report = entry("report", None, Some("pull"));
+        report.after_failure = AfterFailure::Run;
+
+        let sent = run_with_mock(vec![
+            entry("pull", Some("exit 1"), None),
+            entry("claude", None, Some("pull")),
+            entry("review", None, Some("claude")),
+            report,
+        ])
+        .await;
+        // claude is skipped, and so is review, which waits for it
+        assert_eq!(sent, vec!["report"]);
+    }
 }
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::task::JoinHandle;
+
+use crate::agent::{Agent, Agents};
+use crate::config::triggers_config::{Distribution, Trigger, TriggerType};
+use crate::trigger::SourceCounts;
+
+/// Stdin task manager for the `stdin` entry (at most one per config)
+pub struct StdinReader {
+    pub entries: Vec<Trigger>,
+    pub agents: Arc<Agents>,
+}
+
+impl StdinReader {
+    pub fn new(entries: Vec<Trigger>, agents: Arc<Agents>) -> Self {
+        Self { entries, agents }
+    }
+
+    /// Start reading stdin for the entry and return the task handle
+    pub fn start_all_tasks(&self) -> Vec<JoinHandle<()>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.trigger == TriggerType::Stdin)
+            .map(|entry| {
+                let entry = entry.clone();
+                let agents = Arc::clone(&self.agents);
+                tokio::spawn(async move {
+                    read_lines(&entry, &agents, BufReader::new(tokio::io::stdin())).await
+                })
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Run the entry once per line of `input` until EOF (or `max_lines`)
+async fn read_lines(entry: &Trigger, agents: &Agents, input: impl AsyncBufRead + Unpin) {
+    tracing::debug!("Reading stdin for entry '{}'", entry.name);
+
+    let mut lines = input.lines();
+    let mut counts = SourceCounts::default();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => {
+                tracing::info!("Stdin closed, entry '{}' stopped", entry.name);
+                return;
+            }
+            Err(e) => {
+                tracing::error!("Failed to read stdin for entry '{}': {}", entry.name, e);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let agent = agent_for(entry, agents).await;
+        match entry
+            .run_line_recorded(&agent, agents, &line, &mut counts)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => tracing::error!("Error executing stdin entry '{}': {:#}", entry.name, e),
+        }
+    }
+}
+
+/// Agent a line starts from: the next idle agent, or the first pool agent
+/// when `distribution` picks the agent per line
+async fn agent_for(entry: &Trigger, agents: &Agents) -> Arc<Agent> {
+    if entry.distribution != Distribution::Single {
+        return agents.get_agent_by_index(0);
+    }
+    match agents.get_next_idle_agent(entry.tag.as_deref()).await {
+        Some(agent) => agent,
+        None => agents.get_next_agent(entry.tag.as_deref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+
+    fn stdin_entry(name: &str) -> Trigger {
+        Trigger {
+            name: name.to_string(),
+            trigger: TriggerType::Stdin,
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            dedupe: Some(Default::default()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_lines_until_eof() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let agents = Agents::from_agents(vec![], vec![agent]);
+
+        let entry = stdin_entry("stdin_dedupe_test");
+        read_lines(&entry, &agents, &b"task a\n\ntask b\ntask a\n"[..]).await;
+
+        // The blank line is skipped and the repeated line deduped
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["task a", "task b"]);
+        assert_eq!(agents.trigger_runs()["stdin_dedupe_test"].len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_read_lines_stops_at_max_lines() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Arc::new(MockPtyProcess::new());
+        let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock_pty)))
+            .await
+            .unwrap();
+        let agents = Agents::from_agents(vec![], vec![agent]);
+
+        let entry = Trigger {
+            dedupe: None,
+            max_lines: Some(1),
+            ..stdin_entry("stdin-max-lines")
+        };
+        read_lines(&entry, &agents, &b"first\nsecond\n"[..]).await;
+
+        assert_eq!(mock_pty.get_sent_inputs(), vec!["first"]);
+    }
+}
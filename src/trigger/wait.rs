@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Duration, Instant, timeout};
+
+use crate::agent::Agent;
+use crate::config::triggers_config::{WaitCondition, WaitFor};
+use crate::terminal::ansi::strip_ansi;
+
+/// Wait until `wait.condition` holds on `agent`, failing after `wait.timeout`
+pub async fn wait_for(agent: &Agent, wait: &WaitFor) -> Result<()> {
+    let condition = async {
+        match &wait.condition {
+            WaitCondition::Idle => {
+                wait_for_idle(agent).await;
+                Ok(())
+            }
+            WaitCondition::Pattern(pattern) => wait_for_pattern(agent, pattern).await,
+        }
+    };
+
+    match timeout(wait.timeout, condition).await {
+        Ok(result) => result,
+        Err(_) => anyhow::bail!(
+            "Agent {} did not reach {} within {:?}",
+            agent.get_id(),
+            wait.condition,
+            wait.timeout
+        ),
+    }
+}
+
+async fn wait_for_idle(agent: &Agent) {
+    agent.wait_for_status(|status| !status.is_busy()).await;
+}
+
+/// Send `keys` and wait until output produced afterwards matches `pattern`.
+/// A matching prompt already on screen doesn't count.
+pub async fn send_and_wait(
+    agent: &Agent,
+    keys: &str,
+    pattern: &Regex,
+    limit: Duration,
+) -> Result<()> {
+    let receiver = subscribe(agent).await?;
+    agent.send_keys(keys).await?;
+
+    match timeout(limit, wait_for_output(agent, receiver, pattern)).await {
+        Ok(result) => result,
+        Err(_) => anyhow::bail!(
+            "Agent {} did not show pattern '{}' within {:?} after {:?}",
+            agent.get_id(),
+            pattern,
+            limit,
+            keys
+        ),
+    }
+}
+
+/// Match `pattern` against the lines on screen, then against each line of new
+/// output, including a trailing line without newline such as a shell prompt
+async fn wait_for_pattern(agent: &Agent, pattern: &Regex) -> Result<()> {
+    // Subscribe first so output produced while reading the screen isn't missed
+    let receiver = subscribe(agent).await?;
+
+    if let Ok(screen) = agent.get_process().get_screen_text().await
+        && screen.lines.iter().any(|line| pattern.is_match(line))
+    {
+        return Ok(());
+    }
+
+    wait_for_output(agent, receiver, pattern).await
+}
+
+async fn subscribe(agent: &Agent) -> Result<broadcast::Receiver<String>> {
+    agent
+        .get_process()
+        .get_pty_string_receiver()
+        .await
+        .context("Failed to subscribe to agent output")
+}
+
+/// Match `pattern` against each line received, including the incomplete last one
+async fn wait_for_output(
+    agent: &Agent,
+    mut receiver: broadcast::Receiver<String>,
+    pattern: &Regex,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut pending = String::new();
+    loop {
+        let chunk = match receiver.recv().await {
+            Ok(chunk) => chunk,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::debug!("Skipped {} output chunks while waiting", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => anyhow::bail!(
+                "Agent {} output closed after {:?} while waiting for {}",
+                agent.get_id(),
+                started.elapsed(),
+                pattern
+            ),
+        };
+
+        pending.push_str(&strip_ansi(&chunk));
+        if pending
+            .split(['\n', '\r'])
+            .any(|line| pattern.is_match(line))
+        {
+            return Ok(());
+        }
+        // Only the incomplete last line can still match with more output
+        if let Some(end) = pending.rfind(['\n', '\r']) {
+            pending.drain(..=end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentStatus;
+    use crate::config::Config;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use regex::Regex;
+
+    async fn mock_agent() -> std::sync::Arc<Agent> {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock_pty = Box::new(MockPtyProcess::new());
+        Agent::new_with_process(0, &config, mock_pty).await.unwrap()
+    }
+
+    fn pattern(regex: &str) -> WaitFor {
+        WaitFor {
+            condition: WaitCondition::Pattern(Regex::new(regex).unwrap()),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_pattern() {
+        let agent = mock_agent().await;
+        // On screen already
+        assert!(wait_for(&agent, &pattern("screen contents$")).await.is_ok());
+        // In new output
+        assert!(wait_for(&agent, &pattern("^mock out")).await.is_ok());
+
+        let error = wait_for(&agent, &pattern(r"\$ $")).await.unwrap_err();
+        assert!(error.to_string().contains("output closed"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_pattern_matches_screen_lines() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let mock = std::sync::Arc::new(MockPtyProcess::new());
+        mock.set_screen(&["cargo build", "build ok", "$"]);
+        let agent = Agent::new_with_process(0, &config, Box::new(mock))
+            .await
+            .unwrap();
+
+        assert!(wait_for(&agent, &pattern("^build ok$")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_idle() {
+        let agent = mock_agent().await;
+        let wait = WaitFor {
+            condition: WaitCondition::Idle,
+            timeout: Duration::from_millis(300),
+        };
+        assert!(wait_for(&agent, &wait).await.is_ok());
+
+        agent.set_status(AgentStatus::Active);
+        let error = wait_for(&agent, &wait).await.unwrap_err();
+        assert!(
+            error.to_string().contains("did not reach idle"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+    routing::post,
+};
+use serde::Serialize;
+
+use crate::agent::Agents;
+use crate::config::triggers_config::{Trigger, TriggerType};
+
+/// Header carrying the shared secret of a webhook entry, or of POST /api/exec
+pub const SECRET_HEADER: &str = "x-ccauto-secret";
+
+/// Whether `headers` carry `secret` in [`SECRET_HEADER`]. The bytes are
+/// compared in constant time, so response times don't reveal how much of a
+/// guess was right; only the length of the secret can leak.
+pub fn has_secret(headers: &HeaderMap, secret: &str) -> bool {
+    let Some(provided) = headers.get(SECRET_HEADER) else {
+        return false;
+    };
+    let (provided, secret) = (provided.as_bytes(), secret.as_bytes());
+    provided.len() == secret.len()
+        && provided
+            .iter()
+            .zip(secret)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Serialize)]
+struct TriggerResponse {
+    accepted: bool,
+    message: String,
+}
+
+struct WebhookState {
+    /// Webhook entries by path
+    entries: BTreeMap<String, Trigger>,
+    agents: Arc<Agents>,
+}
+
+/// Webhook entries of `entries` as POST /api/triggers/{path}, served on the
+/// control API
+pub fn router(entries: &[Trigger], agents: Arc<Agents>) -> Router {
+    let entries = entries
+        .iter()
+        .filter_map(|entry| match &entry.trigger {
+            TriggerType::Webhook { path, .. } => Some((path.clone(), entry.clone())),
+            _ => None,
+        })
+        .collect();
+    Router::new()
+        .route("/api/triggers/{path}", post(fire_trigger))
+        .with_state(Arc::new(WebhookState { entries, agents }))
+}
+
+async fn fire_trigger(
+    State(state): State<Arc<WebhookState>>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<TriggerResponse>) {
+    let respond = |status: StatusCode, accepted: bool, message: String| {
+        (status, Json(TriggerResponse { accepted, message }))
+    };
+
+    let Some(entry) = state.entries.get(&path) else {
+        return respond(
+            StatusCode::NOT_FOUND,
+            false,
+            format!("Unknown trigger: {}", path),
+        );
+    };
+    let TriggerType::Webhook { secret, field, .. } = &entry.trigger else {
+        unreachable!("Only webhook entries are registered");
+    };
+
+    if let Some(secret) = secret
+        && !has_secret(&headers, secret)
+    {
+        tracing::warn!("Rejected webhook trigger '{}': bad secret", entry.name);
+        return respond(
+            StatusCode::UNAUTHORIZED,
+            false,
+            "Invalid secret".to_string(),
+        );
+    }
+
+    let input = match extract_input(&body, field.as_deref()) {
+        Ok(input) => input,
+        Err(message) => return respond(StatusCode::BAD_REQUEST, false, message),
+    };
+
+    // Prefer an idle agent, but never drop an accepted request
+    let agent = match state.agents.get_next_idle_agent(entry.tag.as_deref()).await {
+        Some(agent) => agent,
+        None => state.agents.get_next_agent(entry.tag.as_deref()),
+    };
+    tracing::info!(
+        "🪝 Webhook trigger '{}' accepted for agent {}",
+        entry.name,
+        agent.get_id()
+    );
+
+    let entry = entry.clone();
+    let agents = Arc::clone(&state.agents);
+    tokio::spawn(async move {
+        if let Err(e) = entry.run_with_input_recorded(&agent, &agents, &input).await {
+            tracing::error!("Error executing webhook entry '{}': {}", entry.name, e);
+        }
+    });
+
+    respond(
+        StatusCode::ACCEPTED,
+        true,
+        format!("Trigger '{}' accepted", path),
+    )
+}
+
+/// Select the value used as ${1}: the whole body, or a JSON pointer into it
+fn extract_input(body: &[u8], field: Option<&str>) -> Result<String, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "Body is not valid UTF-8".to_string())?;
+
+    let Some(pointer) = field else {
+        return Ok(body.to_string());
+    };
+
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("Body is not valid JSON: {}", e))?;
+    match json.pointer(pointer) {
+        Some(serde_json::Value::String(value)) => Ok(value.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(format!("Field not found in body: {}", pointer)),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn create_test_router(secret: Option<&str>) -> Router {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agents = Agents::new_with_mock(vec![], &config).await.unwrap();
+
+        let entries = vec![Trigger {
+            name: "issue".to_string(),
+            trigger: TriggerType::Webhook {
+                path: "issue-assigned".to_string(),
+                secret: secret.map(str::to_string),
+                field: Some("/issue/title".to_string()),
+            },
+            action: ActionType::SendKeys(vec!["fix ${1}".to_string()]),
+            ..Default::default()
+        }];
+
+        router(&entries, Arc::new(agents))
+    }
+
+    async fn post(app: &Router, path: &str, secret: Option<&str>, body: &str) -> StatusCode {
+        let mut request = Request::post(path).header("content-type", "application/json");
+        if let Some(secret) = secret {
+            request = request.header(SECRET_HEADER, secret);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_trigger_accepted() {
+        let app = create_test_router(None).await;
+        let status = post(
+            &app,
+            "/api/triggers/issue-assigned",
+            None,
+            r#"{"issue": {"title": "Crash on start"}}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_trigger_unknown() {
+        let app = create_test_router(None).await;
+        let status = post(&app, "/api/triggers/unknown", None, "{}").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_trigger_secret() {
+        let app = create_test_router(Some("s3cret")).await;
+        let body = r#"{"issue": {"title": "Crash"}}"#;
+        let path = "/api/triggers/issue-assigned";
+
+        assert_eq!(post(&app, path, None, body).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            post(&app, path, Some("wrong"), body).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            post(&app, path, Some("s3cret"), body).await,
+            StatusCode::ACCEPTED
+        );
+    }
+
+    #[test]
+    fn test_has_secret() {
+        let headers = |secret: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(SECRET_HEADER, secret.parse().unwrap());
+            headers
+        };
+        assert!(has_secret(&headers("s3cret"), "s3cret"));
+        assert!(!has_secret(&headers("s3creT"), "s3cret"));
+        assert!(!has_secret(&headers("s3cret2"), "s3cret"));
+        assert!(!has_secret(&headers(""), "s3cret"));
+        assert!(!has_secret(&HeaderMap::new(), "s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_trigger_missing_field() {
+        let app = create_test_router(None).await;
+        let status = post(
+            &app,
+            "/api/triggers/issue-assigned",
+            None,
+            r#"{"action": "x"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_extract_input() {
+        let body = br#"{"issue": {"title": "Crash", "number": 42}}"#;
+        assert_eq!(
+            extract_input(body, None).unwrap(),
+            String::from_utf8_lossy(body)
+        );
+        assert_eq!(extract_input(body, Some("/issue/title")).unwrap(), "Crash");
+        assert_eq!(extract_input(body, Some("/issue/number")).unwrap(), "42");
+        assert!(extract_input(body, Some("/missing")).is_err());
+        assert!(extract_input(b"not json", Some("/issue")).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::{Read, Write};
+use std::os::fd::AsFd;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use nix::sys::termios::{self, SetArg, Termios};
+use tokio::sync::mpsc;
+use tokio_tungstenite::Connector;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::config::web_ui_config::{TlsConfig, WebUIConfig, WebUIMode};
+use crate::terminal::utf8::take_utf8;
+use crate::web_server::control;
+use crate::web_server::tls::client_config;
+
+/// Why an attach session ended
+#[derive(Debug, PartialEq)]
+pub enum Detached {
+    /// The detach key was pressed
+    ByUser,
+    /// ccauto closed the connection, e.g. because it exited
+    Closed,
+}
+
+/// Puts the terminal of stdin into raw mode until dropped
+struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        let stdin = std::io::stdin();
+        let original =
+            termios::tcgetattr(stdin.as_fd()).context("ccauto attach needs a terminal on stdin")?;
+        let mut raw = original.clone();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(stdin.as_fd(), SetArg::TCSANOW, &raw)?;
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(std::io::stdin().as_fd(), SetArg::TCSANOW, &self.original);
+    }
+}
+
+/// Web UI WebSocket of the agent `agent_id` (e.g. `agent-1`), which
+/// listens on `base_port` plus the agent's index, or under `/agents/<id>/`
+/// of the single web server, both below `web_ui.base_path`
+pub fn websocket_url(web_ui: &WebUIConfig, agent_id: &str) -> Result<String> {
+    let (host, base_port) = (&web_ui.host, web_ui.base_port);
+    let scheme = if web_ui.tls.is_some() { "wss" } else { "ws" };
+    let prefix = web_ui.path_prefix();
+    if web_ui.mode == WebUIMode::Single {
+        return Ok(format!(
+            "{}://{}:{}{}/agents/{}/ws",
+            scheme, host, base_port, prefix, agent_id
+        ));
+    }
+    let index: u16 = agent_id
+        .strip_prefix("agent-")
+        .and_then(|index| index.parse().ok())
+        .with_context(|| format!("Invalid agent ID '{}', expected e.g. agent-1", agent_id))?;
+    let port = base_port
+        .checked_add(index)
+        .with_context(|| format!("No web UI port for {}", agent_id))?;
+    Ok(format!("{}://{}:{}{}/ws", scheme, host, port, prefix))
+}
+
+/// [`websocket_url`] on the port the running ccauto reports for the agent,
+/// which `web_ui.port_fallback` may have moved from `base_port` plus its
+/// index. Without an answer from the control API, that's the guess.
+pub async fn running_websocket_url(web_ui: &WebUIConfig, agent_id: &str) -> Result<String> {
+    if web_ui.mode == WebUIMode::PerAgent
+        && let Ok(agents) = control::request_agents(web_ui).await
+        && let Some(port) = agents.get(agent_id).and_then(|agent| agent.port)
+    {
+        let scheme = if web_ui.tls.is_some() { "wss" } else { "ws" };
+        return Ok(format!(
+            "{}://{}:{}{}/ws",
+            scheme,
+            web_ui.host,
+            port,
+            web_ui.path_prefix()
+        ));
+    }
+    websocket_url(web_ui, agent_id)
+}
+
+/// Connect the local terminal to an agent's web UI WebSocket at `url`: its
+/// screen is drawn on connect and kept up to date, and keystrokes are sent
+/// to its shell until `detach_key` is pressed or ccauto closes the socket.
+/// A `wss://` URL is trusted with the certificates of `tls`.
+pub async fn attach(url: &str, detach_key: u8, tls: Option<&TlsConfig>) -> Result<Detached> {
+    let connector = match tls {
+        Some(tls) => Some(Connector::Rustls(Arc::new(client_config(tls)?))),
+        None => None,
+    };
+    let (socket, _) = tokio_tungstenite::connect_async_tls_with_config(url, None, false, connector)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to connect to {} (is ccauto running with web_ui enabled?)",
+                url
+            )
+        })?;
+    let (mut sink, mut stream) = socket.split();
+
+    let _raw_mode = RawMode::enable()?;
+    let mut keys = read_stdin();
+    let mut stdout = std::io::stdout();
+    let mut pending = Vec::new();
+
+    loop {
+        tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(output) = parse_output(&text) {
+                        stdout.write_all(output.as_bytes())?;
+                        stdout.flush()?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => return Ok(Detached::Closed),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e).context("WebSocket connection failed"),
+            },
+            input = keys.recv() => {
+                let Some(input) = input else {
+                    return Ok(Detached::Closed);
+                };
+                pending.extend_from_slice(&input);
+                let detach = pending.iter().position(|byte| *byte == detach_key);
+                if let Some(at) = detach {
+                    pending.truncate(at);
+                }
+                let text = take_utf8(&mut pending);
+                if !text.is_empty() {
+                    sink.send(Message::Text(text.into())).await?;
+                }
+                if detach.is_some() {
+                    let _ = sink.close().await;
+                    return Ok(Detached::ByUser);
+                }
+            }
+        }
+    }
+}
+
+/// Read stdin on a thread of its own: a blocking read left behind in the
+/// runtime would keep ccauto from exiting after a detach
+fn read_stdin() -> mpsc::Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0; 1024];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || sender.blocking_send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Output of an asciinema event `[time, "o", data]`; the header and other
+/// events carry none
+fn parse_output(message: &str) -> Option<String> {
+    match serde_json::from_str::<(f64, String, String)>(message) {
+        Ok((_, kind, data)) if kind == "o" => Some(data),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_url() {
+        let mut web_ui = WebUIConfig {
+            base_port: 9990,
+            ..WebUIConfig::default()
+        };
+        assert_eq!(
+            websocket_url(&web_ui, "agent-2").unwrap(),
+            "ws://localhost:9992/ws"
+        );
+        assert!(websocket_url(&web_ui, "claude").is_err());
+        web_ui.mode = WebUIMode::Single;
+        assert_eq!(
+            websocket_url(&web_ui, "agent-2").unwrap(),
+            "ws://localhost:9990/agents/agent-2/ws"
+        );
+        web_ui.tls = Some(TlsConfig {
+            cert: "web.pem".into(),
+            key: "web.key".into(),
+        });
+        assert_eq!(
+            websocket_url(&web_ui, "agent-2").unwrap(),
+            "wss://localhost:9990/agents/agent-2/ws"
+        );
+        web_ui.base_path = "/ccauto/".to_string();
+        assert_eq!(
+            websocket_url(&web_ui, "agent-2").unwrap(),
+            "wss://localhost:9990/ccauto/agents/agent-2/ws"
+        );
+        web_ui.mode = WebUIMode::PerAgent;
+        assert_eq!(
+            websocket_url(&web_ui, "agent-2").unwrap(),
+            "wss://localhost:9992/ccauto/ws"
+        );
+        web_ui = WebUIConfig {
+            base_port: 65535,
+            ..WebUIConfig::default()
+        };
+        assert!(websocket_url(&web_ui, "agent-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            parse_output(r#"[0.5, "o", "\u001b[2J$ ls"]"#),
+            Some("\u{1b}[2J$ ls".to_string())
+        );
+        assert_eq!(parse_output(r#"{"version": 2, "width": 80}"#), None);
+        assert_eq!(parse_output(r#"[0.5, "i", "ls"]"#), None);
+    }
+}
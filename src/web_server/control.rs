@@ -0,0 +1,1091 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::agent::Agents;
+use crate::agent::agents::{AgentSummary, Readiness};
+use crate::agent::ephemeral::CommandResult;
+use crate::config::effective::EffectiveConfig;
+use crate::config::helper::parse_duration;
+use crate::config::triggers_config::{Trigger, TriggerType};
+use crate::config::web_ui_config::WebUIConfig;
+use crate::trigger::history::TriggerRun;
+use crate::trigger::webhook::{self, SECRET_HEADER};
+use crate::web_server::port;
+use crate::web_server::server::{healthz, readiness_response};
+use crate::web_server::tls;
+
+/// Result of POST /api/triggers/{name}/run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunResponse {
+    pub ok: bool,
+    /// Source lines processed (0 for entries without a source)
+    pub lines: usize,
+    pub message: String,
+}
+
+/// Result of POST /api/triggers/{name}/pause and /resume, and of
+/// POST /api/agents/{id}/rules/pause and /resume
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PauseResponse {
+    pub ok: bool,
+    /// Whether the entry (or the agent's rules) is paused after the request
+    pub paused: bool,
+    pub message: String,
+}
+
+/// Body of POST /api/agents/broadcast
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BroadcastRequest {
+    /// Key sequences, sent like the keys of a send_keys action
+    pub keys: Vec<String>,
+}
+
+/// Result of POST /api/agents/broadcast
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BroadcastResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+/// Result of POST /api/agents/{id}/restarts/reset
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+/// Body of POST /api/agents/{id}/resize
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResizeRequest {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Result of POST /api/agents/{id}/resize
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResizeResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+/// Body of POST /api/exec
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecRequest {
+    /// Shell command, run with `sh -c`
+    pub command: String,
+    /// How long the command may run, e.g. "30s"; defaults to 60s
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+}
+
+/// Result of POST /api/exec
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecResponse {
+    pub ok: bool,
+    pub message: String,
+    /// Output and exit code, unless the command couldn't be run
+    #[serde(flatten)]
+    pub result: Option<CommandResult>,
+}
+
+/// Timeout of POST /api/exec requests without one
+const DEFAULT_EXEC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct RunParams {
+    agent: Option<String>,
+}
+
+struct ControlState {
+    /// All entries by name
+    by_name: BTreeMap<String, Trigger>,
+    agents: Arc<Agents>,
+}
+
+/// Control API the CLI talks to: manual runs, pausing and history of
+/// entries, the agents' rules, restarts, terminal sizes and keys, commands
+/// run outside the pool and the configuration in use. Webhook entries are
+/// served along with it. Served next to the web UI, so its writes are
+/// refused while the web UI of any agent is read-only.
+pub fn router(entries: &[Trigger], agents: Arc<Agents>) -> Router {
+    api(entries, Arc::clone(&agents))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&agents),
+            refuse_read_only,
+        ))
+        .merge(webhook::router(entries, agents))
+}
+
+/// Routes of the control API, without webhook entries
+fn api(entries: &[Trigger], agents: Arc<Agents>) -> Router {
+    let by_name = entries
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.clone()))
+        .collect();
+    let state = Arc::new(ControlState { by_name, agents });
+    Router::new()
+        .route("/api/agents/broadcast", post(broadcast_keys))
+        .route("/api/agents/{id}/rules/pause", post(pause_rules))
+        .route("/api/agents/{id}/rules/resume", post(resume_rules))
+        .route("/api/agents/{id}/restarts/reset", post(reset_restarts))
+        .route("/api/agents/{id}/resize", post(resize_terminal))
+        .route("/api/exec", post(exec_command))
+        .route("/api/config", get(effective_config))
+        .route("/api/triggers", get(trigger_history))
+        .route("/api/triggers/{name}/run", post(run_trigger))
+        .route("/api/triggers/{name}/pause", post(pause_trigger))
+        .route("/api/triggers/{name}/resume", post(resume_trigger))
+        .with_state(state)
+}
+
+/// Refuse requests that change the pool from the port of a read-only web UI
+async fn refuse_read_only(
+    State(agents): State<Arc<Agents>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET && agents.any_read_only() {
+        tracing::warn!(
+            "⚠️ Refused {} {} on a read-only web UI",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, "The web UI is read-only".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Server of the control API on its own port while the web UI is disabled,
+/// with the health checks and agent listing of a web server
+pub struct ControlServer {
+    pub host: String,
+    pub port: u16,
+    app: Router,
+}
+
+impl ControlServer {
+    pub fn new(entries: &[Trigger], agents: Arc<Agents>, host: String, port: u16) -> Self {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/api/agents", get(agent_summaries))
+            .with_state(Arc::clone(&agents))
+            .merge(api(entries, Arc::clone(&agents)))
+            .merge(webhook::router(entries, agents));
+        Self { host, port, app }
+    }
+
+    /// Listen now, so a port in use fails the caller instead of a task
+    /// nobody watches, then serve in the background
+    pub async fn spawn(self) -> Result<JoinHandle<()>> {
+        let listener = port::bind(&self.host, self.port, false)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to serve the control API on web_ui.control_port {}",
+                    self.port
+                )
+            })?;
+        tracing::info!(
+            "🎛️ Control API listening on http://{}:{}/api/",
+            self.host,
+            self.port
+        );
+        Ok(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, self.app).await {
+                tracing::error!("❌ Control server failed: {}", e);
+            }
+        }))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn create_app(&self) -> Router {
+        self.app.clone()
+    }
+}
+
+async fn readyz(State(agents): State<Arc<Agents>>) -> (StatusCode, Json<Readiness>) {
+    readiness_response(agents.readiness())
+}
+
+/// Status and resource usage of every agent
+async fn agent_summaries(
+    State(agents): State<Arc<Agents>>,
+) -> Json<BTreeMap<String, AgentSummary>> {
+    Json(agents.summaries())
+}
+
+/// Rules, entries and settings this process runs with, secrets left out
+async fn effective_config(State(state): State<Arc<ControlState>>) -> Json<EffectiveConfig> {
+    let agents = &state.agents;
+    Json(EffectiveConfig::new(
+        agents.config(),
+        agents.rules(),
+        state.by_name.values(),
+        |name| agents.paused_triggers().is_paused(name),
+    ))
+}
+
+/// Send keys to every agent, reporting the agents that failed together
+async fn broadcast_keys(
+    State(state): State<Arc<ControlState>>,
+    Json(request): Json<BroadcastRequest>,
+) -> (StatusCode, Json<BroadcastResponse>) {
+    let respond = |status: StatusCode, message: String| {
+        let ok = status.is_success();
+        (status, Json(BroadcastResponse { ok, message }))
+    };
+
+    if request.keys.is_empty() {
+        return respond(StatusCode::BAD_REQUEST, "No keys to send".to_string());
+    }
+    tracing::info!("📣 Broadcasting {} keys to all agents", request.keys.len());
+    match state.agents.broadcast_keys(&request.keys).await {
+        Ok(count) => respond(
+            StatusCode::OK,
+            format!("Keys sent to every agent ({})", count),
+        ),
+        Err(e) => {
+            tracing::error!("Broadcast failed: {:#}", e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+        }
+    }
+}
+
+/// Resize the terminal of the agent `id`
+async fn resize_terminal(
+    State(state): State<Arc<ControlState>>,
+    Path(id): Path<String>,
+    Json(request): Json<ResizeRequest>,
+) -> (StatusCode, Json<ResizeResponse>) {
+    let respond = |status: StatusCode, message: String| {
+        let ok = status.is_success();
+        (status, Json(ResizeResponse { ok, message }))
+    };
+
+    let Some(agent) = state.agents.get_agent_by_id(&id) else {
+        return respond(StatusCode::NOT_FOUND, format!("Unknown agent: {}", id));
+    };
+    match agent.resize(request.cols, request.rows).await {
+        Ok(()) => respond(
+            StatusCode::OK,
+            format!(
+                "Terminal of {} resized to {}x{}",
+                id, request.cols, request.rows
+            ),
+        ),
+        Err(e) => respond(StatusCode::BAD_REQUEST, format!("{:#}", e)),
+    }
+}
+
+/// Run a command on a throwaway PTY and return its output once it exits.
+/// Refused unless `web_ui.exec_secret` is set and sent in [`SECRET_HEADER`].
+async fn exec_command(
+    State(state): State<Arc<ControlState>>,
+    headers: HeaderMap,
+    Json(request): Json<ExecRequest>,
+) -> (StatusCode, Json<ExecResponse>) {
+    let respond = |status: StatusCode, message: String, result: Option<CommandResult>| {
+        let ok = status.is_success();
+        (
+            status,
+            Json(ExecResponse {
+                ok,
+                message,
+                result,
+            }),
+        )
+    };
+
+    let Some(secret) = &state.agents.config().web_ui.exec_secret else {
+        return respond(
+            StatusCode::FORBIDDEN,
+            "Running commands is disabled, set web_ui.exec_secret to allow it".to_string(),
+            None,
+        );
+    };
+    if !webhook::has_secret(&headers, secret) {
+        tracing::warn!("Rejected running '{}': bad secret", request.command);
+        return respond(StatusCode::UNAUTHORIZED, "Invalid secret".to_string(), None);
+    }
+    if request.command.trim().is_empty() {
+        return respond(StatusCode::BAD_REQUEST, "No command".to_string(), None);
+    }
+    let timeout = match request.timeout.as_deref().map(parse_duration) {
+        None => DEFAULT_EXEC_TIMEOUT,
+        Some(Ok(timeout)) => timeout,
+        Some(Err(e)) => return respond(StatusCode::BAD_REQUEST, format!("{:#}", e), None),
+    };
+
+    tracing::info!("▶️ Running '{}' outside the pool", request.command);
+    match state.agents.run_ephemeral(&request.command, timeout).await {
+        Ok(result) => {
+            let message = if result.timed_out {
+                format!("Killed after {:?}", timeout)
+            } else {
+                match result.exit_code {
+                    Some(code) => format!("Exited with code {}", code),
+                    None => "Killed by a signal".to_string(),
+                }
+            };
+            respond(StatusCode::OK, message, Some(result))
+        }
+        Err(e) => {
+            tracing::error!("Running '{}' failed: {:#}", request.command, e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e), None)
+        }
+    }
+}
+
+/// Recent runs of every trigger entry, oldest first
+async fn trigger_history(
+    State(state): State<Arc<ControlState>>,
+) -> Json<BTreeMap<String, Vec<TriggerRun>>> {
+    Json(state.agents.trigger_runs())
+}
+
+/// Run an entry now, as its Startup/Periodic manager would, and wait for it
+async fn run_trigger(
+    State(state): State<Arc<ControlState>>,
+    Path(name): Path<String>,
+    Query(params): Query<RunParams>,
+) -> (StatusCode, Json<RunResponse>) {
+    let respond = |status: StatusCode, lines: usize, message: String| {
+        let ok = status.is_success();
+        (status, Json(RunResponse { ok, lines, message }))
+    };
+
+    let Some(entry) = state.by_name.get(&name) else {
+        return respond(
+            StatusCode::NOT_FOUND,
+            0,
+            format!("Unknown trigger: {}", name),
+        );
+    };
+    if matches!(
+        entry.trigger,
+        TriggerType::Webhook { .. }
+            | TriggerType::FileWatch { .. }
+            | TriggerType::Queue { .. }
+            | TriggerType::Stdin
+    ) {
+        return respond(
+            StatusCode::BAD_REQUEST,
+            0,
+            format!("Trigger '{}' needs input and can't be run manually", name),
+        );
+    }
+
+    let agent = match &params.agent {
+        Some(id) => match state.agents.get_agent_by_id(id) {
+            Some(agent) => agent,
+            None => {
+                return respond(StatusCode::NOT_FOUND, 0, format!("Unknown agent: {}", id));
+            }
+        },
+        None => match state.agents.get_next_idle_agent(entry.tag.as_deref()).await {
+            Some(agent) => agent,
+            None => state.agents.get_next_agent(entry.tag.as_deref()),
+        },
+    };
+    tracing::info!(
+        "▶️ Manual run of entry '{}' on agent {}",
+        entry.name,
+        agent.get_id()
+    );
+
+    match entry.run_recorded(&agent, &state.agents).await {
+        Ok(counts) => respond(
+            StatusCode::OK,
+            counts.processed,
+            format!("Trigger '{}' ran on {}", name, agent.get_id()),
+        ),
+        Err(e) => {
+            tracing::error!("Error in manual run of entry '{}': {:#}", entry.name, e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, 0, format!("{:#}", e))
+        }
+    }
+}
+
+async fn pause_trigger(
+    State(state): State<Arc<ControlState>>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<PauseResponse>) {
+    set_paused(&state, &name, true)
+}
+
+async fn resume_trigger(
+    State(state): State<Arc<ControlState>>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<PauseResponse>) {
+    set_paused(&state, &name, false)
+}
+
+/// Pause or resume a timer/cron entry; its task skips ticks while paused
+fn set_paused(state: &ControlState, name: &str, paused: bool) -> (StatusCode, Json<PauseResponse>) {
+    let paused_triggers = state.agents.paused_triggers();
+    let respond = |status: StatusCode, message: String| {
+        let ok = status.is_success();
+        let paused = paused_triggers.is_paused(name);
+        (
+            status,
+            Json(PauseResponse {
+                ok,
+                paused,
+                message,
+            }),
+        )
+    };
+
+    let Some(entry) = state.by_name.get(name) else {
+        return respond(StatusCode::NOT_FOUND, format!("Unknown trigger: {}", name));
+    };
+    if !matches!(
+        entry.trigger,
+        TriggerType::Periodic { .. } | TriggerType::Cron { .. }
+    ) {
+        return respond(
+            StatusCode::BAD_REQUEST,
+            format!("Only timer and cron entries can be paused, not '{}'", name),
+        );
+    }
+
+    let changed = if paused {
+        paused_triggers.pause(name)
+    } else {
+        paused_triggers.resume(name)
+    };
+    let message = match (paused, changed) {
+        (true, true) => format!("Trigger '{}' paused", name),
+        (true, false) => format!("Trigger '{}' was already paused", name),
+        (false, true) => format!("Trigger '{}' resumed", name),
+        (false, false) => format!("Trigger '{}' was not paused", name),
+    };
+    if changed {
+        tracing::info!("⏯️ {}", message);
+    }
+    respond(StatusCode::OK, message)
+}
+
+async fn pause_rules(
+    State(state): State<Arc<ControlState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<PauseResponse>) {
+    set_rules_paused(&state, &id, true)
+}
+
+async fn resume_rules(
+    State(state): State<Arc<ControlState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<PauseResponse>) {
+    set_rules_paused(&state, &id, false)
+}
+
+/// Pause or resume the rule actions of the agent `id`
+fn set_rules_paused(
+    state: &ControlState,
+    id: &str,
+    paused: bool,
+) -> (StatusCode, Json<PauseResponse>) {
+    let respond = |status: StatusCode, paused: bool, message: String| {
+        let ok = status.is_success();
+        let response = PauseResponse {
+            ok,
+            paused,
+            message,
+        };
+        (status, Json(response))
+    };
+
+    let Some(agent) = state.agents.get_agent_by_id(id) else {
+        return respond(
+            StatusCode::NOT_FOUND,
+            false,
+            format!("Unknown agent: {}", id),
+        );
+    };
+    let result = if paused {
+        state.agents.pause_rules(id).map(|()| true)
+    } else {
+        state.agents.resume_rules(id)
+    };
+    let message = match (paused, result) {
+        (true, Ok(_)) => format!("Rules of {} paused", id),
+        (false, Ok(true)) => format!("Rules of {} resumed", id),
+        (false, Ok(false)) => format!("Rules of {} were not paused", id),
+        (_, Err(e)) => {
+            let paused = agent.are_rules_paused();
+            return respond(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                paused,
+                format!("{:#}", e),
+            );
+        }
+    };
+    respond(StatusCode::OK, agent.are_rules_paused(), message)
+}
+
+/// Clear the restart count and last exit reason of the agent `id`
+async fn reset_restarts(
+    State(state): State<Arc<ControlState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ResetResponse>) {
+    let respond = |status: StatusCode, message: String| {
+        let ok = status.is_success();
+        (status, Json(ResetResponse { ok, message }))
+    };
+
+    match state.agents.reset_restart_counters(&id) {
+        Ok(()) => {
+            tracing::info!("🧹 Restart counters of agent {} reset", id);
+            respond(StatusCode::OK, format!("Restart counters of {} reset", id))
+        }
+        Err(e) => respond(StatusCode::NOT_FOUND, format!("{:#}", e)),
+    }
+}
+
+/// Base URL of the control API of the ccauto process running with
+/// `web_ui`: its web servers while the web UI is enabled, `control_port`
+/// otherwise
+pub fn base_url(web_ui: &WebUIConfig) -> String {
+    if web_ui.enabled {
+        format!(
+            "{}://{}:{}{}",
+            web_ui.scheme(),
+            web_ui.host,
+            web_ui.base_port,
+            web_ui.path_prefix()
+        )
+    } else {
+        format!("http://{}:{}", web_ui.host, web_ui.control_port)
+    }
+}
+
+/// Client of [`base_url`], trusting the web UI's own certificates like
+/// `ccauto attach`
+fn client(web_ui: &WebUIConfig) -> Result<reqwest::Client> {
+    match &web_ui.tls {
+        Some(tls) if web_ui.enabled => Ok(reqwest::Client::builder()
+            .use_preconfigured_tls(tls::client_config(tls)?)
+            .build()?),
+        _ => Ok(reqwest::Client::new()),
+    }
+}
+
+/// Ask a running ccauto process to pause (or resume) the entry `name`
+pub async fn request_pause(
+    web_ui: &WebUIConfig,
+    name: &str,
+    paused: bool,
+) -> Result<PauseResponse> {
+    let command = if paused { "pause" } else { "resume" };
+    let url = format!("{}/api/triggers/{}/{}", base_url(web_ui), name, command);
+    let response = client(web_ui)?.post(&url).send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process to send `keys` to every agent
+pub async fn request_broadcast(web_ui: &WebUIConfig, keys: &[String]) -> Result<BroadcastResponse> {
+    let url = format!("{}/api/agents/broadcast", base_url(web_ui));
+    let request = BroadcastRequest {
+        keys: keys.to_vec(),
+    };
+    let response = client(web_ui)?
+        .post(&url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+        })?;
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process to reset the restart counters of `agent_id`
+pub async fn request_reset_restarts(web_ui: &WebUIConfig, agent_id: &str) -> Result<ResetResponse> {
+    let url = format!(
+        "{}/api/agents/{}/restarts/reset",
+        base_url(web_ui),
+        agent_id
+    );
+    let response = client(web_ui)?.post(&url).send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process to resize the terminal of `agent_id`
+pub async fn request_resize(
+    web_ui: &WebUIConfig,
+    agent_id: &str,
+    cols: u16,
+    rows: u16,
+) -> Result<ResizeResponse> {
+    let url = format!("{}/api/agents/{}/resize", base_url(web_ui), agent_id);
+    let response = client(web_ui)?
+        .post(&url)
+        .json(&ResizeRequest { cols, rows })
+        .send()
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+        })?;
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process to run `command` outside its pool, with
+/// its `web_ui.exec_secret`
+pub async fn request_exec(
+    web_ui: &WebUIConfig,
+    secret: Option<&str>,
+    command: &str,
+    timeout: Option<&str>,
+) -> Result<ExecResponse> {
+    let url = format!("{}/api/exec", base_url(web_ui));
+    let request = ExecRequest {
+        command: command.to_string(),
+        timeout: timeout.map(str::to_string),
+    };
+    let mut request = client(web_ui)?.post(&url).json(&request);
+    if let Some(secret) = secret {
+        request = request.header(SECRET_HEADER, secret);
+    }
+    let response = request.send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process for the status of its agents
+pub async fn request_agents(web_ui: &WebUIConfig) -> Result<BTreeMap<String, AgentSummary>> {
+    let url = format!("{}/api/agents", base_url(web_ui));
+    let response = client(web_ui)?.get(&url).send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    Ok(response.json().await?)
+}
+
+/// Ask the ccauto process whose control API is at `url`, e.g.
+/// `http://localhost:9990`, for the configuration it runs with
+pub async fn request_config(url: &str) -> Result<serde_json::Value> {
+    let url = format!("{}/api/config", url.trim_end_matches('/'));
+    let response = reqwest::Client::new().get(&url).send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    if !response.status().is_success() {
+        anyhow::bail!("{} answered {}", url, response.status());
+    }
+    Ok(response.json().await?)
+}
+
+/// Ask a running ccauto process to run the entry `name` now
+pub async fn request_manual_run(
+    web_ui: &WebUIConfig,
+    name: &str,
+    agent: Option<&str>,
+) -> Result<RunResponse> {
+    let url = format!("{}/api/triggers/{}/run", base_url(web_ui), name);
+    let mut request = client(web_ui)?.post(&url);
+    if let Some(agent) = agent {
+        request = request.query(&[("agent", agent)]);
+    }
+
+    let response = request.send().await.map_err(|e| {
+        anyhow::anyhow!("Failed to reach ccauto at {} (is it running?): {}", url, e)
+    })?;
+    Ok(response.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::config::helper::ActionType;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// Secret of POST /api/exec on the test servers
+    const EXEC_SECRET: &str = "3x3c";
+
+    fn issue_entry(secret: Option<&str>) -> Trigger {
+        Trigger {
+            name: "issue".to_string(),
+            trigger: TriggerType::Webhook {
+                path: "issue-assigned".to_string(),
+                secret: secret.map(str::to_string),
+                field: Some("/issue/title".to_string()),
+            },
+            action: ActionType::SendKeys(vec!["fix ${1}".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    async fn create_test_server(secret: Option<&str>) -> (ControlServer, Arc<Agents>) {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.web_ui.exec_secret = Some(EXEC_SECRET.to_string());
+        let agents = Arc::new(Agents::new_with_mock(vec![], &config).await.unwrap());
+        let server = ControlServer::new(
+            &[issue_entry(secret)],
+            Arc::clone(&agents),
+            "localhost".to_string(),
+            config.web_ui.control_port,
+        );
+        (server, agents)
+    }
+
+    async fn run(server: &ControlServer, path: &str) -> (StatusCode, RunResponse) {
+        let request = Request::post(path).body(Body::empty()).unwrap();
+        let response = server.create_app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_manual_run() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        config.agents.pool = 2;
+        let agents = Agents::new_with_mock(vec![], &config).await.unwrap();
+
+        let entry = |name: &str, source: &str| Trigger {
+            name: name.to_string(),
+            trigger: TriggerType::Periodic {
+                interval: std::time::Duration::from_secs(300),
+                options: Default::default(),
+            },
+            action: ActionType::SendKeys(vec!["${1}".to_string()]),
+            source: Some(source.to_string()),
+            ..Default::default()
+        };
+        let server = ControlServer::new(
+            &[
+                entry("issues", "printf 'a\\nb\\n'"),
+                entry("broken", "exit 2"),
+            ],
+            Arc::new(agents),
+            "localhost".to_string(),
+            config.web_ui.control_port,
+        );
+
+        let (status, response) = run(&server, "/api/triggers/issues/run?agent=agent-1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ok);
+        assert_eq!(response.lines, 2);
+        assert!(response.message.contains("agent-1"));
+
+        let (status, response) = run(&server, "/api/triggers/broken/run").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!response.ok);
+
+        let (status, _) = run(&server, "/api/triggers/missing/run").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = run(&server, "/api/triggers/issues/run?agent=agent-9").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Both runs show up in the history
+        let request = Request::get("/api/triggers").body(Body::empty()).unwrap();
+        let response = server.create_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let history: BTreeMap<String, Vec<TriggerRun>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(history["issues"][0].lines_processed, 2);
+        assert_eq!(history["issues"][0].error, None);
+        let error = history["broken"][0].error.as_deref().unwrap();
+        assert!(error.contains("exit 2"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_agent_summaries() {
+        let (server, _) = create_test_server(None).await;
+        let request = Request::get("/api/agents").body(Body::empty()).unwrap();
+        let response = server.create_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["agent-0"]["status"], "Idle");
+        assert_eq!(json["agent-0"]["child_count"], 0);
+        assert!(json["agent-0"]["rss_bytes"].is_u64());
+        assert!(json["agent-0"]["cpu_percent"].is_f64());
+        assert_eq!(json["agent-0"]["restarts"]["restarts"], 0);
+        assert_eq!(json["agent-0"]["times"]["activations"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_effective_config() {
+        let (server, _) = create_test_server(Some("s3cret")).await;
+        let request = Request::get("/api/config").body(Body::empty()).unwrap();
+        let response = server.create_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("s3cret"));
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["triggers"][0]["name"], "issue");
+        assert_eq!(
+            json["triggers"][0]["event"],
+            "webhook:issue-assigned (with secret)"
+        );
+        assert_eq!(json["triggers"][0]["paused"], false);
+        assert_eq!(json["web_ui"]["control_port"], 9989);
+        assert_eq!(json["agents"]["pool"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_keys() {
+        let (server, _) = create_test_server(None).await;
+        let broadcast = |body: &'static str| {
+            let app = server.create_app();
+            async move {
+                let request = Request::post("/api/agents/broadcast")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: BroadcastResponse = serde_json::from_slice(&body).unwrap();
+                (status, response)
+            }
+        };
+
+        let (status, response) = broadcast(r#"{"keys": ["C-c"]}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ok);
+        assert_eq!(response.message, "Keys sent to every agent (1)");
+
+        let (status, response) = broadcast(r#"{"keys": []}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!response.ok);
+    }
+
+    #[tokio::test]
+    async fn test_reset_restarts() {
+        let (server, _) = create_test_server(None).await;
+        let reset = |path: &'static str| {
+            let app = server.create_app();
+            async move {
+                let request = Request::post(path).body(Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: ResetResponse = serde_json::from_slice(&body).unwrap();
+                (status, response)
+            }
+        };
+
+        let (status, response) = reset("/api/agents/agent-0/restarts/reset").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ok);
+
+        let (status, response) = reset("/api/agents/agent-9/restarts/reset").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(response.message, "Unknown agent: agent-9");
+    }
+
+    #[tokio::test]
+    async fn test_resize_terminal() {
+        let (server, agents) = create_test_server(None).await;
+        let resize = |path: &'static str, body: &'static str| {
+            let app = server.create_app();
+            async move {
+                let request = Request::post(path)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: ResizeResponse = serde_json::from_slice(&body).unwrap();
+                (status, response)
+            }
+        };
+
+        let (status, response) =
+            resize("/api/agents/agent-0/resize", r#"{"cols": 160, "rows": 48}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.message, "Terminal of agent-0 resized to 160x48");
+        let agent = agents.get_agent_by_id("agent-0").unwrap();
+        assert_eq!(agent.get_terminal_dimensions(), (160, 48));
+
+        let (status, _) = resize("/api/agents/agent-0/resize", r#"{"cols": 0, "rows": 48}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = resize("/api/agents/agent-9/resize", r#"{"cols": 80, "rows": 24}"#).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_exec_command() {
+        let (server, _) = create_test_server(None).await;
+        let exec_with = |app: Router, secret: &'static str, body: &'static str| async move {
+            let request = Request::post("/api/exec")
+                .header("content-type", "application/json")
+                .header(SECRET_HEADER, secret)
+                .body(Body::from(body))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let response: ExecResponse = serde_json::from_slice(&body).unwrap();
+            (status, response)
+        };
+        let exec = |body: &'static str| exec_with(server.create_app(), EXEC_SECRET, body);
+
+        let (status, response) = exec(r#"{"command": "echo ok"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.message, "Exited with code 0");
+        assert_eq!(response.result.unwrap().output, "ok\n");
+
+        let (status, response) = exec(r#"{"command": "sleep 10", "timeout": "1s"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.result.unwrap().timed_out);
+
+        let (status, response) = exec(r#"{"command": "true", "timeout": "soon"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.result.is_none());
+
+        let (status, response) =
+            exec_with(server.create_app(), "guess", r#"{"command": "id"}"#).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(response.result.is_none());
+
+        // Without web_ui.exec_secret no command runs at all
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agents = Agents::new_with_mock(vec![], &config).await.unwrap();
+        let app = router(&[], Arc::new(agents));
+        let (status, response) = exec_with(app, "", r#"{"command": "id"}"#).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(response.result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_manual_run_rejects_webhook_entries() {
+        let (server, _) = create_test_server(None).await;
+        let (status, response) = run(&server, "/api/triggers/issue/run").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!response.ok);
+
+        // They fire through their own route
+        let request = Request::post("/api/triggers/issue-assigned")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"issue": {"title": "Crash"}}"#))
+            .unwrap();
+        let response = server.create_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let mut config = Config::default();
+        config.web_ui.enabled = false;
+        let agents = Arc::new(Agents::new_with_mock(vec![], &config).await.unwrap());
+        let issue = issue_entry(None);
+        let poller = Trigger {
+            name: "poller".to_string(),
+            trigger: TriggerType::Periodic {
+                interval: std::time::Duration::from_secs(300),
+                options: Default::default(),
+            },
+            ..issue.clone()
+        };
+        let server = ControlServer::new(
+            &[poller, issue],
+            Arc::clone(&agents),
+            "localhost".to_string(),
+            config.web_ui.control_port,
+        );
+
+        let pause = |path: &'static str| {
+            let app = server.create_app();
+            async move {
+                let request = Request::post(path).body(Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: PauseResponse = serde_json::from_slice(&body).unwrap();
+                (status, response)
+            }
+        };
+
+        let (status, response) = pause("/api/triggers/poller/pause").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ok && response.paused);
+        assert!(agents.paused_triggers().is_paused("poller"));
+
+        let (_, response) = pause("/api/triggers/poller/pause").await;
+        assert!(response.message.contains("already paused"));
+
+        let (status, response) = pause("/api/triggers/poller/resume").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ok && !response.paused);
+        assert!(!agents.paused_triggers().is_paused("poller"));
+
+        // Only timer and cron entries tick
+        let (status, response) = pause("/api/triggers/issue/pause").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!response.ok);
+        let (status, _) = pause("/api/triggers/missing/pause").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_base_url_follows_the_web_ui() {
+        let mut web_ui = WebUIConfig::default();
+        assert_eq!(base_url(&web_ui), "http://localhost:9990");
+        web_ui.base_path = "/ccauto/".to_string();
+        assert_eq!(base_url(&web_ui), "http://localhost:9990/ccauto");
+        web_ui.enabled = false;
+        assert_eq!(base_url(&web_ui), "http://localhost:9989");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_fails_on_a_taken_port() {
+        let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut server, _) = create_test_server(None).await;
+        server.port = taken.local_addr().unwrap().port();
+
+        let error = server.spawn().await.unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("web_ui.control_port"),
+            "{:#}",
+            error
+        );
+    }
+}
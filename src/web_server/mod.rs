@@ -1,4 +1,9 @@
+pub mod attach;
+pub mod control;
+pub mod port;
 pub mod server;
+pub mod shutdown;
+pub mod tls;
 pub mod websocket;
 
 #[cfg(test)]
@@ -0,0 +1,167 @@
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use tokio::net::TcpListener;
+
+use crate::terminal::process_tree;
+
+/// Ports tried above a taken one with `web_ui.port_fallback`
+const MAX_FALLBACK_PORTS: u16 = 100;
+
+/// Listen on `port` of `host`. A port in use fails with an error naming
+/// the process holding it, unless `fallback` probes the ports above it
+/// for a free one.
+pub async fn bind(host: &str, port: u16, fallback: bool) -> Result<TcpListener> {
+    let mut candidate = port;
+    loop {
+        match TcpListener::bind(socket_addr(host, candidate)?).await {
+            Ok(listener) => {
+                if candidate != port {
+                    tracing::warn!(
+                        "⚠️ Web UI port {} is in use ({}), using {} instead",
+                        port,
+                        describe_owner(port),
+                        candidate
+                    );
+                }
+                return Ok(listener);
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse && !fallback => {
+                anyhow::bail!(
+                    "Web UI port {} on {} is already in use by {}. Stop it, change \
+                     web_ui.base_port, or set web_ui.port_fallback: true",
+                    port,
+                    host,
+                    describe_owner(port)
+                );
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                candidate = candidate
+                    .checked_add(1)
+                    .filter(|next| next - port <= MAX_FALLBACK_PORTS)
+                    .with_context(|| {
+                        format!(
+                            "No free web UI port among the {} above {}",
+                            MAX_FALLBACK_PORTS, port
+                        )
+                    })?;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to listen on web UI port {} of {}", candidate, host)
+                });
+            }
+        }
+    }
+}
+
+fn socket_addr(host: &str, port: u16) -> Result<SocketAddr> {
+    // Convert localhost to 127.0.0.1 for proper parsing
+    let host = if host == "localhost" {
+        "127.0.0.1"
+    } else {
+        host
+    };
+    Ok(format!("{}:{}", host, port).parse()?)
+}
+
+/// The process listening on `port`, e.g. `pid 42 (python3 -m http.server)`
+fn describe_owner(port: u16) -> String {
+    match port_owner(port) {
+        Some(pid) => match process_tree::cmdline(pid) {
+            Some(command) => format!("pid {} ({})", pid, command),
+            None => format!("pid {}", pid),
+        },
+        None => "another process".to_string(),
+    }
+}
+
+/// PID of the process listening on TCP `port`, found through the socket
+/// inodes of /proc/net/tcp{,6} and the descriptors in /proc/<pid>/fd.
+/// Processes of other users can't be looked into and stay unknown.
+pub fn port_owner(port: u16) -> Option<u32> {
+    let inodes: Vec<u64> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| listening_inodes(&table, port))
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+    let sockets: Vec<String> = inodes
+        .iter()
+        .map(|inode| format!("socket:[{}]", inode))
+        .collect();
+
+    std::fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.file_name().to_str()?.parse::<u32>().ok()?, entry)))
+        .find(|(_, entry)| {
+            std::fs::read_dir(entry.path().join("fd")).is_ok_and(|fds| {
+                fds.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+                    .any(|link| {
+                        sockets
+                            .iter()
+                            .any(|socket| link.as_os_str() == socket.as_str())
+                    })
+            })
+        })
+        .map(|(pid, _)| pid)
+}
+
+/// Inodes of the sockets listening on `port` in a /proc/net/tcp table
+fn listening_inodes(table: &str, port: u16) -> Vec<u64> {
+    // State 0A is TCP_LISTEN
+    const LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            let listening =
+                u16::from_str_radix(local_port, 16).ok()? == port && *fields.get(3)? == LISTEN;
+            listening.then(|| fields.get(9)?.parse().ok())?
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listening_inodes() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+            0: 0100007F:2706 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0 100 0 0 10 0\n\
+            1: 0100007F:2706 0100007F:9A30 01 00000000:00000000 00:00000000 00000000  1000        0 4243 2 0 20 4 18 18 -1\n\
+            2: 00000000:07E8 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 662 1 0 100 0 0 10 0";
+        assert_eq!(listening_inodes(table, 9990), [4242]);
+        assert_eq!(listening_inodes(table, 2024), [662]);
+        assert!(listening_inodes(table, 9991).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bind_names_owner_or_falls_back() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let error = bind("localhost", port, false)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(&format!("port {} ", port)), "{}", error);
+        if std::path::Path::new("/proc/net/tcp").exists() {
+            assert!(
+                error.contains(&format!("pid {}", std::process::id())),
+                "{}",
+                error
+            );
+        }
+
+        let listener = bind("localhost", port, true).await.unwrap();
+        assert!(listener.local_addr().unwrap().port() > port);
+    }
+}
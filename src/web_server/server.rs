@@ -1,22 +1,41 @@
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{
     Router,
-    extract::{State, WebSocketUpgrade},
-    http::StatusCode,
-    response::{Html, Json, Response},
-    routing::{get, post},
+    extract::{
+        ConnectInfo, DefaultBodyLimit, Extension, Path, Query, Request, State, WebSocketUpgrade,
+    },
+    http::{HeaderValue, Method, StatusCode, Uri, header},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Redirect, Response},
+    routing::{any, get, post},
 };
+use axum_server::tls_rustls::RustlsConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Weak;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
+use tokio::task::JoinHandle;
+use tower::ServiceExt;
+use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 
-use super::websocket::handle_websocket;
-use crate::agent::Agent;
+use super::shutdown::Shutdown;
+use super::websocket::{StreamMode, handle_websocket};
+use super::{port, tls};
+use crate::agent::agents::{AgentSummary, Agents, Readiness};
+use crate::agent::recording::{self, RecordingFile};
+use crate::agent::{Agent, AgentStatus};
+use crate::config::helper::ActionType;
+use crate::config::web_ui_config::{TlsConfig, WebFrontend};
+use crate::metrics;
+use crate::rule::RuleHit;
+use crate::terminal::pty_session::parse_key;
+use crate::terminal::screen::ScreenSnapshot;
+use crate::trigger::execute_action_with_agent;
 use crate::web_ui::assets::AssetCache;
 
 #[derive(Deserialize)]
@@ -24,6 +43,15 @@ struct CommandRequest {
     command: String,
 }
 
+/// Largest body of POST /api/keys
+const MAX_KEYS_BODY: usize = 64 * 1024;
+
+/// Body of POST /api/keys: key sequences in send_keys notation
+#[derive(Deserialize)]
+struct KeysRequest {
+    keys: Vec<String>,
+}
+
 #[derive(Serialize)]
 struct CommandResponse {
     success: bool,
@@ -40,74 +68,550 @@ struct TerminalSizeResponse {
 struct AgentStatusResponse {
     state: String,
     message: String,
+    rules_paused: bool,
+    current_command: Option<String>,
+    /// Window title set in the shell, empty if none was
+    title: String,
+    /// Whether a full-screen program shows the alternate screen
+    alternate_screen: bool,
+    /// Writes queued for the shell that it hasn't read yet
+    pending_input: usize,
+    /// Whether this viewer may only watch, by `web_ui.read_only` or
+    /// `?readonly=1`
+    read_only: bool,
+    /// Web terminals connected to the agent
+    viewers: i64,
+}
+
+#[derive(Serialize)]
+struct RuleStatsResponse {
+    agent: String,
+    rules: Vec<RuleHit>,
+}
+
+#[derive(Deserialize)]
+struct WebSocketParams {
+    /// `text` (default), `binary` or `redraw`
+    #[serde(default)]
+    mode: StreamMode,
+    /// `?seq=true` numbers the output events
+    #[serde(default)]
+    seq: bool,
+}
+
+/// `?readonly=1` of a request to an agent's web UI
+#[derive(Deserialize)]
+struct ReadOnlyParams {
+    readonly: Option<String>,
+}
+
+/// Whether a request to the web UI of `agent` may only watch: the agent is
+/// configured read-only, or the request asks for it
+fn is_read_only(agent: &Agent, uri: &Uri) -> bool {
+    agent.get_config().web_read_only(&agent.get_id())
+        || Query::<ReadOnlyParams>::try_from_uri(uri).is_ok_and(|Query(params)| {
+            params
+                .readonly
+                .is_some_and(|value| !matches!(value.as_str(), "0" | "false"))
+        })
+}
+
+#[derive(Deserialize)]
+struct SnapshotParams {
+    /// `json` (default) or `txt`
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SnapshotResponse {
+    agent: String,
+    state: String,
+    /// RFC 3339 time the screen was read
+    timestamp: String,
+    #[serde(flatten)]
+    screen: ScreenSnapshot,
+}
+
+#[derive(Serialize)]
+struct RecordingsResponse {
+    agent: String,
+    recordings: Vec<RecordingFile>,
+}
+
+/// What a web server serves
+#[derive(Clone)]
+pub enum WebTarget {
+    /// One agent at the root of its own port, listing the pool it belongs
+    /// to on `/dashboard`
+    Agent(Arc<Agent>, Weak<Agents>),
+    /// Every pool agent under `/agents/<id>/`, with a landing page listing
+    /// them (`web_ui.mode: single`)
+    Pool(Arc<Agents>),
+}
+
+/// An agent outside of any pool
+impl From<Arc<Agent>> for WebTarget {
+    fn from(agent: Arc<Agent>) -> Self {
+        Self::Agent(agent, Weak::new())
+    }
+}
+
+impl From<Arc<Agents>> for WebTarget {
+    fn from(agents: Arc<Agents>) -> Self {
+        Self::Pool(agents)
+    }
 }
 
 #[derive(Clone)]
 pub struct WebServer {
     pub port: u16,
     pub host: String,
-    pub agent: Arc<Agent>,
+    pub target: WebTarget,
     pub asset_cache: AssetCache,
+    /// Serve HTTPS and WSS instead of HTTP and WS
+    pub tls: Option<TlsConfig>,
+    /// Prefix of every route, e.g. `/agents`; empty to serve at `/`
+    pub base_path: String,
+    /// Origins allowed by CORS, as `web_ui.cors_origins`
+    pub cors_origins: Vec<String>,
+    /// Time to finish in once shut down, as `web_ui.shutdown_timeout`
+    pub shutdown_timeout: Duration,
+    /// Control API and webhook entries served next to the pages
+    pub control: Option<Router>,
+    shutdown: Shutdown,
+}
+
+/// A web server serving in the background, from [`WebServer::spawn`]
+pub struct ServerHandle {
+    shutdown: Shutdown,
+    task: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    /// Stop accepting connections, close every WebSocket with "server
+    /// shutting down" and let the requests in flight finish. Returns once
+    /// the server is done, or has been dropped after its shutdown timeout.
+    pub async fn shutdown(self) {
+        self.shutdown.trigger();
+        let _ = self.task.await;
+    }
 }
 
+/// Prefix taken off a request's path by [`strip_base_path`], for responses
+/// that link back to the server
+#[derive(Clone)]
+struct BasePath(String);
+
 impl WebServer {
-    pub fn new(port: u16, host: String, agent: Arc<Agent>) -> Self {
+    pub fn new(port: u16, host: String, target: impl Into<WebTarget>) -> Self {
         Self {
             port,
             host,
-            agent,
+            target: target.into(),
             asset_cache: AssetCache::new(),
+            tls: None,
+            base_path: String::new(),
+            cors_origins: vec!["*".to_string()],
+            shutdown_timeout: Duration::from_secs(5),
+            control: None,
+            shutdown: Shutdown::new(),
         }
     }
 
-    pub async fn start(&self) -> Result<()> {
-        let app = self.create_app();
-        // Convert localhost to 127.0.0.1 for proper parsing
-        let host = if self.host == "localhost" {
-            "127.0.0.1"
-        } else {
-            &self.host
-        };
-        let addr: SocketAddr = format!("{}:{}", host, self.port).parse()?;
+    /// Finish within `timeout` once shut down, e.g. `web_ui.shutdown_timeout`
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
 
-        info!(
-            "🌐 Starting web server on http://{}:{}",
-            self.host, self.port
-        );
-        println!("🌐 Web server binding to address: {}", addr);
+    /// Serve every route under `base_path`, e.g. `web_ui.path_prefix()`
+    pub fn with_base_path(mut self, base_path: String) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    /// Allow cross-origin requests from `origins`, e.g. `web_ui.cors_origins`
+    pub fn with_cors_origins(mut self, origins: Vec<String>) -> Self {
+        self.cors_origins = origins;
+        self
+    }
+
+    /// Serve `control`, the control API and webhook entries, too
+    pub fn with_control(mut self, control: Router) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Serve with TLS, e.g. `web_ui.tls`
+    pub fn with_tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
 
-        let listener = TcpListener::bind(addr).await?;
-        println!("✅ Web server successfully bound to {}", addr);
+    fn scheme(&self) -> &'static str {
+        if self.tls.is_some() { "https" } else { "http" }
+    }
 
+    /// Listen now, so a port in use fails the caller instead of a task
+    /// nobody watches, then serve in the background. Returns the port
+    /// listened on, which `fallback` may have moved above `port`.
+    pub async fn spawn(mut self, fallback: bool) -> Result<(u16, ServerHandle)> {
+        let listener = port::bind(&self.host, self.port, fallback).await?;
+        self.port = listener.local_addr()?.port();
+        let port = self.port;
         info!(
-            "🚀 Web server ready and listening on http://{}:{}",
-            self.host, self.port
+            "🚀 Web server ready and listening on {}://{}:{}",
+            self.scheme(),
+            self.host,
+            port
         );
+        let shutdown = self.shutdown.clone();
+        let task = tokio::spawn(async move {
+            let serving = async {
+                match self.serve(listener, self.create_app()).await {
+                    // Only a shutdown ends serving: wait for the WebSockets
+                    // to send their close frames
+                    Ok(()) => self.shutdown.connections_closed().await,
+                    Err(e) => tracing::error!("❌ Web server failed on port {}: {}", port, e),
+                }
+            };
+            let overdue = async {
+                self.shutdown.triggered().await;
+                tokio::time::sleep(self.shutdown_timeout).await;
+            };
+            tokio::select! {
+                _ = serving => info!("🛑 Web server on port {} shut down", port),
+                _ = overdue => tracing::warn!(
+                    "⚠️ Web server on port {} did not shut down within {:?}, dropping it",
+                    port,
+                    self.shutdown_timeout
+                ),
+            }
+        });
+        Ok((port, ServerHandle { shutdown, task }))
+    }
 
-        axum::serve(listener, app).await?;
-
+    /// Serve `app` on `listener`, over TLS if configured, until shut down
+    pub(crate) async fn serve(&self, listener: TcpListener, app: Router) -> Result<()> {
+        match &self.tls {
+            Some(tls) => {
+                let config = RustlsConfig::from_config(Arc::new(tls::server_config(tls)?));
+                let handle = axum_server::Handle::new();
+                let shutdown = self.shutdown.clone();
+                let graceful = handle.clone();
+                tokio::spawn(async move {
+                    shutdown.triggered().await;
+                    graceful.graceful_shutdown(None);
+                });
+                axum_server::from_tcp_rustls(listener.into_std()?, config)
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await?;
+            }
+            None => {
+                let shutdown = self.shutdown.clone();
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(async move { shutdown.triggered().await })
+                .await?
+            }
+        }
         Ok(())
     }
 
-    fn create_app(&self) -> Router {
+    pub(crate) fn create_app(&self) -> Router {
+        let app = match &self.target {
+            WebTarget::Agent(agent, pool) => {
+                agent_app(Arc::clone(agent), pool.clone(), self.asset_cache.clone())
+            }
+            WebTarget::Pool(agents) => pool_app(Arc::clone(agents), self.asset_cache.clone()),
+        };
+        // For the WebSockets to close on shutdown
+        let app = app.layer(Extension(self.shutdown.clone()));
+        let app = match cors_layer(&self.cors_origins) {
+            Some(cors) => app.layer(cors),
+            None => app,
+        };
+        // After the CORS layer, so other sites' pages can't drive ccauto
+        let app = match &self.control {
+            Some(control) => app.merge(control.clone()),
+            None => app,
+        };
+        if self.base_path.is_empty() {
+            return app;
+        }
+        // A fallback rather than a `{*rest}` route, whose path parameter
+        // would reach the extractors of `app`
         Router::new()
-            .route("/", get(serve_index))
-            .route("/ws", get(websocket_handler))
-            .route("/api/command", post(send_command))
-            .route("/api/terminal-size", get(get_terminal_size))
-            .route("/api/agent-status", get(get_agent_status))
-            .with_state((self.agent.clone(), self.asset_cache.clone()))
-            .layer(ServiceBuilder::new().layer(CorsLayer::permissive()))
+            .route(&self.base_path, get(redirect_to_base_path))
+            .fallback(strip_base_path)
+            .with_state((self.base_path.clone(), app))
+    }
+}
+
+/// CORS letting the pages of `origins` call the server: any page for `*`,
+/// none for an empty list
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.iter().any(|origin| origin == "*") {
+        return Some(CorsLayer::permissive());
+    }
+    if origins.is_empty() {
+        return None;
+    }
+    let origins: Vec<HeaderValue> = origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect();
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(Any)
+            .allow_headers(Any),
+    )
+}
+
+/// The pages' relative URLs need the trailing slash
+async fn redirect_to_base_path(State((base_path, _)): State<(String, Router)>) -> Redirect {
+    Redirect::permanent(&format!("{}/", base_path))
+}
+
+/// Hand a request under `base_path` to `app`, with the prefix taken off
+/// its path
+async fn strip_base_path(
+    State((base_path, app)): State<(String, Router)>,
+    mut request: Request,
+) -> Response {
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+    let Some(uri) = path
+        .strip_prefix(base_path.as_str())
+        .filter(|rest| rest.starts_with('/'))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match uri.parse::<Uri>() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+    request.extensions_mut().insert(BasePath(base_path));
+    match app.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+/// Pages and API of one agent of `pool`. Pages use relative URLs, so they
+/// work at the root of the agent's port and under `/agents/<id>/` alike.
+fn agent_app(agent: Arc<Agent>, pool: Weak<Agents>, asset_cache: AssetCache) -> Router {
+    Router::new()
+        .route("/", get(serve_index))
+        .route("/history", get(serve_history))
+        .route("/recordings", get(list_recordings))
+        .route("/recordings/{file}", get(serve_recording))
+        .route("/ws", get(websocket_handler))
+        .route("/api/command", post(send_command))
+        .route(
+            "/api/keys",
+            post(send_keys).layer(DefaultBodyLimit::max(MAX_KEYS_BODY)),
+        )
+        .route("/api/terminal-size", get(get_terminal_size))
+        .route("/api/agent-status", get(get_agent_status))
+        .route("/api/snapshot", get(get_snapshot))
+        .route("/api/rules/pause", post(pause_rules))
+        .route("/api/rules/resume", post(resume_rules))
+        .route("/api/rule-stats", get(get_rule_stats))
+        .route("/dashboard", get(serve_dashboard))
+        .route("/api/agents", get(list_pool))
+        .route("/metrics", get(serve_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(agent_readyz))
+        .route_layer(middleware::from_fn_with_state(
+            (Arc::clone(&agent), asset_cache.clone()),
+            refuse_read_only,
+        ))
+        .layer(Extension(pool))
+        .with_state((agent, asset_cache))
+}
+
+/// Refuse requests that change the agent, like typed commands or paused
+/// rules, from viewers that may only watch
+async fn refuse_read_only(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET && is_read_only(&agent, request.uri()) {
+        tracing::warn!(
+            "⚠️ Refused {} {} of a read-only viewer",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, "The web UI is read-only".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Landing page and agent listing of the single server, with each agent's
+/// own routes under `/agents/<id>/`
+fn pool_app(agents: Arc<Agents>, asset_cache: AssetCache) -> Router {
+    Router::new()
+        .route("/", get(serve_dashboard))
+        .route("/dashboard", get(serve_dashboard))
+        .route("/api/agents", get(list_agents))
+        .route("/metrics", get(serve_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(pool_readyz))
+        .route("/agents/{id}", get(redirect_to_agent))
+        .route("/agents/{id}/", any(route_to_agent))
+        .route("/agents/{id}/{*rest}", any(route_to_agent))
+        .with_state((agents, asset_cache))
+}
+
+/// Overview of every pool agent, served by the single server and by each
+/// agent's own server. The page gets the base path to link to the agents'
+/// servers.
+async fn serve_dashboard<T>(
+    State((_, asset_cache)): State<(T, AssetCache)>,
+    base_path: Option<Extension<BasePath>>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let base_path = base_path.map(|Extension(BasePath(path))| path);
+    match asset_cache.get_dashboard_html().await {
+        Ok(content) => Ok(Html(
+            content.replace("__BASE_PATH__", base_path.as_deref().unwrap_or_default()),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to serve dashboard.html: {}", e);
+            Err((
+                StatusCode::NOT_FOUND,
+                "dashboard.html not found".to_string(),
+            ))
+        }
+    }
+}
+
+/// Status of every pool agent, keyed by agent ID
+async fn list_agents(
+    State((agents, _)): State<(Arc<Agents>, AssetCache)>,
+) -> Json<BTreeMap<String, AgentSummary>> {
+    Json(agents.summaries())
+}
+
+/// The pool as seen from a per-agent server
+async fn list_pool(Extension(pool): Extension<Weak<Agents>>) -> Response {
+    match pool.upgrade() {
+        Some(agents) => Json(agents.summaries()).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Agent is not part of a pool".to_string(),
+        )
+            .into_response(),
+    }
+}
+
+/// Process-wide metrics in the Prometheus text format
+async fn serve_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::metrics().encode(),
+    )
+}
+
+/// Answers as long as the process and this server run
+pub(crate) async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// 200 when `readiness` is ready, 503 listing the failing agents otherwise
+pub(crate) fn readiness_response(readiness: Readiness) -> (StatusCode, Json<Readiness>) {
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// Readiness of the agent's pool, or of the agent outside of one
+async fn agent_readyz(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    Extension(pool): Extension<Weak<Agents>>,
+) -> (StatusCode, Json<Readiness>) {
+    readiness_response(match pool.upgrade() {
+        Some(agents) => agents.readiness(),
+        None => Readiness::of([&agent]),
+    })
+}
+
+async fn pool_readyz(
+    State((agents, _)): State<(Arc<Agents>, AssetCache)>,
+) -> (StatusCode, Json<Readiness>) {
+    readiness_response(agents.readiness())
+}
+
+/// The agent pages' relative URLs need the trailing slash
+async fn redirect_to_agent(
+    Path(id): Path<String>,
+    base_path: Option<Extension<BasePath>>,
+) -> Redirect {
+    let base_path = base_path.map(|Extension(BasePath(path))| path);
+    Redirect::permanent(&format!(
+        "{}/agents/{}/",
+        base_path.as_deref().unwrap_or_default(),
+        id
+    ))
+}
+
+/// Hand a request under `/agents/<id>/` to the routes of agent `id`, with
+/// the prefix taken off its path
+async fn route_to_agent(
+    State((agents, asset_cache)): State<(Arc<Agents>, AssetCache)>,
+    Path(params): Path<BTreeMap<String, String>>,
+    mut request: Request,
+) -> Response {
+    let id = params.get("id").map(String::as_str).unwrap_or_default();
+    let Some(agent) = agents.get_agent_by_id(id) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown agent: {}", id)).into_response();
+    };
+
+    // The still encoded path, since the routes of the agent decode it again
+    let rest = request
+        .uri()
+        .path()
+        .strip_prefix("/agents/")
+        .and_then(|path| path.split_once('/'))
+        .map_or("", |(_, rest)| rest);
+    let uri = match request.uri().query() {
+        Some(query) => format!("/{}?{}", rest, query),
+        None => format!("/{}", rest),
+    };
+    match uri.parse::<Uri>() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+    match agent_app(agent, Arc::downgrade(&agents), asset_cache)
+        .oneshot(request)
+        .await
+    {
+        Ok(response) => response,
+        Err(never) => match never {},
     }
 }
 
 async fn serve_index(
-    State((_, asset_cache)): State<(Arc<Agent>, AssetCache)>,
+    State((agent, asset_cache)): State<(Arc<Agent>, AssetCache)>,
 ) -> Result<Html<String>, (StatusCode, String)> {
     info!("📄 Serving index.html to client");
     println!("📄 HTTP request for index page received");
 
-    match asset_cache.get_index_html().await {
+    let page = match agent.get_config().web_ui.frontend {
+        WebFrontend::Asciinema => asset_cache.get_index_html().await,
+        WebFrontend::Xterm => asset_cache.get_xterm_html().await,
+    };
+    match page {
         Ok(content) => Ok(Html(content)),
         Err(e) => {
             tracing::error!("Failed to serve index.html: {}", e);
@@ -116,13 +620,83 @@ async fn serve_index(
     }
 }
 
+async fn serve_history(
+    State((_, asset_cache)): State<(Arc<Agent>, AssetCache)>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    match asset_cache.get_history_html().await {
+        Ok(content) => Ok(Html(content)),
+        Err(e) => {
+            tracing::error!("Failed to serve history.html: {}", e);
+            Err((StatusCode::NOT_FOUND, "history.html not found".to_string()))
+        }
+    }
+}
+
+/// The agent's session recordings, newest first
+async fn list_recordings(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+) -> Result<Json<RecordingsResponse>, (StatusCode, String)> {
+    let dir = &agent.get_config().recording.dir;
+    match recording::list_recordings(dir, &agent.get_id()).await {
+        Ok(recordings) => Ok(Json(RecordingsResponse {
+            agent: agent.get_id(),
+            recordings,
+        })),
+        Err(e) => {
+            tracing::error!("❌ Failed to list recordings: {:#}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+        }
+    }
+}
+
+/// One of the agent's recordings, as asciicast for the player
+async fn serve_recording(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    Path(file): Path<String>,
+) -> Response {
+    let dir = &agent.get_config().recording.dir;
+    let Some(path) = recording::recording_path(dir, &agent.get_id(), &file) else {
+        return (StatusCode::NOT_FOUND, "No such recording").into_response();
+    };
+    match tokio::fs::read(&path).await {
+        Ok(content) => {
+            ([(header::CONTENT_TYPE, "application/x-asciicast")], content).into_response()
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (StatusCode::NOT_FOUND, "No such recording").into_response()
+        }
+        Err(e) => {
+            tracing::error!("❌ Failed to read recording {}: {}", path.display(), e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    Query(params): Query<WebSocketParams>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    shutdown: Option<Extension<Shutdown>>,
+    uri: Uri,
 ) -> Response {
     info!("🔌 WebSocket upgrade request received");
     tracing::debug!("🔌 WebSocket connection attempt");
-    ws.on_upgrade(move |socket| handle_websocket(socket, agent))
+    let read_only = is_read_only(&agent, &uri);
+    let peer = peer.map(|Extension(ConnectInfo(peer))| peer);
+    // Taken before the upgrade, so a shutdown waits for this connection
+    let connection = shutdown.map(|Extension(shutdown)| shutdown.connection());
+    ws.on_upgrade(move |socket| {
+        handle_websocket(
+            socket,
+            agent,
+            params.mode,
+            read_only,
+            params.seq,
+            peer,
+            connection,
+        )
+    })
 }
 
 async fn send_command(
@@ -149,6 +723,73 @@ async fn send_command(
     }
 }
 
+/// Key sequences sent one after another like a trigger's send_keys action.
+/// Named keys such as `Enter` or `C-c`, also written as `<Enter>`, are
+/// translated. An agent whose shell exited or whose rules are paused
+/// refuses them with 409.
+async fn send_keys(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    Json(request): Json<KeysRequest>,
+) -> (StatusCode, Json<CommandResponse>) {
+    let refuse = |status, message: String| {
+        tracing::warn!("⚠️ Keys API request refused: {}", message);
+        (
+            status,
+            Json(CommandResponse {
+                success: false,
+                message,
+            }),
+        )
+    };
+    if request.keys.is_empty() {
+        return refuse(StatusCode::BAD_REQUEST, "No keys to send".to_string());
+    }
+    if agent.get_status() == AgentStatus::Error {
+        return refuse(
+            StatusCode::CONFLICT,
+            format!("Agent {} has no running shell", agent.get_id()),
+        );
+    }
+    if agent.are_rules_paused() {
+        return refuse(
+            StatusCode::CONFLICT,
+            format!("Agent {} is paused", agent.get_id()),
+        );
+    }
+
+    let keys = request.keys.iter().map(|key| api_key(key)).collect();
+    let action = ActionType::SendKeys(keys);
+    match execute_action_with_agent(&action, &agent, "⌨️ Keys API").await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(CommandResponse {
+                success: true,
+                message: format!("Sent {} keys", request.keys.len()),
+            }),
+        ),
+        Err(e) => {
+            tracing::error!("❌ Failed to send keys: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(CommandResponse {
+                    success: false,
+                    message: format!("Failed to send keys: {}", e),
+                }),
+            )
+        }
+    }
+}
+
+/// What is sent for `key` of POST /api/keys: the bytes of a named key, with
+/// or without angle brackets, or else the text itself
+fn api_key(key: &str) -> String {
+    let name = key
+        .strip_prefix('<')
+        .and_then(|key| key.strip_suffix('>'))
+        .filter(|name| parse_key(name) != name.as_bytes());
+    String::from_utf8_lossy(&parse_key(name.unwrap_or(key))).into_owned()
+}
+
 async fn get_terminal_size(
     State((agent, _)): State<(Arc<Agent>, AssetCache)>,
 ) -> Json<TerminalSizeResponse> {
@@ -160,15 +801,102 @@ async fn get_terminal_size(
 
 async fn get_agent_status(
     State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    uri: Uri,
 ) -> Json<AgentStatusResponse> {
     // Get actual agent status
-    let is_active = agent.is_active().await;
-    let state = if is_active { "Active" } else { "Idle" };
+    let state = format!("{:?}", agent.get_status());
 
     info!("📊 Agent status request: {}", state);
 
     Json(AgentStatusResponse {
         state: state.to_string(),
         message: format!("Agent is {}", state.to_lowercase()),
+        rules_paused: agent.are_rules_paused(),
+        current_command: agent.get_current_command(),
+        title: agent.get_title(),
+        alternate_screen: agent.is_alternate_screen(),
+        pending_input: agent.get_pending_input(),
+        read_only: is_read_only(&agent, &uri),
+        viewers: metrics::metrics().websocket_clients(&agent.get_id()),
+    })
+}
+
+/// What the terminal shows right now, as JSON with the agent's status or,
+/// with `?format=txt`, as plain text
+async fn get_snapshot(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+    Query(params): Query<SnapshotParams>,
+) -> Response {
+    let screen = match agent.get_process().get_screen_text().await {
+        Ok(screen) => screen,
+        Err(e) => {
+            tracing::error!("❌ Failed to read the screen: {}", e);
+            return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+        }
+    };
+
+    match params.format.as_deref() {
+        None | Some("json") => Json(SnapshotResponse {
+            agent: agent.get_id(),
+            state: format!("{:?}", agent.get_status()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            screen,
+        })
+        .into_response(),
+        Some("txt") => {
+            // Rows below the last output are left out
+            let used = screen
+                .lines
+                .iter()
+                .rposition(|line| !line.is_empty())
+                .map_or(0, |last| last + 1);
+            let mut text = screen.lines[..used].join("\n");
+            text.push('\n');
+            text.into_response()
+        }
+        Some(format) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unknown format '{}', expected json or txt", format),
+        )
+            .into_response(),
+    }
+}
+
+/// Pause rule actions while intervening manually
+async fn pause_rules(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+) -> (StatusCode, Json<CommandResponse>) {
+    match agent.pause_rules() {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(CommandResponse {
+                success: true,
+                message: "Rules paused".to_string(),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(CommandResponse {
+                success: false,
+                message: format!("{:#}", e),
+            }),
+        ),
+    }
+}
+
+async fn resume_rules(State((agent, _)): State<(Arc<Agent>, AssetCache)>) -> Json<CommandResponse> {
+    agent.resume_rules();
+    Json(CommandResponse {
+        success: true,
+        message: "Rules resumed".to_string(),
+    })
+}
+
+async fn get_rule_stats(
+    State((agent, _)): State<(Arc<Agent>, AssetCache)>,
+) -> Json<RuleStatsResponse> {
+    Json(RuleStatsResponse {
+        agent: agent.get_id(),
+        rules: agent.get_rule_stats().snapshot(),
     })
 }
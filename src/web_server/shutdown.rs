@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Graceful shutdown of one web server: tells the server to stop
+/// accepting connections and its WebSockets to close, and lets it wait
+/// for them. WebSockets are upgraded out of the HTTP server, which doesn't
+/// wait on them, so each one holds a [`Connection`] instead.
+#[derive(Clone)]
+pub struct Shutdown {
+    signal: Arc<watch::Sender<bool>>,
+    connections: Arc<watch::Sender<()>>,
+}
+
+/// Held by a WebSocket for as long as it is open
+pub struct Connection {
+    signal: watch::Receiver<bool>,
+    _open: watch::Receiver<()>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            signal: Arc::new(watch::channel(false).0),
+            connections: Arc::new(watch::channel(()).0),
+        }
+    }
+
+    /// Ask the server and its WebSockets to finish
+    pub fn trigger(&self) {
+        self.signal.send_replace(true);
+    }
+
+    /// Resolves once [`Shutdown::trigger`] was called
+    pub async fn triggered(&self) {
+        let mut signal = self.signal.subscribe();
+        // The sender lives in self
+        let _ = signal.wait_for(|&shutting_down| shutting_down).await;
+    }
+
+    /// Count a WebSocket as open until the returned guard is dropped
+    pub fn connection(&self) -> Connection {
+        Connection {
+            signal: self.signal.subscribe(),
+            _open: self.connections.subscribe(),
+        }
+    }
+
+    /// Resolves once every [`Connection`] is dropped
+    pub async fn connections_closed(&self) {
+        self.connections.closed().await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Connection {
+    /// Resolves once the server shuts down
+    pub async fn shutting_down(&mut self) {
+        if self
+            .signal
+            .wait_for(|&shutting_down| shutting_down)
+            .await
+            .is_err()
+        {
+            // The server is gone without a shutdown
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_connections() {
+        let shutdown = Shutdown::new();
+        let mut connection = shutdown.connection();
+        let connection_shutdown = shutdown.clone();
+        let closed = tokio::spawn(async move { connection_shutdown.connections_closed().await });
+
+        let waiting = tokio::time::timeout(Duration::from_millis(50), connection.shutting_down());
+        assert!(waiting.await.is_err(), "not triggered yet");
+
+        shutdown.trigger();
+        shutdown.triggered().await;
+        connection.shutting_down().await;
+        assert!(!closed.is_finished());
+
+        drop(connection);
+        tokio::time::timeout(Duration::from_secs(1), closed)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -1,13 +1,17 @@
 use super::server::WebServer;
+use super::websocket::{InputLimits, client_init, client_paste, client_resize};
 use crate::agent::Agent;
 use crate::config::Config;
 use crate::config::web_ui_config::WebUIConfig;
 use crate::web_ui::assets::AssetCache;
+use std::sync::Arc;
 
 #[tokio::test]
 async fn test_web_server_creation() {
     let config = Config::default();
-    let agent = Agent::from_config(0, &config).await.unwrap();
+    let agent = Agent::from_config(0, &config, Arc::default())
+        .await
+        .unwrap();
     let web_server = WebServer::new(8080, "localhost".to_string(), agent);
 
     assert_eq!(web_server.port, 8080);
@@ -57,9 +61,1483 @@ async fn test_asset_cache_caching() {
     let cache = AssetCache::new();
 
     // Test that caching works - second access should hit cache
-    if let Ok(content1) = cache.get_index_html().await {
-        if let Ok(content2) = cache.get_index_html().await {
-            assert_eq!(content1, content2);
+    if let Ok(content1) = cache.get_index_html().await
+        && let Ok(content2) = cache.get_index_html().await
+    {
+        assert_eq!(content1, content2);
+    }
+}
+
+#[tokio::test]
+async fn test_rule_stats_endpoint() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+        .await
+        .unwrap();
+    agent
+        .get_rule_stats()
+        .register(0, "when: proceed".to_string());
+    agent.get_rule_stats().record(0, "Do you want to proceed?");
+
+    let app = WebServer::new(8080, "localhost".to_string(), agent).create_app();
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/rule-stats")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent"], "agent-0");
+    assert_eq!(json["rules"][0]["match_count"], 1);
+    assert_eq!(json["rules"][0]["rule"], "when: proceed");
+}
+
+#[tokio::test]
+async fn test_recordings_endpoints() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.5, \"o\", \"$ \"]\n";
+    std::fs::write(dir.path().join("agent-0-20250101-090000.cast"), cast).unwrap();
+    std::fs::write(dir.path().join("agent-1-20250101-090000.cast"), cast).unwrap();
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    config.recording.dir = dir.path().to_path_buf();
+    let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+        .await
+        .unwrap();
+    let app = WebServer::new(8080, "localhost".to_string(), agent).create_app();
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/recordings").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent"], "agent-0");
+    assert_eq!(json["recordings"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        json["recordings"][0]["file"],
+        "agent-0-20250101-090000.cast"
+    );
+    assert_eq!(json["recordings"][0]["size"], cast.len());
+
+    let response = get("/recordings/agent-0-20250101-090000.cast")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, cast.as_bytes());
+
+    // Other agents' recordings and other files stay out of reach
+    for uri in [
+        "/recordings/agent-1-20250101-090000.cast",
+        "/recordings/agent-0-20250102-090000.cast",
+        "/recordings/..%2Fagent-0-20250101-090000.cast",
+    ] {
+        assert_eq!(get(uri).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    let response = get("/history").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_snapshot_endpoint() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let mock = Arc::new(MockPtyProcess::new());
+    mock.set_screen(&["$ cargo build", "   Finished dev", "$"]);
+    let agent = Agent::new_with_process(0, &config, Box::new(mock))
+        .await
+        .unwrap();
+    let app = WebServer::new(8080, "localhost".to_string(), agent).create_app();
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/api/snapshot").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent"], "agent-0");
+    assert_eq!(json["state"], "Idle");
+    assert_eq!(json["lines"][1], "   Finished dev");
+    assert_eq!(json["lines"].as_array().unwrap().len(), 24);
+    assert_eq!(json["cursor"], serde_json::json!([2, 1]));
+    assert_eq!(
+        (json["cols"].clone(), json["rows"].clone()),
+        (80.into(), 24.into())
+    );
+    assert!(chrono::DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap()).is_ok());
+
+    let response = get("/api/snapshot?format=txt").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, "$ cargo build\n   Finished dev\n$\n");
+
+    let response = get("/api/snapshot?format=html").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_agent_status_shows_title() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let mock = Arc::new(MockPtyProcess::new());
+    let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+        .await
+        .unwrap();
+    mock.set_title("✳ Fix the tests");
+    mock.set_alternate_screen(true);
+    let app = WebServer::new(8080, "localhost".to_string(), agent).create_app();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/agent-status")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["title"], "✳ Fix the tests");
+    assert_eq!(json["alternate_screen"], true);
+    assert_eq!(json["pending_input"], 0);
+}
+
+#[test]
+fn test_client_resize_message() {
+    assert_eq!(
+        client_resize(r#"{"type": "resize", "cols": 180, "rows": 45}"#),
+        Some((180, 45))
+    );
+    // Clamped to sane bounds
+    assert_eq!(
+        client_resize(r#"{"type": "resize", "cols": 5000, "rows": 3}"#),
+        Some((500, 20))
+    );
+    // Anything else is keys for the shell
+    assert_eq!(client_resize("ls -la\r"), None);
+    assert_eq!(client_resize(r#"{"type": "input", "payload": "ls"}"#), None);
+    assert_eq!(client_resize(r#"{"type": "resize", "cols": -1}"#), None);
+}
+
+#[test]
+fn test_client_paste_message() {
+    assert_eq!(
+        client_paste(r#"{"type": "paste", "text": "one\ntwo"}"#).as_deref(),
+        Some("one\ntwo")
+    );
+    assert_eq!(client_paste(r#"{"type": "input", "text": "ls"}"#), None);
+    assert_eq!(client_paste("<paste>ls</paste>"), None);
+}
+
+#[test]
+fn test_client_init_message() {
+    assert!(client_init(r#"{"type": "init"}"#));
+    assert!(!client_init(
+        r#"{"type": "resize", "cols": 80, "rows": 24}"#
+    ));
+    assert!(!client_init("init"));
+}
+
+/// Serve the web UI of an agent on a mock shell, on a free local port
+async fn serve_mock_agent() -> (
+    std::net::SocketAddr,
+    std::sync::Arc<crate::terminal::pty_process_trait::MockPtyProcess>,
+) {
+    serve_mock_agent_with(0, Config::default()).await
+}
+
+/// [`serve_mock_agent`] for agent `index` with `config`
+async fn serve_mock_agent_with(
+    index: usize,
+    mut config: Config,
+) -> (
+    std::net::SocketAddr,
+    std::sync::Arc<crate::terminal::pty_process_trait::MockPtyProcess>,
+) {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use std::sync::Arc;
+
+    config.web_ui.enabled = false;
+    let mock = Arc::new(MockPtyProcess::new());
+    let agent = Agent::new_with_process(index, &config, Box::new(Arc::clone(&mock)))
+        .await
+        .unwrap();
+    let app = WebServer::new(0, "localhost".to_string(), agent).create_app();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (addr, mock)
+}
+
+/// Keep writing `output` to the mock shell until a frame passing `is_output`
+/// arrives, since the server subscribes to the output after its first frames
+async fn wait_for_output<S>(
+    socket: &mut S,
+    mock: &crate::terminal::pty_process_trait::MockPtyProcess,
+    output: &[u8],
+    is_output: impl Fn(&tokio_tungstenite::tungstenite::Message) -> bool,
+) -> Vec<tokio_tungstenite::tungstenite::Message>
+where
+    S: futures_util::Stream<
+            Item = Result<
+                tokio_tungstenite::tungstenite::Message,
+                tokio_tungstenite::tungstenite::Error,
+            >,
+        > + Unpin,
+{
+    use futures_util::StreamExt;
+    use std::time::Duration;
+
+    let mut frames = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            mock.emit_output(output);
+            if let Ok(Some(frame)) =
+                tokio::time::timeout(Duration::from_millis(200), socket.next()).await
+            {
+                let frame = frame.unwrap();
+                let done = is_output(&frame);
+                frames.push(frame);
+                if done {
+                    return;
+                }
+            }
+        }
+    })
+    .await
+    .expect("output never arrived");
+    frames
+}
+
+#[tokio::test]
+async fn test_websocket_text_mode_streams_output() {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (addr, mock) = serve_mock_agent().await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    let header: serde_json::Value = serde_json::from_str(&header).unwrap();
+    assert_eq!(header["version"], 2);
+    assert_eq!(
+        (header["width"].clone(), header["height"].clone()),
+        (80.into(), 24.into())
+    );
+    let Some(Ok(Message::Text(initial))) = socket.next().await else {
+        panic!("no initial screen");
+    };
+    let (_, kind, data): (f64, String, String) = serde_json::from_str(&initial).unwrap();
+    assert_eq!(
+        (kind.as_str(), data.as_str()),
+        ("o", "\u{1b}[2J\u{1b}[HMock screen contents")
+    );
+
+    wait_for_output(
+        &mut socket,
+        &mock,
+        b"ls\r\n",
+        |frame| matches!(frame, Message::Text(text) if text.contains("ls\\r\\n")),
+    )
+    .await;
+
+    // A character split across writes arrives whole
+    let split = "é".as_bytes();
+    mock.emit_output(&split[..1]);
+    mock.emit_output(&split[1..]);
+    let data = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            let Some(Ok(Message::Text(text))) = socket.next().await else {
+                panic!("stream ended");
+            };
+            let (_, _, data): (f64, String, String) = serde_json::from_str(&text).unwrap();
+            if data != "ls\r\n" {
+                return data;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(data, "é");
+}
+
+#[tokio::test]
+async fn test_websocket_redraw_mode_sends_screens() {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mut config = Config::default();
+    config.terminal.term = "screen-256color".to_string();
+    let (addr, mock) = serve_mock_agent_with(0, config).await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?mode=redraw", addr))
+        .await
+        .unwrap();
+
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    let header: serde_json::Value = serde_json::from_str(&header).unwrap();
+    assert_eq!(header["version"], 2);
+    // The agent's TERM, as in its recordings
+    assert_eq!(
+        header["env"],
+        serde_json::json!({"TERM": "screen-256color"})
+    );
+
+    let frames = wait_for_output(
+        &mut socket,
+        &mock,
+        b"ls\r\n",
+        |frame| matches!(frame, Message::Text(text) if text.contains("\\u001b[2J")),
+    )
+    .await;
+    for frame in frames {
+        let Message::Text(text) = frame else {
+            panic!("unexpected frame in redraw mode: {:?}", frame);
+        };
+        let (_, kind, data): (f64, String, String) = serde_json::from_str(&text).unwrap();
+        assert_eq!(kind, "o");
+        assert!(data.ends_with("Mock screen contents"), "{:?}", data);
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_binary_mode_forwards_raw_bytes() {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (addr, mock) = serve_mock_agent().await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?mode=binary", addr))
+        .await
+        .unwrap();
+
+    // The header stays asciicast JSON, output doesn't
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    assert!(header.contains("\"version\":2"));
+    let Some(Ok(Message::Binary(initial))) = socket.next().await else {
+        panic!("no initial screen");
+    };
+    assert_eq!(&initial[..], b"\x1b[2J\x1b[HMock screen contents");
+
+    // An image escape with bytes that aren't valid UTF-8 arrives unchanged
+    let image = b"\x1bPq#0;2;0;0;0#0\xff\xfe-\x1b\\";
+    let frames = wait_for_output(
+        &mut socket,
+        &mock,
+        image,
+        |frame| matches!(frame, Message::Binary(bytes) if &bytes[..] == image),
+    )
+    .await;
+    assert!(
+        frames
+            .iter()
+            .all(|frame| matches!(frame, Message::Binary(bytes) if &bytes[..] == image)),
+        "{:?}",
+        frames
+    );
+}
+
+/// Web UI of a pool of two mock agents under one server
+async fn pool_app() -> axum::Router {
+    pool_server().await.create_app()
+}
+
+/// Single server of two mock agents
+async fn pool_server() -> WebServer {
+    use crate::agent::agents::Agents;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use std::sync::Arc;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    config.web_ui.mode = crate::config::web_ui_config::WebUIMode::Single;
+    let mut agents = Vec::new();
+    for i in 0..2 {
+        let mock = Box::new(MockPtyProcess::new());
+        agents.push(Agent::new_with_process(i, &config, mock).await.unwrap());
+    }
+    let agents = Arc::new(Agents::from_agents(Vec::new(), agents));
+    WebServer::new(8080, "localhost".to_string(), agents)
+}
+
+#[tokio::test]
+async fn test_single_server_routes_by_agent_id() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use tower::ServiceExt;
+
+    let app = pool_app().await;
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/agents/agent-1/api/rule-stats").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent"], "agent-1");
+
+    for uri in ["/agents/agent-0/", "/agents/agent-0/history"] {
+        let response = get(uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+    }
+
+    // Relative links of the agent page need the trailing slash
+    let response = get("/agents/agent-0").await.unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(response.headers()[header::LOCATION], "/agents/agent-0/");
+
+    for uri in [
+        "/agents/agent-7/",
+        "/agents/nope/ws",
+        "/agents/nope/api/agent-status",
+    ] {
+        let response = get(uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_single_server_lists_agents() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    let app = pool_app().await;
+    for uri in ["/", "/dashboard"] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("api/agents"));
+    }
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/agents")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent-0"]["status"], "Idle");
+    assert_eq!(json["agent-0"]["last_output_secs"], 0);
+    // Served under /agents/<id>/ rather than on a port of their own
+    assert!(json["agent-1"].is_object());
+    assert!(json["agent-1"].get("port").is_none());
+}
+
+#[tokio::test]
+async fn test_single_server_websocket_per_agent() {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let app = pool_app().await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{}/agents/agent-1/ws", addr))
+            .await
+            .unwrap();
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    assert!(header.contains("\"version\":2"));
+
+    assert!(
+        tokio_tungstenite::connect_async(format!("ws://{}/agents/agent-9/ws", addr))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_dashboard_on_agent_port_lists_its_pool() {
+    use super::server::WebTarget;
+    use crate::agent::agents::Agents;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    // Agent 1's port is taken, so it falls back to the next free one
+    let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut config = Config::default();
+    config.web_ui.base_port = taken.local_addr().unwrap().port() - 1;
+    config.web_ui.port_fallback = true;
+    let mut agents = Vec::new();
+    for i in 0..2 {
+        let mock = Box::new(MockPtyProcess::new());
+        agents.push(Agent::new_with_process(i, &config, mock).await.unwrap());
+    }
+    let pool = Arc::new(Agents::from_agents(Vec::new(), agents));
+    pool.start_web_servers(&axum::Router::new()).await.unwrap();
+    let agent = pool.get_agent_by_index(0);
+    let ports: Vec<_> = (0..2)
+        .map(|i| pool.get_agent_by_index(i).get_web_port())
+        .collect();
+    assert_eq!(ports[0], Some(config.web_ui.base_port));
+    assert!(ports[1] > Some(config.web_ui.base_port + 1));
+    let get = |target: WebTarget, uri: &str| {
+        WebServer::new(9990, "localhost".to_string(), target)
+            .create_app()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    // An agent outside of a pool has none to list
+    let response = get(Arc::clone(&agent).into(), "/dashboard").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get(Arc::clone(&agent).into(), "/api/agents").await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let target = WebTarget::Agent(agent, Arc::downgrade(&pool));
+    let response = get(target, "/api/agents").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["agent-0"]["port"], ports[0].unwrap());
+    assert_eq!(json["agent-1"]["port"], ports[1].unwrap());
+    assert_eq!(json["agent-1"]["status"], "Idle");
+}
+
+#[tokio::test]
+async fn test_keys_endpoint_sends_translated_keys() {
+    use crate::agent::AgentStatus;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let mock = Arc::new(MockPtyProcess::new());
+    let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+        .await
+        .unwrap();
+    let app = WebServer::new(8080, "localhost".to_string(), Arc::clone(&agent)).create_app();
+    let post = |body: String| {
+        app.clone().oneshot(
+            Request::post("/api/keys")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+    };
+
+    let response = post(r#"{"keys": ["echo hi", "<Enter>", "C-c", "Tab", "<b>"]}"#.to_string())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        mock.get_sent_inputs(),
+        ["echo hi", "\r", "\x03", "\t", "<b>"]
+    );
+
+    let response = post(r#"{"keys": []}"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = post(r#"{"keys": "echo hi"}"#.to_string()).await.unwrap();
+    assert!(response.status().is_client_error());
+    let huge = format!(r#"{{"keys": ["{}"]}}"#, "x".repeat(100 * 1024));
+    let response = post(huge).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    agent.pause_rules().unwrap();
+    let response = post(r#"{"keys": ["ls"]}"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    agent.resume_rules();
+
+    agent.set_status(AgentStatus::Error);
+    let response = post(r#"{"keys": ["ls"]}"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["success"], false);
+
+    // Nothing refused reached the shell
+    assert_eq!(mock.get_sent_inputs().len(), 5);
+}
+
+#[tokio::test]
+async fn test_read_only_viewers_cannot_change_the_agent() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let mock = Arc::new(MockPtyProcess::new());
+    let agent = Agent::new_with_process(0, &config, Box::new(Arc::clone(&mock)))
+        .await
+        .unwrap();
+    let app = WebServer::new(8080, "localhost".to_string(), Arc::clone(&agent)).create_app();
+    let request = |method: &str, uri: &str, body: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+    let read_only = |response: axum::response::Response| async {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()["read_only"].clone()
+    };
+
+    // A view-only link watches but can't type, paste or pause rules
+    let keys = r#"{"keys": ["ls"]}"#;
+    let response = request("POST", "/api/keys?readonly=1", keys).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request("POST", "/api/rules/pause?readonly=true", "")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!agent.are_rules_paused());
+    let response = request("GET", "/api/agent-status?readonly=1", "")
+        .await
+        .unwrap();
+    assert_eq!(read_only(response).await, true);
+
+    // The interactive session next to it
+    let response = request("POST", "/api/keys?readonly=0", keys).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request("GET", "/api/agent-status", "").await.unwrap();
+    assert_eq!(read_only(response).await, false);
+    assert_eq!(mock.get_sent_inputs(), ["ls"]);
+
+    // web_ui.read_only makes every viewer read-only
+    config.web_ui.read_only = true;
+    let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+        .await
+        .unwrap();
+    let app = WebServer::new(8080, "localhost".to_string(), agent).create_app();
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/command")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"command": "ls\r"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app
+        .oneshot(Request::get("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_websocket_read_only_link_drops_input() {
+    use futures_util::SinkExt;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (addr, mock) = serve_mock_agent().await;
+    let (mut viewer, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?readonly=1", addr))
+        .await
+        .unwrap();
+    viewer.send(Message::text("rm -rf /tmp/x\r")).await.unwrap();
+    viewer
+        .send(Message::text(r#"{"type": "paste", "text": "a\nb"}"#))
+        .await
+        .unwrap();
+
+    let (mut owner, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+    owner.send(Message::text("ls\r")).await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mock.get_sent_inputs().is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("input of the interactive session never arrived");
+    // Give the viewer's frames as long again to show up
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(mock.get_sent_inputs(), ["ls\r"]);
+}
+
+#[tokio::test]
+async fn test_tls_serves_https_and_wss() {
+    use super::tls::client_config;
+    use crate::config::web_ui_config::TlsConfig;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use futures_util::StreamExt;
+    use std::sync::Arc;
+    use tokio_tungstenite::Connector;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let dir = tempfile::tempdir().unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let tls = TlsConfig {
+        cert: dir.path().join("web.pem"),
+        key: dir.path().join("web.key"),
+    };
+    std::fs::write(&tls.cert, cert.cert.pem()).unwrap();
+    std::fs::write(&tls.key, cert.key_pair.serialize_pem()).unwrap();
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+        .await
+        .unwrap();
+    let server = WebServer::new(0, "localhost".to_string(), agent).with_tls(Some(tls.clone()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let app = server.create_app();
+    tokio::spawn(async move { server.serve(listener, app).await });
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert.cert.pem().as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("https://localhost:{}/api/agent-status", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // Plain HTTP gets no answer from a TLS server
+    assert!(
+        reqwest::get(format!("http://localhost:{}/api/agent-status", port))
+            .await
+            .is_err()
+    );
+
+    let connector = Connector::Rustls(Arc::new(client_config(&tls).unwrap()));
+    let (mut socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+        format!("wss://localhost:{}/ws", port),
+        None,
+        false,
+        Some(connector),
+    )
+    .await
+    .unwrap();
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    assert!(header.contains("\"version\":2"));
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_serves_prometheus_text() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use tower::ServiceExt;
+
+    let stats = crate::rule::RuleStats::for_agent("metrics-test");
+    stats.register(0, "when: ^done$".to_string());
+    stats.record(0, "done");
+    stats.record_dropped_output(2);
+    crate::metrics::metrics().set_queue_depth("metrics-test", 3);
+    crate::metrics::metrics().record_restart("metrics-test", 1_700_000_000);
+    crate::metrics::metrics().record_active_time("metrics-test", std::time::Duration::from_secs(5));
+
+    let response = pool_app()
+        .await
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/plain; version=0.0.4"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let text = String::from_utf8_lossy(&body);
+    assert!(
+        text.contains(r#"ccauto_rule_matches_total{agent="metrics-test",rule="when: ^done$"} 1"#)
+    );
+    assert!(text.contains(r#"ccauto_queue_depth{queue="metrics-test"} 3"#));
+    assert!(text.contains(r#"ccauto_dropped_output_total{agent="metrics-test"} 2"#));
+    assert!(text.contains(r#"ccauto_agent_restarts_total{agent="metrics-test"} 1"#));
+    assert!(text.contains(r#"ccauto_agent_active_seconds_total{agent="metrics-test"} 5"#));
+    assert!(text.contains(r#"ccauto_agent_status{agent="agent-0",status="Idle"}"#));
+}
+
+#[tokio::test]
+async fn test_health_and_readiness_endpoints() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    let app = pool_app().await;
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/healthz").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The agents' monitors were never started
+    let response = get("/readyz").await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let readiness: crate::agent::agents::Readiness = serde_json::from_slice(&body).unwrap();
+    assert!(!readiness.ready);
+    assert_eq!(
+        readiness.failing.keys().collect::<Vec<_>>(),
+        ["agent-0", "agent-1"]
+    );
+    assert_eq!(readiness.failing["agent-0"], ["monitors not started"]);
+}
+
+#[tokio::test]
+async fn test_xterm_frontend_serves_xterm_page() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    config.web_ui.frontend = crate::config::web_ui_config::WebFrontend::Xterm;
+    let agent = Agent::new_with_process(
+        0,
+        &config,
+        Box::new(crate::terminal::pty_process_trait::MockPtyProcess::new()),
+    )
+    .await
+    .unwrap();
+    let response = WebServer::new(0, "localhost".to_string(), agent)
+        .create_app()
+        .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let page = String::from_utf8_lossy(&body);
+    assert!(page.contains("new Terminal("));
+    assert!(page.contains("api/terminal-size"));
+    // Alt+P pauses rules like on the default page
+    assert!(page.contains("attachCustomKeyEventHandler"));
+    assert!(page.contains("'api/rules/' + action"));
+    assert!(!page.contains("AsciinemaPlayer.create"));
+}
+
+#[tokio::test]
+async fn test_websocket_numbers_events_and_sends_fresh_init() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (addr, mock) = serve_mock_agent().await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?seq=true", addr))
+        .await
+        .unwrap();
+
+    let Some(Ok(Message::Text(header))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+    assert!(header.contains("\"version\":2"));
+    // The screen comes first, numbered before any output
+    let Some(Ok(Message::Text(initial))) = socket.next().await else {
+        panic!("no initial screen");
+    };
+    let (_, _, _, seq): (f64, String, String, u64) = serde_json::from_str(&initial).unwrap();
+    assert_eq!(seq, 0);
+
+    let frames = wait_for_output(
+        &mut socket,
+        &mock,
+        b"ls\r\n",
+        |frame| matches!(frame, Message::Text(text) if text.contains("ls\\r\\n")),
+    )
+    .await;
+    let last = frames.last().unwrap().to_text().unwrap();
+    let (_, _, _, seq): (f64, String, String, u64) = serde_json::from_str(last).unwrap();
+    assert!(seq >= 1);
+
+    socket
+        .send(Message::Text(r#"{"type": "init"}"#.into()))
+        .await
+        .unwrap();
+    let frames: Vec<String> = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        let mut frames = Vec::new();
+        while let Some(Ok(Message::Text(text))) = socket.next().await {
+            let done = text.contains("Mock screen contents");
+            frames.push(text.to_string());
+            if done {
+                return frames;
+            }
+        }
+        panic!("stream ended");
+    })
+    .await
+    .unwrap();
+    // A fresh header, then the screen with the current number
+    assert!(frames[frames.len() - 2].contains("\"version\":2"));
+    let (_, _, _, resent): (f64, String, String, u64) =
+        serde_json::from_str(frames.last().unwrap()).unwrap();
+    assert!(resent >= seq);
+}
+
+#[tokio::test]
+async fn test_base_path_prefixes_every_route() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let agent = Agent::new_with_process(
+        0,
+        &config,
+        Box::new(crate::terminal::pty_process_trait::MockPtyProcess::new()),
+    )
+    .await
+    .unwrap();
+    let app = WebServer::new(0, "localhost".to_string(), agent)
+        .with_base_path("/ccauto".to_string())
+        .create_app();
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    for uri in [
+        "/ccauto/",
+        "/ccauto/api/terminal-size",
+        "/ccauto/api/snapshot?format=txt",
+        "/ccauto/healthz",
+    ] {
+        let response = get(uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+    }
+    for uri in ["/", "/api/terminal-size", "/ccautox/", "/other/ccauto/"] {
+        let response = get(uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+
+    let response = get("/ccauto").await.unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(response.headers()[header::LOCATION], "/ccauto/");
+
+    // The dashboard links to the agents' servers below the base path
+    let response = get("/ccauto/dashboard").await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let page = String::from_utf8_lossy(&body);
+    assert!(page.contains("const basePath = '/ccauto';"));
+}
+
+#[tokio::test]
+async fn test_base_path_on_single_server() {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use tower::ServiceExt;
+
+    let app = pool_server()
+        .await
+        .with_base_path("/ccauto".to_string())
+        .create_app();
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/ccauto/agents/agent-1/api/agent-status")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get("/ccauto/agents/agent-1").await.unwrap();
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "/ccauto/agents/agent-1/"
+    );
+}
+
+#[tokio::test]
+async fn test_web_server_serves_the_control_api() {
+    use super::control;
+    use super::server::WebTarget;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let server = pool_server().await;
+    let WebTarget::Pool(agents) = server.target.clone() else {
+        unreachable!("pool_server serves the pool");
+    };
+    let app = server
+        .with_base_path("/ccauto".to_string())
+        .with_control(control::router(&[], Arc::clone(&agents)))
+        .create_app();
+    let request = |method: &str, uri: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = request("GET", "/ccauto/api/config").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request("POST", "/ccauto/api/agents/agent-1/rules/pause")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        agents
+            .get_agent_by_id("agent-1")
+            .unwrap()
+            .are_rules_paused()
+    );
+    // Webhook entries come along
+    let response = request("POST", "/ccauto/api/triggers/unknown")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_read_only_web_ui_refuses_control_writes() {
+    use super::control;
+    use crate::agent::agents::Agents;
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    config.web_ui.read_only = true;
+    let agent = Agent::new_with_process(0, &config, Box::new(MockPtyProcess::new()))
+        .await
+        .unwrap();
+    let agents = Arc::new(Agents::from_agents(vec![], vec![Arc::clone(&agent)]));
+    let app = WebServer::new(8080, "localhost".to_string(), Arc::clone(&agents))
+        .with_control(control::router(&[], Arc::clone(&agents)))
+        .create_app();
+    let request = |method: &str, uri: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = request("GET", "/api/config").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    for uri in [
+        "/api/agents/broadcast",
+        "/api/agents/agent-0/rules/pause",
+        "/api/agents/agent-0/resize",
+        "/api/triggers/build/run",
+        "/api/exec",
+    ] {
+        let response = request("POST", uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", uri);
+    }
+    assert!(!agent.are_rules_paused());
+
+    // The dedicated control port isn't a viewer's
+    let server = control::ControlServer::new(&[], agents, "localhost".to_string(), 0);
+    let response = server
+        .create_app()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/agents/agent-0/rules/pause")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_cors_preflight_follows_origins() {
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode, header};
+    use tower::ServiceExt;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let agent = Agent::new_with_process(
+        0,
+        &config,
+        Box::new(crate::terminal::pty_process_trait::MockPtyProcess::new()),
+    )
+    .await
+    .unwrap();
+    let preflight = |origins: Vec<String>, origin: &'static str| {
+        WebServer::new(0, "localhost".to_string(), std::sync::Arc::clone(&agent))
+            .with_base_path("/ccauto".to_string())
+            .with_cors_origins(origins)
+            .create_app()
+            .oneshot(
+                Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri("/ccauto/api/command")
+                    .header(header::ORIGIN, origin)
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+    };
+    let allowed = |response: &axum::response::Response| {
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .map(|value| value.to_str().unwrap().to_string())
+    };
+
+    // Any origin by default
+    let response = preflight(vec!["*".to_string()], "https://dash.example.com")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(allowed(&response).as_deref(), Some("*"));
+
+    let origins = vec!["https://dash.example.com".to_string()];
+    let response = preflight(origins.clone(), "https://dash.example.com")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        allowed(&response).as_deref(),
+        Some("https://dash.example.com")
+    );
+    let response = preflight(origins, "https://evil.example.com")
+        .await
+        .unwrap();
+    assert_eq!(allowed(&response), None);
+
+    let response = preflight(Vec::new(), "https://dash.example.com")
+        .await
+        .unwrap();
+    assert_eq!(allowed(&response), None);
+}
+
+#[tokio::test]
+async fn test_websocket_drops_silent_clients() {
+    use futures_util::StreamExt;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    // A unique agent, since the viewer count is process-wide
+    let mut config = Config::default();
+    config.web_ui.ping_interval = "1s".to_string();
+    config.web_ui.pong_timeout = "1s".to_string();
+    let (addr, _mock) = serve_mock_agent_with(91, config).await;
+    let viewers = || crate::metrics::metrics().websocket_clients("agent-91");
+    let url = format!("ws://{}/ws", addr);
+
+    // tungstenite answers pings while the stream is read, so the silent
+    // client never pongs
+    let (silent, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let (mut listening, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let listener = tokio::spawn(async move {
+        let mut pings = 0;
+        while let Some(Ok(frame)) = listening.next().await {
+            if matches!(frame, Message::Ping(_)) {
+                pings += 1;
+                if pings == 3 {
+                    return listening;
+                }
+            }
+        }
+        panic!("the listening client was dropped");
+    });
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while viewers() != 2 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("clients never counted");
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while viewers() != 1 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
         }
+    })
+    .await
+    .expect("silent client never dropped");
+
+    // The silent client finds its connection closed once it reads
+    let mut silent = silent;
+    let ended = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match silent.next().await {
+                Some(Ok(_)) => continue,
+                _ => return,
+            }
+        }
+    })
+    .await;
+    assert!(ended.is_ok());
+
+    let listening = tokio::time::timeout(Duration::from_secs(10), listener)
+        .await
+        .expect("listening client got no pings")
+        .unwrap();
+    assert_eq!(viewers(), 1);
+    drop(listening);
+}
+
+#[test]
+fn test_input_limits() {
+    use axum::extract::ws::{Message, close_code};
+
+    let web_ui = WebUIConfig {
+        max_input_bytes: 8,
+        max_paste_bytes: 64,
+        max_input_rate: 4,
+        ..WebUIConfig::default()
+    };
+    let mut limits = InputLimits::new(&web_ui);
+    assert!(limits.check(&Message::Text("ls\r".into())).is_none());
+    let paste = serde_json::json!({"type": "paste", "text": "echo a long line"}).to_string();
+    assert!(limits.check(&Message::Text(paste.into())).is_none());
+    // Control frames don't count
+    for _ in 0..10 {
+        assert!(limits.check(&Message::Ping(Default::default())).is_none());
+    }
+    let frame = limits
+        .check(&Message::Text("echo a long line".into()))
+        .unwrap();
+    assert_eq!(frame.code, close_code::SIZE);
+
+    let mut limits = InputLimits::new(&web_ui);
+    for _ in 0..4 {
+        assert!(limits.check(&Message::Text("a".into())).is_none());
+    }
+    let frame = limits.check(&Message::Text("a".into())).unwrap();
+    assert_eq!(frame.code, close_code::POLICY);
+
+    let unlimited = WebUIConfig {
+        max_input_rate: 0,
+        ..web_ui
+    };
+    let mut limits = InputLimits::new(&unlimited);
+    for _ in 0..100 {
+        assert!(limits.check(&Message::Text("a".into())).is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_closes_clients_over_the_input_limits() {
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    let mut config = Config::default();
+    config.web_ui.max_input_bytes = 64;
+    config.web_ui.max_input_rate = 20;
+    let (addr, mock) = serve_mock_agent_with(0, config).await;
+    let url = format!("ws://{}/ws", addr);
+
+    /// Code of the close frame the server ends the connection with
+    async fn close_code<S>(socket: &mut S) -> Option<CloseCode>
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+            + Unpin,
+    {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(Ok(frame)) = socket.next().await {
+                if let Message::Close(frame) = frame {
+                    return frame.map(|frame| frame.code);
+                }
+            }
+            None
+        })
+        .await
+        .expect("connection never closed")
     }
+
+    // A paste may be larger than typed input
+    let (mut socket, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let text = "x".repeat(1000);
+    let paste = serde_json::json!({"type": "paste", "text": text}).to_string();
+    socket.send(Message::Text(paste.into())).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !mock
+            .get_sent_inputs()
+            .iter()
+            .any(|input| input.contains(&text))
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("paste never arrived");
+
+    socket
+        .send(Message::Text("y".repeat(1000).into()))
+        .await
+        .unwrap();
+    assert_eq!(close_code(&mut socket).await, Some(CloseCode::Size));
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    for _ in 0..30 {
+        if socket.send(Message::Text("a".into())).await.is_err() {
+            break;
+        }
+    }
+    assert_eq!(close_code(&mut socket).await, Some(CloseCode::Policy));
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_websockets_and_stops_listening() {
+    use crate::terminal::pty_process_trait::MockPtyProcess;
+    use futures_util::StreamExt;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    let mut config = Config::default();
+    config.web_ui.enabled = false;
+    let agent = Agent::new_with_process(92, &config, Box::new(Arc::new(MockPtyProcess::new())))
+        .await
+        .unwrap();
+    let (port, handle) = WebServer::new(0, "localhost".to_string(), agent)
+        .with_shutdown_timeout(Duration::from_secs(5))
+        .spawn(false)
+        .await
+        .unwrap();
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}/ws", port))
+        .await
+        .unwrap();
+    let Some(Ok(Message::Text(_))) = socket.next().await else {
+        panic!("no asciicast header");
+    };
+
+    // Resolves with the client still connected, once it got the close
+    tokio::time::timeout(Duration::from_secs(3), handle.shutdown())
+        .await
+        .expect("shutdown took longer than the close");
+    let close = tokio::time::timeout(Duration::from_secs(1), async {
+        while let Some(Ok(frame)) = socket.next().await {
+            if let Message::Close(frame) = frame {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .unwrap()
+    .expect("no close frame");
+    assert_eq!(close.code, CloseCode::Away);
+    assert_eq!(close.reason, "server shutting down");
+
+    assert!(
+        tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+    );
 }
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use std::sync::Arc;
+
+use crate::config::web_ui_config::TlsConfig;
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn load_certs(tls: &TlsConfig) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to load web_ui.tls.cert {}", tls.cert.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate in web_ui.tls.cert {}", tls.cert.display());
+    }
+    Ok(certs)
+}
+
+/// TLS settings of the web servers from the PEM files of `tls`, failing
+/// with the file at fault
+pub fn server_config(tls: &TlsConfig) -> Result<ServerConfig> {
+    let certs = load_certs(tls)?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .with_context(|| format!("Failed to load web_ui.tls.key {}", tls.key.display()))?;
+
+    let mut config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .with_context(|| {
+            format!(
+                "web_ui.tls.key {} doesn't fit web_ui.tls.cert {}",
+                tls.key.display(),
+                tls.cert.display()
+            )
+        })?;
+    // WebSockets upgrade HTTP/1.1 connections
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// TLS settings of `ccauto attach`, trusting the web UI's own certificates
+/// so that a self-signed one works too
+pub fn client_config(tls: &TlsConfig) -> Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(tls)? {
+        roots
+            .add(cert)
+            .with_context(|| format!("Invalid certificate in {}", tls.cert.display()))?;
+    }
+    Ok(ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_config_names_the_file_at_fault() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let other = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let write = |name: &str, contents: String| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let tls = TlsConfig {
+            cert: write("web.pem", cert.cert.pem()),
+            key: write("web.key", cert.key_pair.serialize_pem()),
+        };
+        assert!(server_config(&tls).is_ok());
+        assert!(client_config(&tls).is_ok());
+
+        let error = |tls: TlsConfig| format!("{:#}", server_config(&tls).unwrap_err());
+        let missing = error(TlsConfig {
+            cert: dir.path().join("missing.pem"),
+            ..tls.clone()
+        });
+        assert!(missing.contains("web_ui.tls.cert"), "{}", missing);
+        assert!(missing.contains("missing.pem"), "{}", missing);
+
+        let no_cert = error(TlsConfig {
+            cert: write("empty.pem", String::new()),
+            ..tls.clone()
+        });
+        assert!(no_cert.contains("No certificate"), "{}", no_cert);
+
+        let no_key = error(TlsConfig {
+            key: write("cert-as-key.pem", cert.cert.pem()),
+            ..tls.clone()
+        });
+        assert!(no_key.contains("web_ui.tls.key"), "{}", no_key);
+
+        let mismatch = error(TlsConfig {
+            key: write("other.key", other.key_pair.serialize_pem()),
+            ..tls
+        });
+        assert!(mismatch.contains("doesn't fit"), "{}", mismatch);
+    }
+}
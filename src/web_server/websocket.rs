@@ -1,95 +1,340 @@
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
 use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
 use serde_json::json;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+use tokio::sync::{Notify, broadcast, mpsc};
+
+use super::shutdown::Connection;
 use crate::agent::Agent;
+use crate::config::web_ui_config::WebUIConfig;
+use crate::metrics;
+use crate::terminal::pty_session::{PtyCommand, PtyEvent, PtyEventData};
+use crate::terminal::utf8::take_utf8;
 
-pub async fn handle_websocket(socket: WebSocket, agent: Arc<Agent>) {
-    info!("WebSocket connection established for asciinema streaming");
+/// How terminal output is streamed to a WebSocket client, chosen with the
+/// `mode` query parameter of `/ws`
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamMode {
+    /// asciicast v2 as the asciinema player's WebSocket driver reads it:
+    /// the header, the current screen as the first event, then the
+    /// shell's output as it was written (default)
+    #[default]
+    Text,
+    /// The shell's raw output as binary frames, byte for byte; the header
+    /// and resize events stay asciicast text frames
+    Binary,
+    /// asciicast events redrawing the whole screen, the stream of earlier
+    /// versions. Deprecated, to be removed in the next release.
+    Redraw,
+}
 
-    let (mut sender, mut receiver) = socket.split();
+/// Output events of one connection. With sequence numbers, each text
+/// event carries the number of output events sent before it, plus the
+/// output chunks the server missed, as a fourth element: a client seeing
+/// a gap lost output and can ask for a fresh init with `{"type": "init"}`.
+struct OutputEvents {
+    mode: StreamMode,
+    numbered: bool,
+    seq: u64,
+}
+
+impl OutputEvents {
+    fn new(mode: StreamMode, numbered: bool) -> Self {
+        Self {
+            mode,
+            numbered,
+            seq: 0,
+        }
+    }
+
+    /// Frame carrying terminal output written at `time`
+    fn output(&mut self, time: f64, data: String) -> Message {
+        self.seq += 1;
+        self.frame(time, data)
+    }
+
+    /// Frame replacing the screen with `screen`, which doesn't count as
+    /// output of its own
+    fn redraw(&self, time: f64, screen: &str) -> Message {
+        self.frame(time, format!("\u{001b}[2J\u{001b}[H{}", screen))
+    }
+
+    /// Count `skipped` output chunks that were never sent
+    fn skip(&mut self, skipped: u64) {
+        self.seq += skipped;
+    }
 
-    // Send asciinema header first with dynamic terminal size
+    fn frame(&self, time: f64, data: String) -> Message {
+        match self.mode {
+            StreamMode::Binary => Message::Binary(data.into_bytes().into()),
+            _ if self.numbered => {
+                Message::Text(json!([time, "o", data, self.seq]).to_string().into())
+            }
+            _ => Message::Text(json!([time, "o", data]).to_string().into()),
+        }
+    }
+}
+
+/// Bounds of the columns and rows a browser may resize a terminal to
+const CLIENT_SIZE_RANGE: std::ops::RangeInclusive<usize> = 20..=500;
+
+/// Terminal size of a `{"type": "resize", "cols": .., "rows": ..}` message
+/// sent by the web UI when its window changes, clamped to CLIENT_SIZE_RANGE
+pub(crate) fn client_resize(message: &str) -> Option<(u16, u16)> {
+    match serde_json::from_str(message) {
+        Ok(PtyCommand::Resize { cols, rows }) => {
+            let clamp =
+                |n: usize| n.clamp(*CLIENT_SIZE_RANGE.start(), *CLIENT_SIZE_RANGE.end()) as u16;
+            Some((clamp(cols), clamp(rows)))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `message` is `{"type": "init"}`, asking for the header and the
+/// current screen again, e.g. after a gap in the sequence numbers
+pub(crate) fn client_init(message: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Init {
+        #[serde(rename = "type")]
+        message_type: String,
+    }
+    serde_json::from_str::<Init>(message).is_ok_and(|init| init.message_type == "init")
+}
+
+/// asciicast header with the agent's terminal size as it is now and its
+/// TERM, like the header of its recordings
+fn header(agent: &Agent) -> Message {
     let start_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs();
-
-    // Get actual terminal dimensions from agent config
     let (cols, rows) = agent.get_terminal_dimensions();
-
     let header = json!({
         "version": 2,
         "width": cols,
         "height": rows,
         "timestamp": start_time,
-        "env": {
-            "TERM": "xterm-256color",
-            "SHELL": "/bin/bash"
-        }
+        "env": {"TERM": agent.get_config().terminal_for(&agent.get_id()).term},
     });
+    Message::Text(header.to_string().into())
+}
 
-    info!(
-        "📐 Using terminal dimensions: {}x{} from config",
-        cols, rows
-    );
+/// The init of a connection: the header, then the screen rendered by the
+/// vt100 parser as the first event, even when it is blank, so a client
+/// (re)connecting never waits for new output to see the terminal
+async fn init_frames(agent: &Agent, events: &OutputEvents, time: f64) -> [Message; 2] {
+    let screen = match agent.get_process().get_screen_contents().await {
+        Ok(screen) => screen,
+        Err(e) => {
+            error!("❌ Failed to get screen contents: {}", e);
+            String::new()
+        }
+    };
+    [header(agent), events.redraw(time, &screen)]
+}
 
-    if sender
-        .send(Message::Text(header.to_string().into()))
-        .await
-        .is_err()
-    {
-        error!("Failed to send asciinema header");
-        return;
+/// Clipboard text of a `{"type": "paste", "text": ..}` message, sent by the
+/// web UI so a multi-line paste reaches the program as one
+pub(crate) fn client_paste(message: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Paste {
+        #[serde(rename = "type")]
+        message_type: String,
+        text: String,
     }
+    let paste: Paste = serde_json::from_str(message).ok()?;
+    (paste.message_type == "paste").then_some(paste.text)
+}
 
-    info!("✅ Asciinema header sent successfully");
-
-    // Send initial state from vt100::Parser screen contents
-    match agent.get_process().get_screen_contents().await {
-        Ok(initial_content) => {
-            if !initial_content.trim().is_empty() {
-                let initial_time = 0.0;
-                let initial_event = json!([initial_time, "o", initial_content]);
-
-                info!(
-                    "📺 Sending initial terminal state from vt100: {} bytes at time {:.3}s",
-                    initial_content.len(),
-                    initial_time
-                );
-                info!(
-                    "🔍 Initial content preview (first 200 chars): {:?}",
-                    initial_content.chars().take(200).collect::<String>()
-                );
+/// What one client may send: messages of `web_ui.max_input_bytes`, pastes
+/// of `max_paste_bytes`, and `max_input_rate` messages per second
+pub(crate) struct InputLimits {
+    max_bytes: usize,
+    max_paste_bytes: usize,
+    max_rate: u32,
+    /// Start of the current second, and the messages sent in it
+    window: Instant,
+    count: u32,
+}
 
-                if sender
-                    .send(Message::Text(initial_event.to_string().into()))
-                    .await
-                    .is_err()
-                {
-                    error!("Failed to send initial terminal state");
-                    return;
-                }
+impl InputLimits {
+    pub(crate) fn new(web_ui: &WebUIConfig) -> Self {
+        Self {
+            max_bytes: web_ui.max_input_bytes,
+            max_paste_bytes: web_ui.max_paste_bytes,
+            max_rate: web_ui.max_input_rate,
+            window: Instant::now(),
+            count: 0,
+        }
+    }
 
-                info!("✅ Initial terminal state sent successfully");
-            } else {
-                info!("📺 No terminal content to send (empty screen)");
+    /// Count `message`, and return why the connection is closed if it
+    /// breaks a limit. Pings, pongs and closes aren't counted.
+    pub(crate) fn check(&mut self, message: &Message) -> Option<CloseFrame> {
+        let (len, limit) = match message {
+            Message::Text(text) if text.len() > self.max_bytes && client_paste(text).is_some() => {
+                (text.len(), self.max_paste_bytes)
             }
+            Message::Text(text) => (text.len(), self.max_bytes),
+            Message::Binary(data) => (data.len(), self.max_bytes),
+            _ => return None,
+        };
+        if self.window.elapsed() >= Duration::from_secs(1) {
+            self.window = Instant::now();
+            self.count = 0;
         }
-        Err(e) => {
-            error!("❌ Failed to get screen contents: {}", e);
-            info!("📺 Proceeding without initial state");
+        self.count += 1;
+        if self.max_rate > 0 && self.count > self.max_rate {
+            return Some(CloseFrame {
+                code: close_code::POLICY,
+                reason: format!("More than {} messages per second", self.max_rate).into(),
+            });
         }
+        (len > limit).then(|| CloseFrame {
+            code: close_code::SIZE,
+            reason: format!("Message of {} bytes, the limit is {}", len, limit).into(),
+        })
     }
+}
+
+/// Ask for a ping every `interval` through `control`, and return once the
+/// client sent nothing, not even the pong, within `timeout` of one: its
+/// network is gone without the connection being closed. Never returns for
+/// a zero `interval`.
+async fn missed_pong(
+    interval: Duration,
+    timeout: Duration,
+    last_seen: &Mutex<Instant>,
+    control: &mpsc::Sender<Message>,
+) {
+    if interval.is_zero() {
+        return std::future::pending().await;
+    }
+    loop {
+        tokio::time::sleep(interval).await;
+        let sent = Instant::now();
+        // A full queue means the output task is stuck, and the pong
+        // missing anyway
+        let _ = control.try_send(Message::Ping(bytes::Bytes::new()));
+        tokio::time::sleep(timeout).await;
+        if *last_seen.lock().unwrap() < sent {
+            return;
+        }
+    }
+}
 
-    // Spawn task to handle incoming WebSocket messages (not used in asciinema mode)
-    let input_task = tokio::spawn(async move {
+/// Time the output task gets to send the close frame of a client that
+/// broke the input limits, or of every client on shutdown
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Resolves once the server of `connection` shuts down; never without one
+async fn shutting_down(connection: &mut Option<Connection>) {
+    match connection {
+        Some(connection) => connection.shutting_down().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Stream the agent's terminal to `socket`. A `read_only` viewer only
+/// watches: its input, pastes and resizes are dropped. With `numbered`,
+/// text events carry sequence numbers (see [`OutputEvents`]). A client
+/// breaking the [`InputLimits`] is closed, naming its `peer` in the log.
+/// Every client is closed with "server shutting down" once the server of
+/// `connection` shuts down.
+pub async fn handle_websocket(
+    socket: WebSocket,
+    agent: Arc<Agent>,
+    mode: StreamMode,
+    read_only: bool,
+    numbered: bool,
+    peer: Option<std::net::SocketAddr>,
+    mut connection: Option<Connection>,
+) {
+    info!(
+        "WebSocket connection established for asciinema streaming ({:?} mode{})",
+        mode,
+        if read_only { ", read-only" } else { "" }
+    );
+
+    // Counted as connected until this handler returns
+    let _client = metrics::metrics().websocket_client(&agent.get_id());
+
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = OutputEvents::new(mode, numbered);
+
+    for frame in init_frames(&agent, &events, 0.0).await {
+        if sender.send(frame).await.is_err() {
+            error!("Failed to send the terminal's initial state");
+            return;
+        }
+    }
+    info!("✅ Header and initial terminal state sent");
+
+    // Asked by the client for a fresh init
+    let resync = Arc::new(Notify::new());
+    // Frames for the output task, which owns the sender: pings asked for
+    // by missed_pong, and the close of a client breaking the limits
+    let (control, mut control_rx) = mpsc::channel::<Message>(4);
+    // The client's last sign of life
+    let last_seen = Arc::new(Mutex::new(Instant::now()));
+    let mut limits = InputLimits::new(&agent.get_config().web_ui);
+
+    // Spawn task to handle incoming WebSocket messages. Text messages are
+    // keys for the shell, waking a sleeping agent, except for resize
+    // requests, pastes and init requests. Viewers share the terminal, so
+    // the last resize wins.
+    let agent_input = agent.clone();
+    let resync_input = Arc::clone(&resync);
+    let last_seen_input = Arc::clone(&last_seen);
+    let mut input_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
+            // Any message, pongs included, shows the client is there
+            *last_seen_input.lock().unwrap() = Instant::now();
+            if let Ok(message) = &msg
+                && let Some(frame) = limits.check(message)
+            {
+                warn!(
+                    "🚫 Closing the WebSocket of {} to {}: {}",
+                    peer.map_or("an unknown peer".to_string(), |peer| peer.to_string()),
+                    agent_input.get_id(),
+                    frame.reason
+                );
+                return Some(frame);
+            }
             match msg {
+                // Changes nothing, so read-only viewers may ask too
+                Ok(Message::Text(text)) if client_init(&text) => {
+                    resync_input.notify_one();
+                }
+                Ok(Message::Text(_)) if read_only => {
+                    debug!("Dropped input of a read-only viewer");
+                }
+                Ok(Message::Text(text)) => {
+                    if let Some((cols, rows)) = client_resize(&text) {
+                        if agent_input.get_terminal_dimensions() != (cols, rows)
+                            && let Err(e) = agent_input.resize(cols, rows).await
+                        {
+                            error!("❌ Failed to resize terminal: {}", e);
+                        }
+                        continue;
+                    }
+                    if let Some(paste) = client_paste(&text) {
+                        if let Err(e) = agent_input.paste(&paste).await {
+                            error!("❌ Failed to paste WebSocket input: {}", e);
+                        }
+                        continue;
+                    }
+                    if let Err(e) = agent_input.send_keys(&text).await {
+                        error!("❌ Failed to send WebSocket input: {}", e);
+                    }
+                }
                 Ok(Message::Close(_)) => {
                     info!("WebSocket connection closed by client");
                     break;
@@ -101,29 +346,163 @@ pub async fn handle_websocket(socket: WebSocket, agent: Arc<Agent>) {
                 _ => {}
             }
         }
+        None
     });
 
-    // Full-screen redraw approach instead of incremental updates
+    // Output as written, or full-screen redraws in Redraw mode
     let agent_output = agent.clone();
     let session_start = std::time::Instant::now();
 
-    let output_task = tokio::spawn(async move {
-        info!("🔄 WebSocket full-screen output task started");
+    let mut output_task = tokio::spawn(async move {
+        info!("🔄 WebSocket output task started");
 
         // Get direct access to PTY raw bytes broadcast channel
-        if let Ok(mut pty_bytes_rx) = agent_output.get_process().get_pty_bytes_receiver().await {
+        let process = agent_output.get_process();
+        if let (Ok(mut pty_bytes_rx), Ok(mut pty_events_rx)) = (
+            process.get_pty_bytes_receiver().await,
+            process.get_pty_event_receiver().await,
+        ) {
             info!("✅ Connected to PTY raw bytes broadcast channel");
 
-            info!("🔄 WebSocket: Starting recv loop for full-screen updates");
-
+            // Text mode: the end of a character split across chunks
+            let mut pending = Vec::new();
             // Track last screen content to avoid redundant updates
             let mut last_screen_content = String::new();
             let mut last_update = std::time::Instant::now();
             const UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100); // ~10fps
             const DEBOUNCE_TIME: std::time::Duration = std::time::Duration::from_millis(50); // Debounce rapid changes
 
-            while let Ok(_bytes_data) = pty_bytes_rx.recv().await {
-                // Wait for debounce time or update interval
+            loop {
+                tokio::select! {
+                    Some(frame) = control_rx.recv() => {
+                        let close = matches!(frame, Message::Close(_));
+                        if sender.send(frame).await.is_err() {
+                            info!("WebSocket sender closed, stopping output task");
+                            break;
+                        }
+                        if close {
+                            break;
+                        }
+                        continue;
+                    }
+                    _ = resync.notified() => {
+                        let time = session_start.elapsed().as_secs_f64();
+                        info!("📺 Sending a fresh init at {:.3}s", time);
+                        pending.clear();
+                        let mut sent = true;
+                        for frame in init_frames(&agent_output, &events, time).await {
+                            sent = sent && sender.send(frame).await.is_ok();
+                        }
+                        if !sent {
+                            info!("WebSocket sender closed, stopping output task");
+                            break;
+                        }
+                        continue;
+                    }
+                    bytes_data = pty_bytes_rx.recv() => {
+                        match bytes_data {
+                            // Forward the bytes as the program wrote them
+                            Ok(bytes) if mode == StreamMode::Binary => {
+                                if sender.send(Message::Binary(bytes)).await.is_err() {
+                                    info!("WebSocket sender closed, stopping output task");
+                                    break;
+                                }
+                                continue;
+                            }
+                            Ok(bytes) if mode == StreamMode::Text => {
+                                pending.extend_from_slice(&bytes);
+                                let text = take_utf8(&mut pending);
+                                if text.is_empty() {
+                                    continue;
+                                }
+                                let time = session_start.elapsed().as_secs_f64();
+                                if sender.send(events.output(time, text)).await.is_err() {
+                                    info!("WebSocket sender closed, stopping output task");
+                                    break;
+                                }
+                                continue;
+                            }
+                            // Missed bytes can't be replayed, so a streaming
+                            // client gets a redraw of the screen instead
+                            Err(broadcast::error::RecvError::Lagged(skipped))
+                                if mode != StreamMode::Redraw =>
+                            {
+                                pending.clear();
+                                events.skip(skipped);
+                                info!("📺 Redrawing screen after {} skipped output chunks", skipped);
+                                let Ok(screen_content) = process.get_screen_contents().await else {
+                                    continue;
+                                };
+                                let time = session_start.elapsed().as_secs_f64();
+                                if sender.send(events.redraw(time, &screen_content)).await.is_err() {
+                                    info!("WebSocket sender closed, stopping output task");
+                                    break;
+                                }
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => events.skip(skipped),
+                            Ok(_) => {}
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        }
+                    }
+                    event = pty_events_rx.recv() => {
+                        let Ok(event) = event else {
+                            continue;
+                        };
+                        // The terminal is dead: tell the client instead of
+                        // leaving the screen frozen
+                        if let Some(reason) = event.data.exit_reason() {
+                            let time = session_start.elapsed().as_secs_f64();
+                            let notice = format!("\r\n[ccauto: shell exited ({})]\r\n", reason);
+                            info!("📺 Sending shell exit notice at {:.3}s", time);
+                            if sender
+                                .send(events.output(time, notice))
+                                .await
+                                .is_err()
+                            {
+                                info!("WebSocket sender closed, stopping output task");
+                                break;
+                            }
+                        }
+                        // asciicast resize event, which the player follows
+                        if let PtyEventData::Resize { cols, rows } = event.data {
+                            let time = session_start.elapsed().as_secs_f64();
+                            let resize_event = json!([time, "r", format!("{}x{}", cols, rows)]);
+                            info!("📐 Sending terminal resize to {}x{}", cols, rows);
+                            if sender
+                                .send(Message::Text(resize_event.to_string().into()))
+                                .await
+                                .is_err()
+                            {
+                                info!("WebSocket sender closed, stopping output task");
+                                break;
+                            }
+                            continue;
+                        }
+                        // The shell was respawned: replace the screen like on connect
+                        if let PtyEvent {
+                            data: PtyEventData::Init { initial_data, .. },
+                            ..
+                        } = event
+                        {
+                            let time = session_start.elapsed().as_secs_f64();
+                            info!("📺 Sending respawned terminal state at {:.3}s", time);
+                            if sender
+                                .send(events.redraw(time, &initial_data))
+                                .await
+                                .is_err()
+                            {
+                                info!("WebSocket sender closed, stopping output task");
+                                break;
+                            }
+                            last_screen_content = initial_data;
+                            last_update = std::time::Instant::now();
+                        }
+                        continue;
+                    }
+                }
+
+                // Only Redraw mode gets here: wait for debounce time or update interval
                 if last_update.elapsed() < UPDATE_INTERVAL {
                     // For rapid changes, wait a bit to accumulate
                     if last_update.elapsed() < DEBOUNCE_TIME {
@@ -138,29 +517,16 @@ pub async fn handle_websocket(socket: WebSocket, agent: Arc<Agent>) {
                     Ok(screen_content) => {
                         // Only send if screen content has changed
                         if screen_content != last_screen_content {
-                            // Calculate elapsed time from session start
                             let time = session_start.elapsed().as_secs_f64();
-
-                            // Clear screen and redraw
-                            let clear_screen = "\u{001b}[2J\u{001b}[H"; // Clear screen and move cursor to home
-                            let full_update = format!("{}{}", clear_screen, screen_content);
-
-                            // Create asciinema event with full screen content
-                            let asciinema_event = json!([time, "o", full_update]);
-                            let event_str = asciinema_event.to_string();
-
-                            info!(
-                                "📤 Sending full screen update: {} bytes at {:.3}s",
-                                event_str.len(),
-                                time
-                            );
-
-                            if sender.send(Message::Text(event_str.into())).await.is_err() {
+                            debug!("📤 Sending full screen update at {:.3}s", time);
+                            if sender
+                                .send(events.redraw(time, &screen_content))
+                                .await
+                                .is_err()
+                            {
                                 info!("WebSocket sender closed, stopping output task");
                                 break;
                             }
-
-                            info!("✅ Full screen update sent successfully");
                             last_screen_content = screen_content;
                             last_update = std::time::Instant::now();
                         }
@@ -170,7 +536,7 @@ pub async fn handle_websocket(socket: WebSocket, agent: Arc<Agent>) {
                     }
                 }
             }
-            info!("🔚 WebSocket: Full screen update loop ended");
+            info!("🔚 WebSocket: output loop ended");
         } else {
             error!("❌ Failed to get PTY output receiver from agent");
         }
@@ -178,15 +544,42 @@ pub async fn handle_websocket(socket: WebSocket, agent: Arc<Agent>) {
         info!("🔚 WebSocket output task terminated");
     });
 
+    // Checked when the config was loaded
+    let web_ui = &agent.get_config().web_ui;
+    let interval = web_ui.ping_interval().unwrap_or_default();
+    let timeout = web_ui.pong_timeout().unwrap_or_default();
+
     // Wait for any task to complete
     tokio::select! {
-        _ = input_task => {
+        closed = &mut input_task => {
             debug!("Input task completed");
+            if let Ok(Some(frame)) = closed
+                && control.send(Message::Close(Some(frame))).await.is_ok()
+            {
+                let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut output_task).await;
+            }
         }
-        _ = output_task => {
+        _ = &mut output_task => {
             debug!("Output task completed");
         }
+        _ = missed_pong(interval, timeout, &last_seen, &control) => {
+            warn!("💔 Dropping a WebSocket client that missed its pong");
+        }
+        _ = shutting_down(&mut connection) => {
+            info!("🛑 Closing a WebSocket client, the server is shutting down");
+            let frame = CloseFrame {
+                code: close_code::AWAY,
+                reason: "server shutting down".into(),
+            };
+            if control.send(Message::Close(Some(frame))).await.is_ok() {
+                let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut output_task).await;
+            }
+        }
     }
+    // Drop the socket and the output receivers with the tasks, so a dead
+    // client stops being a subscriber
+    input_task.abort();
+    output_task.abort();
 
     info!("WebSocket connection closed");
 }
@@ -4,6 +4,9 @@ use anyhow::Result;
 
 // Embed index.html at build time
 const INDEX_HTML: &str = include_str!("index.html");
+const HISTORY_HTML: &str = include_str!("history.html");
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+const XTERM_HTML: &str = include_str!("xterm.html");
 
 #[derive(Clone, Default)]
 pub struct AssetCache;
@@ -16,4 +19,16 @@ impl AssetCache {
     pub async fn get_index_html(&self) -> Result<String> {
         Ok(INDEX_HTML.to_string())
     }
+
+    pub async fn get_xterm_html(&self) -> Result<String> {
+        Ok(XTERM_HTML.to_string())
+    }
+
+    pub async fn get_history_html(&self) -> Result<String> {
+        Ok(HISTORY_HTML.to_string())
+    }
+
+    pub async fn get_dashboard_html(&self) -> Result<String> {
+        Ok(DASHBOARD_HTML.to_string())
+    }
 }
@@ -41,3 +41,297 @@ fn test_binary_version() {
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains("ccauto"));
 }
+
+#[test]
+fn test_show_stats_without_running_instance() {
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "show",
+            "--stats",
+            "--state-file",
+            "nonexistent-state.json",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is ccauto running?"));
+}
+
+#[test]
+fn test_test_rule_with_sample_file() {
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let input = dir.path().join("sample.txt");
+    std::fs::write(&input, "working...\nDo you want to proceed?\n").unwrap();
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--config",
+            "examples/basic/config.yaml",
+            "test-rule",
+            "--input",
+            input.to_str().unwrap(),
+            "--chunk-size",
+            "64",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("rule #0"));
+    assert!(stdout.contains(r#"action: send_keys ["1", "\r"]"#));
+}
+
+/// Whether `pid` still runs (zombies only wait to be reaped)
+fn process_alive(pid: &str) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false)
+}
+
+#[test]
+fn test_shutdown_terminates_agent_children() {
+    use std::time::{Duration, Instant};
+
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let pid_file = dir.path().join("sleep.pid");
+    let config = dir.path().join("config.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            r#"
+web_ui:
+  enabled: false
+agents:
+  pool: 1
+  shutdown_grace: 2s
+  respawn: false
+  init_wait_for: {{pattern: "[$#] $", timeout: 20s}}
+  init_keys: ["sh -c 'echo $$ > {}; exec sleep 1000'\r"]
+"#,
+            pid_file.display()
+        ),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccauto"))
+        .args(["--config", config.to_str().unwrap()])
+        .current_dir(dir.path())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start ccauto");
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let sleep_pid = loop {
+        if let Ok(pid) = std::fs::read_to_string(&pid_file)
+            && !pid.trim().is_empty()
+        {
+            break pid.trim().to_string();
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("The init keys never started sleep");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(process_alive(&sleep_pid));
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("ccauto did not exit after Ctrl+C");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // Reparented orphans may take a moment to be reaped
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while process_alive(&sleep_pid) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(
+        !process_alive(&sleep_pid),
+        "sleep {} outlived ccauto",
+        sleep_pid
+    );
+}
+
+#[test]
+fn test_orphaned_grandchild_keeps_agent_active() {
+    use std::time::{Duration, Instant};
+
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let pid_file = dir.path().join("sleep.pid");
+    let state_file = dir.path().join("state.json");
+    let config = dir.path().join("config.yaml");
+    // The wrapper exits right away, leaving sleep behind in the shell's session
+    std::fs::write(
+        &config,
+        format!(
+            r#"
+web_ui:
+  enabled: false
+agents:
+  pool: 1
+  shutdown_grace: 1s
+  respawn: false
+  init_wait_for: {{pattern: "[$#] $", timeout: 20s}}
+  init_keys: ["sh -c 'sleep 1000 & echo $! > {}'\r"]
+"#,
+            pid_file.display()
+        ),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccauto"))
+        .args(["--config", config.to_str().unwrap()])
+        .args(["--state-file", state_file.to_str().unwrap()])
+        .current_dir(dir.path())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start ccauto");
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let (found, output) = loop {
+        let sleep_pid = std::fs::read_to_string(&pid_file).unwrap_or_default();
+        let show = Command::new(env!("CARGO_BIN_EXE_ccauto"))
+            .args(["show", "--agents", "--state-file"])
+            .arg(&state_file)
+            .output()
+            .unwrap();
+        let output = String::from_utf8_lossy(&show.stdout).to_string();
+        if !sleep_pid.trim().is_empty()
+            && output.contains("Active")
+            && output.contains(&format!("{} sleep", sleep_pid.trim()))
+        {
+            break (true, output);
+        }
+        if Instant::now() > deadline {
+            break (false, output);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    };
+
+    let _ = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while child.try_wait().unwrap().is_none() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let _ = child.kill();
+    assert!(
+        found,
+        "agent not Active with the orphaned sleep:\n{}",
+        output
+    );
+}
+
+#[test]
+fn test_secret_keys_stay_out_of_logs_and_recordings() {
+    use std::time::{Duration, Instant};
+
+    const TOKEN: &str = "e2e-token-8c51d";
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let config = dir.path().join("config.yaml");
+    std::fs::write(
+        &config,
+        r#"
+web_ui:
+  enabled: false
+agents:
+  pool: 1
+  shutdown_grace: 1s
+  respawn: false
+  init_wait_for: {pattern: "[$#] $", timeout: 20s}
+  triggers:
+    - name: login
+      event: startup
+      action: send_keys
+      keys: ["${env.CCAUTO_TEST_TOKEN}", "\r"]
+      secret: true
+logging:
+  pty_dir: logs
+recording:
+  enabled: true
+  dir: casts
+"#,
+    )
+    .unwrap();
+    // Logs are written to stdout
+    let debug_log = dir.path().join("debug.log");
+    let stdout = std::fs::File::create(&debug_log).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccauto"))
+        .args(["--config", config.to_str().unwrap(), "--debug"])
+        .env("CCAUTO_TEST_TOKEN", TOKEN)
+        .current_dir(dir.path())
+        .stdin(std::process::Stdio::null())
+        .stdout(stdout)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start ccauto");
+
+    // The shell echoes the token and then complains about it as a command
+    let pty_log = dir.path().join("logs").join("agent-0.log");
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let sent = loop {
+        let log = std::fs::read_to_string(&pty_log).unwrap_or_default();
+        if log.contains("not found") {
+            break true;
+        }
+        if Instant::now() > deadline {
+            break false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+
+    let _ = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while child.try_wait().unwrap().is_none() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let _ = child.kill();
+
+    let read = |path: &std::path::Path| std::fs::read_to_string(path).unwrap();
+    let log = read(&pty_log);
+    assert!(sent, "the token never reached the shell:\n{}", log);
+    assert!(log.contains("***"), "{}", log);
+    let debug = read(&debug_log);
+    assert!(debug.contains(r#"Keys: ["***", "***"]"#), "{}", debug);
+    let casts: Vec<_> = std::fs::read_dir(dir.path().join("casts"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert!(!casts.is_empty());
+    for (path, content) in [(pty_log.clone(), log), (debug_log, debug)]
+        .into_iter()
+        .chain(casts.iter().map(|path| (path.clone(), read(path))))
+    {
+        assert!(
+            !content.contains(TOKEN),
+            "{} contains the secret:\n{}",
+            path.display(),
+            content
+        );
+    }
+}